@@ -0,0 +1,551 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::cell::{Cell, RefCell};
+
+/// The error returned when an [`Allocator`] cannot satisfy a request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// The error returned by the `try_*` methods of the crate's containers when
+/// they cannot make room.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The requested capacity does not fit in the address space.
+    CapacityOverflow,
+    /// The allocator failed to provide a block of `layout`.
+    AllocError { layout: Layout },
+}
+
+impl TryReserveError {
+    /// Diverge the way the infallible methods do: panic on capacity overflow,
+    /// and hand allocation failures to [`std::alloc::handle_alloc_error`].
+    pub(crate) fn handle(self) -> ! {
+        match self {
+            Self::CapacityOverflow => panic!("capacity overflow"),
+            Self::AllocError { layout } => std::alloc::handle_alloc_error(layout),
+        }
+    }
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CapacityOverflow => f.write_str("capacity overflow"),
+            Self::AllocError { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {}
+
+/// A source of memory for the crate's containers.
+///
+/// # Safety
+/// Memory returned by [`Allocator::allocate`] must stay valid until it is
+/// passed to [`Allocator::deallocate`] (or [`Allocator::grow`]), or until the
+/// allocator itself is dropped, whichever comes first.
+pub unsafe trait Allocator {
+    /// Returns a block of memory fitting `layout`.
+    ///
+    /// `layout` must have a non-zero size.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// Release a block of memory.
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by `self` with `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Move a block of memory into a larger block, preserving its contents.
+    ///
+    /// On failure the original block is left untouched.
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by `self` with `old`, and `new` must be
+    /// at least as large as `old` with the same alignment.
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old: Layout,
+        new: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let new_ptr = self.allocate(new)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old.size());
+            self.deallocate(ptr, old);
+        }
+        Ok(new_ptr)
+    }
+}
+
+/// The global allocator, as registered through `#[global_allocator]`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        NonNull::new(unsafe { std::alloc::alloc(layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { std::alloc::dealloc(ptr.as_ptr(), layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old: Layout,
+        new: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let ptr = unsafe { std::alloc::realloc(ptr.as_ptr(), old, new.size()) };
+        NonNull::new(ptr).ok_or(AllocError)
+    }
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { (**self).deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old: Layout,
+        new: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        unsafe { (**self).grow(ptr, old, new) }
+    }
+}
+
+/// The size of the first chunk a [`Bump`] allocates.
+const FIRST_CHUNK: usize = 4096;
+
+/// An arena that allocates by bumping a pointer through large chunks.
+///
+/// Individual allocations are never freed, the memory is reclaimed all at once
+/// by [`Bump::reset`] or by dropping the arena. Destructors of values placed in
+/// the arena are not run.
+///
+/// ```
+/// use nomicon::alloc::Bump;
+///
+/// let bump = Bump::new();
+/// let a = bump.alloc(1u64);
+/// let b = bump.alloc_slice(&[2u8, 3, 4]);
+/// *a += 10;
+///
+/// assert_eq!(*a, 11);
+/// assert_eq!(b, &[2, 3, 4]);
+/// ```
+///
+/// [`&Bump`](Bump) implements [`Allocator`], so containers can place their
+/// buffers in the arena.
+///
+/// ```
+/// use nomicon::{alloc::Bump, Vec};
+///
+/// let bump = Bump::new();
+/// let mut v = Vec::new_in(&bump);
+/// v.extend_from_slice(&[1, 2, 3]);
+/// assert_eq!(&*v, &[1, 2, 3]);
+/// ```
+pub struct Bump {
+    /// Every chunk owned by the arena, the last one is being bumped through.
+    chunks: RefCell<Vec<Chunk>>,
+    /// The next free byte of the current chunk, null before the first chunk
+    /// is allocated.
+    ptr: Cell<*mut u8>,
+    /// One past the last byte of the current chunk.
+    end: Cell<*mut u8>,
+}
+
+struct Chunk {
+    start: NonNull<u8>,
+    layout: Layout,
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.start.as_ptr(), self.layout) }
+    }
+}
+
+impl Bump {
+    /// Returns an empty arena, no memory is allocated until the first
+    /// allocation.
+    pub const fn new() -> Self {
+        Self {
+            chunks: RefCell::new(Vec::new()),
+            ptr: Cell::new(std::ptr::null_mut()),
+            end: Cell::new(std::ptr::null_mut()),
+        }
+    }
+
+    /// Returns the total size of the chunks owned by the arena.
+    pub fn allocated_bytes(&self) -> usize {
+        self.chunks.borrow().iter().map(|c| c.layout.size()).sum()
+    }
+
+    /// Move `value` into the arena.
+    // Every allocation is a distinct block, so handing out &mut from &self
+    // never aliases.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+        unsafe {
+            // SAFETY
+            // * The block fits T and is handed out exactly once.
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copy `src` into the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let layout = Layout::for_value(src);
+        let ptr = self.alloc_layout(layout).cast::<T>();
+        unsafe {
+            // SAFETY
+            // * The block fits src and is handed out exactly once.
+            std::ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+            std::slice::from_raw_parts_mut(ptr.as_ptr(), src.len())
+        }
+    }
+
+    /// Returns a block of uninitialized memory fitting `layout`.
+    ///
+    /// # Panics
+    /// If the system allocator fails to provide a new chunk.
+    pub fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        match self.try_alloc_layout(layout) {
+            Ok(ptr) => ptr,
+            Err(AllocError) => std::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    fn try_alloc_layout(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if let Some(ptr) = self.bump(layout) {
+            return Ok(ptr);
+        }
+        self.new_chunk(layout)?;
+        Ok(self.bump(layout).expect("new chunk fits the layout"))
+    }
+
+    /// Carve `layout` out of the current chunk, if it fits.
+    fn bump(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let ptr = self.ptr.get();
+        if ptr.is_null() {
+            return None;
+        }
+        let start = ptr.addr().checked_next_multiple_of(layout.align())?;
+        let end = start.checked_add(layout.size())?;
+        if end > self.end.get().addr() {
+            return None;
+        }
+        // Only the address moves, the pointer keeps the provenance of the
+        // chunk it was derived from.
+        self.ptr.set(ptr.with_addr(end));
+        NonNull::new(ptr.with_addr(start))
+    }
+
+    /// Allocate a chunk at least twice as large as the last, with room for
+    /// `layout`.
+    fn new_chunk(&self, layout: Layout) -> Result<(), AllocError> {
+        let mut chunks = self.chunks.borrow_mut();
+        let last = chunks.last().map_or(FIRST_CHUNK / 2, |c| c.layout.size());
+        let size = (last * 2).max(layout.size() + layout.align());
+        let chunk_layout =
+            Layout::from_size_align(size, layout.align().max(16)).map_err(|_| AllocError)?;
+        let start = NonNull::new(unsafe { std::alloc::alloc(chunk_layout) }).ok_or(AllocError)?;
+        self.ptr.set(start.as_ptr());
+        self.end.set(unsafe { start.as_ptr().add(size) });
+        chunks.push(Chunk {
+            start,
+            layout: chunk_layout,
+        });
+        Ok(())
+    }
+
+    /// Free every allocation, keeping the largest chunk around to serve future
+    /// allocations.
+    ///
+    /// Taking `&mut self` guarantees no references into the arena are alive.
+    pub fn reset(&mut self) {
+        let mut chunks = self.chunks.borrow_mut();
+        if let Some(largest) = chunks.pop() {
+            chunks.clear();
+            self.ptr.set(largest.start.as_ptr());
+            self.end
+                .set(unsafe { largest.start.as_ptr().add(largest.layout.size()) });
+            chunks.push(largest);
+        }
+    }
+}
+
+impl Default for Bump {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl Allocator for Bump {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.try_alloc_layout(layout)
+    }
+
+    /// Memory is only reclaimed by [`Bump::reset`].
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+/// A wrapper around a [`GlobalAlloc`] counting what is allocated through it.
+///
+/// Besides the number of live allocations and bytes it records the peak
+/// number of live bytes, and can be told to fail an upcoming allocation to
+/// exercise out of memory paths.
+///
+/// It can be registered as the `#[global_allocator]`, or handed to a container
+/// directly as an [`Allocator`].
+///
+/// ```
+/// use nomicon::{alloc::TrackingAlloc, Vec};
+///
+/// let tracking = TrackingAlloc::system();
+/// let mut v = Vec::with_capacity_in(4, &tracking);
+/// v.extend_from_slice(&[1u32, 2, 3, 4]);
+/// assert_eq!(tracking.live_allocations(), 1);
+/// assert_eq!(tracking.live_bytes(), 16);
+///
+/// drop(v);
+/// assert_eq!(tracking.live_bytes(), 0);
+/// assert_eq!(tracking.peak_bytes(), 16);
+/// ```
+///
+/// ```no_run
+/// use nomicon::alloc::TrackingAlloc;
+///
+/// #[global_allocator]
+/// static ALLOC: TrackingAlloc = TrackingAlloc::system();
+/// ```
+#[derive(Debug, Default)]
+pub struct TrackingAlloc<A = System> {
+    inner: A,
+    allocations: AtomicUsize,
+    bytes: AtomicUsize,
+    peak: AtomicUsize,
+    /// The number of calls left until one fails, zero when no failure is
+    /// scheduled.
+    fail_in: AtomicUsize,
+}
+
+impl TrackingAlloc {
+    /// Returns a tracker around the system allocator.
+    pub const fn system() -> Self {
+        Self::new(System)
+    }
+}
+
+impl<A> TrackingAlloc<A> {
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner,
+            allocations: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            fail_in: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of allocations that have not been freed.
+    pub fn live_allocations(&self) -> usize {
+        self.allocations.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes that have not been freed.
+    pub fn live_bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the largest number of live bytes since creation or the last
+    /// [`TrackingAlloc::reset_peak`].
+    pub fn peak_bytes(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_peak(&self) {
+        self.peak.store(self.live_bytes(), Ordering::Relaxed);
+    }
+
+    /// Make the `n`th allocation from now on fail, counting from 1.
+    ///
+    /// Passing 0 cancels a scheduled failure. Growing an allocation counts as
+    /// an allocation.
+    pub fn fail_at(&self, n: usize) {
+        self.fail_in.store(n, Ordering::Relaxed);
+    }
+
+    /// Returns true if the scheduled failure is due.
+    fn should_fail(&self) -> bool {
+        self.fail_in
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            == Ok(1)
+    }
+
+    fn record_alloc(&self, size: usize) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.add_bytes(size);
+    }
+
+    fn record_dealloc(&self, size: usize) {
+        self.allocations.fetch_sub(1, Ordering::Relaxed);
+        self.bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    fn add_bytes(&self, size: usize) {
+        let live = self.bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(live, Ordering::Relaxed);
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.should_fail() {
+            return std::ptr::null_mut();
+        }
+        let ptr = unsafe { self.inner.alloc(layout) };
+        if !ptr.is_null() {
+            self.record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if self.should_fail() {
+            return std::ptr::null_mut();
+        }
+        let ptr = unsafe { self.inner.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            self.record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.inner.dealloc(ptr, layout) };
+        self.record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if self.should_fail() {
+            return std::ptr::null_mut();
+        }
+        let new = unsafe { self.inner.realloc(ptr, layout, new_size) };
+        if !new.is_null() {
+            // The block moved or changed size in place, either way it is still
+            // a single live allocation.
+            self.bytes.fetch_sub(layout.size(), Ordering::Relaxed);
+            self.add_bytes(new_size);
+        }
+        new
+    }
+}
+
+unsafe impl<A: GlobalAlloc> Allocator for TrackingAlloc<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        NonNull::new(unsafe { GlobalAlloc::alloc(self, layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { GlobalAlloc::dealloc(self, ptr.as_ptr(), layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old: Layout,
+        new: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let ptr = unsafe { GlobalAlloc::realloc(self, ptr.as_ptr(), old, new.size()) };
+        NonNull::new(ptr).ok_or(AllocError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn alignment() {
+        let bump = Bump::new();
+        bump.alloc(1u8);
+        let wide = bump.alloc(2u128);
+        assert!((wide as *mut u128).is_aligned());
+        bump.alloc_slice(&[1u8; 3]);
+        let n = bump.alloc(7u32);
+        assert!((n as *mut u32).is_aligned());
+        assert_eq!(*wide, 2);
+    }
+
+    #[test]
+    fn grows_and_resets() {
+        let mut bump = Bump::new();
+        let values = (0..10_000u64).map(|n| &*bump.alloc(n)).collect::<Vec<_>>();
+        assert!(values.iter().copied().eq(&(0..10_000).collect::<Vec<_>>()));
+        let big = bump.alloc_slice(&[0u8; FIRST_CHUNK * 4]);
+        assert_eq!(big.len(), FIRST_CHUNK * 4);
+
+        let before = bump.allocated_bytes();
+        bump.reset();
+        let after = bump.allocated_bytes();
+        assert!(after < before);
+        for n in 0..100u64 {
+            bump.alloc(n);
+        }
+        assert_eq!(bump.allocated_bytes(), after);
+    }
+
+    #[test]
+    fn tracks_and_fails() {
+        let tracking = TrackingAlloc::system();
+        let mut v = crate::Vec::new_in(&tracking);
+        for n in 0..100u64 {
+            v.push(n);
+        }
+        assert_eq!(tracking.live_allocations(), 1);
+        assert_eq!(tracking.live_bytes(), v.capacity() * 8);
+        drop(v);
+        assert_eq!(tracking.live_allocations(), 0);
+        assert_eq!(tracking.live_bytes(), 0);
+        assert_eq!(tracking.peak_bytes(), 128 * 8);
+
+        let layout = Layout::new::<u64>();
+        tracking.fail_at(2);
+        let first = tracking.allocate(layout).unwrap();
+        assert_eq!(tracking.allocate(layout), Err(AllocError));
+        let third = tracking.allocate(layout).unwrap();
+        unsafe {
+            tracking.deallocate(first, layout);
+            tracking.deallocate(third, layout);
+        }
+        assert_eq!(tracking.live_allocations(), 0);
+    }
+}
@@ -1,30 +1,96 @@
 #![allow(unused)]
 
 use std::{
+    alloc::Layout,
+    borrow::Borrow,
     cell::UnsafeCell,
-    ptr::NonNull,
+    hash::{Hash, Hasher},
     sync::atomic::{AtomicUsize, Ordering},
 };
 
+use crate::{
+    alloc::{AllocError, Allocator, Global},
+    ptr::Unique,
+};
+
+/// The handles share the allocation and each can reach the value from its
+/// own thread, so it is Send and Sync only when the value is both.
+///
+/// ```compile_fail
+/// use nomicon::{arc::Arc, cell::Cell};
+///
+/// let shared = Arc::new(Cell::new(0));
+/// let clone = Arc::clone(&shared);
+/// std::thread::spawn(move || clone.set(1));
+/// shared.set(2);
+/// ```
 pub struct Arc<T> {
-    inner: NonNull<ArcInner<T>>,
+    inner: Unique<ArcInner<T>>,
 }
 
 impl<T> Arc<T> {
     pub fn new(value: T) -> Self {
         Self {
-            inner: unsafe {
-                let ptr = Box::into_raw(Box::new(ArcInner::new(value)));
-                NonNull::new_unchecked(ptr)
-            },
+            inner: Unique::from(Box::new(ArcInner::new(value))),
+        }
+    }
+
+    /// Returns an error instead of aborting if the allocation fails.
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        // Allocated the way Box would, the allocation is freed through one.
+        let inner = Global
+            .allocate(Layout::new::<ArcInner<T>>())?
+            .cast::<ArcInner<T>>();
+        unsafe { inner.as_ptr().write(ArcInner::new(value)) };
+        Ok(Self {
+            inner: Unique::from_non_null(inner),
+        })
+    }
+
+    /// Consume the handle, returning a pointer to the value that keeps its
+    /// count. The handle can be rebuilt through [`Arc::from_raw`].
+    pub fn into_raw(this: Self) -> *const T {
+        let this = std::mem::ManuallyDrop::new(this);
+        // The value is the first field of the repr(C) inner.
+        this.inner.as_ptr().cast_const().cast()
+    }
+
+    /// Rebuild a handle from a pointer returned by [`Arc::into_raw`].
+    ///
+    /// # Safety
+    /// `ptr` came from [`Arc::into_raw`] of the same `T`, and every pointer
+    /// is rebuilt at most once.
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        Self {
+            inner: unsafe { Unique::new_unchecked(ptr.cast_mut().cast()) },
         }
     }
 
+    /// Panic if the count could not belong to a live handle.
+    #[cfg(feature = "debug-invariants")]
+    pub fn assert_invariants(&self) {
+        let count = self.count();
+        assert!(count != 0, "Arc alive with a count of zero");
+        // Far more handles than could exist, a decrement went below zero.
+        assert!(
+            count <= isize::MAX as usize,
+            "Arc count {count} underflowed"
+        );
+    }
+
+    /// Runs [`Arc::assert_invariants`] in debug builds with the
+    /// `debug-invariants` feature.
+    fn check_invariants(&self) {
+        #[cfg(all(feature = "debug-invariants", debug_assertions))]
+        self.assert_invariants();
+    }
+
     fn increment(&self) {
         unsafe { self.inner.as_ref() }.increment()
     }
 
-    fn decrement(&self) {
+    /// Returns the count before decrementing.
+    fn decrement(&self) -> usize {
         unsafe { self.inner.as_ref() }.decrement()
     }
 
@@ -33,9 +99,6 @@ impl<T> Arc<T> {
     }
 }
 
-unsafe impl<T: Send> Send for Arc<T> {}
-unsafe impl<T: Sync> Sync for Arc<T> {}
-
 impl<T> std::ops::Deref for Arc<T> {
     type Target = T;
 
@@ -46,6 +109,7 @@ impl<T> std::ops::Deref for Arc<T> {
 
 impl<T> Clone for Arc<T> {
     fn clone(&self) -> Self {
+        self.check_invariants();
         self.increment();
         Self { inner: self.inner }
     }
@@ -53,13 +117,56 @@ impl<T> Clone for Arc<T> {
 
 impl<T> Drop for Arc<T> {
     fn drop(&mut self) {
-        self.decrement();
-        if self.count() == 0 {
+        // Checking the result of the decrement itself, rather than loading the
+        // count afterwards, ensures only the last handle frees the allocation.
+        if self.decrement() == 1 {
+            std::sync::atomic::fence(Ordering::Acquire);
             std::mem::drop(unsafe { Box::from_raw(self.inner.as_ptr()) })
         }
     }
 }
 
+impl<T> AsRef<T> for Arc<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+/// Hashes and compares like the value, so the value can look up a key.
+impl<T> Borrow<T> for Arc<T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: PartialEq> PartialEq for Arc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for Arc<T> {}
+
+impl<T: PartialOrd> PartialOrd for Arc<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord> Ord for Arc<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash> Hash for Arc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+/// The value comes first, so a pointer to it is a pointer to the inner.
+#[repr(C)]
 struct ArcInner<T> {
     value: T,
     count: AtomicUsize,
@@ -77,13 +184,13 @@ impl<T> ArcInner<T> {
         self.count.fetch_add(1, Ordering::AcqRel);
     }
 
-    fn decrement(&self) {
-        self.count.fetch_sub(1, Ordering::AcqRel);
+    fn decrement(&self) -> usize {
+        self.count.fetch_sub(1, Ordering::Release)
     }
 }
 
-unsafe impl<T: Send> Send for ArcInner<T> {}
-unsafe impl<T: Sync> Sync for ArcInner<T> {}
+unsafe impl<T: Send + Sync> Send for ArcInner<T> {}
+unsafe impl<T: Send + Sync> Sync for ArcInner<T> {}
 
 #[cfg(test)]
 mod test {
@@ -100,6 +207,17 @@ mod test {
         assert_eq!(cloned.count(), 1);
     }
 
+    #[test]
+    fn raw_round_trip() {
+        let arc = Arc::new(String::from("raw"));
+        let ptr = Arc::into_raw(Arc::clone(&arc));
+        assert_eq!(unsafe { &*ptr }, "raw");
+        let rebuilt = unsafe { Arc::from_raw(ptr) };
+        assert_eq!(arc.count(), 2);
+        drop(rebuilt);
+        assert_eq!(arc.count(), 1);
+    }
+
     #[test]
     fn threads() {
         let arc = Arc::new(String::from("Hello, World"));
@@ -0,0 +1,310 @@
+use std::{
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut, Range, RangeBounds},
+    ptr::{self, NonNull},
+};
+
+/// A vector with a fixed capacity of `N`, storing its elements inline.
+pub struct ArrayVec<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Append `item` to the back of the vector.
+    ///
+    /// # Panics
+    /// If the vector is full.
+    pub fn push(&mut self, item: T) {
+        if self.try_push(item).is_err() {
+            panic!("ArrayVec is full")
+        }
+    }
+
+    /// Append `item` to the back of the vector, handing it back if the vector
+    /// is full.
+    ///
+    /// ```
+    /// use nomicon::ArrayVec;
+    ///
+    /// let mut v = ArrayVec::<u8, 1>::new();
+    /// assert_eq!(v.try_push(1), Ok(()));
+    /// assert_eq!(v.try_push(2), Err(2));
+    /// ```
+    pub fn try_push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        self.buf[self.len].write(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY
+        // * Every slot below the old len is initialized, and the len has been
+        //   decremented so the slot is never read again.
+        Some(unsafe { self.buf[self.len].assume_init_read() })
+    }
+
+    /// Drop every element, keeping the (inline) storage.
+    pub fn clear(&mut self) {
+        let elems: *mut [T] = self.as_mut_slice();
+        // SAFETY
+        // * The len is reset before dropping so a panicking destructor leaks
+        //   rather than double drops.
+        unsafe {
+            self.len = 0;
+            ptr::drop_in_place(elems);
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY
+        // * The first self.len elements are initialized.
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY
+        // * The first self.len elements are initialized.
+        unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+
+    fn as_ptr(&self) -> *const T {
+        self.buf.as_ptr().cast()
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut T {
+        self.buf.as_mut_ptr().cast()
+    }
+
+    /// Remove the elements in `range`, returning them through an iterator.
+    ///
+    /// Elements after the range are shifted down once the [`Drain`] is
+    /// dropped. Any elements not yielded by the iterator are dropped with it.
+    ///
+    /// # Panics
+    /// If the range is out of bounds.
+    ///
+    /// ```
+    /// use nomicon::ArrayVec;
+    ///
+    /// let mut v = ArrayVec::<u8, 4>::new();
+    /// v.push(1);
+    /// v.push(2);
+    /// v.push(3);
+    /// let drained = v.drain(..2).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(drained, [1, 2]);
+    /// assert_eq!(&*v, &[3]);
+    /// ```
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, N> {
+        let Range { start, end } = crate::slice::range(range, self.len);
+
+        let tail_len = self.len - end;
+        // Pretend the drained range and tail are gone, if the Drain is leaked
+        // we only leak elements instead of exposing moved out slots.
+        self.len = start;
+        Drain {
+            vec: NonNull::from(&mut *self),
+            start,
+            end,
+            tail_start: end,
+            tail_len,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize> Default for ArrayVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for ArrayVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut new = Self::new();
+        for item in self.iter() {
+            new.push(item.clone());
+        }
+        new
+    }
+}
+
+impl<T: std::fmt::Debug, const N: usize> std::fmt::Debug for ArrayVec<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A draining iterator over a range of an [`ArrayVec`].
+///
+/// This type can be constructed through [`ArrayVec::drain`].
+pub struct Drain<'a, T, const N: usize> {
+    vec: NonNull<ArrayVec<T, N>>,
+    start: usize,
+    end: usize,
+    tail_start: usize,
+    tail_len: usize,
+    _marker: std::marker::PhantomData<&'a mut ArrayVec<T, N>>,
+}
+
+impl<'a, T, const N: usize> Iterator for Drain<'a, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start == self.end {
+            return None;
+        }
+        // SAFETY
+        // * Slots in start..end are initialized and not covered by the
+        //   vector's len, so each is read exactly once.
+        let item = unsafe { (*self.vec.as_ptr()).as_ptr().add(self.start).read() };
+        self.start += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for Drain<'a, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        // SAFETY
+        // * See Drain::next.
+        Some(unsafe { (*self.vec.as_ptr()).as_ptr().add(self.end).read() })
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for Drain<'a, T, N> {}
+
+impl<'a, T, const N: usize> Drop for Drain<'a, T, N> {
+    fn drop(&mut self) {
+        /// Shifts the tail down even if dropping a remaining element panics.
+        struct MoveTail<'r, 'a, T, const N: usize>(&'r mut Drain<'a, T, N>);
+
+        impl<'r, 'a, T, const N: usize> Drop for MoveTail<'r, 'a, T, N> {
+            fn drop(&mut self) {
+                let drain = &mut *self.0;
+                // SAFETY
+                // * The Drain holds the only access to the vector.
+                // * The tail is initialized and within capacity.
+                unsafe {
+                    let vec = drain.vec.as_mut();
+                    let start = vec.len;
+                    let base = vec.as_mut_ptr();
+                    ptr::copy(base.add(drain.tail_start), base.add(start), drain.tail_len);
+                    vec.len = start + drain.tail_len;
+                }
+            }
+        }
+
+        let guard = MoveTail(self);
+        for _ in &mut *guard.0 {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let mut v = ArrayVec::<String, 2>::new();
+        v.push("a".into());
+        v.push("b".into());
+        assert!(v.is_full());
+        assert_eq!(v.try_push("c".into()), Err("c".to_string()));
+        assert_eq!(v.pop().as_deref(), Some("b"));
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn push_full() {
+        let mut v = ArrayVec::<u8, 0>::new();
+        v.push(1);
+    }
+
+    #[test]
+    fn drain_middle() {
+        let mut v = ArrayVec::<u8, 5>::new();
+        (0..5).for_each(|n| v.push(n));
+        let mut drain = v.drain(1..4);
+        assert_eq!(drain.next(), Some(1));
+        assert_eq!(drain.next_back(), Some(3));
+        std::mem::drop(drain);
+        assert_eq!(&*v, &[0, 4]);
+    }
+
+    #[test]
+    fn drops_everything() {
+        let rc = Rc::new(());
+        {
+            let mut v = ArrayVec::<Rc<()>, 8>::new();
+            (0..8).for_each(|_| v.push(Rc::clone(&rc)));
+            let mut drain = v.drain(2..6);
+            std::mem::drop(drain.next());
+            std::mem::drop(drain);
+            assert_eq!(Rc::strong_count(&rc), 5);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,6 @@
+//! Primitives for coordinating async tasks, waiting on them suspends the task
+//! instead of blocking the thread.
+
+mod notify;
+
+pub use notify::{Notified, Notify};
@@ -0,0 +1,255 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use crate::sync::{Mutex, MutexGuard, PoisonError};
+
+struct State {
+    /// Kept by [`Notify::notify_one`] when no task is waiting.
+    permit: bool,
+    /// Bumped by every [`Notify::notify_waiters`].
+    generation: usize,
+    next_id: usize,
+    /// Suspended tasks, in the order they started waiting.
+    waiters: VecDeque<(usize, Waker)>,
+}
+
+/// Wakes tasks waiting on it, without any data of its own.
+///
+/// [`Notify::notify_one`] wakes a single waiting task, or stores a permit if
+/// none is waiting so the next [`Notify::notified`] completes right away. At
+/// most one permit is stored. [`Notify::notify_waiters`] wakes every task
+/// that called [`Notify::notified`] before it, and stores nothing.
+///
+/// Being removed from the queue of waiters is what tells a task it was
+/// woken.
+///
+/// ```
+/// use nomicon::{async_sync::Notify, executor::block_on};
+///
+/// let notify = Notify::new();
+/// // The permit is kept for the next waiter.
+/// notify.notify_one();
+/// block_on(notify.notified());
+/// ```
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                permit: false,
+                generation: 0,
+                next_id: 0,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns a future completing once notified.
+    ///
+    /// The future counts as waiting for [`Notify::notify_waiters`] from the
+    /// moment it is created, and for [`Notify::notify_one`] from its first
+    /// poll.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            generation: self.lock().generation,
+            waiting: None,
+            done: false,
+        }
+    }
+
+    /// Wake the longest waiting task, or store a permit if none is waiting.
+    pub fn notify_one(&self) {
+        let waker = {
+            let mut state = self.lock();
+            match state.waiters.pop_front() {
+                Some((_, waker)) => waker,
+                None => {
+                    state.permit = true;
+                    return;
+                }
+            }
+        };
+        waker.wake();
+    }
+
+    /// Wake every waiting task, without storing a permit.
+    pub fn notify_waiters(&self) {
+        let waiters = {
+            let mut state = self.lock();
+            state.generation = state.generation.wrapping_add(1);
+            std::mem::take(&mut state.waiters)
+        };
+        // Waking outside the lock, wakers may poll right away.
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A task waiting on a [`Notify`].
+///
+/// Dropping it after being picked by [`Notify::notify_one`], but before
+/// completing, passes the notification on.
+///
+/// This type can be constructed through [`Notify::notified`].
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    generation: usize,
+    /// The id of the queue entry, while in the queue.
+    waiting: Option<usize>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done {
+            return Poll::Ready(());
+        }
+        let notify = self.notify;
+        let mut state = notify.lock();
+        let notified = match self.waiting {
+            None if state.generation != self.generation => true,
+            None if state.permit => {
+                state.permit = false;
+                true
+            }
+            None => {
+                let id = state.next_id;
+                state.next_id = state.next_id.wrapping_add(1);
+                state.waiters.push_back((id, cx.waker().clone()));
+                self.waiting = Some(id);
+                false
+            }
+            Some(id) => match state.waiters.iter_mut().find(|(other, _)| *other == id) {
+                Some((_, waker)) => {
+                    waker.clone_from(cx.waker());
+                    false
+                }
+                None => true,
+            },
+        };
+        drop(state);
+        if notified {
+            self.waiting = None;
+            self.done = true;
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiting else {
+            return;
+        };
+        let mut state = self.notify.lock();
+        if let Some(index) = state.waiters.iter().position(|(other, _)| *other == id) {
+            state.waiters.remove(index);
+        } else if state.generation == self.generation {
+            // Picked by notify_one, which has to reach someone.
+            drop(state);
+            self.notify.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{arc::Arc, task::ArcWake};
+
+    struct Counter(AtomicUsize);
+
+    impl ArcWake for Counter {
+        fn wake_by_ref(this: &Arc<Self>) {
+            this.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn poll(notified: &mut Notified<'_>, counter: &Arc<Counter>) -> bool {
+        let waker = crate::task::waker(Arc::clone(counter));
+        Pin::new(notified)
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready()
+    }
+
+    fn counter() -> Arc<Counter> {
+        Arc::new(Counter(AtomicUsize::new(0)))
+    }
+
+    #[test]
+    fn one_permit_is_kept() {
+        let notify = Notify::new();
+        notify.notify_one();
+        notify.notify_one();
+        let counter = counter();
+        assert!(poll(&mut notify.notified(), &counter));
+
+        let mut waiting = notify.notified();
+        assert!(!poll(&mut waiting, &counter));
+        notify.notify_one();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert!(poll(&mut waiting, &counter));
+    }
+
+    #[test]
+    fn notify_waiters_wakes_created_futures() {
+        let notify = Notify::new();
+        let counter = counter();
+        let mut polled = notify.notified();
+        let mut created = notify.notified();
+        assert!(!poll(&mut polled, &counter));
+        notify.notify_waiters();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert!(poll(&mut created, &counter));
+        assert!(poll(&mut polled, &counter));
+        // Nothing is stored for later waiters.
+        assert!(!poll(&mut notify.notified(), &counter));
+    }
+
+    #[test]
+    fn dropping_passes_notification_on() {
+        let notify = Notify::new();
+        let (first, second) = (counter(), counter());
+        let mut a = notify.notified();
+        let mut b = notify.notified();
+        assert!(!poll(&mut a, &first));
+        assert!(!poll(&mut b, &second));
+        notify.notify_one();
+        assert_eq!(first.0.load(Ordering::Relaxed), 1);
+        drop(a);
+        assert_eq!(second.0.load(Ordering::Relaxed), 1);
+        assert!(poll(&mut b, &second));
+
+        // A waiter dropped before being picked leaves no trace.
+        let mut c = notify.notified();
+        assert!(!poll(&mut c, &first));
+        drop(c);
+        notify.notify_one();
+        assert!(poll(&mut notify.notified(), &first));
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    borrow::{Borrow, ToOwned},
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+/// A clone-on-write smart pointer.
+///
+/// Holds either a borrowed `&'a B` or the owned form of `B`, only allocating
+/// when mutable access is requested through [`Cow::to_mut`].
+pub enum Cow<'a, B>
+where
+    B: ?Sized + ToOwned + 'a,
+{
+    Borrowed(&'a B),
+    Owned(<B as ToOwned>::Owned),
+}
+
+impl<'a, B> Cow<'a, B>
+where
+    B: ?Sized + ToOwned,
+{
+    /// Returns true if the data is borrowed.
+    pub const fn is_borrowed(&self) -> bool {
+        matches!(self, Cow::Borrowed(_))
+    }
+
+    /// Returns true if the data is owned.
+    pub const fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+
+    /// Return a mutable reference to the owned form of the data, cloning the
+    /// borrowed data if needed.
+    ///
+    /// ```
+    /// use nomicon::borrow::Cow;
+    ///
+    /// let mut c = Cow::Borrowed("Hello");
+    /// c.to_mut().push_str(", World");
+    ///
+    /// assert!(c.is_owned());
+    /// assert_eq!(&*c, "Hello, World");
+    /// ```
+    pub fn to_mut(&mut self) -> &mut <B as ToOwned>::Owned {
+        if let Cow::Borrowed(borrowed) = *self {
+            *self = Cow::Owned(borrowed.to_owned());
+        }
+        match self {
+            Cow::Owned(owned) => owned,
+            Cow::Borrowed(_) => unreachable!(),
+        }
+    }
+
+    /// Extract the owned data, cloning the borrowed data if needed.
+    ///
+    /// ```
+    /// use nomicon::borrow::Cow;
+    ///
+    /// let c: Cow<'_, [u8]> = Cow::Borrowed(&[1, 2, 3]);
+    /// assert_eq!(c.into_owned(), vec![1, 2, 3]);
+    /// ```
+    pub fn into_owned(self) -> <B as ToOwned>::Owned {
+        match self {
+            Cow::Borrowed(borrowed) => borrowed.to_owned(),
+            Cow::Owned(owned) => owned,
+        }
+    }
+}
+
+impl<'a, B> Deref for Cow<'a, B>
+where
+    B: ?Sized + ToOwned,
+{
+    type Target = B;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Cow::Borrowed(borrowed) => borrowed,
+            Cow::Owned(owned) => owned.borrow(),
+        }
+    }
+}
+
+impl<'a, B> Clone for Cow<'a, B>
+where
+    B: ?Sized + ToOwned,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Cow::Borrowed(borrowed) => Cow::Borrowed(borrowed),
+            Cow::Owned(owned) => Cow::Owned(owned.borrow().to_owned()),
+        }
+    }
+}
+
+impl<'a, B> Default for Cow<'a, B>
+where
+    B: ?Sized + ToOwned,
+    <B as ToOwned>::Owned: Default,
+{
+    fn default() -> Self {
+        Cow::Owned(Default::default())
+    }
+}
+
+impl<'a, B> fmt::Debug for Cow<'a, B>
+where
+    B: ?Sized + ToOwned + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, B> fmt::Display for Cow<'a, B>
+where
+    B: ?Sized + ToOwned + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<'a, 'b, B, C> PartialEq<Cow<'b, C>> for Cow<'a, B>
+where
+    B: ?Sized + ToOwned + PartialEq<C>,
+    C: ?Sized + ToOwned,
+{
+    fn eq(&self, other: &Cow<'b, C>) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, B> Eq for Cow<'a, B> where B: ?Sized + ToOwned + Eq {}
+
+impl<'a, B> PartialOrd for Cow<'a, B>
+where
+    B: ?Sized + ToOwned + PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<'a, B> Ord for Cow<'a, B>
+where
+    B: ?Sized + ToOwned + Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<'a, B> Hash for Cow<'a, B>
+where
+    B: ?Sized + ToOwned + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<'a, B> AsRef<B> for Cow<'a, B>
+where
+    B: ?Sized + ToOwned,
+{
+    fn as_ref(&self) -> &B {
+        self
+    }
+}
+
+/// Hashes and compares like the borrowed form, whichever variant it is.
+impl<'a, B> Borrow<B> for Cow<'a, B>
+where
+    B: ?Sized + ToOwned,
+{
+    fn borrow(&self) -> &B {
+        self
+    }
+}
+
+impl<'a> From<&'a str> for Cow<'a, str> {
+    fn from(value: &'a str) -> Self {
+        Cow::Borrowed(value)
+    }
+}
+
+impl<'a> From<&'a String> for Cow<'a, str> {
+    fn from(value: &'a String) -> Self {
+        Cow::Borrowed(value.as_str())
+    }
+}
+
+impl<'a> From<String> for Cow<'a, str> {
+    fn from(value: String) -> Self {
+        Cow::Owned(value)
+    }
+}
+
+impl<'a, T: Clone> From<&'a [T]> for Cow<'a, [T]> {
+    fn from(value: &'a [T]) -> Self {
+        Cow::Borrowed(value)
+    }
+}
+
+impl<'a, T: Clone> From<&'a std::vec::Vec<T>> for Cow<'a, [T]> {
+    fn from(value: &'a std::vec::Vec<T>) -> Self {
+        Cow::Borrowed(value.as_slice())
+    }
+}
+
+impl<'a, T: Clone> From<std::vec::Vec<T>> for Cow<'a, [T]> {
+    fn from(value: std::vec::Vec<T>) -> Self {
+        Cow::Owned(value)
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for String {
+    fn from(value: Cow<'a, str>) -> Self {
+        value.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn either_variant_looks_up_keys() {
+        let mut map = std::collections::HashMap::new();
+        map.insert(Cow::Owned(String::from("owned")), 1);
+        map.insert(Cow::Borrowed("borrowed"), 2);
+        assert_eq!(map.get("owned"), Some(&1));
+        assert_eq!(map.get("borrowed"), Some(&2));
+    }
+
+    fn strip_spaces(input: &str) -> Cow<'_, str> {
+        if input.contains(' ') {
+            Cow::Owned(input.replace(' ', ""))
+        } else {
+            Cow::Borrowed(input)
+        }
+    }
+
+    #[test]
+    fn borrowed_until_mutated() {
+        assert!(strip_spaces("abc").is_borrowed());
+        let mut c = strip_spaces("a b c");
+        assert!(c.is_owned());
+        assert_eq!(c, Cow::Borrowed("abc"));
+
+        let mut b: Cow<'_, [u8]> = Cow::from(&[1, 2][..]);
+        b.to_mut().push(3);
+        assert!(b.is_owned());
+        assert_eq!(&*b, &[1, 2, 3]);
+
+        c.to_mut().clear();
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn conversions() {
+        let owned = String::from("Hello");
+        let c = Cow::from(&owned);
+        assert!(c.is_borrowed());
+        assert_eq!(String::from(c.clone()), owned);
+        assert_eq!(Cow::from(owned.clone()), c);
+        assert_eq!(format!("{c} {c:?}"), "Hello \"Hello\"");
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    alloc::Layout,
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::{
+    alloc::{AllocError, Allocator, Global},
+    ptr::Unique,
+};
+
+/// An owned value on the heap, allocated from an [`Allocator`].
+///
+/// Zero sized values are never allocated.
+///
+/// ```
+/// use nomicon::{alloc::Bump, boxed::Box};
+///
+/// let bump = Bump::new();
+/// let mut boxed = Box::new_in([1, 2, 3], &bump);
+/// boxed[0] = 10;
+/// assert_eq!(*boxed, [10, 2, 3]);
+/// assert_eq!(Box::into_inner(boxed), [10, 2, 3]);
+/// ```
+pub struct Box<T, A: Allocator = Global> {
+    ptr: Unique<T>,
+    alloc: A,
+}
+
+impl<T> Box<T> {
+    pub fn new(value: T) -> Self {
+        Self::new_in(value, Global)
+    }
+
+    /// Returns an error instead of aborting if the allocation fails.
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        Self::try_new_in(value, Global)
+    }
+}
+
+impl<T, A: Allocator> Box<T, A> {
+    /// Move `value` into memory from `alloc`.
+    pub fn new_in(value: T, alloc: A) -> Self {
+        match Self::try_new_in(value, alloc) {
+            Ok(boxed) => boxed,
+            Err(AllocError) => std::alloc::handle_alloc_error(Layout::new::<T>()),
+        }
+    }
+
+    /// Returns an error instead of aborting if the allocation fails.
+    pub fn try_new_in(value: T, alloc: A) -> Result<Self, AllocError> {
+        let layout = Layout::new::<T>();
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            alloc.allocate(layout)?.cast::<T>()
+        };
+        unsafe { ptr.as_ptr().write(value) };
+        Ok(Self {
+            ptr: Unique::from_non_null(ptr),
+            alloc,
+        })
+    }
+
+    /// Move the value out, freeing the allocation.
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        // SAFETY
+        // * `this` is never dropped, the value and the allocator are read
+        //   out exactly once.
+        unsafe {
+            let value = this.ptr.as_ptr().read();
+            let alloc = std::ptr::read(&this.alloc);
+            deallocate(this.ptr, &alloc);
+            value
+        }
+    }
+
+    pub const fn allocator(this: &Self) -> &A {
+        &this.alloc
+    }
+}
+
+/// Free the allocation behind `ptr` without dropping the value.
+///
+/// # Safety
+/// `ptr` must come from a [`Box`] allocated by `alloc`, that is not used
+/// again.
+unsafe fn deallocate<T>(ptr: Unique<T>, alloc: &impl Allocator) {
+    let layout = Layout::new::<T>();
+    if layout.size() != 0 {
+        unsafe { alloc.deallocate(ptr.as_non_null().cast(), layout) }
+    }
+}
+
+impl<T, A: Allocator> Deref for Box<T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T, A: Allocator> DerefMut for Box<T, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T, A: Allocator> Drop for Box<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            self.ptr.as_ptr().drop_in_place();
+            deallocate(self.ptr, &self.alloc);
+        }
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for Box<T, A> {
+    fn clone(&self) -> Self {
+        Self::new_in((**self).clone(), self.alloc.clone())
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for Box<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq, A: Allocator> PartialEq for Box<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for Box<T, A> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::alloc::{Bump, TrackingAlloc};
+
+    #[test]
+    fn allocates_from_the_arena() {
+        let bump = Bump::new();
+        let a = Box::new_in(1u64, &bump);
+        let b = Box::new_in(String::from("bump"), &bump);
+        assert_eq!(bump.allocated_bytes(), 4096);
+        assert_eq!((*a, b.as_str()), (1, "bump"));
+        assert!(std::ptr::eq(*Box::allocator(&b), &bump));
+
+        let flag = std::rc::Rc::new(());
+        drop(Box::new_in(std::rc::Rc::clone(&flag), &bump));
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn frees_and_fails() {
+        let tracking = TrackingAlloc::system();
+        let boxed = Box::new_in([1u32; 4], &tracking);
+        assert_eq!(tracking.live_bytes(), 16);
+        assert_eq!(Box::into_inner(boxed), [1; 4]);
+        assert_eq!(tracking.live_allocations(), 0);
+
+        drop(Box::new_in((), &tracking));
+        assert_eq!(tracking.peak_bytes(), 16);
+        tracking.fail_at(1);
+        assert_eq!(Box::try_new_in(1u8, &tracking), Err(AllocError));
+    }
+}
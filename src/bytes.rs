@@ -0,0 +1,423 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut, Range, RangeBounds},
+    ptr::NonNull,
+};
+
+use crate::{arc::Arc, slice, Vec};
+
+/// A buffer shared between every [`Bytes`] and [`BytesMut`] split from it.
+///
+/// The vector only owns the allocation, it is never accessed after `base` is
+/// taken from it. Every handle reads and writes its own window of the buffer
+/// through `base`, so no reference to the whole buffer ever exists.
+struct Shared {
+    _vec: Vec<u8>,
+    base: NonNull<u8>,
+    cap: usize,
+}
+
+impl Shared {
+    /// Returns the shared buffer and the length of its initialized prefix.
+    fn new(mut vec: Vec<u8>) -> (Arc<Self>, usize) {
+        let len = vec.len();
+        let cap = vec.capacity();
+        // SAFETY
+        // * A vector's pointer is never null.
+        let base = unsafe { NonNull::new_unchecked(vec.as_mut_ptr()) };
+        let shared = Self {
+            _vec: vec,
+            base,
+            cap,
+        };
+        (Arc::new(shared), len)
+    }
+}
+
+// SAFETY
+// * The buffer is only accessed through handles owning disjoint mutable
+//   windows or shared immutable windows.
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+/// A cheaply cloneable, immutable view into a shared byte buffer.
+///
+/// Cloning and slicing only bump a reference count, the bytes are never
+/// copied.
+///
+/// ```
+/// use nomicon::bytes::Bytes;
+///
+/// let mut b = Bytes::copy_from_slice(b"Hello, World");
+/// let hello = b.split_to(5);
+///
+/// assert_eq!(&*hello, b"Hello");
+/// assert_eq!(&*b.slice(2..), b"World");
+/// ```
+#[derive(Clone)]
+pub struct Bytes {
+    shared: Arc<Shared>,
+    off: usize,
+    len: usize,
+}
+
+impl Bytes {
+    pub fn new() -> Self {
+        Self::from(Vec::new())
+    }
+
+    pub fn copy_from_slice(data: &[u8]) -> Self {
+        let mut vec = Vec::with_capacity(data.len());
+        vec.extend_from_slice(data);
+        Self::from(vec)
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a view of the bytes in `range`, sharing the buffer.
+    ///
+    /// # Panics
+    /// If the range is out of bounds.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Self {
+        let Range { start, end } = slice::range(range, self.len);
+        Self {
+            shared: Arc::clone(&self.shared),
+            off: self.off + start,
+            len: end - start,
+        }
+    }
+
+    /// Split off and return the bytes before `at`, leaving `self` with the
+    /// bytes after.
+    ///
+    /// # Panics
+    /// If `at` is greater than the length.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let head = self.slice(..at);
+        self.off += at;
+        self.len -= at;
+        head
+    }
+
+    pub fn contains_byte(&self, byte: u8) -> bool {
+        slice::contains_byte(self, byte)
+    }
+
+    /// Returns the index of the first `byte`, searching a word at a time.
+    ///
+    /// ```
+    /// use nomicon::bytes::Bytes;
+    ///
+    /// let mut b = Bytes::copy_from_slice(b"key=value");
+    /// let at = b.position_byte(b'=').unwrap();
+    /// let key = b.split_to(at);
+    /// assert_eq!(&*key, b"key");
+    /// ```
+    pub fn position_byte(&self, byte: u8) -> Option<usize> {
+        slice::position_byte(self, byte)
+    }
+
+    /// Split off and return the bytes after `at`, leaving `self` with the bytes
+    /// before.
+    ///
+    /// # Panics
+    /// If `at` is greater than the length.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let tail = self.slice(at..);
+        self.len = at;
+        tail
+    }
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    /// Takes ownership of the vector's allocation without copying.
+    fn from(vec: Vec<u8>) -> Self {
+        let (shared, len) = Shared::new(vec);
+        Self {
+            shared,
+            off: 0,
+            len,
+        }
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY
+        // * The window is initialized and only ever read through Bytes.
+        unsafe {
+            let ptr = self.shared.base.as_ptr().add(self.off);
+            std::slice::from_raw_parts(ptr, self.len)
+        }
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Bytes {}
+
+impl PartialEq<[u8]> for Bytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A uniquely owned, growable window into a shared byte buffer.
+///
+/// Splitting hands out handles to disjoint parts of the same allocation, and
+/// [`BytesMut::freeze`] turns a handle into [`Bytes`], both without copying.
+///
+/// ```
+/// use nomicon::bytes::BytesMut;
+///
+/// let mut buf = BytesMut::with_capacity(64);
+/// buf.extend_from_slice(b"GET / HTTP/1.1\r\n");
+/// buf.extend_from_slice(b"Host: ");
+///
+/// let line = buf.split_to(16).freeze();
+/// assert_eq!(&*line, b"GET / HTTP/1.1\r\n");
+/// assert_eq!(&*buf, b"Host: ");
+/// ```
+pub struct BytesMut {
+    shared: Arc<Shared>,
+    off: usize,
+    len: usize,
+    /// The length of the window this handle owns, from `off`.
+    cap: usize,
+}
+
+impl BytesMut {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        let (shared, len) = Shared::new(Vec::with_capacity(cap));
+        let cap = shared.cap;
+        Self {
+            shared,
+            off: 0,
+            len,
+            cap,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Make room for at least `additional` more bytes.
+    ///
+    /// A handle never grows into a window owned by another handle, when its
+    /// own window is too small the bytes are copied into a fresh buffer.
+    pub fn reserve(&mut self, additional: usize) {
+        if self.cap - self.len >= additional {
+            return;
+        }
+        let cap = (self.len + additional).max(self.cap * 2);
+        let mut vec = Vec::with_capacity(cap);
+        vec.extend_from_slice(self);
+        let (shared, len) = Shared::new(vec);
+        *self = Self {
+            cap: shared.cap,
+            shared,
+            off: 0,
+            len,
+        };
+    }
+
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        self.reserve(data.len());
+        // SAFETY
+        // * We reserved room for data within our own window.
+        unsafe {
+            let dst = self.shared.base.as_ptr().add(self.off + self.len);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+        }
+        self.len += data.len();
+    }
+
+    pub fn put_u8(&mut self, byte: u8) {
+        self.extend_from_slice(&[byte]);
+    }
+
+    /// Split off and return the bytes before `at`, leaving `self` with the
+    /// bytes and spare capacity after.
+    ///
+    /// # Panics
+    /// If `at` is greater than the length.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split index out of bounds");
+        let head = Self {
+            shared: Arc::clone(&self.shared),
+            off: self.off,
+            len: at,
+            cap: at,
+        };
+        self.off += at;
+        self.len -= at;
+        self.cap -= at;
+        head
+    }
+
+    /// Split off and return the bytes and spare capacity after `at`, leaving
+    /// `self` with the bytes before.
+    ///
+    /// # Panics
+    /// If `at` is greater than the length.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split index out of bounds");
+        let tail = Self {
+            shared: Arc::clone(&self.shared),
+            off: self.off + at,
+            len: self.len - at,
+            cap: self.cap - at,
+        };
+        self.len = at;
+        self.cap = at;
+        tail
+    }
+
+    /// Convert into an immutable [`Bytes`] over the same window.
+    pub fn freeze(self) -> Bytes {
+        Bytes {
+            shared: self.shared,
+            off: self.off,
+            len: self.len,
+        }
+    }
+}
+
+impl Default for BytesMut {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for BytesMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY
+        // * The window is initialized up to len and owned by self.
+        unsafe {
+            let ptr = self.shared.base.as_ptr().add(self.off);
+            std::slice::from_raw_parts(ptr, self.len)
+        }
+    }
+}
+
+impl DerefMut for BytesMut {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY
+        // * The window is initialized up to len and exclusively owned by self.
+        unsafe {
+            let ptr = self.shared.base.as_ptr().add(self.off);
+            std::slice::from_raw_parts_mut(ptr, self.len)
+        }
+    }
+}
+
+impl AsRef<[u8]> for BytesMut {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for BytesMut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn zero_copy_views() {
+        let mut vec = Vec::new();
+        vec.extend_from_slice(b"abcdef");
+        let ptr = vec.as_ptr();
+        let mut bytes = Bytes::from(vec);
+        let tail = bytes.split_off(4);
+        assert_eq!(bytes.as_ptr(), ptr);
+        assert_eq!(&*tail, b"ef");
+        assert_eq!(bytes.slice(1..=2), *b"bc".as_slice());
+        assert_eq!(bytes.clone().split_to(0).len(), 0);
+    }
+
+    #[test]
+    fn split_windows_are_disjoint() {
+        let mut buf = BytesMut::with_capacity(8);
+        buf.extend_from_slice(b"1234");
+        let mut tail = buf.split_off(2);
+        // The head has no spare capacity left and must not overwrite "34".
+        buf.put_u8(b'x');
+        tail.extend_from_slice(b"56");
+        tail[0] = b'!';
+        assert_eq!(&*buf, b"12x");
+        assert_eq!(&*tail, b"!456");
+        assert_eq!(tail.freeze(), Bytes::copy_from_slice(b"!456"));
+    }
+
+    #[test]
+    fn shares_across_threads() {
+        let bytes = Bytes::copy_from_slice(&[7; 1024]);
+        let handles = (0..8)
+            .map(|n| {
+                let part = bytes.slice(n * 128..(n + 1) * 128);
+                thread::spawn(move || part.iter().map(|b| *b as usize).sum::<usize>())
+            })
+            .collect::<std::vec::Vec<_>>();
+        let total = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>();
+        assert_eq!(total, 7 * 1024);
+    }
+}
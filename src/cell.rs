@@ -3,6 +3,8 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
+use crate::{marker::PhantomUnsync, num::NonMaxUsize};
+
 /// A memory location that can be updated through a shared reference.
 #[derive(Debug, Default)]
 pub struct Cell<T> {
@@ -58,16 +60,24 @@ impl<T> From<T> for Cell<T> {
 pub struct RefCell<T> {
     value: UnsafeCell<T>,
     state: Cell<RefState>,
+    /// The state is not atomic, borrows can only be tracked on one thread.
+    _not_sync: PhantomUnsync,
 }
 
 impl<T> RefCell<T> {
     pub const fn new(value: T) -> Self {
         Self {
             value: UnsafeCell::new(value),
-            state: Cell::new(RefState::Unshared),
+            state: Cell::new(RefState::UNSHARED),
+            _not_sync: PhantomUnsync::new(),
         }
     }
 
+    /// Consume the cell, returning the value.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
     /// Return a mutable handle to the value.
     ///
     /// # Panics
@@ -89,7 +99,8 @@ impl<T> RefCell<T> {
     /// Return a shared reference to the value.
     ///
     /// # Panics
-    /// If the value is already borrowed through an exclusive reference.
+    /// If the value is already borrowed through an exclusive reference, or
+    /// the count of shared borrows is full.
     ///
     /// ```
     /// use nomicon::cell::RefCell;
@@ -105,11 +116,15 @@ impl<T> RefCell<T> {
     pub fn borrow(&self) -> Ref<'_, T> {
         match self.try_borrow() {
             Some(r) => r,
-            None => panic!("Already exclusively borrowed"),
+            None if self.state.get() == RefState::Exclusive => {
+                panic!("Already exclusively borrowed")
+            }
+            None => panic!("too many shared borrows"),
         }
     }
 
-    /// Return a shared reference to the value if no exclusive references exist.
+    /// Return a shared reference to the value if no exclusive references exist,
+    /// and the count of shared ones is not full.
     ///
     /// ```
     /// use nomicon::cell::RefCell;
@@ -125,15 +140,14 @@ impl<T> RefCell<T> {
     /// ```
     pub const fn try_borrow(&self) -> Option<Ref<'_, T>> {
         match self.state.get() {
-            RefState::Unshared => {
-                self.state.set(RefState::Shared(1));
-                Some(Ref { refcell: self })
-            }
             RefState::Exclusive => None,
-            RefState::Shared(count) => {
-                self.state.set(RefState::Shared(count + 1));
-                Some(Ref { refcell: self })
-            }
+            RefState::Shared(count) => match count.checked_add(1) {
+                Some(count) => {
+                    self.state.set(RefState::Shared(count));
+                    Some(Ref { refcell: self })
+                }
+                None => None,
+            },
         }
     }
 
@@ -157,7 +171,7 @@ impl<T> RefCell<T> {
     /// ```
     pub const fn try_borrow_mut(&self) -> Option<RefMut<'_, T>> {
         match self.state.get() {
-            RefState::Unshared => {
+            RefState::UNSHARED => {
                 self.state.set(RefState::Exclusive);
                 Some(RefMut { refcell: self })
             }
@@ -176,7 +190,7 @@ pub struct RefMut<'a, T> {
 
 impl<'a, T> Drop for RefMut<'a, T> {
     fn drop(&mut self) {
-        self.refcell.state.set(RefState::Unshared);
+        self.refcell.state.set(RefState::UNSHARED);
     }
 }
 
@@ -226,22 +240,45 @@ impl<'a, T> Deref for Ref<'a, T> {
 impl<'a, T> Drop for Ref<'a, T> {
     fn drop(&mut self) {
         match self.refcell.state.get() {
-            RefState::Unshared | RefState::Exclusive => unreachable!(),
-            RefState::Shared(1) => {
-                self.refcell.state.set(RefState::Unshared);
-            }
+            RefState::Exclusive => unreachable!(),
             RefState::Shared(count) => {
-                let new = RefState::Shared(count - 1);
-                self.refcell.state.set(new);
+                let count = count.checked_sub(1).expect("a shared borrow is live");
+                self.refcell.state.set(RefState::Shared(count));
             }
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+/// The size of a usize, the count leaves a niche for [`RefState::Exclusive`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum RefState {
-    #[default]
-    Unshared,
     Exclusive,
-    Shared(usize),
+    /// The number of live [`Ref`]s, zero while unborrowed.
+    Shared(NonMaxUsize),
+}
+
+impl RefState {
+    const UNSHARED: Self = Self::Shared(NonMaxUsize::ZERO);
+}
+
+impl Default for RefState {
+    fn default() -> Self {
+        Self::UNSHARED
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn saturated_count() {
+        let r = RefCell::new(1);
+        r.state.set(RefState::Shared(NonMaxUsize::MAX));
+        assert!(r.try_borrow().is_none());
+        assert_eq!(r.state.get(), RefState::Shared(NonMaxUsize::MAX));
+        assert!(r.try_borrow_mut().is_none());
+        r.state.set(RefState::UNSHARED);
+        assert_eq!(*r.borrow(), 1);
+    }
 }
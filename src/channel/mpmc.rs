@@ -0,0 +1,692 @@
+//! A bounded multi producer multi consumer channel.
+//!
+//! Values live in a fixed array of slots, each stamped with the lap of the
+//! index it is waiting for (Dmitry Vyukov's bounded queue). A sender claims
+//! a slot by moving the tail past it and a receiver by moving the head past
+//! it, so both sides only ever contend on a single compare and swap. Blocked
+//! operations spin for a short while, then sleep until the other side makes
+//! progress. Waiting threads are registered with the channel, so a
+//! [`Select`](super::Select) can wait on it alongside other channels, and
+//! the receiver can be polled as a [`Stream`].
+//!
+//! A channel with a capacity of zero buffers nothing, every send waits for a
+//! receiver to take the value.
+
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    pin::Pin,
+    sync::atomic::{self, AtomicUsize, Ordering},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use super::{
+    select::{sealed::Sealed, SelectRecv, SelectSend},
+    waker::{self, Waiters},
+    RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError,
+};
+use crate::{
+    arc::Arc,
+    stream::Stream,
+    sync::{Backoff, CachePadded, Unparker},
+};
+
+mod zero;
+
+use zero::Zero;
+
+struct Slot<T> {
+    /// `index + 1` once the slot is written for the lap of `index`, and
+    /// `index + one_lap` once it is read again.
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+struct Channel<T> {
+    /// Index of the next slot to read, the bits above `one_lap` count laps.
+    head: CachePadded<AtomicUsize>,
+    /// Index of the next slot to write.
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[Slot<T>]>,
+    /// A power of two larger than the capacity, the stride of one lap.
+    one_lap: usize,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    /// Senders waiting for room.
+    send_waiters: Waiters,
+    /// Receivers waiting for a value.
+    recv_waiters: Waiters,
+    /// Set for a capacity of zero, replacing the empty buffer.
+    zero: Option<Zero<T>>,
+}
+
+// Slots are handed between threads through their stamps, a value is only
+// ever accessed by the thread that claimed its slot.
+unsafe impl<T: Send> Sync for Channel<T> {}
+
+impl<T> Channel<T> {
+    fn cap(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The index following `index`, moving on to the next lap after the
+    /// last slot.
+    fn next_index(&self, index: usize) -> usize {
+        if (index & (self.one_lap - 1)) + 1 < self.cap() {
+            index + 1
+        } else {
+            (index & !(self.one_lap - 1)).wrapping_add(self.one_lap)
+        }
+    }
+
+    fn push(&self, value: T) -> Result<(), T> {
+        let backoff = Backoff::new();
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[tail & (self.one_lap - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if tail == stamp {
+                // The slot is free for this lap.
+                match self.tail.compare_exchange_weak(
+                    tail,
+                    self.next_index(tail),
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY
+                        // * Moving the tail past the slot claimed it.
+                        unsafe { slot.value.get().write(MaybeUninit::new(value)) };
+                        slot.stamp.store(tail + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => {
+                        tail = current;
+                        backoff.spin();
+                    }
+                }
+            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+                // The slot still holds the value of the previous lap.
+                atomic::fence(Ordering::SeqCst);
+                let head = self.head.load(Ordering::Relaxed);
+                if head.wrapping_add(self.one_lap) == tail {
+                    return Err(value);
+                }
+                backoff.spin();
+                tail = self.tail.load(Ordering::Relaxed);
+            } else {
+                // Another sender claimed the slot but has not moved on yet.
+                backoff.snooze();
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        let backoff = Backoff::new();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[head & (self.one_lap - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if head + 1 == stamp {
+                // The slot was written for this lap.
+                match self.head.compare_exchange_weak(
+                    head,
+                    self.next_index(head),
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY
+                        // * Moving the head past the written slot claimed it.
+                        let value = unsafe { slot.value.get().read().assume_init() };
+                        slot.stamp
+                            .store(head.wrapping_add(self.one_lap), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => {
+                        head = current;
+                        backoff.spin();
+                    }
+                }
+            } else if stamp == head {
+                // The slot is waiting to be written for this lap.
+                atomic::fence(Ordering::SeqCst);
+                let tail = self.tail.load(Ordering::Relaxed);
+                if tail == head {
+                    return None;
+                }
+                backoff.spin();
+                head = self.head.load(Ordering::Relaxed);
+            } else {
+                // Another receiver claimed the slot but has not moved on yet.
+                backoff.snooze();
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            if self.tail.load(Ordering::SeqCst) == tail {
+                let head_index = head & (self.one_lap - 1);
+                let tail_index = tail & (self.one_lap - 1);
+                return if head_index < tail_index {
+                    tail_index - head_index
+                } else if head_index > tail_index {
+                    self.cap() - head_index + tail_index
+                } else if tail == head {
+                    0
+                } else {
+                    self.cap()
+                };
+            }
+        }
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        if self.receivers.load(Ordering::SeqCst) == 0 {
+            return Err(TrySendError::Disconnected(value));
+        }
+        if let Some(zero) = &self.zero {
+            return zero.try_send(value).map_err(TrySendError::Full);
+        }
+        self.push(value).map_err(TrySendError::Full)?;
+        self.recv_waiters.notify();
+        Ok(())
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        if let Some(zero) = &self.zero {
+            return zero
+                .try_recv()
+                .ok_or(if self.senders.load(Ordering::SeqCst) == 0 {
+                    TryRecvError::Disconnected
+                } else {
+                    TryRecvError::Empty
+                });
+        }
+        let value = match self.pop() {
+            Some(value) => value,
+            // Values sent before the last sender left are still received.
+            None if self.senders.load(Ordering::SeqCst) == 0 => {
+                self.pop().ok_or(TryRecvError::Disconnected)?
+            }
+            None => return Err(TryRecvError::Empty),
+        };
+        self.send_waiters.notify();
+        Ok(value)
+    }
+
+    fn send(&self, value: T, deadline: Option<Instant>) -> Result<(), TrySendError<T>> {
+        if self.receivers.load(Ordering::SeqCst) == 0 {
+            return Err(TrySendError::Disconnected(value));
+        }
+        if let Some(zero) = &self.zero {
+            return zero.send(self, value, deadline);
+        }
+        let mut value = Some(value);
+        let result = waker::block_on(
+            || match self.try_send(value.take().expect("put back after every attempt")) {
+                Err(TrySendError::Full(back)) => {
+                    value = Some(back);
+                    None
+                }
+                result => Some(result),
+            },
+            |id, unparker| self.send_waiters.register(id, unparker),
+            |id| self.send_waiters.unregister(id),
+            deadline,
+        );
+        result.unwrap_or_else(|| Err(TrySendError::Full(value.take().expect("timed out"))))
+    }
+
+    fn recv(&self, deadline: Option<Instant>) -> Result<T, TryRecvError> {
+        if let Some(zero) = &self.zero {
+            return zero.recv(self, deadline);
+        }
+        waker::block_on(
+            || match self.try_recv() {
+                Err(TryRecvError::Empty) => None,
+                result => Some(result),
+            },
+            |id, unparker| self.recv_waiters.register(id, unparker),
+            |id| self.recv_waiters.unregister(id),
+            deadline,
+        )
+        .unwrap_or(Err(TryRecvError::Empty))
+    }
+}
+
+impl<T> Drop for Channel<T> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let start = head & (self.one_lap - 1);
+        for i in 0..self.len() {
+            let index = (start + i) % self.cap();
+            // SAFETY
+            // * Every handle is gone, the slots between head and tail hold
+            //   values that were sent but never received.
+            unsafe { (*self.buffer[index].value.get()).assume_init_drop() };
+        }
+    }
+}
+
+/// Returns a channel holding at most `cap` values, both sides can be cloned.
+///
+/// With a `cap` of zero the channel is a rendezvous: [`Sender::send`] blocks
+/// until a receiver is waiting and hands the value over directly, and
+/// [`Sender::try_send`] only succeeds while a receiver is blocked. At least one
+/// side of each exchange has to be a blocking call, two [`Select`]s never
+/// meet.
+///
+/// [`Select`]: super::Select
+///
+/// ```
+/// use nomicon::channel::mpmc;
+///
+/// let (tx, rx) = mpmc::bounded(8);
+/// let total = std::thread::scope(|s| {
+///     let workers = (0..4)
+///         .map(|_| {
+///             let rx = rx.clone();
+///             s.spawn(move || rx.iter().sum::<u32>())
+///         })
+///         .collect::<Vec<_>>();
+///     for job in 1..=100 {
+///         tx.send(job).unwrap();
+///     }
+///     drop(tx);
+///     workers.into_iter().map(|w| w.join().unwrap()).sum::<u32>()
+/// });
+/// assert_eq!(total, 5050);
+/// ```
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(Channel {
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        buffer: (0..cap)
+            .map(|index| Slot {
+                stamp: AtomicUsize::new(index),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect(),
+        one_lap: (cap + 1).next_power_of_two(),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        send_waiters: Waiters::new(),
+        recv_waiters: Waiters::new(),
+        zero: (cap == 0).then(Zero::new),
+    });
+    let receiver = Receiver {
+        channel: Arc::clone(&channel),
+    };
+    (Sender { channel }, receiver)
+}
+
+/// The sending half of a channel.
+///
+/// This type can be constructed through [`bounded`].
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send `value`, blocking while the channel is full.
+    ///
+    /// Fails once every receiver is dropped, handing the value back.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.channel
+            .send(value, None)
+            .map_err(|err| SendError(err.into_inner()))
+    }
+
+    /// Send `value` if the channel has room.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.channel.try_send(value)
+    }
+
+    /// Send `value`, blocking for at most `timeout` while the channel is
+    /// full.
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), TrySendError<T>> {
+        self.channel
+            .send(value, Instant::now().checked_add(timeout))
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.cap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            channel: Arc::clone(&self.channel),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            if let Some(zero) = &self.channel.zero {
+                zero.disconnect();
+            }
+            self.channel.recv_waiters.notify();
+        }
+    }
+}
+
+/// The receiving half of a channel.
+///
+/// This type can be constructed through [`bounded`].
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Block until a value arrives.
+    ///
+    /// Fails once the channel is empty and every sender is dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.channel.recv(None).map_err(|_| RecvError)
+    }
+
+    /// Returns a value if one is ready.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.channel.try_recv()
+    }
+
+    /// Block until a value arrives, for at most `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match self.channel.recv(Instant::now().checked_add(timeout)) {
+            Ok(value) => Ok(value),
+            Err(TryRecvError::Empty) => Err(RecvTimeoutError::Timeout),
+            Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+        }
+    }
+
+    /// Returns an iterator blocking on each value, ending once every sender
+    /// is dropped.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    /// Returns an iterator over the values that are ready, never blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.cap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.channel.receivers.fetch_add(1, Ordering::Relaxed);
+        Self {
+            channel: Arc::clone(&self.channel),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.channel.receivers.fetch_sub(1, Ordering::SeqCst) == 1 {
+            if let Some(zero) = &self.channel.zero {
+                zero.disconnect();
+            }
+            self.channel.send_waiters.notify();
+        }
+    }
+}
+
+impl<T> Sealed for Sender<T> {}
+
+impl<T> SelectSend<T> for Sender<T> {
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.channel.try_send(value)
+    }
+
+    fn register(&self, id: usize, unparker: &Unparker) {
+        self.channel.send_waiters.register(id, unparker);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.channel.send_waiters.unregister(id);
+    }
+}
+
+impl<T> Sealed for Receiver<T> {}
+
+impl<T> SelectRecv<T> for Receiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.channel.try_recv()
+    }
+
+    fn register(&self, id: usize, unparker: &Unparker) {
+        self.channel.recv_waiters.register(id, unparker);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.channel.recv_waiters.unregister(id);
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let poll = || match self.try_recv() {
+            Ok(value) => Some(Some(value)),
+            Err(TryRecvError::Disconnected) => Some(None),
+            Err(TryRecvError::Empty) => None,
+        };
+        if let Some(next) = poll() {
+            return Poll::Ready(next);
+        }
+        // Checking again after registering, a value sent in between would
+        // otherwise go unnoticed.
+        self.channel.recv_waiters.register_waker(cx.waker());
+        match poll() {
+            Some(next) => Poll::Ready(next),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { receiver: self }
+    }
+}
+
+/// A blocking iterator over the values of a [`Receiver`].
+///
+/// This type can be constructed through [`Receiver::iter`].
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// An iterator over the values ready in a [`Receiver`].
+///
+/// This type can be constructed through [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// An owning blocking iterator over the values of a [`Receiver`].
+///
+/// This type can be constructed through [`Receiver::into_iter`].
+pub struct IntoIter<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_operations() {
+        let (tx, rx) = bounded(3);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        for round in 0..4 {
+            for n in 0..3 {
+                tx.try_send(round * 3 + n).unwrap();
+            }
+            assert_eq!(tx.try_send(0), Err(TrySendError::Full(0)));
+            assert_eq!(rx.len(), 3);
+            assert!(rx.try_iter().eq(round * 3..round * 3 + 3));
+        }
+        assert_eq!(tx.send_timeout(1, Duration::from_millis(1)), Ok(()));
+        drop(tx);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(1)), Ok(1));
+        assert_eq!(rx.recv(), Err(RecvError));
+
+        let flag = std::rc::Rc::new(());
+        let (tx, rx) = bounded(4);
+        for _ in 0..3 {
+            tx.try_send(std::rc::Rc::clone(&flag)).unwrap();
+        }
+        drop(rx.try_recv());
+        drop(rx);
+        assert!(tx
+            .try_send(std::rc::Rc::clone(&flag))
+            .unwrap_err()
+            .is_disconnected());
+        drop(tx);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn rendezvous() {
+        let (tx, rx) = bounded(0);
+        assert_eq!(tx.capacity(), 0);
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(
+            tx.send_timeout(1, Duration::from_millis(5)),
+            Err(TrySendError::Full(1))
+        );
+        std::thread::scope(|s| {
+            let receiver = s.spawn(|| rx.iter().take(1_000).sum::<u32>());
+            for n in 0..1_000 {
+                tx.send(n).unwrap();
+                assert!(tx.is_empty());
+            }
+            assert_eq!(receiver.join().unwrap(), (0..1_000).sum());
+
+            // The value waits in the sender until it is taken.
+            let sender = {
+                let tx = tx.clone();
+                s.spawn(move || tx.send(5))
+            };
+            while rx.try_recv().map(|n| assert_eq!(n, 5)).is_err() {
+                std::thread::yield_now();
+            }
+            sender.join().unwrap().unwrap();
+
+            let receiver = s.spawn(|| rx.recv());
+            std::thread::sleep(Duration::from_millis(10));
+            drop(tx);
+            assert_eq!(receiver.join().unwrap(), Err(RecvError));
+        });
+    }
+
+    #[test]
+    fn blocking_between_threads() {
+        let (tx, rx) = bounded(2);
+        let sum = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                let rx = rx.clone();
+                let sum = &sum;
+                s.spawn(move || {
+                    for n in rx {
+                        sum.fetch_add(n, Ordering::Relaxed);
+                    }
+                });
+            }
+            for _ in 0..3 {
+                let tx = tx.clone();
+                s.spawn(move || {
+                    for n in 0..2_000 {
+                        tx.send(n).unwrap();
+                    }
+                });
+            }
+            drop((tx, rx));
+        });
+        assert_eq!(sum.into_inner(), 3 * (0..2_000).sum::<usize>());
+
+        let (tx, rx) = bounded::<u8>(1);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(20)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        tx.send(0).unwrap();
+        assert_eq!(
+            tx.send_timeout(1, Duration::from_millis(20)),
+            Err(TrySendError::Full(1))
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! The rendezvous flavour of the channel, for a capacity of zero.
+//!
+//! Nothing is buffered, a sender hands its value straight to a receiver. The
+//! side arriving first queues up with its parker and the value, if it is a
+//! sender, and the other side completes the exchange and unparks it. Being
+//! taken off the queue is what tells a parked thread its exchange happened.
+
+use std::{collections::VecDeque, sync::atomic::Ordering, time::Instant};
+
+use super::Channel;
+use crate::{
+    channel::{waker, TryRecvError, TrySendError},
+    sync::{Mutex, MutexGuard, Parker, PoisonError, Unparker},
+};
+
+struct State<T> {
+    /// Blocked senders, with the value they are offering.
+    senders: VecDeque<(usize, T, Unparker)>,
+    /// Blocked receivers.
+    receivers: VecDeque<(usize, Unparker)>,
+    /// Values handed to receivers that have not woken up yet.
+    delivered: Vec<(usize, T)>,
+}
+
+pub(super) struct Zero<T> {
+    state: Mutex<State<T>>,
+}
+
+impl<T> Zero<T> {
+    pub(super) fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                senders: VecDeque::new(),
+                receivers: VecDeque::new(),
+                delivered: Vec::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        // Only queue operations run under the lock.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hand `value` to a blocked receiver, if there is one.
+    pub(super) fn try_send(&self, value: T) -> Result<(), T> {
+        let mut state = self.state();
+        match state.receivers.pop_front() {
+            Some((id, unparker)) => {
+                state.delivered.push((id, value));
+                unparker.unpark();
+                Ok(())
+            }
+            None => Err(value),
+        }
+    }
+
+    /// Take the value of a blocked sender, if there is one.
+    pub(super) fn try_recv(&self) -> Option<T> {
+        let (_, value, unparker) = self.state().senders.pop_front()?;
+        unparker.unpark();
+        Some(value)
+    }
+
+    /// Block until a receiver takes `value`.
+    pub(super) fn send(
+        &self,
+        channel: &Channel<T>,
+        value: T,
+        deadline: Option<Instant>,
+    ) -> Result<(), TrySendError<T>> {
+        let value = match self.try_send(value) {
+            Ok(()) => return Ok(()),
+            Err(value) => value,
+        };
+        let parker = Parker::new();
+        let id = waker::next_id();
+        {
+            let mut state = self.state();
+            if channel.receivers.load(Ordering::SeqCst) == 0 {
+                return Err(TrySendError::Disconnected(value));
+            }
+            state
+                .senders
+                .push_back((id, value, parker.unparker().clone()));
+        }
+        // A select waiting to receive can now take the value.
+        channel.recv_waiters.notify();
+
+        loop {
+            if !park(&parker, deadline) || channel.receivers.load(Ordering::SeqCst) == 0 {
+                let mut state = self.state();
+                let Some(index) = state.senders.iter().position(|(entry, ..)| *entry == id) else {
+                    // Taken right before giving up.
+                    return Ok(());
+                };
+                let (_, value, _) = state.senders.remove(index).expect("index was found");
+                return Err(if channel.receivers.load(Ordering::SeqCst) == 0 {
+                    TrySendError::Disconnected(value)
+                } else {
+                    TrySendError::Full(value)
+                });
+            }
+            if !self.state().senders.iter().any(|(entry, ..)| *entry == id) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Block until a sender hands over a value.
+    pub(super) fn recv(
+        &self,
+        channel: &Channel<T>,
+        deadline: Option<Instant>,
+    ) -> Result<T, TryRecvError> {
+        if let Some(value) = self.try_recv() {
+            return Ok(value);
+        }
+        let parker = Parker::new();
+        let id = waker::next_id();
+        {
+            let mut state = self.state();
+            if channel.senders.load(Ordering::SeqCst) == 0 {
+                return Err(TryRecvError::Disconnected);
+            }
+            state.receivers.push_back((id, parker.unparker().clone()));
+        }
+        // A select waiting to send can now hand its value over.
+        channel.send_waiters.notify();
+
+        loop {
+            let gave_up = !park(&parker, deadline) || channel.senders.load(Ordering::SeqCst) == 0;
+            let mut state = self.state();
+            if let Some(index) = state.delivered.iter().position(|(entry, _)| *entry == id) {
+                return Ok(state.delivered.swap_remove(index).1);
+            }
+            if gave_up {
+                state.receivers.retain(|(entry, _)| *entry != id);
+                return Err(if channel.senders.load(Ordering::SeqCst) == 0 {
+                    TryRecvError::Disconnected
+                } else {
+                    TryRecvError::Empty
+                });
+            }
+        }
+    }
+
+    /// Wake every blocked thread, to notice the other side is gone.
+    pub(super) fn disconnect(&self) {
+        let state = self.state();
+        for (_, _, unparker) in &state.senders {
+            unparker.unpark();
+        }
+        for (_, unparker) in &state.receivers {
+            unparker.unpark();
+        }
+    }
+}
+
+/// Park until unparked, returning false once the deadline has passed.
+fn park(parker: &Parker, deadline: Option<Instant>) -> bool {
+    match deadline {
+        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+            Some(timeout) => parker.park_timeout(timeout),
+            None => false,
+        },
+        None => {
+            parker.park();
+            true
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! A multi producer single consumer channel.
+//!
+//! A queue behind a [`Mutex`], with one [`Condvar`] for the receiver to wait
+//! on and one for senders waiting on a full bounded channel. Threads waiting
+//! in a [`Select`](super::Select) are registered with the channel instead,
+//! as are tasks polling the receiver as a [`Stream`].
+
+use std::{
+    collections::VecDeque,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use super::{
+    select::{sealed::Sealed, SelectRecv, SelectSend},
+    waker::Waiters,
+    RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError,
+};
+use crate::{
+    arc::Arc,
+    stream::Stream,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError, Unparker},
+};
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Signalled when a value is sent or the last sender is dropped.
+    not_empty: Condvar,
+    /// Signalled when a value is received or the receiver is dropped.
+    not_full: Condvar,
+    /// Selects waiting to receive, notified along with `not_empty`.
+    recv_waiters: Waiters,
+    /// Selects waiting to send, notified along with `not_full`.
+    send_waiters: Waiters,
+    /// `None` for an unbounded channel.
+    cap: Option<usize>,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver: bool,
+}
+
+impl<T> Shared<T> {
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        // No user code runs with the lock held, a panic can not leave the
+        // state half updated.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn is_full(&self, state: &State<T>) -> bool {
+        self.cap.is_some_and(|cap| state.queue.len() >= cap)
+    }
+}
+
+fn channel<T>(cap: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            senders: 1,
+            receiver: true,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        recv_waiters: Waiters::new(),
+        send_waiters: Waiters::new(),
+        cap,
+    });
+    let receiver = Receiver {
+        shared: Arc::clone(&shared),
+        _marker: PhantomData,
+    };
+    (Sender { shared }, receiver)
+}
+
+/// Returns a channel that holds any number of values, sending never blocks.
+///
+/// ```
+/// use nomicon::channel::mpsc;
+///
+/// let (tx, rx) = mpsc::unbounded();
+/// std::thread::scope(|s| {
+///     for n in 0..4 {
+///         let tx = tx.clone();
+///         s.spawn(move || tx.send(n).unwrap());
+///     }
+/// });
+/// drop(tx);
+/// let mut values = rx.iter().collect::<Vec<_>>();
+/// values.sort();
+/// assert_eq!(values, [0, 1, 2, 3]);
+/// ```
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    channel(None)
+}
+
+/// Returns a channel holding at most `cap` values, sending blocks while it
+/// is full.
+///
+/// # Panics
+/// If `cap` is zero.
+///
+/// ```
+/// use nomicon::channel::{mpsc, TrySendError};
+///
+/// let (tx, rx) = mpsc::bounded(1);
+/// tx.send(1).unwrap();
+/// assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+/// assert_eq!(rx.recv(), Ok(1));
+/// tx.try_send(2).unwrap();
+/// ```
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "bounded channels need a capacity");
+    channel(Some(cap))
+}
+
+/// The sending half of a channel, it can be cloned to send from several
+/// threads.
+///
+/// This type can be constructed through [`unbounded`] and [`bounded`].
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send `value`, blocking while a bounded channel is full.
+    ///
+    /// Fails once the receiver is dropped, handing the value back.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let state = self.shared.state();
+        let mut state = self
+            .shared
+            .not_full
+            .wait_while(state, |state| state.receiver && self.shared.is_full(state))
+            .unwrap_or_else(PoisonError::into_inner);
+        if !state.receiver {
+            return Err(SendError(value));
+        }
+        state.queue.push_back(value);
+        drop(state);
+        self.shared.not_empty.notify_one();
+        self.shared.recv_waiters.notify();
+        Ok(())
+    }
+
+    /// Send `value` if the channel has room.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.shared.state();
+        if !state.receiver {
+            return Err(TrySendError::Disconnected(value));
+        }
+        if self.shared.is_full(&state) {
+            return Err(TrySendError::Full(value));
+        }
+        state.queue.push_back(value);
+        drop(state);
+        self.shared.not_empty.notify_one();
+        self.shared.recv_waiters.notify();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state().senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        state.senders -= 1;
+        let last = state.senders == 0;
+        drop(state);
+        if last {
+            self.shared.not_empty.notify_all();
+            self.shared.recv_waiters.notify();
+        }
+    }
+}
+
+/// The receiving half of a channel.
+///
+/// The receiver can be moved to another thread but not shared, there is only
+/// ever a single consumer.
+///
+/// This type can be constructed through [`unbounded`] and [`bounded`].
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    _marker: PhantomData<std::cell::Cell<()>>,
+}
+
+impl<T> Receiver<T> {
+    /// Block until a value arrives.
+    ///
+    /// Fails once the channel is empty and every sender is dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let state = self.shared.state();
+        let state = self
+            .shared
+            .not_empty
+            .wait_while(state, |state| state.queue.is_empty() && state.senders > 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        self.take(state).ok_or(RecvError)
+    }
+
+    /// Returns a value if one is ready.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let state = self.shared.state();
+        let disconnected = state.senders == 0;
+        match self.take(state) {
+            Some(value) => Ok(value),
+            None if disconnected => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Block until a value arrives, for at most `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let state = self.shared.state();
+        let (state, _) = self
+            .shared
+            .not_empty
+            .wait_timeout_while(state, timeout, |state| {
+                state.queue.is_empty() && state.senders > 0
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        let disconnected = state.senders == 0;
+        match self.take(state) {
+            Some(value) => Ok(value),
+            None if disconnected => Err(RecvTimeoutError::Disconnected),
+            None => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    /// Pop the front value, making room for a blocked sender.
+    fn take(&self, mut state: MutexGuard<'_, State<T>>) -> Option<T> {
+        let value = state.queue.pop_front()?;
+        drop(state);
+        if self.shared.cap.is_some() {
+            self.shared.not_full.notify_one();
+            self.shared.send_waiters.notify();
+        }
+        Some(value)
+    }
+
+    /// Returns an iterator blocking on each value, ending once every sender
+    /// is dropped.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    /// Returns an iterator over the values that are ready, never blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        state.receiver = false;
+        // Values nobody will receive are dropped outside of the lock.
+        let queue = std::mem::take(&mut state.queue);
+        drop(state);
+        self.shared.not_full.notify_all();
+        self.shared.send_waiters.notify();
+        drop(queue);
+    }
+}
+
+impl<T> Sealed for Sender<T> {}
+
+impl<T> SelectSend<T> for Sender<T> {
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        Sender::try_send(self, value)
+    }
+
+    fn register(&self, id: usize, unparker: &Unparker) {
+        self.shared.send_waiters.register(id, unparker);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.shared.send_waiters.unregister(id);
+    }
+}
+
+impl<T> Sealed for Receiver<T> {}
+
+impl<T> SelectRecv<T> for Receiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        Receiver::try_recv(self)
+    }
+
+    fn register(&self, id: usize, unparker: &Unparker) {
+        self.shared.recv_waiters.register(id, unparker);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.shared.recv_waiters.unregister(id);
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let poll = || match self.try_recv() {
+            Ok(value) => Some(Some(value)),
+            Err(TryRecvError::Disconnected) => Some(None),
+            Err(TryRecvError::Empty) => None,
+        };
+        if let Some(next) = poll() {
+            return Poll::Ready(next);
+        }
+        // Checking again after registering, a value sent in between would
+        // otherwise go unnoticed.
+        self.shared.recv_waiters.register_waker(cx.waker());
+        match poll() {
+            Some(next) => Poll::Ready(next),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { receiver: self }
+    }
+}
+
+/// A blocking iterator over the values of a [`Receiver`].
+///
+/// This type can be constructed through [`Receiver::iter`].
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// An iterator over the values ready in a [`Receiver`].
+///
+/// This type can be constructed through [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// An owning blocking iterator over the values of a [`Receiver`].
+///
+/// This type can be constructed through [`Receiver::into_iter`].
+pub struct IntoIter<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disconnection() {
+        let (tx, rx) = unbounded();
+        tx.send(1).unwrap();
+        let tx2 = tx.clone();
+        drop(tx);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        drop(tx2);
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        let flag = std::rc::Rc::new(());
+        let (tx, rx) = unbounded();
+        tx.send(std::rc::Rc::clone(&flag)).unwrap();
+        drop(rx);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+        assert!(tx.send(std::rc::Rc::clone(&flag)).is_err());
+    }
+
+    #[test]
+    fn bounded_blocks_senders() {
+        let (tx, rx) = bounded(2);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let tx = tx.clone();
+                s.spawn(move || {
+                    for n in 0..1_000 {
+                        tx.send(t * 1_000 + n).unwrap();
+                    }
+                });
+            }
+            drop(tx);
+            let mut values = rx.into_iter().collect::<Vec<_>>();
+            values.sort();
+            assert!(values.into_iter().eq(0..4_000));
+        });
+
+        let (tx, rx) = bounded(1);
+        tx.send(0).unwrap();
+        std::thread::scope(|s| {
+            let blocked = s.spawn(|| tx.send(1));
+            std::thread::sleep(Duration::from_millis(20));
+            drop(rx);
+            assert_eq!(blocked.join().unwrap(), Err(SendError(1)));
+        });
+    }
+}
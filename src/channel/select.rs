@@ -0,0 +1,316 @@
+use std::{
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use super::{waker, RecvError, SendError, TryRecvError, TrySendError};
+use crate::{cell::RefCell, sync::Unparker};
+
+pub(super) mod sealed {
+    /// Keeps the select traits implemented by the crate's channels only.
+    pub trait Sealed {}
+}
+
+/// A channel end a [`Select`] can receive from.
+pub trait SelectRecv<T>: sealed::Sealed {
+    #[doc(hidden)]
+    fn try_recv(&self) -> Result<T, TryRecvError>;
+    #[doc(hidden)]
+    fn register(&self, id: usize, unparker: &Unparker);
+    #[doc(hidden)]
+    fn unregister(&self, id: usize);
+}
+
+/// A channel end a [`Select`] can send on.
+pub trait SelectSend<T>: sealed::Sealed {
+    #[doc(hidden)]
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>>;
+    #[doc(hidden)]
+    fn register(&self, id: usize, unparker: &Unparker);
+    #[doc(hidden)]
+    fn unregister(&self, id: usize);
+}
+
+/// One registered operation, with the closure handling its result.
+trait Operation<R> {
+    /// Complete the operation if it is ready, or disconnected.
+    fn attempt(&mut self) -> Option<R>;
+    fn register(&self, id: usize, unparker: &Unparker);
+    fn unregister(&self, id: usize);
+}
+
+struct RecvOp<'a, C: ?Sized, T, F> {
+    receiver: &'a C,
+    f: Option<F>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<C, T, F, R> Operation<R> for RecvOp<'_, C, T, F>
+where
+    C: SelectRecv<T> + ?Sized,
+    F: FnOnce(Result<T, RecvError>) -> R,
+{
+    fn attempt(&mut self) -> Option<R> {
+        let result = match self.receiver.try_recv() {
+            Ok(value) => Ok(value),
+            Err(TryRecvError::Disconnected) => Err(RecvError),
+            Err(TryRecvError::Empty) => return None,
+        };
+        let f = self.f.take().expect("operations complete once");
+        Some(f(result))
+    }
+
+    fn register(&self, id: usize, unparker: &Unparker) {
+        self.receiver.register(id, unparker);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.receiver.unregister(id);
+    }
+}
+
+struct SendOp<'a, C: ?Sized, T, F> {
+    sender: &'a C,
+    /// Handed back by every failed attempt.
+    value: Option<T>,
+    f: Option<F>,
+}
+
+impl<C, T, F, R> Operation<R> for SendOp<'_, C, T, F>
+where
+    C: SelectSend<T> + ?Sized,
+    F: FnOnce(Result<(), SendError<T>>) -> R,
+{
+    fn attempt(&mut self) -> Option<R> {
+        let value = self.value.take().expect("operations complete once");
+        let result = match self.sender.try_send(value) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Disconnected(value)) => Err(SendError(value)),
+            Err(TrySendError::Full(value)) => {
+                self.value = Some(value);
+                return None;
+            }
+        };
+        let f = self.f.take().expect("operations complete once");
+        Some(f(result))
+    }
+
+    fn register(&self, id: usize, unparker: &Unparker) {
+        self.sender.register(id, unparker);
+    }
+
+    fn unregister(&self, id: usize) {
+        self.sender.unregister(id);
+    }
+}
+
+/// Wait on several channel operations at once, completing exactly one.
+///
+/// Each operation comes with a closure that is handed its result, only the
+/// closure of the operation that completed runs. Receiving from a
+/// disconnected channel, or sending to one, counts as completing. When
+/// several operations are ready the one tried first is picked, and the
+/// starting point rotates between calls so no channel is starved.
+///
+/// While waiting, the thread is registered with every channel involved, and
+/// any progress on one of them wakes it to try again.
+///
+/// Works with the [`mpsc`](super::mpsc) and [`mpmc`](super::mpmc) channels.
+///
+/// ```
+/// use nomicon::channel::{mpmc, mpsc, Select};
+///
+/// let (jobs_tx, jobs) = mpsc::unbounded();
+/// let (quit_tx, quit) = mpmc::bounded(1);
+/// std::thread::scope(|s| {
+///     s.spawn(move || {
+///         jobs_tx.send(1).unwrap();
+///         jobs_tx.send(2).unwrap();
+///         quit_tx.send(()).unwrap();
+///     });
+///     let mut done = 0;
+///     loop {
+///         let stop = Select::new()
+///             .recv(&jobs, |job| {
+///                 done += job.unwrap_or(0);
+///                 false
+///             })
+///             .recv(&quit, |_| true)
+///             .wait();
+///         if stop {
+///             break;
+///         }
+///     }
+///     // The quit message may win the race against the second job.
+///     assert!(done == 1 || done == 3);
+/// });
+/// ```
+pub struct Select<'a, R> {
+    operations: RefCell<Vec<Box<dyn Operation<R> + 'a>>>,
+}
+
+impl<'a, R> Select<'a, R> {
+    pub fn new() -> Self {
+        Self {
+            operations: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Add receiving from `receiver`, calling `f` with the value.
+    pub fn recv<T: 'a, C: SelectRecv<T> + ?Sized>(
+        self,
+        receiver: &'a C,
+        f: impl FnOnce(Result<T, RecvError>) -> R + 'a,
+    ) -> Self {
+        self.operations.borrow_mut().push(Box::new(RecvOp {
+            receiver,
+            f: Some(f),
+            _marker: PhantomData,
+        }));
+        self
+    }
+
+    /// Add sending `value` on `sender`, calling `f` once it is sent. The
+    /// value is dropped if another operation completes instead.
+    pub fn send<T: 'a, C: SelectSend<T> + ?Sized>(
+        self,
+        sender: &'a C,
+        value: T,
+        f: impl FnOnce(Result<(), SendError<T>>) -> R + 'a,
+    ) -> Self {
+        self.operations.borrow_mut().push(Box::new(SendOp {
+            sender,
+            value: Some(value),
+            f: Some(f),
+        }));
+        self
+    }
+
+    /// Block until one of the operations completes, returning what its
+    /// closure returned.
+    ///
+    /// # Panics
+    /// If no operation was added.
+    pub fn wait(self) -> R {
+        self.run(None)
+            .unwrap_or_else(|_| unreachable!("waits without a deadline complete"))
+    }
+
+    /// Complete an operation that is ready, or hand the select back.
+    pub fn try_wait(self) -> Result<R, Self> {
+        match self.attempt() {
+            Some(result) => Ok(result),
+            None => Err(self),
+        }
+    }
+
+    /// Block until one of the operations completes, for at most `timeout`.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<R, Self> {
+        self.run(Instant::now().checked_add(timeout))
+    }
+
+    /// Try every operation once, starting at a different one each call.
+    fn attempt(&self) -> Option<R> {
+        let mut operations = self.operations.borrow_mut();
+        let len = operations.len();
+        let start = waker::next_id() % len.max(1);
+        (0..len).find_map(|i| operations[(start + i) % len].attempt())
+    }
+
+    fn run(self, deadline: Option<Instant>) -> Result<R, Self> {
+        assert!(
+            !self.operations.borrow().is_empty(),
+            "select needs an operation to wait on"
+        );
+        let result = waker::block_on(
+            || self.attempt(),
+            |id, unparker| {
+                for operation in self.operations.borrow().iter() {
+                    operation.register(id, unparker);
+                }
+            },
+            |id| {
+                for operation in self.operations.borrow().iter() {
+                    operation.unregister(id);
+                }
+            },
+            deadline,
+        );
+        result.ok_or(self)
+    }
+}
+
+impl<R> Default for Select<'_, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::{mpmc, mpsc};
+
+    #[test]
+    fn completes_exactly_one() {
+        let (a_tx, a) = mpmc::bounded(4);
+        let (b_tx, b) = mpsc::unbounded();
+        a_tx.send(1).unwrap();
+        b_tx.send(2).unwrap();
+        let mut runs = 0;
+        let got = Select::new()
+            .recv(&a, |v| {
+                runs += 1;
+                v.unwrap()
+            })
+            .recv(&b, |v| v.unwrap())
+            .wait();
+        assert_eq!(runs, u32::from(got == 1));
+        assert_eq!(a.len() + usize::from(b.try_recv().is_ok()), 1);
+
+        // Only one of the values is sent.
+        let (c_tx, c) = mpmc::bounded(1);
+        let sent = Select::new()
+            .send(&a_tx, 10, |r| r.map(|()| "a"))
+            .send(&c_tx, 20, |r| r.map(|()| "c"))
+            .wait()
+            .unwrap();
+        assert_eq!(
+            a.try_iter().count() + c.try_iter().count(),
+            1 + usize::from(got == 2)
+        );
+        assert!(sent == "a" || sent == "c");
+    }
+
+    #[test]
+    fn wakes_on_any_channel() {
+        let (a_tx, a) = mpsc::unbounded::<u8>();
+        let (b_tx, b) = mpmc::bounded(1);
+        let (full_tx, _full) = mpmc::bounded(1);
+        full_tx.send(0).unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                b_tx.send(7).unwrap();
+            });
+            let got = Select::new()
+                .recv(&a, |v| v.unwrap())
+                .recv(&b, |v| v.unwrap())
+                .send(&full_tx, 1, |_| 0)
+                .wait();
+            assert_eq!(got, 7);
+        });
+
+        drop(a_tx);
+        let disconnected = Select::new().recv(&a, |v| v.is_err()).wait();
+        assert!(disconnected);
+    }
+
+    #[test]
+    fn times_out() {
+        let (_tx, rx) = mpmc::bounded::<u8>(1);
+        let select = Select::new().recv(&rx, |v| v.unwrap());
+        let select = select.try_wait().err().unwrap();
+        assert!(select.wait_timeout(Duration::from_millis(10)).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! A bounded single producer single consumer channel.
+//!
+//! A ring buffer with a power of two capacity, every slot is allocated when
+//! the channel is created. Pushing and popping never block, never allocate,
+//! and finish in a bounded number of steps.
+//!
+//! Each side keeps its own index locally and only publishes it, and keeps a
+//! cached copy of the other side's index, only reloading it when the buffer
+//! looks full or empty. In the common case an operation touches a single
+//! shared cache line.
+
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use super::{TryRecvError, TrySendError};
+use crate::{arc::Arc, sync::CachePadded};
+
+struct Shared<T> {
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// The index of the next value to pop, written by the consumer.
+    head: CachePadded<AtomicUsize>,
+    /// The index of the next slot to push into, written by the producer.
+    tail: CachePadded<AtomicUsize>,
+    producer: AtomicBool,
+    consumer: AtomicBool,
+}
+
+// Each slot is only accessed by the side that owns it according to the
+// indices, the hand over happens through the release and acquire on them.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    /// # Safety
+    /// The caller has to own the slot at `index`.
+    unsafe fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buffer[index & (self.buffer.len() - 1)].get()
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        // The indices wrap, the tail can be below the head.
+        for offset in 0..tail.wrapping_sub(head) {
+            // SAFETY
+            // * Both sides are gone, and the slots between the indices hold
+            //   values that were pushed but never popped.
+            unsafe { (*self.slot(head.wrapping_add(offset))).assume_init_drop() };
+        }
+    }
+}
+
+/// Returns a channel holding at most `cap` values, rounded up to a power of
+/// two.
+///
+/// ```
+/// use nomicon::channel::{spsc, TryRecvError};
+///
+/// let (mut producer, mut consumer) = spsc::channel(4);
+/// std::thread::scope(|s| {
+///     s.spawn(move || {
+///         for n in 0..100 {
+///             while producer.push(n).is_err() {
+///                 std::thread::yield_now();
+///             }
+///         }
+///     });
+///     let mut next = 0;
+///     loop {
+///         match consumer.pop() {
+///             Ok(n) => {
+///                 assert_eq!(n, next);
+///                 next += 1;
+///             }
+///             Err(TryRecvError::Empty) => std::thread::yield_now(),
+///             Err(TryRecvError::Disconnected) => break,
+///         }
+///     }
+///     assert_eq!(next, 100);
+/// });
+/// ```
+pub fn channel<T>(cap: usize) -> (Producer<T>, Consumer<T>) {
+    let cap = cap.max(1).next_power_of_two();
+    let shared = Arc::new(Shared {
+        buffer: (0..cap)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        producer: AtomicBool::new(true),
+        consumer: AtomicBool::new(true),
+    });
+    let consumer = Consumer {
+        shared: Arc::clone(&shared),
+        head: 0,
+        tail: 0,
+    };
+    let producer = Producer {
+        shared,
+        tail: 0,
+        head: 0,
+    };
+    (producer, consumer)
+}
+
+/// The sending half of a [`channel`].
+///
+/// This type can be constructed through [`channel`].
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+    /// The producer's own index, published to `shared.tail`.
+    tail: usize,
+    /// The last head seen, the consumer may have moved past it.
+    head: usize,
+}
+
+impl<T> Producer<T> {
+    /// Push `value` if the buffer has room.
+    pub fn push(&mut self, value: T) -> Result<(), TrySendError<T>> {
+        if !self.shared.consumer.load(Ordering::Relaxed) {
+            return Err(TrySendError::Disconnected(value));
+        }
+        if self.tail.wrapping_sub(self.head) == self.capacity() {
+            self.head = self.shared.head.load(Ordering::Acquire);
+            if self.tail.wrapping_sub(self.head) == self.capacity() {
+                return Err(TrySendError::Full(value));
+            }
+        }
+        // SAFETY
+        // * The slot is past the consumer's head, only the producer touches
+        //   it until the new tail is published.
+        unsafe { (*self.shared.slot(self.tail)).write(value) };
+        self.tail = self.tail.wrapping_add(1);
+        self.shared.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.buffer.len()
+    }
+
+    /// Returns the number of values in the buffer, the consumer may be
+    /// popping concurrently.
+    pub fn len(&self) -> usize {
+        self.tail
+            .wrapping_sub(self.shared.head.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.shared.producer.store(false, Ordering::Release);
+    }
+}
+
+/// The receiving half of a [`channel`].
+///
+/// This type can be constructed through [`channel`].
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+    /// The consumer's own index, published to `shared.head`.
+    head: usize,
+    /// The last tail seen, the producer may have moved past it.
+    tail: usize,
+}
+
+impl<T> Consumer<T> {
+    /// Pop the front value if there is one.
+    pub fn pop(&mut self) -> Result<T, TryRecvError> {
+        if self.head == self.tail {
+            self.tail = self.shared.tail.load(Ordering::Acquire);
+            if self.head == self.tail {
+                if self.shared.producer.load(Ordering::Acquire) {
+                    return Err(TryRecvError::Empty);
+                }
+                // The producer may have pushed right before leaving.
+                self.tail = self.shared.tail.load(Ordering::Acquire);
+                if self.head == self.tail {
+                    return Err(TryRecvError::Disconnected);
+                }
+            }
+        }
+        // SAFETY
+        // * The slot is before the producer's tail, so it holds a value, and
+        //   only the consumer touches it until the new head is published.
+        let value = unsafe { (*self.shared.slot(self.head)).assume_init_read() };
+        self.head = self.head.wrapping_add(1);
+        self.shared.head.store(self.head, Ordering::Release);
+        Ok(value)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.buffer.len()
+    }
+
+    /// Returns the number of values in the buffer, the producer may be
+    /// pushing concurrently.
+    pub fn len(&self) -> usize {
+        self.shared
+            .tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.shared.consumer.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn full_and_empty() {
+        let (mut producer, mut consumer) = channel(3);
+        assert_eq!(producer.capacity(), 4);
+        assert_eq!(consumer.pop(), Err(TryRecvError::Empty));
+        // Go around the ring a few times.
+        for round in 0..5 {
+            for n in 0..4 {
+                producer.push(round * 4 + n).unwrap();
+            }
+            assert_eq!(producer.push(0), Err(TrySendError::Full(0)));
+            assert_eq!(consumer.len(), 4);
+            for n in 0..4 {
+                assert_eq!(consumer.pop(), Ok(round * 4 + n));
+            }
+        }
+        producer.push(1).unwrap();
+        drop(producer);
+        assert_eq!(consumer.pop(), Ok(1));
+        assert_eq!(consumer.pop(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn drops_unread_values() {
+        let flag = std::rc::Rc::new(());
+        let (mut producer, mut consumer) = channel(8);
+        for _ in 0..5 {
+            producer.push(std::rc::Rc::clone(&flag)).unwrap();
+        }
+        drop(consumer.pop());
+        drop(consumer);
+        assert!(producer.push(std::rc::Rc::clone(&flag)).is_err());
+        drop(producer);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn drops_unread_values_across_the_wrap() {
+        let flag = std::rc::Rc::new(());
+        let (mut producer, mut consumer) = channel(4);
+        let start = usize::MAX - 1;
+        (producer.head, producer.tail) = (start, start);
+        (consumer.head, consumer.tail) = (start, start);
+        producer.shared.head.store(start, Ordering::Relaxed);
+        producer.shared.tail.store(start, Ordering::Relaxed);
+        for _ in 0..4 {
+            producer.push(std::rc::Rc::clone(&flag)).unwrap();
+        }
+        drop(consumer.pop());
+        drop((producer, consumer));
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn streams_between_threads() {
+        let (mut producer, mut consumer) = channel(64);
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for n in 0..100_000u64 {
+                    let mut value = n;
+                    while let Err(TrySendError::Full(back)) = producer.push(value) {
+                        value = back;
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            let mut expected = 0;
+            while expected < 100_000 {
+                match consumer.pop() {
+                    Ok(n) => {
+                        assert_eq!(n, expected);
+                        expected += 1;
+                    }
+                    Err(e) => {
+                        assert_eq!(e, TryRecvError::Empty, "disconnected early");
+                        std::thread::yield_now();
+                    }
+                }
+            }
+        });
+    }
+}
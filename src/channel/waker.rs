@@ -0,0 +1,147 @@
+//! Threads blocked on a channel, registered so the other side can wake them.
+//!
+//! A blocked operation, or a [`Select`](super::Select) waiting on several,
+//! registers the [`Unparker`] of its thread with every channel side it waits
+//! on, checks once more whether it can proceed, then parks. Channels notify
+//! their waiters after every change, and woken threads simply retry.
+//!
+//! Receivers polled as a [`Stream`](crate::stream::Stream) register the
+//! task's [`Waker`] instead, which is woken once and then dropped.
+
+use std::{
+    sync::atomic::{self, AtomicUsize, Ordering},
+    task::Waker,
+    time::Instant,
+};
+
+use crate::sync::{Backoff, Parker, SpinMutex, Unparker};
+
+/// Returns an id no other wait uses, to unregister by.
+pub(super) fn next_id() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+enum Waiter {
+    /// Unregistered by the thread once it is done waiting.
+    Thread(usize, Unparker),
+    /// Removed when woken.
+    Task(Waker),
+}
+
+/// The threads and tasks waiting for one side of a channel to make
+/// progress.
+pub(super) struct Waiters {
+    entries: SpinMutex<Vec<Waiter>>,
+    /// Lets notifiers skip the lock when nobody waits.
+    len: AtomicUsize,
+}
+
+impl Waiters {
+    pub(super) const fn new() -> Self {
+        Self {
+            entries: SpinMutex::new(Vec::new()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    pub(super) fn register(&self, id: usize, unparker: &Unparker) {
+        let mut entries = self.entries.lock();
+        entries.push(Waiter::Thread(id, unparker.clone()));
+        self.len.store(entries.len(), Ordering::SeqCst);
+    }
+
+    pub(super) fn unregister(&self, id: usize) {
+        let mut entries = self.entries.lock();
+        entries.retain(|entry| !matches!(entry, Waiter::Thread(other, _) if *other == id));
+        self.len.store(entries.len(), Ordering::SeqCst);
+    }
+
+    /// Register a task to wake on the next notification.
+    pub(super) fn register_waker(&self, waker: &Waker) {
+        let mut entries = self.entries.lock();
+        // A task polled again before being woken is already registered.
+        let registered = entries
+            .iter()
+            .any(|entry| matches!(entry, Waiter::Task(other) if other.will_wake(waker)));
+        if !registered {
+            entries.push(Waiter::Task(waker.clone()));
+            self.len.store(entries.len(), Ordering::SeqCst);
+        }
+    }
+
+    /// Wake every waiter, call after making progress.
+    ///
+    /// Waiters register before their last check, and the fence orders the
+    /// progress before reading the count, so either the waiter sees the
+    /// progress or it is seen here.
+    pub(super) fn notify(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.len.load(Ordering::SeqCst) > 0 {
+            let mut tasks = Vec::new();
+            {
+                let mut entries = self.entries.lock();
+                entries.retain(|entry| match entry {
+                    Waiter::Thread(_, unparker) => {
+                        unparker.unpark();
+                        true
+                    }
+                    Waiter::Task(waker) => {
+                        tasks.push(waker.clone());
+                        false
+                    }
+                });
+                self.len.store(entries.len(), Ordering::SeqCst);
+            }
+            // Wakers run arbitrary code, call them without the spin lock.
+            for waker in tasks {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Retry `attempt` until it returns a result, parking in between.
+///
+/// `register` and `unregister` add and remove the parked thread from the
+/// waiters of every channel `attempt` touches. Returns `None` once the
+/// deadline passes.
+pub(super) fn block_on<R>(
+    mut attempt: impl FnMut() -> Option<R>,
+    register: impl Fn(usize, &Unparker),
+    unregister: impl Fn(usize),
+    deadline: Option<Instant>,
+) -> Option<R> {
+    // Short waits are cheaper spent spinning than registering.
+    let backoff = Backoff::new();
+    while !backoff.is_completed() {
+        if let Some(result) = attempt() {
+            return Some(result);
+        }
+        backoff.snooze();
+    }
+
+    let parker = Parker::new();
+    loop {
+        let id = next_id();
+        register(id, parker.unparker());
+        let result = attempt();
+        if result.is_none() {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        unregister(id);
+                        return attempt();
+                    }
+                    parker.park_timeout(deadline - now);
+                }
+                None => parker.park(),
+            }
+        }
+        unregister(id);
+        if result.is_some() {
+            return result;
+        }
+    }
+}
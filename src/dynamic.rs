@@ -0,0 +1,242 @@
+//! Dynamic dispatch by hand, the way `dyn Trait` works underneath.
+//!
+//! A trait object is a pointer to the value plus a pointer to a vtable, a
+//! static table the compiler writes once per type and trait. It holds the
+//! value's size, alignment, drop glue, and one function pointer per method.
+//! [`VTable`] is that table with the methods left to the user, and
+//! [`RawObject`] the owning pointer pair.
+//!
+//! ```
+//! use nomicon::dynamic::{RawObject, VTable};
+//!
+//! /// The methods of a hand rolled `Shape` interface, each taking a pointer
+//! /// to the erased value.
+//! struct Shape {
+//!     area: unsafe fn(*const ()) -> f64,
+//! }
+//!
+//! struct Square(f64);
+//! struct Circle(f64);
+//!
+//! static SQUARE: VTable<Shape> = VTable::new::<Square>(Shape {
+//!     area: |this| unsafe { (*this.cast::<Square>()).0.powi(2) },
+//! });
+//! static CIRCLE: VTable<Shape> = VTable::new::<Circle>(Shape {
+//!     area: |this| unsafe { (*this.cast::<Circle>()).0.powi(2) * std::f64::consts::PI },
+//! });
+//!
+//! let shapes = [
+//!     RawObject::new(Square(2.0), &SQUARE),
+//!     RawObject::new(Circle(1.0), &CIRCLE),
+//! ];
+//! // SAFETY: every vtable method is called with a value of its own type.
+//! let total: f64 = shapes
+//!     .iter()
+//!     .map(|shape| unsafe { (shape.methods().area)(shape.as_ptr()) })
+//!     .sum();
+//! assert!((total - 4.0 - std::f64::consts::PI).abs() < 1e-9);
+//! assert!(shapes[0].is::<Square>());
+//! ```
+
+use std::{alloc::Layout, any::TypeId, fmt, mem::ManuallyDrop, ptr::NonNull};
+
+/// The table of a single type, shared by every [`RawObject`] of it.
+///
+/// The header describing the type is filled in by [`VTable::new`], the
+/// methods are up to the caller. Methods take the value as an erased
+/// pointer, and are unsafe to call with anything but a value of the type the
+/// table was built for.
+///
+/// This type can be constructed through [`VTable::new`].
+pub struct VTable<M> {
+    drop_in_place: unsafe fn(*mut ()),
+    layout: Layout,
+    type_id: fn() -> TypeId,
+    type_name: fn() -> &'static str,
+    methods: M,
+}
+
+unsafe fn drop_in_place<T>(ptr: *mut ()) {
+    unsafe { ptr.cast::<T>().drop_in_place() }
+}
+
+impl<M> VTable<M> {
+    /// Returns the table for `T`, usually stored in a `static`.
+    pub const fn new<T: 'static>(methods: M) -> Self {
+        Self {
+            drop_in_place: drop_in_place::<T>,
+            layout: Layout::new::<T>(),
+            type_id: TypeId::of::<T>,
+            type_name: std::any::type_name::<T>,
+            methods,
+        }
+    }
+
+    pub const fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        (self.type_id)()
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        (self.type_name)()
+    }
+
+    pub const fn methods(&self) -> &M {
+        &self.methods
+    }
+}
+
+/// An owned value of an erased type, along with the table describing it.
+///
+/// The same shape as a `Box<dyn Trait>`, a data pointer and a vtable
+/// pointer, with the vtable one of the crate's [`VTable`]s. Dropping the
+/// object drops the value through the table's drop glue and frees it with
+/// the table's layout.
+///
+/// This type can be constructed through [`RawObject::new`].
+pub struct RawObject<M: 'static> {
+    data: NonNull<()>,
+    vtable: &'static VTable<M>,
+}
+
+impl<M: 'static> RawObject<M> {
+    /// Box `value` and pair it with `vtable`.
+    ///
+    /// # Panics
+    /// If `vtable` was not built for `T`.
+    pub fn new<T: 'static>(value: T, vtable: &'static VTable<M>) -> Self {
+        assert!(
+            vtable.type_id() == TypeId::of::<T>(),
+            "vtable built for {}, not {}",
+            vtable.type_name(),
+            std::any::type_name::<T>(),
+        );
+        Self {
+            data: NonNull::from(Box::leak(Box::new(value))).cast(),
+            vtable,
+        }
+    }
+
+    pub fn vtable(&self) -> &'static VTable<M> {
+        self.vtable
+    }
+
+    pub fn methods(&self) -> &'static M {
+        &self.vtable.methods
+    }
+
+    /// Returns the erased pointer to pass to the table's methods.
+    pub fn as_ptr(&self) -> *const () {
+        self.data.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut () {
+        self.data.as_ptr()
+    }
+
+    /// Returns true if the value is a `T`.
+    pub fn is<T: 'static>(&self) -> bool {
+        self.vtable.type_id() == TypeId::of::<T>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        // SAFETY
+        // * The vtable was checked to be for T on construction.
+        self.is::<T>()
+            .then(|| unsafe { self.data.cast::<T>().as_ref() })
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.is::<T>()
+            .then(|| unsafe { self.data.cast::<T>().as_mut() })
+    }
+
+    /// Take the value back out, if it is a `T`.
+    pub fn downcast<T: 'static>(self) -> Result<Box<T>, Self> {
+        if !self.is::<T>() {
+            return Err(self);
+        }
+        let this = ManuallyDrop::new(self);
+        // SAFETY
+        // * The data was allocated by a Box<T>, and this object is never
+        //   dropped.
+        Ok(unsafe { Box::from_raw(this.data.cast::<T>().as_ptr()) })
+    }
+}
+
+impl<M: 'static> Drop for RawObject<M> {
+    fn drop(&mut self) {
+        let layout = self.vtable.layout;
+        unsafe {
+            // SAFETY
+            // * The data is a live value of the table's type, allocated the
+            //   way Box allocates one of its layout.
+            (self.vtable.drop_in_place)(self.data.as_ptr());
+            if layout.size() != 0 {
+                std::alloc::dealloc(self.data.as_ptr().cast(), layout);
+            }
+        }
+    }
+}
+
+impl<M: 'static> fmt::Debug for RawObject<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawObject")
+            .field("type", &self.vtable.type_name())
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct Describe {
+        describe: unsafe fn(*const ()) -> String,
+    }
+
+    static RC: VTable<Describe> = VTable::new::<Rc<()>>(Describe {
+        describe: |this| {
+            format!("rc of {}", unsafe {
+                Rc::strong_count(&*this.cast::<Rc<()>>())
+            })
+        },
+    });
+    static UNIT: VTable<Describe> = VTable::new::<()>(Describe {
+        describe: |_| String::from("unit"),
+    });
+
+    #[test]
+    fn drops_and_downcasts() {
+        let flag = Rc::new(());
+        let object = RawObject::new(Rc::clone(&flag), &RC);
+        assert_eq!(
+            unsafe { (object.methods().describe)(object.as_ptr()) },
+            "rc of 2"
+        );
+        drop(object);
+        assert_eq!(Rc::strong_count(&flag), 1);
+
+        let object = RawObject::new(Rc::clone(&flag), &RC);
+        let object = object.downcast::<()>().unwrap_err();
+        let rc = object.downcast::<Rc<()>>().unwrap();
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        let mut unit = RawObject::new((), &UNIT);
+        assert_eq!(unsafe { (unit.methods().describe)(unit.as_ptr()) }, "unit");
+        assert!(unit.downcast_mut::<()>().is_some());
+        assert!(unit.downcast_ref::<u8>().is_none());
+    }
+
+    #[test]
+    #[should_panic = "vtable built for"]
+    fn checks_the_type() {
+        RawObject::new(1u8, &UNIT);
+    }
+}
@@ -0,0 +1,451 @@
+//! Errors of any type behind one pointer, with context and downcasting.
+//!
+//! [`BoxError`] is a [`RawObject`] whose vtable turns the erased value back
+//! into a `dyn` [`Error`], and finds the values it can be downcast to. Any
+//! [`Error`] converts into one with `?`, and [`Context`] wraps either a
+//! failed result or a missing option with a message saying what was being
+//! done.
+//!
+//! ```
+//! use nomicon::error::{BoxError, Context};
+//!
+//! fn parse(input: &str) -> Result<u8, BoxError> {
+//!     let n = input.parse::<u8>().with_context(|| format!("parsing {input:?}"))?;
+//!     (n % 2 == 0).then_some(n).context("odd number")
+//! }
+//!
+//! assert_eq!(parse("42").unwrap(), 42);
+//! let error = parse("300").unwrap_err();
+//! assert_eq!(error.to_string(), "parsing \"300\"");
+//! assert_eq!(format!("{error:#}"), "parsing \"300\": number too large to fit in target type");
+//! assert!(error.downcast_ref::<std::num::ParseIntError>().is_some());
+//! assert_eq!(error.chain().count(), 2);
+//! ```
+
+use std::{
+    any::TypeId,
+    fmt::{self, Debug, Display},
+    marker::PhantomData,
+};
+
+use crate::dynamic::{RawObject, VTable};
+
+/// A failure with a message, and possibly the failure that caused it.
+///
+/// The crate's own version of [`std::error::Error`], for errors that can be
+/// boxed into a [`BoxError`].
+pub trait Error: Debug + Display {
+    /// The error this one was caused by.
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl dyn Error + 'static {
+    /// Walk from this error through its sources.
+    pub fn chain(&self) -> Chain<'_> {
+        Chain { next: Some(self) }
+    }
+}
+
+/// Iterates over an error and its sources, outermost first.
+///
+/// This type can be constructed through [`BoxError::chain`].
+#[derive(Clone)]
+pub struct Chain<'a> {
+    next: Option<&'a (dyn Error + 'static)>,
+}
+
+impl<'a> Iterator for Chain<'a> {
+    type Item = &'a (dyn Error + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let error = self.next?;
+        self.next = error.source();
+        Some(error)
+    }
+}
+
+/// The methods of a boxed error, each taking a pointer to it.
+struct Methods {
+    as_error: unsafe fn(*const ()) -> *const (dyn Error + 'static),
+    /// Returns a pointer to the value of the given type inside, or null.
+    downcast: unsafe fn(*mut (), TypeId) -> *mut (),
+}
+
+unsafe fn as_error<E: Error + 'static>(this: *const ()) -> *const (dyn Error + 'static) {
+    this.cast::<E>()
+}
+
+unsafe fn as_message<M: Display + Debug + 'static>(
+    this: *const (),
+) -> *const (dyn Error + 'static) {
+    this.cast::<Message<M>>()
+}
+
+unsafe fn downcast<T: 'static>(this: *mut (), id: TypeId) -> *mut () {
+    if id == TypeId::of::<T>() {
+        this
+    } else {
+        std::ptr::null_mut()
+    }
+}
+
+unsafe fn downcast_context<C: 'static>(this: *mut (), id: TypeId) -> *mut () {
+    let this = this.cast::<WithContext<C>>();
+    if id == TypeId::of::<C>() {
+        // SAFETY:
+        // * The pointer is to a live WithContext<C>.
+        return unsafe { (&raw mut (*this).context).cast() };
+    }
+    // SAFETY:
+    // * As above, and the pointer to the source is the one its own object
+    //   hands out.
+    unsafe {
+        let object = &(*this).source.object;
+        (object.methods().downcast)(object.as_ptr().cast_mut(), id)
+    }
+}
+
+/// The table of an error type, built once per type.
+struct Table<T>(PhantomData<T>);
+
+impl<E: Error + 'static> Table<E> {
+    const ERROR: &'static VTable<Methods> = &VTable::new::<E>(Methods {
+        as_error: as_error::<E>,
+        downcast: downcast::<E>,
+    });
+}
+
+impl<M: Display + Debug + 'static> Table<M> {
+    /// Stores the bare message, so it downcasts to `M`.
+    const MESSAGE: &'static VTable<Methods> = &VTable::new::<M>(Methods {
+        as_error: as_message::<M>,
+        downcast: downcast::<M>,
+    });
+}
+
+impl<C: Display + Debug + 'static> Table<WithContext<C>> {
+    const CONTEXT: &'static VTable<Methods> = &VTable::new::<WithContext<C>>(Methods {
+        as_error: as_error::<WithContext<C>>,
+        downcast: downcast_context::<C>,
+    });
+}
+
+/// A message used as an error.
+#[repr(transparent)]
+struct Message<M>(M);
+
+impl<M: Debug> Debug for Message<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<M: Display> Display for Message<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<M: Display + Debug> Error for Message<M> {}
+
+/// An error with a message about what was being done when it happened.
+struct WithContext<C> {
+    context: C,
+    source: BoxError,
+}
+
+impl<C: Debug> Debug for WithContext<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithContext")
+            .field("context", &self.context)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl<C: Display> Display for WithContext<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.context.fmt(f)
+    }
+}
+
+impl<C: Display + Debug> Error for WithContext<C> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_error())
+    }
+}
+
+/// An owned error of any type.
+///
+/// Displays as the outermost error, or with `{:#}` as the whole chain
+/// joined by colons. Debug formatting lists the causes below the error.
+///
+/// This type can be constructed through [`BoxError::new`],
+/// [`BoxError::msg`], and `From` any [`Error`].
+pub struct BoxError {
+    object: RawObject<Methods>,
+}
+
+impl BoxError {
+    pub fn new<E: Error + 'static>(error: E) -> Self {
+        Self {
+            object: RawObject::new(error, Table::<E>::ERROR),
+        }
+    }
+
+    /// Returns an error that is just `message`.
+    ///
+    /// ```
+    /// use nomicon::error::BoxError;
+    ///
+    /// let error = BoxError::msg("disk full");
+    /// assert_eq!(error.to_string(), "disk full");
+    /// assert_eq!(error.downcast::<&str>().unwrap(), "disk full");
+    /// ```
+    pub fn msg<M: Display + Debug + 'static>(message: M) -> Self {
+        Self {
+            object: RawObject::new(message, Table::<M>::MESSAGE),
+        }
+    }
+
+    /// Wrap the error in `context`, which becomes the message and has the
+    /// error as its source.
+    pub fn context<C: Display + Debug + 'static>(self, context: C) -> Self {
+        let error = WithContext {
+            context,
+            source: self,
+        };
+        Self {
+            object: RawObject::new(error, Table::<WithContext<C>>::CONTEXT),
+        }
+    }
+
+    pub fn as_error(&self) -> &(dyn Error + 'static) {
+        // SAFETY:
+        // * The table is the one built for the value's type.
+        unsafe { &*(self.object.methods().as_error)(self.object.as_ptr()) }
+    }
+
+    pub fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.as_error().source()
+    }
+
+    pub fn chain(&self) -> Chain<'_> {
+        self.as_error().chain()
+    }
+
+    /// The innermost source.
+    pub fn root_cause(&self) -> &(dyn Error + 'static) {
+        self.chain().last().unwrap()
+    }
+
+    /// Returns true if the error, any context around it, or a message is a
+    /// `T`.
+    pub fn is<T: 'static>(&self) -> bool {
+        self.downcast_ref::<T>().is_some()
+    }
+
+    /// Returns the error, any context around it, or a message as a `T`.
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        // SAFETY:
+        // * The table is the one built for the value's type, and only hands
+        //   out a pointer to a T when asked for one.
+        unsafe {
+            let ptr = (self.object.methods().downcast)(
+                self.object.as_ptr().cast_mut(),
+                TypeId::of::<T>(),
+            );
+            ptr.cast::<T>().as_ref()
+        }
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        // SAFETY:
+        // * As in downcast_ref, through the mutable pointer.
+        unsafe {
+            let ptr = (self.object.methods().downcast)(self.object.as_mut_ptr(), TypeId::of::<T>());
+            ptr.cast::<T>().as_mut()
+        }
+    }
+
+    /// Take the value back out, if the outermost error or message is a `T`.
+    pub fn downcast<T: 'static>(self) -> Result<T, Self> {
+        match self.object.downcast::<T>() {
+            Ok(value) => Ok(*value),
+            Err(object) => Err(Self { object }),
+        }
+    }
+}
+
+impl<E: Error + 'static> From<E> for BoxError {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl Display for BoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_error(), f)?;
+        if f.alternate() {
+            for cause in self.chain().skip(1) {
+                write!(f, ": {cause}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for BoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return Debug::fmt(self.as_error(), f);
+        }
+        write!(f, "{}", self.as_error())?;
+        for (n, cause) in self.chain().skip(1).enumerate() {
+            if n == 0 {
+                f.write_str("\n\nCaused by:")?;
+            }
+            write!(f, "\n    {n}: {cause}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds context to a failed [`Result`] or a missing [`Option`].
+pub trait Context<T> {
+    fn context<C: Display + Debug + 'static>(self, context: C) -> Result<T, BoxError>;
+
+    /// Like [`Context::context`], only building the context on failure.
+    fn with_context<C: Display + Debug + 'static>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, BoxError>;
+}
+
+impl<T, E: Error + 'static> Context<T> for Result<T, E> {
+    fn context<C: Display + Debug + 'static>(self, context: C) -> Result<T, BoxError> {
+        self.map_err(|error| BoxError::new(error).context(context))
+    }
+
+    fn with_context<C: Display + Debug + 'static>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, BoxError> {
+        self.map_err(|error| BoxError::new(error).context(context()))
+    }
+}
+
+impl<T> Context<T> for Result<T, BoxError> {
+    fn context<C: Display + Debug + 'static>(self, context: C) -> Result<T, BoxError> {
+        self.map_err(|error| error.context(context))
+    }
+
+    fn with_context<C: Display + Debug + 'static>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, BoxError> {
+        self.map_err(|error| error.context(context()))
+    }
+}
+
+/// The context becomes the error.
+impl<T> Context<T> for Option<T> {
+    fn context<C: Display + Debug + 'static>(self, context: C) -> Result<T, BoxError> {
+        self.ok_or_else(|| BoxError::msg(context))
+    }
+
+    fn with_context<C: Display + Debug + 'static>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, BoxError> {
+        self.ok_or_else(|| BoxError::msg(context()))
+    }
+}
+
+/// Implement [`Error`] without a source for types that implement the std
+/// one, whose sources are std errors and so can not be followed.
+macro_rules! error {
+    ($($({$($generic:ident),*})? $ty:ty),* $(,)?) => {
+        $(impl$(<$($generic),*>)? Error for $ty {})*
+    };
+}
+
+error!(
+    crate::alloc::AllocError,
+    crate::alloc::TryReserveError,
+    {T} crate::channel::SendError<T>,
+    {T} crate::channel::TrySendError<T>,
+    crate::channel::RecvError,
+    crate::channel::TryRecvError,
+    crate::channel::RecvTimeoutError,
+    crate::executor::Elapsed,
+    crate::ffi::FromBytesWithNulError,
+    crate::ffi::NulError,
+    crate::ffi::IntoStringError,
+    crate::string::Utf8Error,
+    {T} crate::sync::PoisonError<T>,
+    {T} crate::sync::TryLockError<T>,
+    fmt::Error,
+    std::io::Error,
+    std::num::ParseIntError,
+    std::num::ParseFloatError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+);
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Fail(Rc<()>);
+
+    impl Display for Fail {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("fail")
+        }
+    }
+
+    impl Error for Fail {}
+
+    #[test]
+    fn context_layers_downcast_and_drop() {
+        let token = Rc::new(());
+        let mut error = BoxError::new(Fail(Rc::clone(&token)))
+            .context("inner")
+            .context(7u8);
+        assert_eq!(format!("{error:#}"), "7: inner: fail");
+        assert_eq!(
+            format!("{error:?}"),
+            "7\n\nCaused by:\n    0: inner\n    1: fail"
+        );
+        assert_eq!(error.root_cause().to_string(), "fail");
+
+        assert_eq!(error.downcast_ref::<&str>(), Some(&"inner"));
+        *error.downcast_mut::<u8>().unwrap() += 1;
+        assert_eq!(error.to_string(), "8");
+        assert!(error.is::<Fail>());
+        assert!(!error.is::<String>());
+
+        let error = error.downcast::<Fail>().unwrap_err();
+        assert_eq!(Rc::strong_count(&token), 2);
+        drop(error);
+        assert_eq!(Rc::strong_count(&token), 1);
+
+        let fail = BoxError::from(Fail(Rc::clone(&token)))
+            .downcast::<Fail>()
+            .unwrap();
+        assert!(Rc::ptr_eq(&fail.0, &token));
+    }
+
+    #[test]
+    fn zero_sized_errors() {
+        let error = BoxError::from(crate::channel::RecvError);
+        assert!(error.source().is_none());
+        assert!(error.downcast::<crate::channel::RecvError>().is_ok());
+    }
+}
@@ -0,0 +1,367 @@
+//! Running futures to completion on the current thread.
+//!
+//! [`block_on`] drives a single future, [`Executor`] interleaves many. In
+//! both the thread parks while nothing is ready, and wakers unpark it, so
+//! futures can be woken from any thread. While parked, the thread also
+//! keeps track of the deadlines of [`sleep`] and [`timeout`].
+
+mod timer;
+
+pub use timer::{sleep, timeout, Elapsed, Sleep, Timeout};
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::{pin, Pin},
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+use crate::{
+    arc::Arc,
+    cell::RefCell,
+    rc::Rc,
+    slab::Slab,
+    sync::{Mutex, Parker, PoisonError, Unparker},
+    task::{waker, ArcWake},
+};
+
+impl ArcWake for Unparker {
+    fn wake_by_ref(this: &Arc<Self>) {
+        this.unpark();
+    }
+}
+
+/// Run `future` to completion on the current thread.
+///
+/// ```
+/// use nomicon::executor::block_on;
+///
+/// let answer = block_on(async { 6 * 7 });
+/// assert_eq!(answer, 42);
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let parker = Parker::new();
+    let waker = waker(Arc::new(parker.unparker().clone()));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        park(&parker);
+    }
+}
+
+/// Park until woken, or until the next sleep on this thread is due.
+fn park(parker: &Parker) {
+    // Firing sleeps wakes their tasks, which leaves a token for the parker.
+    match timer::fire() {
+        Some(deadline) => {
+            parker.park_timeout(deadline.saturating_duration_since(Instant::now()));
+        }
+        None => parker.park(),
+    }
+}
+
+/// Tasks that were woken, shared with every task's waker.
+struct Ready {
+    queue: Mutex<VecDeque<usize>>,
+    unparker: Unparker,
+}
+
+/// Queues its task on the executor when woken.
+struct TaskWaker {
+    key: usize,
+    /// Set while the task sits in the queue, so it is queued once.
+    queued: AtomicBool,
+    ready: Arc<Ready>,
+}
+
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(this: &Arc<Self>) {
+        if !this.queued.swap(true, Ordering::AcqRel) {
+            this.ready
+                .queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push_back(this.key);
+            this.ready.unparker.unpark();
+        }
+    }
+}
+
+struct Task<'a> {
+    /// Taken out while the task is polled.
+    future: Option<Pin<Box<dyn Future<Output = ()> + 'a>>>,
+    wake: Arc<TaskWaker>,
+    waker: Waker,
+}
+
+/// A single threaded executor, running spawned futures until all of them
+/// finish.
+///
+/// Futures do not have to be [`Send`], and may borrow from the stack. Woken
+/// tasks are queued, and [`Executor::run`] polls them in the order they were
+/// woken, parking the thread while the queue is empty.
+///
+/// ```
+/// use nomicon::executor::Executor;
+///
+/// let executor = Executor::new();
+/// let a = executor.spawn(async { 1 });
+/// let b = executor.spawn(async { 2 });
+/// let sum = executor.spawn(async { a.await + b.await });
+/// executor.run();
+/// assert_eq!(sum.try_take(), Some(3));
+/// ```
+pub struct Executor<'a> {
+    tasks: RefCell<Slab<Task<'a>>>,
+    ready: Arc<Ready>,
+    parker: Parker,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new() -> Self {
+        let parker = Parker::new();
+        Self {
+            tasks: RefCell::new(Slab::new()),
+            ready: Arc::new(Ready {
+                queue: Mutex::new(VecDeque::new()),
+                unparker: parker.unparker().clone(),
+            }),
+            parker,
+        }
+    }
+
+    /// Queue `future` to be polled by [`Executor::run`], returning a handle
+    /// to its output.
+    pub fn spawn<T: 'a>(&self, future: impl Future<Output = T> + 'a) -> JoinHandle<T> {
+        let state = Rc::new(RefCell::new(JoinState {
+            output: None,
+            waker: None,
+        }));
+        let handle = JoinHandle {
+            state: Rc::clone(&state),
+        };
+        let future = async move {
+            let output = future.await;
+            let mut state = state.borrow_mut();
+            state.output = Some(output);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        };
+
+        let mut tasks = self.tasks.borrow_mut();
+        let key = tasks.vacant_key();
+        let wake = Arc::new(TaskWaker {
+            key,
+            queued: AtomicBool::new(false),
+            ready: Arc::clone(&self.ready),
+        });
+        tasks.insert(Task {
+            future: Some(Box::pin(future)),
+            wake: Arc::clone(&wake),
+            waker: waker(Arc::clone(&wake)),
+        });
+        // Every task is polled once to get it going.
+        TaskWaker::wake(wake);
+        handle
+    }
+
+    /// Returns the number of tasks that have not finished.
+    pub fn len(&self) -> usize {
+        self.tasks.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Poll woken tasks until every task has finished.
+    pub fn run(&self) {
+        while !self.is_empty() {
+            let next = self
+                .ready
+                .queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front();
+            match next {
+                Some(key) => self.poll(key),
+                None => park(&self.parker),
+            }
+        }
+    }
+
+    fn poll(&self, key: usize) {
+        let (mut future, waker) = {
+            let mut tasks = self.tasks.borrow_mut();
+            // A stale wake of a finished task, whose key may have been reused.
+            let Some(task) = tasks.get_mut(key) else {
+                return;
+            };
+            let Some(future) = task.future.take() else {
+                return;
+            };
+            // Wakes from here on queue the task again.
+            task.wake.queued.store(false, Ordering::Release);
+            (future, task.waker.clone())
+        };
+
+        let done = future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready();
+        let mut tasks = self.tasks.borrow_mut();
+        if done {
+            tasks.remove(key);
+        } else {
+            tasks[key].future = Some(future);
+        }
+    }
+}
+
+impl Default for Executor<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    /// The task awaiting the handle.
+    waker: Option<Waker>,
+}
+
+/// The output of a task spawned on an [`Executor`], awaiting the handle
+/// waits for the task to finish.
+///
+/// Dropping the handle does not cancel the task.
+///
+/// This type can be constructed through [`Executor::spawn`].
+pub struct JoinHandle<T> {
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Take the output, if the task has finished.
+    pub fn try_take(&self) -> Option<T> {
+        self.state.borrow_mut().output.take()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Pending until another thread wakes it, once.
+    struct Remote {
+        waker: std::sync::Arc<std::sync::Mutex<(bool, Option<Waker>)>>,
+    }
+
+    impl Future for Remote {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut state = self.waker.lock().unwrap();
+            if state.0 {
+                return Poll::Ready(());
+            }
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn remote() -> (Remote, impl FnOnce() + Send) {
+        let shared = std::sync::Arc::new(std::sync::Mutex::new((false, None::<Waker>)));
+        let waker = std::sync::Arc::clone(&shared);
+        let wake = move || {
+            std::thread::sleep(Duration::from_millis(10));
+            let mut state = waker.lock().unwrap();
+            state.0 = true;
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        };
+        (Remote { waker: shared }, wake)
+    }
+
+    #[test]
+    fn block_on_parks_until_woken() {
+        let (future, wake) = remote();
+        let thread = std::thread::spawn(wake);
+        block_on(future);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn runs_every_task() {
+        let log = RefCell::new(Vec::new());
+        let executor = Executor::new();
+        let (future, wake) = remote();
+        let thread = std::thread::spawn(wake);
+        let waiting = executor.spawn(async {
+            future.await;
+            log.borrow_mut().push("woken");
+        });
+        for n in 0..3 {
+            let log = &log;
+            executor.spawn(async move { log.borrow_mut().push(["a", "b", "c"][n]) });
+        }
+        let last = executor.spawn(async {
+            waiting.await;
+            "done"
+        });
+        assert_eq!(executor.len(), 5);
+        executor.run();
+        thread.join().unwrap();
+        assert!(executor.is_empty());
+        assert_eq!(last.try_take(), Some("done"));
+        assert_eq!(*log.borrow(), ["a", "b", "c", "woken"]);
+    }
+
+    #[test]
+    fn sleeps_fire_in_order() {
+        let log = RefCell::new(Vec::new());
+        let executor = Executor::new();
+        for ms in [30, 10, 20] {
+            let log = &log;
+            executor.spawn(async move {
+                sleep(Duration::from_millis(ms)).await;
+                log.borrow_mut().push(ms);
+            });
+        }
+        let start = Instant::now();
+        let raced = executor.spawn(timeout(
+            Duration::from_millis(5),
+            sleep(Duration::from_secs(60)),
+        ));
+        let finished = executor.spawn(timeout(Duration::from_secs(60), sleep(Duration::ZERO)));
+        executor.run();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert!(start.elapsed() < Duration::from_secs(60));
+        assert_eq!(*log.borrow(), [10, 20, 30]);
+        assert_eq!(raced.try_take(), Some(Err(Elapsed)));
+        assert_eq!(finished.try_take(), Some(Ok(())));
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    error::Error,
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::cell::RefCell;
+
+/// The sleeps registered on a thread, fired by whichever of
+/// [`super::block_on`] and [`super::Executor::run`] drives it.
+#[derive(Default)]
+struct Timers {
+    /// Deadlines, soonest first. Entries of dropped sleeps are left behind
+    /// and skipped when they come up.
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+    next_id: u64,
+}
+
+thread_local! {
+    static TIMERS: RefCell<Timers> = RefCell::new(Timers::default());
+}
+
+/// Wake every sleep whose deadline has passed, returning the next deadline.
+pub(super) fn fire() -> Option<Instant> {
+    let mut expired = Vec::new();
+    let next = TIMERS.with(|timers| {
+        let mut timers = timers.borrow_mut();
+        let now = Instant::now();
+        while let Some(&Reverse((deadline, id))) = timers.deadlines.peek() {
+            if deadline > now {
+                return Some(deadline);
+            }
+            timers.deadlines.pop();
+            expired.extend(timers.wakers.remove(&id));
+        }
+        None
+    });
+    // Wakers may register new sleeps, wake outside the borrow.
+    for waker in expired {
+        waker.wake();
+    }
+    next
+}
+
+/// Returns a future completing once `duration` has passed.
+///
+/// Timers belong to the thread polling the future, and only fire while
+/// [`super::block_on`] or [`super::Executor::run`] drives that thread.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// use nomicon::executor::{block_on, sleep};
+///
+/// let start = Instant::now();
+/// block_on(sleep(Duration::from_millis(10)));
+/// assert!(start.elapsed() >= Duration::from_millis(10));
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    // Deadlines too far out to represent never come.
+    let deadline = Instant::now().checked_add(duration);
+    Sleep {
+        deadline,
+        id: None,
+        _marker: PhantomData,
+    }
+}
+
+/// See [`sleep`].
+///
+/// This type can be constructed through [`sleep`].
+pub struct Sleep {
+    deadline: Option<Instant>,
+    /// Set once registered with the thread's timers.
+    id: Option<u64>,
+    /// Registered with the timers of the thread it was polled on.
+    _marker: PhantomData<*const ()>,
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_elapsed() {
+            if let Some(id) = self.id.take() {
+                TIMERS.with(|timers| timers.borrow_mut().wakers.remove(&id));
+            }
+            return Poll::Ready(());
+        }
+        let Some(deadline) = self.deadline else {
+            return Poll::Pending;
+        };
+        let id = TIMERS.with(|timers| {
+            let mut timers = timers.borrow_mut();
+            let id = match self.id {
+                Some(id) => id,
+                None => {
+                    let id = timers.next_id;
+                    timers.next_id += 1;
+                    timers.deadlines.push(Reverse((deadline, id)));
+                    id
+                }
+            };
+            match timers.wakers.get_mut(&id) {
+                Some(waker) => waker.clone_from(cx.waker()),
+                None => {
+                    timers.wakers.insert(id, cx.waker().clone());
+                }
+            }
+            id
+        });
+        self.id = Some(id);
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            // The thread may be exiting, with its timers already gone.
+            let _ = TIMERS.try_with(|timers| timers.borrow_mut().wakers.remove(&id));
+        }
+    }
+}
+
+/// Run `future`, giving up once `duration` has passed.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use nomicon::{
+///     executor::{block_on, timeout},
+///     future,
+/// };
+///
+/// let result = block_on(timeout(Duration::from_millis(10), future::pending::<()>()));
+/// assert!(result.is_err());
+/// ```
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+crate::pin_project! {
+    #[project = TimeoutProjection]
+    /// See [`timeout`].
+    ///
+    /// This type can be constructed through [`timeout`].
+    pub struct Timeout<F> {
+        #[pin]
+        future: F,
+        sleep: Sleep,
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(this.sleep).poll(cx).map(|()| Err(Elapsed))
+    }
+}
+
+/// The error returned by [`timeout`] when the future did not finish in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
@@ -0,0 +1,337 @@
+//! Strings crossing into C, nul terminated and free of interior nuls.
+//!
+//! [`CString`] owns its bytes and can hand them to C and take them back,
+//! [`CStr`] borrows them, including strings C allocated.
+
+use std::{error::Error, ffi::c_char, fmt, ops::Deref, str::Utf8Error};
+
+/// A borrowed nul terminated string.
+///
+/// Unsized like [`str`], the slice includes the terminating nul and holds no
+/// other.
+///
+/// ```
+/// use nomicon::ffi::CStr;
+///
+/// let hello = CStr::from_bytes_with_nul(b"hello\0").unwrap();
+/// assert_eq!(hello.to_bytes(), b"hello");
+/// assert!(CStr::from_bytes_with_nul(b"hel\0lo\0").is_err());
+/// ```
+///
+/// This type can be constructed through [`CStr::from_bytes_with_nul`] and
+/// [`CStr::from_ptr`].
+#[repr(transparent)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct CStr {
+    bytes: [u8],
+}
+
+impl CStr {
+    /// Returns the string in `bytes`, which has to end in its only nul.
+    pub fn from_bytes_with_nul(bytes: &[u8]) -> Result<&Self, FromBytesWithNulError> {
+        match bytes.iter().position(|&byte| byte == 0) {
+            Some(position) if position + 1 == bytes.len() => {
+                Ok(unsafe { Self::from_bytes_with_nul_unchecked(bytes) })
+            }
+            Some(position) => Err(FromBytesWithNulError::InteriorNul { position }),
+            None => Err(FromBytesWithNulError::NotNulTerminated),
+        }
+    }
+
+    /// # Safety
+    /// `bytes` ends in a nul, and holds no other.
+    pub const unsafe fn from_bytes_with_nul_unchecked(bytes: &[u8]) -> &Self {
+        // SAFETY
+        // * CStr is a transparent wrapper around [u8].
+        unsafe { &*(bytes as *const [u8] as *const Self) }
+    }
+
+    /// Wrap a string handed over by C.
+    ///
+    /// # Safety
+    /// `ptr` points to a nul terminated string that is valid and unchanged
+    /// for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *const c_char) -> &'a Self {
+        let ptr = ptr.cast::<u8>();
+        let mut len = 0;
+        // Looking for the nul ourselves is what strlen would do.
+        while unsafe { *ptr.add(len) } != 0 {
+            len += 1;
+        }
+        unsafe { Self::from_bytes_with_nul_unchecked(std::slice::from_raw_parts(ptr, len + 1)) }
+    }
+
+    /// Returns the pointer to pass to C, valid while `self` is borrowed.
+    pub const fn as_ptr(&self) -> *const c_char {
+        self.bytes.as_ptr().cast()
+    }
+
+    /// Returns the length, not counting the nul.
+    pub const fn count_bytes(&self) -> usize {
+        self.bytes.len() - 1
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.count_bytes() == 0
+    }
+
+    /// Returns the bytes without the nul.
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.bytes[..self.count_bytes()]
+    }
+
+    pub const fn to_bytes_with_nul(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn to_str(&self) -> Result<&str, Utf8Error> {
+        std::str::from_utf8(self.to_bytes())
+    }
+
+    pub fn to_owned(&self) -> CString {
+        CString {
+            bytes: self.bytes.into(),
+        }
+    }
+}
+
+impl fmt::Debug for CStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&String::from_utf8_lossy(self.to_bytes()), f)
+    }
+}
+
+impl AsRef<CStr> for CStr {
+    fn as_ref(&self) -> &CStr {
+        self
+    }
+}
+
+/// An owned nul terminated string, allocated by Rust.
+///
+/// ```
+/// use nomicon::ffi::CString;
+///
+/// let greeting = CString::new("hi").unwrap();
+/// assert_eq!(greeting.to_bytes_with_nul(), b"hi\0");
+/// assert_eq!(CString::new("h\0i").unwrap_err().nul_position(), 1);
+///
+/// // Ownership goes to C and comes back.
+/// let raw = greeting.into_raw();
+/// let greeting = unsafe { CString::from_raw(raw) };
+/// assert_eq!(greeting.into_string().unwrap(), "hi");
+/// ```
+///
+/// This type can be constructed through [`CString::new`].
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct CString {
+    /// Ends in the only nul.
+    bytes: Box<[u8]>,
+}
+
+impl CString {
+    /// Append a nul to `bytes`.
+    ///
+    /// Fails if `bytes` already holds a nul, handing them back.
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Result<Self, NulError> {
+        let mut bytes = bytes.into();
+        if let Some(position) = bytes.iter().position(|&byte| byte == 0) {
+            return Err(NulError { position, bytes });
+        }
+        bytes.reserve_exact(1);
+        bytes.push(0);
+        Ok(Self {
+            bytes: bytes.into_boxed_slice(),
+        })
+    }
+
+    /// Hand the string over to C, which has to give it back through
+    /// [`CString::from_raw`] to free it.
+    pub fn into_raw(self) -> *mut c_char {
+        Box::into_raw(self.bytes).cast()
+    }
+
+    /// Take back a string given out by [`CString::into_raw`].
+    ///
+    /// # Safety
+    /// `ptr` came from [`CString::into_raw`] and is taken back once. C may
+    /// have changed the bytes, but not the position of the nul.
+    pub unsafe fn from_raw(ptr: *mut c_char) -> Self {
+        // The length is not stored, the nul is what tells it.
+        let len = unsafe { CStr::from_ptr(ptr) }.bytes.len();
+        let bytes = std::ptr::slice_from_raw_parts_mut(ptr.cast::<u8>(), len);
+        // SAFETY
+        // * The slice has the length of the original box, nul included, so
+        //   it is freed with the layout it was allocated with.
+        Self {
+            bytes: unsafe { Box::from_raw(bytes) },
+        }
+    }
+
+    pub fn as_c_str(&self) -> &CStr {
+        // SAFETY
+        // * The bytes end in their only nul.
+        unsafe { CStr::from_bytes_with_nul_unchecked(&self.bytes) }
+    }
+
+    /// Returns the bytes without the nul.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::from(self.bytes);
+        bytes.pop();
+        bytes
+    }
+
+    /// Convert to a [`String`] without the nul, if the bytes are UTF-8.
+    pub fn into_string(self) -> Result<String, IntoStringError> {
+        String::from_utf8(self.into_bytes()).map_err(|err| IntoStringError {
+            utf8: err.utf8_error(),
+            inner: Self::new(err.into_bytes()).expect("bytes came from a CString"),
+        })
+    }
+}
+
+impl Deref for CString {
+    type Target = CStr;
+
+    fn deref(&self) -> &CStr {
+        self.as_c_str()
+    }
+}
+
+impl AsRef<CStr> for CString {
+    fn as_ref(&self) -> &CStr {
+        self
+    }
+}
+
+impl fmt::Debug for CString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_c_str(), f)
+    }
+}
+
+impl From<&CStr> for CString {
+    fn from(value: &CStr) -> Self {
+        value.to_owned()
+    }
+}
+
+impl TryFrom<String> for CString {
+    type Error = NulError;
+
+    fn try_from(value: String) -> Result<Self, NulError> {
+        Self::new(value)
+    }
+}
+
+/// The error returned by [`CStr::from_bytes_with_nul`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FromBytesWithNulError {
+    /// A nul before the last byte.
+    InteriorNul { position: usize },
+    /// The last byte is not a nul.
+    NotNulTerminated,
+}
+
+impl fmt::Display for FromBytesWithNulError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InteriorNul { position } => write!(f, "interior nul byte at {position}"),
+            Self::NotNulTerminated => f.write_str("bytes are not nul terminated"),
+        }
+    }
+}
+
+impl Error for FromBytesWithNulError {}
+
+/// The error returned by [`CString::new`], handing the bytes back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NulError {
+    position: usize,
+    bytes: Vec<u8>,
+}
+
+impl NulError {
+    /// Returns the index of the first nul.
+    pub fn nul_position(&self) -> usize {
+        self.position
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl fmt::Display for NulError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nul byte found at {}", self.position)
+    }
+}
+
+impl Error for NulError {}
+
+/// The error returned by [`CString::into_string`], handing the string back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntoStringError {
+    inner: CString,
+    utf8: Utf8Error,
+}
+
+impl IntoStringError {
+    pub fn into_cstring(self) -> CString {
+        self.inner
+    }
+
+    pub fn utf8_error(&self) -> Utf8Error {
+        self.utf8
+    }
+}
+
+impl fmt::Display for IntoStringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "C string is not UTF-8: {}", self.utf8)
+    }
+}
+
+impl Error for IntoStringError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    extern "C" {
+        fn strlen(s: *const c_char) -> usize;
+        fn strchr(s: *const c_char, c: i32) -> *const c_char;
+    }
+
+    #[test]
+    fn round_trips_through_c() {
+        let owned = CString::new("key=value").unwrap();
+        assert_eq!(unsafe { strlen(owned.as_ptr()) }, 9);
+        let value = unsafe { CStr::from_ptr(strchr(owned.as_ptr(), i32::from(b'='))) };
+        assert_eq!(value.to_str(), Ok("=value"));
+
+        let raw = owned.into_raw();
+        unsafe { *raw = b'K' as c_char };
+        let owned = unsafe { CString::from_raw(raw) };
+        assert_eq!(owned.to_bytes(), b"Key=value");
+
+        let empty = CString::new(Vec::new()).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(CString::from(empty.as_c_str()), empty);
+    }
+
+    #[test]
+    fn rejects_bad_bytes() {
+        assert_eq!(
+            CStr::from_bytes_with_nul(b"no nul"),
+            Err(FromBytesWithNulError::NotNulTerminated)
+        );
+        let err = CString::new(b"a\0b".to_vec()).unwrap_err();
+        assert_eq!(err.into_vec(), b"a\0b");
+
+        let invalid = CString::new(vec![0xff]).unwrap();
+        let err = invalid.into_string().unwrap_err();
+        assert_eq!(err.into_cstring().to_bytes(), [0xff]);
+    }
+}
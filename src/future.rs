@@ -0,0 +1,313 @@
+//! Composing futures without a runtime of their own.
+//!
+//! Every combinator here drives its inner futures from its own `poll`, so
+//! they run concurrently on whatever executor polls the outer future, for
+//! instance [`crate::executor::block_on`].
+
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A future that is ready with `value` on its first poll.
+///
+/// ```
+/// use nomicon::{executor::block_on, future};
+///
+/// assert_eq!(block_on(future::ready(7)), 7);
+/// ```
+pub fn ready<T>(value: T) -> Ready<T> {
+    Ready(Some(value))
+}
+
+/// See [`ready`].
+///
+/// This type can be constructed through [`ready`].
+#[derive(Debug, Clone)]
+pub struct Ready<T>(Option<T>);
+
+impl<T> Unpin for Ready<T> {}
+
+impl<T> Future for Ready<T> {
+    type Output = T;
+
+    /// # Panics
+    /// If polled again after completing.
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+        Poll::Ready(self.0.take().expect("Ready polled after completion"))
+    }
+}
+
+/// A future that never completes.
+pub fn pending<T>() -> Pending<T> {
+    Pending(PhantomData)
+}
+
+/// See [`pending`].
+///
+/// This type can be constructed through [`pending`].
+#[derive(Debug, Clone)]
+pub struct Pending<T>(PhantomData<fn() -> T>);
+
+impl<T> Future for Pending<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+        Poll::Pending
+    }
+}
+
+/// A future and then its output, held until every sibling is done.
+enum MaybeDone<F: Future> {
+    Future(F),
+    Done(F::Output),
+    Taken,
+}
+
+impl<F: Future> MaybeDone<F> {
+    /// Poll the future if it is still running, returns true once done.
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> bool {
+        // SAFETY
+        // * The future is pinned structurally, it is never moved out, only
+        //   dropped in place by `set`.
+        let future = match unsafe { self.as_mut().get_unchecked_mut() } {
+            MaybeDone::Future(future) => unsafe { Pin::new_unchecked(future) },
+            MaybeDone::Done(_) => return true,
+            MaybeDone::Taken => panic!("future polled after completion"),
+        };
+        match future.poll(cx) {
+            Poll::Ready(output) => {
+                self.set(MaybeDone::Done(output));
+                true
+            }
+            Poll::Pending => false,
+        }
+    }
+
+    fn take(self: Pin<&mut Self>) -> F::Output {
+        // SAFETY
+        // * Only the output is moved out, the future is already gone.
+        let this = unsafe { self.get_unchecked_mut() };
+        match std::mem::replace(this, MaybeDone::Taken) {
+            MaybeDone::Done(output) => output,
+            _ => unreachable!("output taken before completion"),
+        }
+    }
+}
+
+/// Wait for both `a` and `b`, returning both outputs.
+///
+/// ```
+/// use nomicon::{executor::block_on, future};
+///
+/// let both = future::join(async { 1 }, future::ready("two"));
+/// assert_eq!(block_on(both), (1, "two"));
+/// ```
+pub fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join {
+        a: MaybeDone::Future(a),
+        b: MaybeDone::Future(b),
+    }
+}
+
+crate::pin_project! {
+    #[project = JoinProjection]
+    /// See [`join`].
+    ///
+    /// This type can be constructed through [`join`].
+    pub struct Join<A: Future, B: Future> {
+        #[pin]
+        a: MaybeDone<A>,
+        #[pin]
+        b: MaybeDone<B>,
+    }
+}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let JoinProjection { mut a, mut b } = self.project();
+        // Both are polled even if one is pending, each needs its waker.
+        let a_done = a.as_mut().poll(cx);
+        let b_done = b.as_mut().poll(cx);
+        if a_done && b_done {
+            Poll::Ready((a.take(), b.take()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Wait for every future of `futures`, returning their outputs in order.
+///
+/// ```
+/// use nomicon::{executor::block_on, future};
+///
+/// let all = future::join_all((1..4).map(future::ready));
+/// assert_eq!(block_on(all), [1, 2, 3]);
+/// ```
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    JoinAll {
+        futures: Box::into_pin(futures.into_iter().map(MaybeDone::Future).collect()),
+    }
+}
+
+/// See [`join_all`].
+///
+/// This type can be constructed through [`join_all`].
+pub struct JoinAll<F: Future> {
+    /// Never reallocated, so the futures stay put while the outer future
+    /// moves.
+    futures: Pin<Box<[MaybeDone<F>]>>,
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut done = true;
+        for future in Self::iter_pin(self.futures.as_mut()) {
+            done &= future.poll(cx);
+        }
+        if done {
+            Poll::Ready(
+                Self::iter_pin(self.futures.as_mut())
+                    .map(MaybeDone::take)
+                    .collect(),
+            )
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<F: Future> JoinAll<F> {
+    fn iter_pin(slice: Pin<&mut [MaybeDone<F>]>) -> impl Iterator<Item = Pin<&mut MaybeDone<F>>> {
+        // SAFETY
+        // * The elements of a pinned slice are pinned, none is moved.
+        unsafe { slice.get_unchecked_mut() }
+            .iter_mut()
+            .map(|future| unsafe { Pin::new_unchecked(future) })
+    }
+}
+
+/// One of two values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Wait for the first of `a` and `b` to finish, dropping the other one.
+///
+/// `a` is polled first, so it wins if both are ready.
+///
+/// ```
+/// use nomicon::{
+///     executor::block_on,
+///     future::{self, Either},
+/// };
+///
+/// let first = future::select(future::pending::<()>(), async { "b" });
+/// assert_eq!(block_on(first), Either::Right("b"));
+/// ```
+pub fn select<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a, b }
+}
+
+crate::pin_project! {
+    #[project = SelectProjection]
+    /// See [`select`].
+    ///
+    /// This type can be constructed through [`select`].
+    pub struct Select<A, B> {
+        #[pin]
+        a: A,
+        #[pin]
+        b: B,
+    }
+}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.a.poll(cx) {
+            return Poll::Ready(Either::Left(output));
+        }
+        if let Poll::Ready(output) = this.b.poll(cx) {
+            return Poll::Ready(Either::Right(output));
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::executor::{block_on, Executor};
+
+    /// Pending for `polls` polls, waking itself each time.
+    struct Countdown(usize);
+
+    impl Future for Countdown {
+        type Output = usize;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+            if self.0 == 0 {
+                return Poll::Ready(0);
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn join_waits_for_both() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let executor = Executor::new();
+        let handle = executor.spawn(join(
+            async {
+                Countdown(3).await;
+                "slow"
+            },
+            async {
+                tx.send(()).unwrap();
+                "fast"
+            },
+        ));
+        executor.run();
+        rx.try_recv().unwrap();
+        assert_eq!(handle.try_take(), Some(("slow", "fast")));
+
+        let all = join_all((0..5).map(|n| async move { Countdown(5 - n).await + n }));
+        assert_eq!(block_on(all), [0, 1, 2, 3, 4]);
+        assert_eq!(block_on(join_all(Vec::<Ready<()>>::new())), []);
+    }
+
+    #[test]
+    fn select_drops_the_loser() {
+        let flag = std::rc::Rc::new(());
+        let loser = {
+            let flag = std::rc::Rc::clone(&flag);
+            async move {
+                Countdown(10).await;
+                drop(flag);
+            }
+        };
+        let winner = block_on(select(loser, Countdown(2)));
+        assert_eq!(winner, Either::Right(0));
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+
+        assert_eq!(block_on(select(ready(1), ready(2))), Either::Left(1));
+    }
+}
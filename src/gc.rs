@@ -0,0 +1,626 @@
+//! A tracing, cycle collecting alternative to [`crate::rc::Rc`].
+//!
+//! Every [`Gc`] lives on a heap local to the thread that allocated it. Like
+//! an [`Rc`](crate::rc::Rc), a box is freed as soon as its last handle is
+//! dropped. Boxes that only keep each other alive are freed by [`collect`].
+//!
+//! Roots are found from the handle counts rather than registered by hand. A
+//! box with more handles than the heap holds to it has a handle somewhere
+//! outside the heap, on the stack or in some other collection, and
+//! everything reachable from such a box is kept.
+//!
+//! [`CcRc`] uses the same [`Trace`] without a heap, only looking at the
+//! boxes that might be part of a cycle.
+//!
+//! ```
+//! use nomicon::gc::{self, Gc};
+//! use std::cell::RefCell;
+//!
+//! struct Node {
+//!     next: RefCell<Option<Gc<Node>>>,
+//! }
+//! nomicon::trace!(Node { next });
+//!
+//! let a = Gc::new(Node { next: RefCell::new(None) });
+//! let b = Gc::new(Node { next: RefCell::new(Some(a.clone())) });
+//! *a.next.borrow_mut() = Some(b.clone());
+//!
+//! // Both are reachable from the handles on the stack.
+//! assert_eq!(gc::collect(), 0);
+//! drop((a, b));
+//! assert_eq!(gc::collect(), 2);
+//! ```
+
+use std::{fmt, marker::PhantomData, mem::ManuallyDrop, ops::Deref, ptr::NonNull};
+
+use crate::{cell::Cell, cell::RefCell, guard::guard};
+
+/// Generates a test that values dropped by a collection can move handles to
+/// other collected boxes out, which keep those boxes allocated.
+#[cfg(test)]
+macro_rules! resurrection_test {
+    ($handle:ident, $collect:path) => {
+        #[test]
+        fn resurrected_handles_keep_the_box() {
+            use crate::cell::RefCell;
+
+            struct Escape {
+                other: RefCell<Option<$handle<Escape>>>,
+            }
+            crate::trace!(Escape { other });
+
+            thread_local! {
+                static ESCAPED: RefCell<Vec<$handle<Escape>>> = const { RefCell::new(Vec::new()) };
+            }
+
+            impl Drop for Escape {
+                fn drop(&mut self) {
+                    if let Some(other) = self.other.borrow_mut().take() {
+                        ESCAPED.with(|escaped| escaped.borrow_mut().push(other));
+                    }
+                }
+            }
+
+            let a = $handle::new(Escape {
+                other: RefCell::new(None),
+            });
+            let b = $handle::new(Escape {
+                other: RefCell::new(Some(a.clone())),
+            });
+            *a.other.borrow_mut() = Some(b);
+            drop(a);
+            assert_eq!($collect(), 2);
+
+            let escaped = ESCAPED.with(|escaped| std::mem::take(&mut *escaped.borrow_mut()));
+            assert_eq!(escaped.len(), 2);
+            assert!(escaped.iter().all(|handle| $handle::count(handle) == 1));
+            let deref = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                escaped[0].other.borrow().is_some()
+            }));
+            assert!(deref.is_err());
+
+            // Tracing skips the dead boxes, the dropped clone buffers the
+            // holder for a cycle collection.
+            let holder = $handle::new(Escape {
+                other: RefCell::new(Some(escaped[1].clone())),
+            });
+            drop(holder.clone());
+            assert_eq!($collect(), 0);
+            drop((holder, escaped));
+            ESCAPED.with(|escaped| escaped.borrow_mut().clear());
+        }
+    };
+}
+
+mod cc;
+
+pub use cc::{collect_cycles, CcRc};
+
+/// A value the collector can look through for the [`Gc`]s it owns.
+///
+/// Forgetting a handle only makes the collector keep what it points to, so
+/// an empty `trace` is always safe, if leaky. Safe implementations can be
+/// written with [`trace!`](crate::trace).
+///
+/// # Safety
+/// `trace` visits each [`Gc`] owned by the value at most once, never a
+/// handle it does not own, and visits the same handles on every call.
+pub unsafe trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+/// Implement [`Trace`] for a struct by tracing the listed fields.
+///
+/// Fields left out are not traced, which is safe but keeps whatever they
+/// hold alive.
+///
+/// ```
+/// use nomicon::gc::Gc;
+///
+/// struct Pair {
+///     left: Gc<u32>,
+///     right: Option<Gc<u32>>,
+///     name: String,
+/// }
+/// nomicon::trace!(Pair { left, right, name });
+/// ```
+#[macro_export]
+macro_rules! trace {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        // SAFETY:
+        // * The fields are destructured, each is bound and traced once.
+        unsafe impl $crate::gc::Trace for $ty {
+            fn trace(&self, tracer: &mut $crate::gc::Tracer) {
+                let Self { $($field,)* .. } = self;
+                $($crate::gc::Trace::trace($field, tracer);)*
+            }
+        }
+    };
+}
+
+/// Passed to [`Trace::trace`], visited by every [`Gc`] it reaches.
+pub struct Tracer {
+    phase: Phase,
+    /// Marked boxes whose values are yet to be traced.
+    pending: Vec<Erased>,
+    /// The [`CcRc`]s visited while listing children.
+    children: Vec<cc::Erased>,
+}
+
+enum Phase {
+    /// Count the handles held by the heap itself.
+    Count,
+    /// Mark everything reachable from the roots.
+    Mark,
+    /// List the [`CcRc`]s of a single value, [`Gc`]s are skipped.
+    Children,
+}
+
+impl Tracer {
+    fn new(phase: Phase) -> Self {
+        Self {
+            phase,
+            pending: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, ptr: Erased) {
+        let header = &unsafe { ptr.as_ref() }.header;
+        if header.collected.is_dead() {
+            return;
+        }
+        match self.phase {
+            Phase::Count => header.internal.set(header.internal.get() + 1),
+            Phase::Mark => {
+                if !header.marked.get() {
+                    header.marked.set(true);
+                    self.pending.push(ptr);
+                }
+            }
+            Phase::Children => {}
+        }
+    }
+}
+
+struct Header {
+    /// The number of live handles, wherever they are.
+    count: Cell<usize>,
+    /// The number of handles found inside the heap by the last collection.
+    internal: Cell<usize>,
+    marked: Cell<bool>,
+    collected: Collected,
+    /// The index of the box in the heap.
+    slot: Cell<usize>,
+}
+
+/// Whether a collection took the value of a box, shared by [`Gc`] and
+/// [`CcRc`].
+///
+/// A collection drops every unreachable value before freeing any box. Those
+/// drops can move a handle to another collected box out, which then panics
+/// when dereferenced, is skipped when traced, and frees the box once it is
+/// the last handle dropped.
+#[derive(Default)]
+struct Collected {
+    /// Set once a collection found the box unreachable.
+    dead: Cell<bool>,
+    /// Set once the value was dropped with handles left, the last of them
+    /// frees the box.
+    dropped: Cell<bool>,
+}
+
+impl Collected {
+    fn is_dead(&self) -> bool {
+        self.dead.get()
+    }
+
+    fn kill(&self) {
+        self.dead.set(true);
+    }
+
+    /// Panics if the value is gone, for a [`Deref`] of `handle`.
+    fn assert_alive(&self, handle: &str) {
+        assert!(
+            !self.is_dead(),
+            "{handle} dereferenced while it is being collected"
+        );
+    }
+
+    /// Called by the collection once the value is dropped, returns true if
+    /// no handle is left and the box is to be freed now.
+    fn value_dropped(&self, count: usize) -> bool {
+        self.dropped.set(count != 0);
+        count == 0
+    }
+
+    /// Called by a handle of a dead box leaving `count` others, returns true
+    /// if it has to free the box.
+    fn handle_dropped(&self, count: usize) -> bool {
+        count == 0 && self.dropped.get()
+    }
+}
+
+struct GcBox<T: ?Sized> {
+    header: Header,
+    /// Dropped apart from the allocation, so a collection can drop every
+    /// unreachable value before freeing any of the boxes.
+    value: ManuallyDrop<T>,
+}
+
+type Erased = NonNull<GcBox<dyn Trace>>;
+
+#[derive(Default)]
+struct Heap {
+    boxes: Vec<Option<Erased>>,
+    /// Slots of freed boxes, reused before the heap grows.
+    free: Vec<usize>,
+    collecting: bool,
+}
+
+impl Heap {
+    fn insert(&mut self, ptr: Erased) {
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.boxes[slot] = Some(ptr);
+                slot
+            }
+            None => {
+                self.boxes.push(Some(ptr));
+                self.boxes.len() - 1
+            }
+        };
+        unsafe { ptr.as_ref() }.header.slot.set(slot);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.boxes[slot] = None;
+        self.free.push(slot);
+    }
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+/// A shared handle to a value on the thread's collected heap.
+///
+/// This type can be constructed through [`Gc::new`].
+pub struct Gc<T: Trace + 'static> {
+    ptr: NonNull<GcBox<T>>,
+    _owns: PhantomData<T>,
+}
+
+impl<T: Trace + 'static> Gc<T> {
+    pub fn new(value: T) -> Self {
+        let ptr = NonNull::from(Box::leak(Box::new(GcBox {
+            header: Header {
+                count: Cell::new(1),
+                internal: Cell::new(0),
+                marked: Cell::new(false),
+                collected: Collected::default(),
+                slot: Cell::new(0),
+            },
+            value: ManuallyDrop::new(value),
+        })));
+        HEAP.with(|heap| heap.borrow_mut().insert(ptr));
+        Self {
+            ptr,
+            _owns: PhantomData,
+        }
+    }
+
+    /// Returns true if both handles point to the same box.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+
+    /// The number of handles to the box.
+    pub fn count(this: &Self) -> usize {
+        this.header().count.get()
+    }
+
+    fn header(&self) -> &Header {
+        &unsafe { self.ptr.as_ref() }.header
+    }
+}
+
+impl<T: Trace + 'static> Deref for Gc<T> {
+    type Target = T;
+
+    /// # Panics
+    /// If the value was collected. A handle to it is only reachable from the
+    /// drop of another value of the same collection, or kept from there.
+    fn deref(&self) -> &Self::Target {
+        let inner = unsafe { self.ptr.as_ref() };
+        inner.header.collected.assert_alive("Gc");
+        &inner.value
+    }
+}
+
+impl<T: Trace + 'static> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        let count = self.header().count.get();
+        self.header()
+            .count
+            .set(count.checked_add(1).expect("Gc count overflown"));
+        Self {
+            ptr: self.ptr,
+            _owns: PhantomData,
+        }
+    }
+}
+
+impl<T: Trace + 'static> Drop for Gc<T> {
+    fn drop(&mut self) {
+        let header = self.header();
+        let count = header.count.get() - 1;
+        header.count.set(count);
+        if count != 0 {
+            return;
+        }
+        if header.collected.is_dead() {
+            if header.collected.handle_dropped(count) {
+                std::mem::drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+            }
+            return;
+        }
+        let slot = header.slot.get();
+        // The heap is already gone if the thread is exiting.
+        let _ = HEAP.try_with(|heap| heap.borrow_mut().remove(slot));
+        let mut inner = unsafe { Box::from_raw(self.ptr.as_ptr()) };
+        // SAFETY:
+        // * This was the last handle, nothing else can reach the value.
+        unsafe { ManuallyDrop::drop(&mut inner.value) };
+    }
+}
+
+impl<T: Trace + fmt::Debug + 'static> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Drop every value on the thread's heap that can not be reached from a
+/// handle outside of it, returning how many were dropped.
+///
+/// A box is freed along with its value, unless the drop of a value moved a
+/// handle to it elsewhere. That handle then panics when dereferenced, and
+/// frees the box once it is the last one dropped.
+///
+/// Does nothing if called from the drop of a value being collected.
+///
+/// # Panics
+/// If a value on the heap panics while being traced, such as a
+/// [`std::cell::RefCell`] that is mutably borrowed.
+pub fn collect() -> usize {
+    let boxes = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        if heap.collecting {
+            return None;
+        }
+        heap.collecting = true;
+        Some(heap.boxes.iter().flatten().copied().collect::<Vec<_>>())
+    });
+    let Some(boxes) = boxes else {
+        return 0;
+    };
+    let _done = guard((), |()| {
+        HEAP.with(|heap| heap.borrow_mut().collecting = false);
+    });
+    let header = |ptr: &Erased| &unsafe { ptr.as_ref() }.header;
+    let value = |ptr: &Erased| &*unsafe { ptr.as_ref() }.value;
+
+    for ptr in &boxes {
+        header(ptr).internal.set(0);
+        header(ptr).marked.set(false);
+    }
+    let mut tracer = Tracer::new(Phase::Count);
+    for ptr in &boxes {
+        value(ptr).trace(&mut tracer);
+    }
+
+    tracer.phase = Phase::Mark;
+    for ptr in &boxes {
+        if header(ptr).count.get() > header(ptr).internal.get() {
+            tracer.visit(*ptr);
+        }
+    }
+    while let Some(ptr) = tracer.pending.pop() {
+        value(&ptr).trace(&mut tracer);
+    }
+
+    let dead = boxes
+        .into_iter()
+        .filter(|ptr| !header(ptr).marked.get())
+        .collect::<Vec<_>>();
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        for ptr in &dead {
+            header(ptr).collected.kill();
+            heap.remove(header(ptr).slot.get());
+        }
+    });
+    // Every value is dropped before any box is freed, the values still hold
+    // handles to each other.
+    for ptr in &dead {
+        // SAFETY:
+        // * Unreachable boxes are only pointed to from other unreachable
+        //   boxes, and dead handles do not free or dereference them.
+        unsafe { ManuallyDrop::drop(&mut (*ptr.as_ptr()).value) };
+    }
+    for ptr in &dead {
+        if header(ptr).collected.value_dropped(header(ptr).count.get()) {
+            std::mem::drop(unsafe { Box::from_raw(ptr.as_ptr()) });
+        }
+    }
+    dead.len()
+}
+
+unsafe impl<T: Trace + 'static> Trace for Gc<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit(self.ptr);
+    }
+}
+
+macro_rules! leaf {
+    ($($ty:ty),* $(,)?) => {
+        $(unsafe impl Trace for $ty {
+            fn trace(&self, _: &mut Tracer) {}
+        })*
+    };
+}
+
+leaf!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    &'static str,
+    String,
+);
+
+unsafe impl<T: Trace> Trace for Option<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(value) = self {
+            value.trace(tracer);
+        }
+    }
+}
+
+unsafe impl<T: Trace + ?Sized> Trace for Box<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        (**self).trace(tracer);
+    }
+}
+
+unsafe impl<T: Trace> Trace for [T] {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self {
+            value.trace(tracer);
+        }
+    }
+}
+
+unsafe impl<T: Trace, const N: usize> Trace for [T; N] {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.as_slice().trace(tracer);
+    }
+}
+
+unsafe impl<T: Trace> Trace for Vec<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.as_slice().trace(tracer);
+    }
+}
+
+unsafe impl<T: Trace> Trace for crate::Vec<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        (**self).trace(tracer);
+    }
+}
+
+unsafe impl<T: Trace> Trace for std::cell::RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.borrow().trace(tracer);
+    }
+}
+
+unsafe impl<T: Trace> Trace for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.borrow().trace(tracer);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+        _token: Token,
+    }
+    crate::trace!(Node { next });
+
+    /// Counts live nodes through its strong count.
+    #[derive(Clone)]
+    struct Token(Rc<()>);
+
+    impl Node {
+        fn new(token: &Token) -> Gc<Self> {
+            Gc::new(Self {
+                next: RefCell::new(None),
+                _token: token.clone(),
+            })
+        }
+    }
+
+    #[test]
+    fn acyclic_freed_on_drop() {
+        let token = Token(Rc::new(()));
+        let a = Node::new(&token);
+        let b = Node::new(&token);
+        *a.next.borrow_mut() = Some(b);
+        assert_eq!(Rc::strong_count(&token.0), 3);
+        drop(a);
+        assert_eq!(Rc::strong_count(&token.0), 1);
+    }
+
+    #[test]
+    fn cycle_freed_by_collect() {
+        let token = Token(Rc::new(()));
+        let a = Node::new(&token);
+        let b = Node::new(&token);
+        *a.next.borrow_mut() = Some(b.clone());
+        *b.next.borrow_mut() = Some(a.clone());
+        drop(b);
+        // Still rooted through a.
+        assert_eq!(collect(), 0);
+        assert!(a.next.borrow().as_ref().unwrap().next.borrow().is_some());
+
+        drop(a);
+        assert_eq!(Rc::strong_count(&token.0), 3);
+        assert_eq!(collect(), 2);
+        assert_eq!(Rc::strong_count(&token.0), 1);
+    }
+
+    #[test]
+    fn self_cycle_and_reachable_survivor() {
+        let token = Token(Rc::new(()));
+        let lone = Node::new(&token);
+        *lone.next.borrow_mut() = Some(lone.clone());
+        drop(lone);
+
+        // Held by a collection outside the heap, pointing into a cycle.
+        let a = Node::new(&token);
+        let b = Node::new(&token);
+        *a.next.borrow_mut() = Some(b.clone());
+        *b.next.borrow_mut() = Some(a.clone());
+        let outside = [b];
+        drop(a);
+
+        assert_eq!(collect(), 1);
+        assert_eq!(Rc::strong_count(&token.0), 3);
+        assert_eq!(Gc::count(&outside[0]), 2);
+        drop(outside);
+        assert_eq!(collect(), 2);
+        assert_eq!(Rc::strong_count(&token.0), 1);
+    }
+
+    resurrection_test!(Gc, collect);
+}
@@ -0,0 +1,400 @@
+use std::{fmt, marker::PhantomData, mem::ManuallyDrop, ops::Deref, ptr::NonNull};
+
+use super::{Collected, Phase, Trace, Tracer};
+use crate::cell::{Cell, RefCell};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Color {
+    /// In use, or freed.
+    Black,
+    /// Possibly part of a cycle, being trial decremented.
+    Gray,
+    /// Garbage once the scan is over.
+    White,
+    /// Possibly the root of a garbage cycle, buffered for the next pass.
+    Purple,
+}
+
+pub(super) struct Header {
+    count: Cell<usize>,
+    color: Cell<Color>,
+    /// In the buffer of possible roots, which then frees the allocation.
+    buffered: Cell<bool>,
+    /// Dead boxes are only counted and no longer buffered.
+    collected: Collected,
+}
+
+pub(super) struct CcBox<T: ?Sized> {
+    header: Header,
+    value: ManuallyDrop<T>,
+}
+
+pub(super) type Erased = NonNull<CcBox<dyn Trace>>;
+
+thread_local! {
+    /// Boxes decremented to a nonzero count since the last pass.
+    static ROOTS: RefCell<Vec<Erased>> = const { RefCell::new(Vec::new()) };
+    static COLLECTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A reference counted handle, whose garbage cycles are freed by
+/// [`collect_cycles`].
+///
+/// Any drop that leaves a nonzero count buffers the box as a possible root
+/// of a cycle. Values must hand their handles to the collector through
+/// [`Trace`], an untraced handle only keeps what it points to alive.
+///
+/// ```
+/// use nomicon::gc::{self, CcRc};
+/// use std::cell::RefCell;
+///
+/// struct Node {
+///     prev: RefCell<Option<CcRc<Node>>>,
+///     next: RefCell<Option<CcRc<Node>>>,
+/// }
+/// nomicon::trace!(Node { prev, next });
+///
+/// let a = CcRc::new(Node { prev: RefCell::new(None), next: RefCell::new(None) });
+/// let b = CcRc::new(Node { prev: RefCell::new(Some(a.clone())), next: RefCell::new(None) });
+/// *a.next.borrow_mut() = Some(b.clone());
+/// drop((a, b));
+/// assert_eq!(gc::collect_cycles(), 2);
+/// ```
+///
+/// This type can be constructed through [`CcRc::new`].
+pub struct CcRc<T: Trace + 'static> {
+    ptr: NonNull<CcBox<T>>,
+    _owns: PhantomData<T>,
+}
+
+impl<T: Trace + 'static> CcRc<T> {
+    pub fn new(value: T) -> Self {
+        let ptr = NonNull::from(Box::leak(Box::new(CcBox {
+            header: Header {
+                count: Cell::new(1),
+                color: Cell::new(Color::Black),
+                buffered: Cell::new(false),
+                collected: Collected::default(),
+            },
+            value: ManuallyDrop::new(value),
+        })));
+        Self {
+            ptr,
+            _owns: PhantomData,
+        }
+    }
+
+    /// Returns true if both handles point to the same box.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+
+    /// The number of handles to the box.
+    pub fn count(this: &Self) -> usize {
+        this.header().count.get()
+    }
+
+    fn header(&self) -> &Header {
+        &unsafe { self.ptr.as_ref() }.header
+    }
+}
+
+impl<T: Trace + 'static> Deref for CcRc<T> {
+    type Target = T;
+
+    /// # Panics
+    /// If the value was collected. A handle to it is only reachable from the
+    /// drop of another value of the same cycle, or kept from there.
+    fn deref(&self) -> &Self::Target {
+        let inner = unsafe { self.ptr.as_ref() };
+        inner.header.collected.assert_alive("CcRc");
+        &inner.value
+    }
+}
+
+impl<T: Trace + 'static> Clone for CcRc<T> {
+    fn clone(&self) -> Self {
+        let header = self.header();
+        let count = header.count.get();
+        header
+            .count
+            .set(count.checked_add(1).expect("CcRc count overflown"));
+        header.color.set(Color::Black);
+        Self {
+            ptr: self.ptr,
+            _owns: PhantomData,
+        }
+    }
+}
+
+impl<T: Trace + 'static> Drop for CcRc<T> {
+    fn drop(&mut self) {
+        let header = self.header();
+        let count = header.count.get() - 1;
+        header.count.set(count);
+        if header.collected.is_dead() {
+            if header.collected.handle_dropped(count) {
+                std::mem::drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+            }
+            return;
+        }
+        if count != 0 {
+            possible_root(self.ptr);
+            return;
+        }
+        header.color.set(Color::Black);
+        // SAFETY:
+        // * This was the last handle, nothing else can reach the value.
+        unsafe { ManuallyDrop::drop(&mut (*self.ptr.as_ptr()).value) };
+        // Otherwise the buffer frees it on the next pass.
+        if !self.header().buffered.get() {
+            std::mem::drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+        }
+    }
+}
+
+impl<T: Trace + fmt::Debug + 'static> fmt::Debug for CcRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+unsafe impl<T: Trace + 'static> Trace for CcRc<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Phase::Children = tracer.phase {
+            if !self.header().collected.is_dead() {
+                tracer.children.push(self.ptr);
+            }
+        }
+    }
+}
+
+fn possible_root(ptr: Erased) {
+    let header = header(ptr);
+    if header.color.get() == Color::Purple {
+        return;
+    }
+    header.color.set(Color::Purple);
+    if !header.buffered.get() {
+        // The buffer is already gone if the thread is exiting, the box
+        // is leaked.
+        let buffered = ROOTS.try_with(|roots| roots.borrow_mut().push(ptr));
+        header.buffered.set(buffered.is_ok());
+    }
+}
+
+fn header<'a>(ptr: Erased) -> &'a Header {
+    &unsafe { ptr.as_ref() }.header
+}
+
+/// The handles held by the value of a live box.
+fn children(ptr: Erased) -> Vec<Erased> {
+    let mut tracer = Tracer::new(Phase::Children);
+    unsafe { ptr.as_ref() }.value.trace(&mut tracer);
+    tracer.children
+}
+
+/// Trial decrement everything reachable from `ptr`, removing the counts
+/// held from inside the subgraph.
+fn mark_gray(ptr: Erased) {
+    if header(ptr).color.get() == Color::Gray {
+        return;
+    }
+    header(ptr).color.set(Color::Gray);
+    let mut stack = vec![ptr];
+    while let Some(ptr) = stack.pop() {
+        for child in children(ptr) {
+            let header = header(child);
+            header.count.set(header.count.get() - 1);
+            if header.color.get() != Color::Gray {
+                header.color.set(Color::Gray);
+                stack.push(child);
+            }
+        }
+    }
+}
+
+/// Whiten the gray boxes with no counts from outside, restoring everything
+/// reachable from those that have some.
+fn scan(ptr: Erased) {
+    let mut stack = vec![ptr];
+    while let Some(ptr) = stack.pop() {
+        let header = header(ptr);
+        if header.color.get() != Color::Gray {
+            continue;
+        }
+        if header.count.get() > 0 {
+            scan_black(ptr);
+        } else {
+            header.color.set(Color::White);
+            stack.extend(children(ptr));
+        }
+    }
+}
+
+fn scan_black(ptr: Erased) {
+    header(ptr).color.set(Color::Black);
+    let mut stack = vec![ptr];
+    while let Some(ptr) = stack.pop() {
+        for child in children(ptr) {
+            let header = header(child);
+            header.count.set(header.count.get() + 1);
+            if header.color.get() != Color::Black {
+                header.color.set(Color::Black);
+                stack.push(child);
+            }
+        }
+    }
+}
+
+/// Gather the white boxes reachable from `ptr`.
+fn collect_white(ptr: Erased, garbage: &mut Vec<Erased>) {
+    let mut stack = vec![ptr];
+    while let Some(ptr) = stack.pop() {
+        let header = header(ptr);
+        if header.color.get() != Color::White || header.buffered.get() {
+            continue;
+        }
+        header.color.set(Color::Black);
+        garbage.push(ptr);
+        stack.extend(children(ptr));
+    }
+}
+
+/// Drop the garbage cycles among the boxes buffered since the last pass,
+/// returning how many values were dropped.
+///
+/// As with [`collect`](super::collect), a box a handle escaped to while
+/// dropping the garbage stays allocated until that handle is dropped.
+///
+/// Does nothing if called from the drop of a value being collected.
+///
+/// # Panics
+/// If a value panics while being traced, such as a [`std::cell::RefCell`]
+/// that is mutably borrowed.
+pub fn collect_cycles() -> usize {
+    if COLLECTING.with(|collecting| collecting.get()) {
+        return 0;
+    }
+    COLLECTING.with(|collecting| collecting.set(true));
+    let _done = crate::guard::guard((), |()| {
+        COLLECTING.with(|collecting| collecting.set(false));
+    });
+
+    let mut roots = ROOTS.with(|roots| std::mem::take(&mut *roots.borrow_mut()));
+    roots.retain(|&ptr| {
+        let header = header(ptr);
+        if header.color.get() == Color::Purple && header.count.get() > 0 {
+            mark_gray(ptr);
+            return true;
+        }
+        header.buffered.set(false);
+        // Gray boxes were reached from an earlier root, their count is only
+        // trial decremented.
+        if header.color.get() == Color::Black && header.count.get() == 0 {
+            // The value was dropped while buffered.
+            std::mem::drop(unsafe { Box::from_raw(ptr.as_ptr()) });
+        }
+        false
+    });
+    for &ptr in &roots {
+        scan(ptr);
+    }
+    let mut garbage = Vec::new();
+    for &ptr in &roots {
+        header(ptr).buffered.set(false);
+        collect_white(ptr, &mut garbage);
+    }
+
+    // Give back the counts garbage holds, the drops below take them again.
+    // What is left on a garbage box after them are handles that escaped.
+    for &ptr in &garbage {
+        for child in children(ptr) {
+            let header = header(child);
+            header.count.set(header.count.get() + 1);
+        }
+    }
+    for &ptr in &garbage {
+        header(ptr).collected.kill();
+    }
+    for &ptr in &garbage {
+        // SAFETY:
+        // * Garbage is only pointed to from other garbage, and dead handles
+        //   do not free or dereference it.
+        unsafe { ManuallyDrop::drop(&mut (*ptr.as_ptr()).value) };
+    }
+    for &ptr in &garbage {
+        if header(ptr).collected.value_dropped(header(ptr).count.get()) {
+            std::mem::drop(unsafe { Box::from_raw(ptr.as_ptr()) });
+        }
+    }
+    garbage.len()
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct Node {
+        links: RefCell<crate::Vec<CcRc<Node>>>,
+        _token: Rc<()>,
+    }
+    crate::trace!(Node { links });
+
+    fn node(token: &Rc<()>) -> CcRc<Node> {
+        CcRc::new(Node {
+            links: RefCell::new(crate::Vec::new()),
+            _token: Rc::clone(token),
+        })
+    }
+
+    #[test]
+    fn acyclic_freed_on_drop() {
+        let token = Rc::new(());
+        let a = node(&token);
+        a.links.borrow_mut().push(node(&token));
+        drop(a);
+        assert_eq!(Rc::strong_count(&token), 1);
+        assert_eq!(collect_cycles(), 0);
+    }
+
+    #[test]
+    fn doubly_linked_cycle() {
+        let token = Rc::new(());
+        let nodes = [node(&token), node(&token), node(&token)];
+        for pair in nodes.windows(2) {
+            pair[0].links.borrow_mut().push(pair[1].clone());
+            pair[1].links.borrow_mut().push(pair[0].clone());
+        }
+        // Still reachable through the array.
+        assert_eq!(collect_cycles(), 0);
+        assert_eq!(Rc::strong_count(&token), 4);
+
+        drop(nodes);
+        assert_eq!(Rc::strong_count(&token), 4);
+        assert_eq!(collect_cycles(), 3);
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn garbage_pointing_at_live() {
+        let token = Rc::new(());
+        let live = node(&token);
+        let a = node(&token);
+        let b = node(&token);
+        a.links.borrow_mut().push(b.clone());
+        b.links.borrow_mut().push(a.clone());
+        a.links.borrow_mut().push(live.clone());
+        drop((a, b));
+
+        assert_eq!(collect_cycles(), 2);
+        assert_eq!(CcRc::count(&live), 1);
+        assert_eq!(Rc::strong_count(&token), 2);
+        drop(live);
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    resurrection_test!(CcRc, collect_cycles);
+}
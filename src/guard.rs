@@ -0,0 +1,278 @@
+//! Running cleanup code when a scope is left, by returning or by panicking.
+//!
+//! Unsafe code in the middle of an operation often leaves a value in a state
+//! that is only valid once the operation finishes. A [`ScopeGuard`] holds the
+//! code restoring it, so a panic partway through cannot leave it broken.
+//!
+//! [`DropBomb`] is the opposite check, for values that must be disposed of
+//! explicitly, and [`TakeCell`] lets a `Drop` impl move a field out.
+
+use std::ops::{Deref, DerefMut};
+
+/// Returns a guard calling `cleanup` with `value` once dropped.
+///
+/// ```
+/// use nomicon::guard::{guard, ScopeGuard};
+///
+/// let mut log = Vec::new();
+/// {
+///     let mut pending = guard(&mut log, |log| log.push("cleaned up"));
+///     pending.push("working");
+/// }
+/// assert_eq!(log, ["working", "cleaned up"]);
+///
+/// // Defused, the cleanup never runs.
+/// let value = ScopeGuard::into_inner(guard(5, |_| unreachable!()));
+/// assert_eq!(value, 5);
+/// ```
+pub fn guard<T, F: FnOnce(T)>(value: T, cleanup: F) -> ScopeGuard<T, F> {
+    ScopeGuard {
+        value: TakeCell::new(value),
+        cleanup: TakeCell::new(cleanup),
+    }
+}
+
+/// A value along with code to run on it when the guard is dropped.
+///
+/// The value is reachable through [`Deref`] and [`DerefMut`] while the guard
+/// lives.
+///
+/// This type can be constructed through [`guard`].
+pub struct ScopeGuard<T, F: FnOnce(T)> {
+    value: TakeCell<T>,
+    /// Taken when defused.
+    cleanup: TakeCell<F>,
+}
+
+impl<T, F: FnOnce(T)> ScopeGuard<T, F> {
+    /// Defuse the guard, returning the value without running the cleanup.
+    pub fn into_inner(mut this: Self) -> T {
+        drop(TakeCell::take(&mut this.cleanup));
+        TakeCell::take(&mut this.value)
+    }
+}
+
+impl<T, F: FnOnce(T)> Deref for ScopeGuard<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, F: FnOnce(T)> DerefMut for ScopeGuard<T, F> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T, F: FnOnce(T)> Drop for ScopeGuard<T, F> {
+    fn drop(&mut self) {
+        if let Some(cleanup) = TakeCell::try_take(&mut self.cleanup) {
+            cleanup(TakeCell::take(&mut self.value));
+        }
+    }
+}
+
+/// Run the given statements when the current scope is left.
+///
+/// Deferred blocks run in reverse order, like any other locals being
+/// dropped.
+///
+/// ```
+/// use nomicon::defer;
+///
+/// let log = std::cell::RefCell::new(Vec::new());
+/// {
+///     defer! { log.borrow_mut().push("first deferred"); }
+///     defer! { log.borrow_mut().push("second deferred"); }
+///     log.borrow_mut().push("body");
+/// }
+/// assert_eq!(*log.borrow(), ["body", "second deferred", "first deferred"]);
+/// ```
+#[macro_export]
+macro_rules! defer {
+    ($($body:tt)*) => {
+        let _guard = $crate::guard::guard((), |()| { $($body)* });
+    };
+}
+
+/// Panics when dropped, unless defused first.
+///
+/// Embedded in a type that has to be consumed through a specific method,
+/// it turns forgetting to call that method into a loud failure. It does not
+/// go off while the thread is already panicking, which would abort.
+///
+/// ```should_panic
+/// use nomicon::guard::DropBomb;
+///
+/// struct Transaction {
+///     bomb: DropBomb,
+/// }
+///
+/// impl Transaction {
+///     fn commit(mut self) {
+///         self.bomb.defuse();
+///     }
+/// }
+///
+/// Transaction { bomb: DropBomb::new("transaction neither committed nor rolled back") }
+///     .commit();
+/// // Dropped without committing, panics.
+/// let _ = Transaction { bomb: DropBomb::new("transaction neither committed nor rolled back") };
+/// ```
+///
+/// This type can be constructed through [`DropBomb::new`].
+#[derive(Debug)]
+pub struct DropBomb {
+    message: &'static str,
+    armed: bool,
+}
+
+impl DropBomb {
+    /// Returns an armed bomb, panicking with `message` when dropped.
+    pub const fn new(message: &'static str) -> Self {
+        Self {
+            message,
+            armed: true,
+        }
+    }
+
+    pub fn defuse(&mut self) {
+        self.armed = false;
+    }
+
+    pub const fn is_armed(&self) -> bool {
+        self.armed
+    }
+}
+
+impl Drop for DropBomb {
+    fn drop(&mut self) {
+        if self.armed && !std::thread::panicking() {
+            panic!("{}", self.message);
+        }
+    }
+}
+
+/// A value that can be moved out of once, through a mutable reference.
+///
+/// A `Drop` impl only gets `&mut self`, so a field that has to be consumed
+/// by value there, like a closure to call or a handle to send back, is
+/// kept in a [`TakeCell`]. Without it that takes [`std::mem::ManuallyDrop`]
+/// and an unsafe read.
+///
+/// ```
+/// use nomicon::guard::TakeCell;
+///
+/// struct Reply {
+///     sender: TakeCell<std::sync::mpsc::Sender<&'static str>>,
+/// }
+///
+/// impl Drop for Reply {
+///     fn drop(&mut self) {
+///         // Consumes the sender, which Drop could not otherwise move.
+///         let sender = TakeCell::take(&mut self.sender);
+///         let _ = sender.send("dropped");
+///     }
+/// }
+///
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// drop(Reply { sender: TakeCell::new(tx) });
+/// assert_eq!(rx.recv(), Ok("dropped"));
+/// ```
+///
+/// This type can be constructed through [`TakeCell::new`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TakeCell<T>(Option<T>);
+
+impl<T> TakeCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self(Some(value))
+    }
+
+    /// Move the value out.
+    ///
+    /// # Panics
+    /// If it was already taken.
+    pub fn take(this: &mut Self) -> T {
+        Self::try_take(this).expect("value already taken")
+    }
+
+    /// Move the value out, if it was not taken yet.
+    pub fn try_take(this: &mut Self) -> Option<T> {
+        this.0.take()
+    }
+
+    pub fn is_taken(this: &Self) -> bool {
+        this.0.is_none()
+    }
+}
+
+impl<T> Deref for TakeCell<T> {
+    type Target = T;
+
+    /// # Panics
+    /// If the value was taken.
+    fn deref(&self) -> &T {
+        self.0.as_ref().expect("value already taken")
+    }
+}
+
+impl<T> DerefMut for TakeCell<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.0.as_mut().expect("value already taken")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn runs_on_panic() {
+        let flag = std::rc::Rc::new(());
+        let cleaned = std::cell::Cell::new(false);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = guard(std::rc::Rc::clone(&flag), |_| cleaned.set(true));
+            panic!("interrupted");
+        }));
+        assert!(result.is_err());
+        assert!(cleaned.get());
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+
+        // Defusing drops the cleanup, and hands the value back.
+        let defused = guard(std::rc::Rc::clone(&flag), |_| unreachable!());
+        let value = ScopeGuard::into_inner(defused);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 2);
+        drop(value);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn bombs() {
+        let mut bomb = DropBomb::new("boom");
+        assert!(bomb.is_armed());
+        bomb.defuse();
+        drop(bomb);
+
+        let exploded = std::panic::catch_unwind(|| drop(DropBomb::new("boom")));
+        assert!(exploded.is_err());
+
+        // Does not go off during an unwind, which would abort.
+        let unwound = std::panic::catch_unwind(|| {
+            let _bomb = DropBomb::new("boom");
+            panic!("already panicking");
+        });
+        assert!(unwound.is_err());
+    }
+
+    #[test]
+    fn take_cells() {
+        let mut cell = TakeCell::new(String::from("once"));
+        cell.push('!');
+        assert_eq!(TakeCell::take(&mut cell), "once!");
+        assert!(TakeCell::is_taken(&cell));
+        assert_eq!(TakeCell::try_take(&mut cell), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! SipHash-1-3, keyed per map, the default hasher of the crate's maps.
+//!
+//! Without the key an attacker can not build keys that collide, so a map
+//! fed untrusted keys keeps its expected O(1) operations.
+//!
+//! ```
+//! use nomicon::hash::RandomState;
+//! use std::hash::BuildHasher;
+//!
+//! let (a, b) = (RandomState::new(), RandomState::new());
+//! assert_eq!(a.hash_one("key"), a.hash_one("key"));
+//! assert_ne!(a.hash_one("key"), b.hash_one("key"));
+//! ```
+
+use std::{
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+/// SipHash with one compression round per word and three finalization
+/// rounds.
+///
+/// This type can be constructed through [`SipHasher13::new_with_keys`].
+#[derive(Debug, Clone)]
+pub struct SipHasher13 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    /// Bytes written that do not fill a word yet, little endian.
+    tail: u64,
+    ntail: usize,
+    length: usize,
+}
+
+impl SipHasher13 {
+    pub const fn new_with_keys(k0: u64, k1: u64) -> Self {
+        Self {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    const fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13) ^ self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16) ^ self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21) ^ self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17) ^ self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    const fn compress(&mut self, word: u64) {
+        self.v3 ^= word;
+        self.round();
+        self.v0 ^= word;
+    }
+}
+
+impl Hasher for SipHasher13 {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len();
+        if self.ntail != 0 {
+            let take = bytes.len().min(8 - self.ntail);
+            for (index, &byte) in bytes[..take].iter().enumerate() {
+                self.tail |= (byte as u64) << (8 * (self.ntail + index));
+            }
+            self.ntail += take;
+            bytes = &bytes[take..];
+            if self.ntail < 8 {
+                return;
+            }
+            self.compress(self.tail);
+            (self.tail, self.ntail) = (0, 0);
+        }
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            self.compress(u64::from_le_bytes(word.try_into().unwrap()));
+        }
+        for (index, &byte) in words.remainder().iter().enumerate() {
+            self.tail |= (byte as u64) << (8 * index);
+        }
+        self.ntail = words.remainder().len();
+    }
+
+    fn finish(&self) -> u64 {
+        let mut state = self.clone();
+        let last = ((self.length as u64 & 0xff) << 56) | self.tail;
+        state.compress(last);
+        state.v2 ^= 0xff;
+        for _ in 0..3 {
+            state.round();
+        }
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+}
+
+/// Builds [`SipHasher13`]s with keys drawn once per map.
+///
+/// This type can be constructed through [`RandomState::new`].
+#[derive(Debug, Clone)]
+pub struct RandomState {
+    k0: u64,
+    k1: u64,
+}
+
+impl RandomState {
+    /// Returns a state with fresh keys.
+    ///
+    /// Keys are derived from a per process seed and a counter, so they are
+    /// distinct for every map and unpredictable without the seed.
+    pub fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let (s0, s1) = seed();
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut hasher = SipHasher13::new_with_keys(s0, s1);
+        hasher.write_u64(n);
+        let k0 = hasher.finish();
+        hasher.write_u64(k0);
+        Self {
+            k0,
+            k1: hasher.finish(),
+        }
+    }
+}
+
+impl Default for RandomState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildHasher for RandomState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        SipHasher13::new_with_keys(self.k0, self.k1)
+    }
+}
+
+/// The process seed keys are derived from.
+///
+/// Collected from the clock and from where the stack, the heap and the
+/// binary were placed, which address space layout randomization varies per
+/// run.
+fn seed() -> (u64, u64) {
+    static SEED: OnceLock<(u64, u64)> = OnceLock::new();
+    *SEED.get_or_init(|| {
+        let mut hasher = SipHasher13::new_with_keys(0, 0);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.write_u128(now.as_nanos());
+        let local = 0u8;
+        let heap = Box::new(0u8);
+        hasher.write_usize((&raw const local).addr());
+        hasher.write_usize((&raw const *heap).addr());
+        hasher.write_usize((seed as fn() -> (u64, u64) as *const ()).addr());
+        hasher.write_u32(std::process::id());
+        let k0 = hasher.finish();
+        hasher.write_u64(k0);
+        (k0, hasher.finish())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Outputs of the SipHash-1-3 reference implementation, keyed with the
+    /// bytes 0 to 15 and hashing the first `len` bytes of 0, 1, 2...
+    const VECTORS: [(usize, u64); 12] = [
+        (0, 0xabac_0158_050f_c4dc),
+        (1, 0xc9f4_9bf3_7d57_ca93),
+        (2, 0x82cb_9b02_4dc7_d44d),
+        (3, 0x8bf8_0ab8_e7dd_f7fb),
+        (7, 0xd392_7d98_9bb1_1140),
+        (8, 0x3690_9511_8d29_9a8e),
+        (9, 0x25a4_8eb3_6c06_3de4),
+        (15, 0xd320_d86d_2a51_9956),
+        (16, 0xcc4f_dd1a_7d90_8b66),
+        (17, 0x9cf2_6890_63db_d80c),
+        (31, 0x2370_dd1f_8c21_d1bc),
+        (63, 0x9d19_9062_b7bb_b3a8),
+    ];
+
+    fn keyed() -> SipHasher13 {
+        let key = (0..16u8).collect::<std::vec::Vec<_>>();
+        SipHasher13::new_with_keys(
+            u64::from_le_bytes(key[..8].try_into().unwrap()),
+            u64::from_le_bytes(key[8..].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn reference_vectors() {
+        let data = (0..64u8).collect::<std::vec::Vec<_>>();
+        for (len, expected) in VECTORS {
+            let mut hasher = keyed();
+            hasher.write(&data[..len]);
+            assert_eq!(hasher.finish(), expected, "{len} bytes");
+        }
+
+        // Split writes hash like one.
+        let mut split = keyed();
+        for chunk in data[..63].chunks(3) {
+            split.write(chunk);
+        }
+        assert_eq!(split.finish(), VECTORS[11].1);
+    }
+}
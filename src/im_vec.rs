@@ -0,0 +1,241 @@
+use crate::rc::Rc;
+
+const BITS: usize = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+/// A persistent vector, updates return a new version leaving `self` intact.
+///
+/// Elements are stored in a radix balanced trie with a branching factor of
+/// 32. Versions share every node that an update did not touch, so an update
+/// only copies the O(log n) nodes on the path to the changed element.
+///
+/// ```
+/// use nomicon::ImVec;
+///
+/// let v1 = ImVec::new().push(1).push(2);
+/// let v2 = v1.update(0, 10);
+///
+/// assert_eq!(v1.get(0), Some(&1));
+/// assert_eq!(v2.get(0), Some(&10));
+/// assert_eq!(v2.get(1), Some(&2));
+/// ```
+pub struct ImVec<T> {
+    root: Rc<Node<T>>,
+    len: usize,
+    /// The number of index bits consumed below the root.
+    shift: usize,
+}
+
+enum Node<T> {
+    Branch(Vec<Rc<Node<T>>>),
+    Leaf(Vec<T>),
+}
+
+/// Build a fresh path of nodes from `shift` down to a leaf holding `value`.
+fn new_path<T>(shift: usize, value: T) -> Rc<Node<T>> {
+    if shift == 0 {
+        Rc::new(Node::Leaf(vec![value]))
+    } else {
+        Rc::new(Node::Branch(vec![new_path(shift - BITS, value)]))
+    }
+}
+
+fn push_into<T: Clone>(node: &Node<T>, shift: usize, index: usize, value: T) -> Rc<Node<T>> {
+    match node {
+        Node::Leaf(items) => {
+            let mut items = items.clone();
+            items.push(value);
+            Rc::new(Node::Leaf(items))
+        }
+        Node::Branch(children) => {
+            let mut children = children.clone();
+            let child = (index >> shift) & MASK;
+            match children.get(child) {
+                Some(existing) => {
+                    children[child] = push_into(existing, shift - BITS, index, value);
+                }
+                None => children.push(new_path(shift - BITS, value)),
+            }
+            Rc::new(Node::Branch(children))
+        }
+    }
+}
+
+fn update_in<T: Clone>(node: &Node<T>, shift: usize, index: usize, value: T) -> Rc<Node<T>> {
+    match node {
+        Node::Leaf(items) => {
+            let mut items = items.clone();
+            items[index & MASK] = value;
+            Rc::new(Node::Leaf(items))
+        }
+        Node::Branch(children) => {
+            let mut children = children.clone();
+            let child = (index >> shift) & MASK;
+            children[child] = update_in(&children[child], shift - BITS, index, value);
+            Rc::new(Node::Branch(children))
+        }
+    }
+}
+
+impl<T> ImVec<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Rc::new(Node::Leaf(Vec::new())),
+            len: 0,
+            shift: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        let mut node = &*self.root;
+        let mut shift = self.shift;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = &children[(index >> shift) & MASK];
+                    shift -= BITS;
+                }
+                Node::Leaf(items) => return items.get(index & MASK),
+            }
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            vec: self,
+            index: 0,
+        }
+    }
+}
+
+impl<T: Clone> ImVec<T> {
+    /// Returns a new version with `value` appended.
+    pub fn push(&self, value: T) -> Self {
+        if self.len == 1 << (self.shift + BITS) {
+            // The trie is full, grow a new root above it.
+            let children = vec![self.root.clone(), new_path(self.shift, value)];
+            Self {
+                root: Rc::new(Node::Branch(children)),
+                len: self.len + 1,
+                shift: self.shift + BITS,
+            }
+        } else {
+            Self {
+                root: push_into(&self.root, self.shift, self.len, value),
+                len: self.len + 1,
+                shift: self.shift,
+            }
+        }
+    }
+
+    /// Returns a new version with the element at `index` replaced by `value`.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds.
+    pub fn update(&self, index: usize, value: T) -> Self {
+        assert!(index < self.len, "index out of bounds");
+        Self {
+            root: update_in(&self.root, self.shift, index, value),
+            len: self.len,
+            shift: self.shift,
+        }
+    }
+}
+
+impl<T> Clone for ImVec<T> {
+    /// Returns another handle to the same version, this never copies elements.
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            len: self.len,
+            shift: self.shift,
+        }
+    }
+}
+
+impl<T> Default for ImVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ImVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for ImVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::new(), |v, item| v.push(item))
+    }
+}
+
+/// An iterator over the elements of an [`ImVec`].
+///
+/// This type can be constructed through [`ImVec::iter`].
+pub struct Iter<'a, T> {
+    vec: &'a ImVec<T>,
+    index: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.vec.get(self.index)?;
+        self.index += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.vec.len - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn versions_are_independent() {
+        let count = WIDTH * WIDTH + 7;
+        let full = (0..count).collect::<ImVec<_>>();
+        assert_eq!(full.len(), count);
+        assert!(full.iter().copied().eq(0..count));
+
+        let updated = full.update(WIDTH * 3 + 1, 0);
+        let pushed = full.push(count);
+        assert_eq!(full.get(WIDTH * 3 + 1), Some(&(WIDTH * 3 + 1)));
+        assert_eq!(updated.get(WIDTH * 3 + 1), Some(&0));
+        assert_eq!(full.get(count), None);
+        assert_eq!(pushed.get(count), Some(&count));
+    }
+
+    #[test]
+    fn shares_structure() {
+        let v = (0..WIDTH * 2).map(|n| n.to_string()).collect::<ImVec<_>>();
+        let w = v.update(0, "zero".into());
+        let (Node::Branch(a), Node::Branch(b)) = (&*v.root, &*w.root) else {
+            panic!("expected a branch root");
+        };
+        // The second leaf was untouched by the update.
+        assert!(std::ptr::eq(&*a[1], &*b[1]));
+        assert!(!std::ptr::eq(&*a[0], &*b[0]));
+    }
+}
@@ -0,0 +1,528 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::hash::RandomState;
+
+/// A hash map that remembers the order keys were inserted in.
+///
+/// Entries are stored densely in insertion order, the hash table itself only
+/// stores indices into the entries.
+pub struct IndexMap<K, V, S = RandomState> {
+    entries: Vec<Bucket<K, V>>,
+    /// Open addressed (linear probing) table of indices into `entries`, the
+    /// length is always zero or a power of two.
+    indices: Vec<Option<usize>>,
+    hash_builder: S,
+}
+
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+/// Where a key lives, or would live, in the index table.
+enum Probe {
+    Found { slot: usize, index: usize },
+    Vacant { slot: usize },
+}
+
+impl<K, V> IndexMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S> {
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            entries: Vec::new(),
+            indices: Vec::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the entry at position `index` in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|b| (&b.key, &b.value))
+    }
+
+    /// Return the entry at position `index` in insertion order.
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries.get_mut(index).map(|b| (&b.key, &mut b.value))
+    }
+
+    /// Iterate over the entries in their current order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.iter_mut().for_each(|slot| *slot = None);
+    }
+
+    /// Panic if the index table does not match the entries: every entry must
+    /// be reachable by probing from its hash, exactly once.
+    #[cfg(feature = "debug-invariants")]
+    pub fn assert_invariants(&self) {
+        let table = self.indices.len();
+        assert!(
+            table == 0 || table.is_power_of_two(),
+            "IndexMap table length {table} is not a power of two"
+        );
+        assert!(
+            self.entries.len() * 4 <= table * 3,
+            "IndexMap holds {} entries in a table of {table}",
+            self.entries.len()
+        );
+        let occupied = self.indices.iter().flatten().count();
+        assert_eq!(occupied, self.entries.len(), "IndexMap slot count");
+        for (index, bucket) in self.entries.iter().enumerate() {
+            let mut slot = bucket.hash as usize & self.mask();
+            loop {
+                match self.indices[slot] {
+                    Some(found) if found == index => break,
+                    Some(_) => slot = (slot + 1) & self.mask(),
+                    None => panic!("IndexMap entry {index} is cut off from its probe sequence"),
+                }
+            }
+        }
+    }
+
+    /// Runs [`IndexMap::assert_invariants`] in debug builds with the
+    /// `debug-invariants` feature.
+    fn check_invariants(&self) {
+        #[cfg(all(feature = "debug-invariants", debug_assertions))]
+        self.assert_invariants();
+    }
+
+    /// Keep only the entries `keep` returns true for, preserving their order.
+    ///
+    /// ```
+    /// use nomicon::IndexMap;
+    ///
+    /// let mut map = (0..6).map(|n| (n, n * 10)).collect::<IndexMap<_, _>>();
+    /// map.retain(|key, _| key % 2 == 1);
+    /// assert_eq!(map.keys().collect::<Vec<_>>(), [&1, &3, &5]);
+    /// ```
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &mut V) -> bool) {
+        let len = self.entries.len();
+        self.entries
+            .retain_mut(|bucket| keep(&bucket.key, &mut bucket.value));
+        if self.entries.len() != len {
+            self.indices.iter_mut().for_each(|slot| *slot = None);
+            self.reindex();
+        }
+        self.check_invariants();
+    }
+
+    const fn mask(&self) -> usize {
+        self.indices.len().wrapping_sub(1)
+    }
+
+    /// Grow the index table once it is three quarters full.
+    fn reserve_one(&mut self) {
+        if (self.entries.len() + 1) * 4 <= self.indices.len() * 3 {
+            return;
+        }
+        let new_len = (self.indices.len() * 2).max(8);
+        self.indices = vec![None; new_len];
+        self.reindex();
+    }
+
+    /// Point the (empty) index table at every entry.
+    fn reindex(&mut self) {
+        for (index, bucket) in self.entries.iter().enumerate() {
+            let mut slot = bucket.hash as usize & self.mask();
+            while self.indices[slot].is_some() {
+                slot = (slot + 1) & self.mask();
+            }
+            self.indices[slot] = Some(index);
+        }
+    }
+
+    /// Empty `slot`, shifting back any later entries in its probe sequence so
+    /// that lookups never stop early at the hole.
+    fn erase_slot(&mut self, mut hole: usize) {
+        self.indices[hole] = None;
+        let mut slot = (hole + 1) & self.mask();
+        while let Some(index) = self.indices[slot] {
+            let ideal = self.entries[index].hash as usize & self.mask();
+            if (slot.wrapping_sub(ideal) & self.mask()) >= (slot.wrapping_sub(hole) & self.mask()) {
+                self.indices[hole] = self.indices[slot].take();
+                hole = slot;
+            }
+            slot = (slot + 1) & self.mask();
+        }
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn probe<Q>(&self, hash: u64, key: &Q) -> Probe
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        let mut slot = hash as usize & self.mask();
+        loop {
+            match self.indices[slot] {
+                None => return Probe::Vacant { slot },
+                Some(index) => {
+                    let bucket = &self.entries[index];
+                    if bucket.hash == hash && bucket.key.borrow() == key {
+                        return Probe::Found { slot, index };
+                    }
+                }
+            }
+            slot = (slot + 1) & self.mask();
+        }
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if self.indices.is_empty() {
+            return None;
+        }
+        match self.probe(self.hash_builder.hash_one(key), key) {
+            Probe::Found { slot, index } => Some((slot, index)),
+            Probe::Vacant { .. } => None,
+        }
+    }
+
+    /// Insert a key value pair, returning the old value if the key was already
+    /// present.
+    ///
+    /// Replacing the value of an existing key keeps its position.
+    ///
+    /// ```
+    /// use nomicon::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert("b", 1);
+    /// map.insert("a", 2);
+    /// assert_eq!(map.insert("b", 3), Some(1));
+    ///
+    /// assert_eq!(map.get_index(0), Some((&"b", &3)));
+    /// assert_eq!(map.get_index(1), Some((&"a", &2)));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.reserve_one();
+        let hash = self.hash_builder.hash_one(&key);
+        match self.probe(hash, &key) {
+            Probe::Found { index, .. } => {
+                Some(std::mem::replace(&mut self.entries[index].value, value))
+            }
+            Probe::Vacant { slot } => {
+                self.indices[slot] = Some(self.entries.len());
+                self.entries.push(Bucket { hash, key, value });
+                self.check_invariants();
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let (_, index) = self.find(key)?;
+        Some(&self.entries[index].value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let (_, index) = self.find(key)?;
+        Some(&mut self.entries[index].value)
+    }
+
+    /// Return the position of `key` in insertion order.
+    pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.find(key).map(|(_, index)| index)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Remove `key` by swapping the last entry into its position.
+    ///
+    /// This is O(1) but perturbs the order of the last entry.
+    ///
+    /// ```
+    /// use nomicon::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert('a', 1);
+    /// map.insert('b', 2);
+    /// map.insert('c', 3);
+    /// assert_eq!(map.swap_remove(&'a'), Some(1));
+    ///
+    /// assert_eq!(map.keys().collect::<Vec<_>>(), [&'c', &'b']);
+    /// ```
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let (slot, index) = self.find(key)?;
+        self.erase_slot(slot);
+        let last = self.entries.len() - 1;
+        if index != last {
+            // Point the slot of the last entry at its new position.
+            let hash = self.entries[last].hash;
+            let mut slot = hash as usize & self.mask();
+            while self.indices[slot] != Some(last) {
+                slot = (slot + 1) & self.mask();
+            }
+            self.indices[slot] = Some(index);
+        }
+        let bucket = self.entries.swap_remove(index);
+        self.check_invariants();
+        Some(bucket.value)
+    }
+
+    /// Remove `key` by shifting every later entry down by one.
+    ///
+    /// This is O(n) but preserves the order of the remaining entries.
+    ///
+    /// ```
+    /// use nomicon::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert('a', 1);
+    /// map.insert('b', 2);
+    /// map.insert('c', 3);
+    /// assert_eq!(map.shift_remove(&'a'), Some(1));
+    ///
+    /// assert_eq!(map.keys().collect::<Vec<_>>(), [&'b', &'c']);
+    /// ```
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let (slot, index) = self.find(key)?;
+        self.erase_slot(slot);
+        for i in self.indices.iter_mut().flatten() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        let bucket = self.entries.remove(index);
+        self.check_invariants();
+        Some(bucket.value)
+    }
+}
+
+impl<K, V> Default for IndexMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug, S> std::fmt::Debug for IndexMap<K, V, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for IndexMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (k, v) in iter {
+            map.insert(k, v);
+        }
+        map
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a IndexMap<K, V, S> {
+    type IntoIter = Iter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for IndexMap<K, V, S> {
+    type IntoIter = IntoIter<K, V>;
+    type Item = (K, V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.entries.into_iter(),
+        }
+    }
+}
+
+/// An iterator over the entries of an [`IndexMap`] in order.
+///
+/// This type can be constructed through [`IndexMap::iter`].
+pub struct Iter<'a, K, V> {
+    inner: std::slice::Iter<'a, Bucket<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|b| (&b.key, &b.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|b| (&b.key, &b.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// An owning iterator over the entries of an [`IndexMap`] in order.
+///
+/// This type can be constructed through [`IndexMap::into_iter`].
+pub struct IntoIter<K, V> {
+    inner: std::vec::IntoIter<Bucket<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|b| (b.key, b.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|b| (b.key, b.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_and_get() {
+        let mut map = IndexMap::new();
+        for n in 0..100 {
+            assert_eq!(map.insert(n.to_string(), n), None);
+        }
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get("42"), Some(&42));
+        assert_eq!(map.get_index_of("42"), Some(42));
+        assert_eq!(map.get("100"), None);
+        *map.get_mut("7").unwrap() = 700;
+        assert_eq!(map.get_index(7), Some((&"7".to_string(), &700)));
+        let mut owned = map.into_iter();
+        assert_eq!(owned.next(), Some(("0".to_string(), 0)));
+        assert_eq!(owned.next_back(), Some(("99".to_string(), 99)));
+        assert_eq!(owned.len(), 98);
+    }
+
+    #[test]
+    fn removes_keep_lookups_valid() {
+        let mut map = (0..64).map(|n| (n, n * 2)).collect::<IndexMap<_, _>>();
+        for n in (0..64).step_by(3) {
+            assert_eq!(map.swap_remove(&n), Some(n * 2));
+        }
+        for n in (1..64).step_by(3) {
+            assert_eq!(map.shift_remove(&n), Some(n * 2));
+        }
+        assert_eq!(map.swap_remove(&0), None);
+        for n in (2..64).step_by(3) {
+            let index = map.get_index_of(&n).unwrap();
+            assert_eq!(map.get_index(index), Some((&n, &(n * 2))));
+        }
+        assert_eq!(map.len(), 21);
+    }
+
+    #[test]
+    fn retain_keeps_lookups_valid() {
+        let mut map = (0..64).map(|n| (n, n)).collect::<IndexMap<_, _>>();
+        map.retain(|key, value| {
+            *value += 1;
+            key % 4 == 0
+        });
+        assert_eq!(map.len(), 16);
+        assert_eq!(map.get(&8), Some(&9));
+        assert_eq!(map.get(&9), None);
+        assert_eq!(map.get_index_of(&8), Some(2));
+    }
+
+    #[cfg(feature = "debug-invariants")]
+    #[test]
+    #[should_panic = "cut off from its probe sequence"]
+    fn broken_probe_sequence() {
+        let mut map = (0..4).map(|n| (n, n)).collect::<IndexMap<_, _>>();
+        map.assert_invariants();
+        // Move an entry past the hole left in its own probe sequence.
+        let slot = map.indices.iter().position(Option::is_some).unwrap();
+        let index = map.indices[slot].take();
+        let past = (1..map.indices.len())
+            .map(|n| (slot + n) & map.mask())
+            .find(|&s| map.indices[s].is_none())
+            .unwrap();
+        map.indices[past] = index;
+        map.assert_invariants();
+    }
+
+    #[test]
+    fn iterates_in_insertion_order() {
+        let keys = ["z", "a", "m", "b"];
+        let map = keys.iter().map(|k| (*k, ())).collect::<IndexMap<_, _>>();
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), keys);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::IndexMap;
+
+/// A compact handle to a string stored in an [`Interner`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Returns the position of the symbol in its interner.
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Deduplicates strings, handing out a [`Symbol`] for each distinct string.
+///
+/// Strings are kept in an [`IndexMap`], its hash table gives lookups by
+/// string and the position of each string doubles as its symbol.
+///
+/// ```
+/// use nomicon::interner::Interner;
+///
+/// let mut interner = Interner::new();
+/// let a = interner.intern("foo");
+/// let b = interner.intern("bar");
+///
+/// assert_eq!(interner.intern("foo"), a);
+/// assert_ne!(a, b);
+/// assert_eq!(interner.resolve(b), "bar");
+/// ```
+#[derive(Default)]
+pub struct Interner {
+    strings: IndexMap<Box<str>, ()>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns the symbol for `string`, storing it if it is new.
+    ///
+    /// # Panics
+    /// If more than `u32::MAX` strings are interned.
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.get(string) {
+            return symbol;
+        }
+        let index = u32::try_from(self.strings.len()).expect("Interner symbols overflown");
+        self.strings.insert(string.into(), ());
+        Symbol(index)
+    }
+
+    /// Returns the symbol for `string` if it has been interned.
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        self.strings
+            .get_index_of(string)
+            .map(|index| Symbol(index as u32))
+    }
+
+    /// Returns the string for `symbol` if it came from this interner.
+    pub fn try_resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get_index(symbol.index()).map(|(s, _)| &**s)
+    }
+
+    /// Returns the string for `symbol`.
+    ///
+    /// # Panics
+    /// If `symbol` did not come from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        match self.try_resolve(symbol) {
+            Some(s) => s,
+            None => panic!("Symbol does not belong to this Interner"),
+        }
+    }
+
+    /// Iterate over every symbol and its string, in the order they were
+    /// interned.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.strings
+            .keys()
+            .enumerate()
+            .map(|(index, s)| (Symbol(index as u32), &**s))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deduplicates() {
+        let mut interner = Interner::new();
+        let words = "the quick brown fox jumps over the lazy dog the end";
+        let symbols = words
+            .split(' ')
+            .map(|w| interner.intern(w))
+            .collect::<Vec<_>>();
+        assert_eq!(interner.len(), 9);
+        assert_eq!(symbols[0], symbols[6]);
+        assert_eq!(symbols[0], symbols[9]);
+        let roundtrip = symbols
+            .iter()
+            .map(|s| interner.resolve(*s))
+            .collect::<Vec<_>>();
+        assert_eq!(roundtrip.join(" "), words);
+    }
+
+    #[test]
+    fn lookups() {
+        let mut interner = Interner::new();
+        assert_eq!(interner.get("a"), None);
+        let a = interner.intern("a");
+        assert_eq!(interner.get("a"), Some(a));
+        assert_eq!(interner.try_resolve(Symbol(1)), None);
+        assert_eq!(interner.iter().collect::<Vec<_>>(), [(a, "a")]);
+    }
+}
@@ -0,0 +1,355 @@
+//! Collections whose nodes live wherever their owners put them.
+//!
+//! A [`Node`] is pinned by its owner and registered with a [`List`], which
+//! only links to it. Dropping a node unlinks it, so a future can wait in a
+//! queue without allocating and leave it by simply being dropped.
+//!
+//! ```
+//! use nomicon::intrusive::{List, Node};
+//! use std::pin::pin;
+//!
+//! let mut list = List::new();
+//! let mut a = pin!(Node::new('a'));
+//! list.push_back(a.as_mut());
+//! {
+//!     let mut b = pin!(Node::new('b'));
+//!     list.push_back(b.as_mut());
+//!     assert_eq!(list.len(), 2);
+//! }
+//! // b unlinked itself when it went out of scope.
+//! assert_eq!(list.pop_front_with(|value| *value), Some('a'));
+//! assert!(list.is_empty());
+//! ```
+
+use std::{marker::PhantomData, marker::PhantomPinned, ops::Deref, pin::Pin, ptr::NonNull};
+
+use crate::{cell::Cell, guard::guard};
+
+/// The ends of a list, at a stable address its nodes can point back to.
+struct Ends {
+    head: Cell<Option<NonNull<Link>>>,
+    tail: Cell<Option<NonNull<Link>>>,
+    len: Cell<usize>,
+}
+
+/// The part of a [`Node`] its list points to.
+struct Link {
+    prev: Cell<Option<NonNull<Link>>>,
+    next: Cell<Option<NonNull<Link>>>,
+    /// The list the node is in.
+    list: Cell<Option<NonNull<Ends>>>,
+    /// Set while the list hands out the value.
+    borrowed: Cell<bool>,
+}
+
+impl Link {
+    const fn new() -> Self {
+        Self {
+            prev: Cell::new(None),
+            next: Cell::new(None),
+            list: Cell::new(None),
+            borrowed: Cell::new(false),
+        }
+    }
+
+    /// Take the link out of its list, if it is in one.
+    fn unlink(&self) {
+        let Some(list) = self.list.get() else {
+            return;
+        };
+        // SAFETY:
+        // * A list clears the links of its nodes before it is freed.
+        let list = unsafe { list.as_ref() };
+        let (prev, next) = (self.prev.get(), self.next.get());
+        // SAFETY:
+        // * Linked nodes unlink themselves before they are freed.
+        match prev {
+            Some(prev) => unsafe { prev.as_ref() }.next.set(next),
+            None => list.head.set(next),
+        }
+        match next {
+            Some(next) => unsafe { next.as_ref() }.prev.set(prev),
+            None => list.tail.set(prev),
+        }
+        list.len.set(list.len.get() - 1);
+        self.prev.set(None);
+        self.next.set(None);
+        self.list.set(None);
+    }
+}
+
+/// A value that can be linked into a [`List`] once pinned.
+///
+/// This type can be constructed through [`Node::new`].
+#[repr(C)]
+pub struct Node<T> {
+    /// First, so a pointer to the link is a pointer to the node.
+    link: Link,
+    value: T,
+    _pinned: PhantomPinned,
+}
+
+impl<T> Node<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            link: Link::new(),
+            value,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    pub fn is_linked(&self) -> bool {
+        self.link.list.get().is_some()
+    }
+
+    /// Take the node out of its list, if it is in one.
+    pub fn unlink(self: Pin<&mut Self>) {
+        self.link.unlink();
+    }
+}
+
+impl<T> Deref for Node<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> Drop for Node<T> {
+    fn drop(&mut self) {
+        if self.link.borrowed.get() {
+            // The list is handing out a reference to the value, which would
+            // dangle once the memory is reused. Unwinding does not stop that.
+            std::process::abort();
+        }
+        self.link.unlink();
+    }
+}
+
+/// A doubly linked list of pinned [`Node`]s it does not own.
+///
+/// Values are only ever handed out to a closure, for the duration of which
+/// the node can not be dropped; doing so aborts the process.
+///
+/// This type can be constructed through [`List::new`].
+pub struct List<T> {
+    /// Boxed, so the list can move while nodes point to it, but kept as a
+    /// pointer as the nodes change it behind any borrow of the list.
+    ends: NonNull<Ends>,
+    _nodes: PhantomData<T>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        let ends = Box::new(Ends {
+            head: Cell::new(None),
+            tail: Cell::new(None),
+            len: Cell::new(0),
+        });
+        Self {
+            ends: NonNull::from(Box::leak(ends)),
+            _nodes: PhantomData,
+        }
+    }
+
+    fn ends(&self) -> &Ends {
+        unsafe { self.ends.as_ref() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends().len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Link the pointer to the whole node, not just its link, so the value
+    /// can be reached from it.
+    fn link_of(node: Pin<&mut Node<T>>) -> NonNull<Link> {
+        assert!(!node.is_linked(), "Node is already in a list");
+        // SAFETY:
+        // * The node is never moved out of, only linked.
+        NonNull::from(unsafe { node.get_unchecked_mut() }).cast()
+    }
+
+    /// Link `node` at the back of the list.
+    ///
+    /// # Panics
+    /// If the node is already in a list.
+    pub fn push_back(&mut self, node: Pin<&mut Node<T>>) {
+        let link = Self::link_of(node);
+        let ends = self.ends();
+        let tail = ends.tail.get();
+        let new = unsafe { link.as_ref() };
+        new.prev.set(tail);
+        new.list.set(Some(self.ends));
+        match tail {
+            Some(tail) => unsafe { tail.as_ref() }.next.set(Some(link)),
+            None => ends.head.set(Some(link)),
+        }
+        ends.tail.set(Some(link));
+        ends.len.set(ends.len.get() + 1);
+    }
+
+    /// Link `node` at the front of the list.
+    ///
+    /// # Panics
+    /// If the node is already in a list.
+    pub fn push_front(&mut self, node: Pin<&mut Node<T>>) {
+        let link = Self::link_of(node);
+        let ends = self.ends();
+        let head = ends.head.get();
+        let new = unsafe { link.as_ref() };
+        new.next.set(head);
+        new.list.set(Some(self.ends));
+        match head {
+            Some(head) => unsafe { head.as_ref() }.prev.set(Some(link)),
+            None => ends.tail.set(Some(link)),
+        }
+        ends.head.set(Some(link));
+        ends.len.set(ends.len.get() + 1);
+    }
+
+    /// Call `f` on the value of the node behind `link`, which can not be
+    /// dropped until it returns.
+    ///
+    /// # Safety
+    /// `link` belongs to a live node of this list.
+    unsafe fn with<R>(link: NonNull<Link>, f: impl FnOnce(&T) -> R) -> R {
+        let node = unsafe { link.cast::<Node<T>>().as_ref() };
+        node.link.borrowed.set(true);
+        let _release = guard((), |()| node.link.borrowed.set(false));
+        f(&node.value)
+    }
+
+    /// Unlink the first node, passing its value to `f`.
+    pub fn pop_front_with<R>(&mut self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let head = self.ends().head.get()?;
+        unsafe { head.as_ref() }.unlink();
+        Some(unsafe { Self::with(head, f) })
+    }
+
+    /// Unlink the last node, passing its value to `f`.
+    pub fn pop_back_with<R>(&mut self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let tail = self.ends().tail.get()?;
+        unsafe { tail.as_ref() }.unlink();
+        Some(unsafe { Self::with(tail, f) })
+    }
+
+    /// Call `f` on the value of every node, front to back.
+    ///
+    /// Stops early if `f` unlinks the node it was called on.
+    pub fn for_each(&self, mut f: impl FnMut(&T)) {
+        let mut cursor = self.ends().head.get();
+        while let Some(link) = cursor {
+            unsafe { Self::with(link, &mut f) };
+            // Read after `f`, which may have unlinked any other node.
+            cursor = unsafe { link.as_ref() }.next.get();
+        }
+    }
+
+    /// Unlink every node `keep` returns false for.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let mut cursor = self.ends().head.get();
+        while let Some(link) = cursor {
+            let kept = unsafe { Self::with(link, &mut keep) };
+            let link = unsafe { link.as_ref() };
+            cursor = link.next.get();
+            if !kept {
+                link.unlink();
+            }
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        while let Some(head) = self.ends().head.get() {
+            unsafe { head.as_ref() }.unlink();
+        }
+        std::mem::drop(unsafe { Box::from_raw(self.ends.as_ptr()) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::pin::pin;
+
+    use super::*;
+
+    fn values(list: &List<u32>) -> Vec<u32> {
+        let mut values = Vec::new();
+        list.for_each(|value| values.push(*value));
+        values
+    }
+
+    #[test]
+    fn push_and_pop_both_ends() {
+        let mut list = List::new();
+        let mut nodes = (0..4).map(|n| Box::pin(Node::new(n))).collect::<Vec<_>>();
+        for node in &mut nodes[..2] {
+            list.push_back(node.as_mut());
+        }
+        for node in &mut nodes[2..] {
+            list.push_front(node.as_mut());
+        }
+        assert_eq!(values(&list), [3, 2, 0, 1]);
+        assert_eq!(list.pop_back_with(|value| *value), Some(1));
+        assert_eq!(list.pop_front_with(|value| *value), Some(3));
+        assert!(!nodes[3].is_linked());
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn drop_unlinks() {
+        let mut list = List::new();
+        let mut nodes = (0..5).map(|n| Box::pin(Node::new(n))).collect::<Vec<_>>();
+        for node in &mut nodes {
+            list.push_back(node.as_mut());
+        }
+        drop(nodes.remove(2));
+        drop(nodes.remove(0));
+        assert_eq!(values(&list), [1, 3, 4]);
+        nodes[1].as_mut().unlink();
+        assert_eq!(values(&list), [1, 4]);
+
+        list.retain(|value| *value != 4);
+        assert_eq!(values(&list), [1]);
+        drop(list);
+        assert!(!nodes[0].is_linked());
+    }
+
+    #[test]
+    fn for_each_survives_unlinking_others() {
+        let mut list = List::new();
+        let mut first = pin!(Node::new(0));
+        let second = std::cell::RefCell::new(Some(Box::pin(Node::new(1))));
+        list.push_back(first.as_mut());
+        list.push_back(second.borrow_mut().as_mut().unwrap().as_mut());
+
+        let mut seen = Vec::new();
+        list.for_each(|value| {
+            seen.push(*value);
+            second.borrow_mut().take();
+        });
+        assert_eq!(seen, [0]);
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    #[should_panic = "already in a list"]
+    fn push_twice() {
+        let mut list = List::new();
+        let mut node = pin!(Node::new(0));
+        list.push_back(node.as_mut());
+        list.push_back(node.as_mut());
+    }
+}
@@ -0,0 +1,236 @@
+//! [`std::io`] adapters for the crate's byte buffers.
+//!
+//! [`Vec<u8>`](crate::Vec) and [`BytesMut`] can be written to, and
+//! [`Bytes`] read from, directly. A [`Cursor`] adds a position, for reads and
+//! seeks through any buffer and overwriting writes into a vector.
+//!
+//! ```
+//! use nomicon::{bytes::BytesMut, io::Cursor};
+//! use std::io::{Read, Seek, SeekFrom, Write};
+//!
+//! let mut out = BytesMut::new();
+//! write!(out, "{}-{}", 4, 2).unwrap();
+//!
+//! let mut cursor = Cursor::new(out.freeze());
+//! cursor.seek(SeekFrom::End(-1)).unwrap();
+//! let mut last = String::new();
+//! cursor.read_to_string(&mut last).unwrap();
+//! assert_eq!(last, "2");
+//! ```
+
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use crate::{
+    bytes::{Bytes, BytesMut},
+    Vec,
+};
+
+/// A buffer with a read and write position.
+///
+/// This type can be constructed through [`Cursor::new`].
+#[derive(Debug, Default, Clone)]
+pub struct Cursor<T> {
+    inner: T,
+    pos: u64,
+}
+
+impl<T> Cursor<T> {
+    /// Returns a cursor at the start of `inner`.
+    pub const fn new(inner: T) -> Self {
+        Self { inner, pos: 0 }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    pub const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Changing the length of the buffer leaves the position where it was.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub const fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// The position may be past the end, reads then return nothing and
+    /// writes pad the gap with zeroes.
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+}
+
+impl<T: AsRef<[u8]>> Cursor<T> {
+    /// The bytes from the position on.
+    pub fn remaining_slice(&self) -> &[u8] {
+        let data = self.inner.as_ref();
+        let start = usize::try_from(self.pos).map_or(data.len(), |pos| pos.min(data.len()));
+        &data[start..]
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.remaining_slice().read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: AsRef<[u8]>> BufRead for Cursor<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.remaining_slice())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+    fn seek(&mut self, style: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match style {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.inner.as_ref().len() as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl Write for Cursor<Vec<u8>> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_at(&mut self.pos, &mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Cursor<&mut Vec<u8>> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_at(&mut self.pos, self.inner, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Overwrite `vec` from `pos`, growing it as needed.
+fn write_at(pos: &mut u64, vec: &mut Vec<u8>, buf: &[u8]) -> io::Result<usize> {
+    let start = usize::try_from(*pos).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cursor position exceeds the address space",
+        )
+    })?;
+    if vec.len() < start {
+        vec.reserve(start - vec.len() + buf.len());
+        while vec.len() < start {
+            vec.push(0);
+        }
+    }
+    let overlap = (vec.len() - start).min(buf.len());
+    vec[start..start + overlap].copy_from_slice(&buf[..overlap]);
+    vec.extend_from_slice(&buf[overlap..]);
+    *pos += buf.len() as u64;
+    Ok(buf.len())
+}
+
+/// Appends.
+impl Write for Vec<u8> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends.
+impl Write for BytesMut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads split the bytes off the front, sharing the buffer.
+impl Read for Bytes {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = Read::read(&mut &**self, buf)?;
+        self.split_to(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Bytes {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.split_to(amt);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_overwrites_and_pads() {
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.write_all(b"hello").unwrap();
+        cursor.set_position(1);
+        cursor.write_all(b"EL").unwrap();
+        cursor.set_position(7);
+        cursor.write_all(b"!").unwrap();
+        assert_eq!(&**cursor.get_ref(), b"hELlo\0\0!");
+
+        assert_eq!(cursor.seek(SeekFrom::Current(-3)).unwrap(), 5);
+        assert!(cursor.seek(SeekFrom::End(-9)).is_err());
+        cursor.rewind().unwrap();
+        let mut first = [0; 3];
+        cursor.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"hEL");
+    }
+
+    #[test]
+    fn bytes_read_lines() {
+        let mut bytes = Bytes::copy_from_slice(b"one\ntwo\n");
+        let mut line = String::new();
+        bytes.read_line(&mut line).unwrap();
+        assert_eq!(line, "one\n");
+        assert_eq!(&*bytes, b"two\n");
+
+        let mut rest = std::vec::Vec::new();
+        bytes.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"two\n");
+        assert!(bytes.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use std::{alloc::Layout, ptr::NonNull};
+
+use crate::guard::{guard, ScopeGuard};
+
+/// A header followed by a slice, stored in a single allocation.
+///
+/// The constructors take care of the layout arithmetic, and of cleaning up if
+/// filling in the slice panics. Pointer types wanting a header in front of
+/// their payload can build one and take the allocation over through
+/// [`Box::into_raw`].
+///
+/// ```
+/// use nomicon::layout::HeaderSlice;
+///
+/// let boxed = HeaderSlice::new("squares", (1..5).map(|n| n * n));
+/// assert_eq!(boxed.header, "squares");
+/// assert_eq!(boxed.slice, [1, 4, 9, 16]);
+/// ```
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct HeaderSlice<H, T> {
+    pub header: H,
+    pub slice: [T],
+}
+
+impl<H, T> HeaderSlice<H, T> {
+    /// Returns the layout of a [`HeaderSlice`] holding `len` elements.
+    ///
+    /// # Panics
+    /// If the size overflows `isize::MAX`.
+    pub fn layout(len: usize) -> Layout {
+        Layout::array::<T>(len)
+            .and_then(|slice| Layout::new::<H>().extend(slice))
+            .map(|(layout, _)| layout.pad_to_align())
+            .expect("capacity overflow")
+    }
+
+    /// Returns the byte offset of the slice from the start of the allocation.
+    pub fn slice_offset() -> usize {
+        let (_, offset) = Layout::new::<H>()
+            .extend(Layout::new::<T>())
+            .expect("offset fits a layout");
+        offset
+    }
+
+    /// Build a [`HeaderSlice`] out of every item of `items`.
+    ///
+    /// # Panics
+    /// If the iterator yields fewer items than it reported.
+    pub fn new<I>(header: H, items: I) -> Box<Self>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let mut items = items.into_iter();
+        let len = items.len();
+        let layout = Self::layout(len);
+        let base = if layout.size() == 0 {
+            // Zero sized allocations only need to be aligned.
+            NonNull::new(std::ptr::without_provenance_mut(layout.align())).unwrap()
+        } else {
+            let ptr = unsafe { std::alloc::alloc(layout) };
+            NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout))
+        };
+
+        let slice = unsafe { base.add(Self::slice_offset()).cast::<T>() };
+        // Frees a partially filled allocation if the source iterator panics.
+        let mut written = guard(0, |written| unsafe {
+            NonNull::slice_from_raw_parts(slice, written).drop_in_place();
+            if layout.size() != 0 {
+                std::alloc::dealloc(base.as_ptr(), layout);
+            }
+        });
+        for _ in 0..len {
+            let item = items.next().expect("iterator reported too many items");
+            unsafe { slice.add(*written).write(item) };
+            *written += 1;
+        }
+        unsafe { base.cast::<H>().write(header) };
+
+        ScopeGuard::into_inner(written);
+        let fat = std::ptr::slice_from_raw_parts_mut(base.as_ptr(), len) as *mut Self;
+        // SAFETY
+        // * The allocation was made with Layout::for_value of the resulting
+        //   type, which is what Box frees it with.
+        // * The header and every element of the slice are initialized.
+        unsafe { Box::from_raw(fat) }
+    }
+
+    /// Build a [`HeaderSlice`] holding clones of `items`.
+    pub fn from_slice(header: H, items: &[T]) -> Box<Self>
+    where
+        T: Clone,
+    {
+        Self::new(header, items.iter().cloned())
+    }
+}
+
+/// A header followed by a string, stored in a single allocation.
+///
+/// ```
+/// use nomicon::layout::HeaderStr;
+///
+/// let boxed = HeaderStr::new(5usize, "hello");
+/// assert_eq!(boxed.header, 5);
+/// assert_eq!(&boxed.str, "hello");
+/// ```
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct HeaderStr<H> {
+    pub header: H,
+    pub str: str,
+}
+
+impl<H> HeaderStr<H> {
+    pub fn new(header: H, s: &str) -> Box<Self> {
+        let bytes = Box::into_raw(HeaderSlice::from_slice(header, s.as_bytes()));
+        // SAFETY
+        // * Both types are repr(C) with a [u8] or str tail, so they share a
+        //   layout and pointer metadata.
+        // * The bytes came from a str.
+        unsafe { Box::from_raw(bytes as *mut Self) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layouts() {
+        let boxed = HeaderSlice::<u8, u64>::from_slice(1, &[2, 3]);
+        assert_eq!(
+            HeaderSlice::<u8, u64>::layout(2),
+            Layout::for_value(&*boxed)
+        );
+        assert_eq!(HeaderSlice::<u8, u64>::slice_offset(), 8);
+        assert_eq!(HeaderSlice::<u64, u8>::layout(3).size(), 16);
+
+        let empty = HeaderSlice::<(), ()>::new((), std::iter::empty());
+        assert_eq!(empty.slice.len(), 0);
+        let zsts = HeaderSlice::<(), ()>::new((), [(), ()]);
+        assert_eq!(zsts.slice.len(), 2);
+    }
+
+    #[test]
+    fn drops_on_panic() {
+        let flag = std::rc::Rc::new(());
+        let items = (0..4).map(|n| {
+            assert!(n < 3, "iterator panicked");
+            std::rc::Rc::clone(&flag)
+        });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            HeaderSlice::new(std::rc::Rc::clone(&flag), items)
+        }));
+        assert!(result.is_err());
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+
+        let boxed = HeaderSlice::new(std::rc::Rc::clone(&flag), vec![std::rc::Rc::clone(&flag)]);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 3);
+        drop(boxed);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+}
@@ -1,9 +1,64 @@
-#![feature(ptr_as_ref_unchecked, const_ptr_as_ref, const_mut_refs)]
 #![doc = include_str!("../README.md")]
 
+pub mod alloc;
 pub mod arc;
+pub mod array_vec;
+pub mod async_sync;
+pub mod borrow;
+pub mod boxed;
+pub mod bytes;
 pub mod cell;
+pub mod channel;
+pub mod dynamic;
+pub mod error;
+pub mod executor;
+pub mod ffi;
+pub mod future;
+pub mod gc;
+pub mod guard;
+pub mod hash;
+pub mod im_vec;
+pub mod index_map;
+pub mod interner;
+pub mod intrusive;
+pub mod io;
+pub mod layout;
+pub mod lockfree;
+pub mod lru_cache;
+pub mod marker;
+pub mod num;
+pub mod observe;
+pub mod owning_ref;
+pub mod pin;
+pub mod pool;
+pub mod ptr;
+mod raw_vec;
 pub mod rc;
+pub mod rope;
+pub mod slab;
+pub mod slice;
+pub mod slot_map;
+pub mod small_vec;
+pub mod stream;
+pub mod string;
+pub mod sync;
+pub mod task;
+pub mod thread;
+pub mod tree;
+pub mod typed_arena;
 mod vec;
+pub mod vec_map;
+pub mod weak_value_map;
 
+pub use array_vec::ArrayVec;
+pub use im_vec::ImVec;
+pub use index_map::IndexMap;
+pub use lru_cache::LruCache;
+pub use rope::Rope;
+pub use slab::Slab;
+pub use slot_map::SlotMap;
+pub use small_vec::SmallVec;
+pub use typed_arena::TypedArena;
 pub use vec::Vec;
+pub use vec_map::VecMap;
+pub use weak_value_map::WeakValueHashMap;
@@ -0,0 +1,12 @@
+//! Data structures that never block, a stalled thread can not keep others
+//! from making progress.
+//!
+//! Nodes unlinked by one thread may still be read by another, so they are
+//! retired through hazard pointers instead of freed right away.
+
+mod deque;
+mod hazard;
+mod queue;
+
+pub use deque::{Steal, Stealer, Worker};
+pub use queue::Queue;
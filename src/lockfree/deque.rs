@@ -0,0 +1,371 @@
+use std::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::atomic::{self, AtomicIsize, AtomicPtr, Ordering},
+};
+
+use super::hazard::{self, Hazard};
+use crate::{arc::Arc, sync::CachePadded};
+
+/// The capacity of a new deque's buffer.
+const MIN_CAP: usize = 16;
+
+/// A ring buffer indexed by the ever increasing deque indices.
+struct Buffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Buffer<T> {
+    fn alloc(cap: usize) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            slots: (0..cap)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+        }))
+    }
+
+    fn cap(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn at(&self, index: isize) -> *mut MaybeUninit<T> {
+        self.slots[index as usize & (self.cap() - 1)].get()
+    }
+
+    /// # Safety
+    /// The slot at `index` must not be read concurrently.
+    unsafe fn write(&self, index: isize, value: T) {
+        unsafe { self.at(index).write(MaybeUninit::new(value)) };
+    }
+
+    /// Returns a bitwise copy of the slot at `index`, which only owns the
+    /// value once the caller won the race for it.
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        unsafe { self.at(index).read() }
+    }
+}
+
+struct Inner<T> {
+    /// The index thieves steal from.
+    front: CachePadded<AtomicIsize>,
+    /// The index the worker pushes to and pops from.
+    back: CachePadded<AtomicIsize>,
+    buffer: CachePadded<AtomicPtr<Buffer<T>>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let (front, back) = (*self.front.get_mut(), *self.back.get_mut());
+        // SAFETY
+        // * Every handle is gone, the slots between front and back hold
+        //   values, and older buffers were retired on resize.
+        unsafe {
+            let buffer = Box::from_raw(*self.buffer.get_mut());
+            for index in front..back {
+                (*buffer.at(index)).assume_init_drop();
+            }
+        }
+    }
+}
+
+/// The result of [`Stealer::steal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Steal<T> {
+    /// The deque was empty.
+    Empty,
+    /// A value was stolen.
+    Success(T),
+    /// Another thread won the race for the value, the steal may be retried.
+    Retry,
+}
+
+impl<T> Steal<T> {
+    pub fn success(self) -> Option<T> {
+        match self {
+            Self::Success(value) => Some(value),
+            Self::Empty | Self::Retry => None,
+        }
+    }
+}
+
+/// The owning end of a Chase-Lev work stealing deque.
+///
+/// The worker pushes and pops at the back, last in first out, so a thread
+/// keeps working on the task it spawned most recently while its cache is
+/// still warm. [`Stealer`]s take the oldest tasks from the front. Only the
+/// race for the very last value needs a compare and swap on the worker's
+/// side. The buffer grows as needed, old buffers are retired through hazard
+/// pointers as thieves may still be reading them.
+///
+/// ```
+/// use nomicon::lockfree::{Steal, Worker};
+///
+/// let worker = Worker::new();
+/// let stealer = worker.stealer();
+/// for task in 0..4 {
+///     worker.push(task);
+/// }
+/// assert_eq!(worker.pop(), Some(3));
+/// std::thread::scope(|s| {
+///     s.spawn(|| assert_eq!(stealer.steal(), Steal::Success(0)));
+/// });
+/// assert_eq!(worker.len(), 2);
+/// ```
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    /// Only the worker replaces the buffer, so it keeps a copy at hand.
+    buffer: Cell<*mut Buffer<T>>,
+    /// The worker can be moved to another thread, but not shared.
+    _marker: PhantomData<Cell<()>>,
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+
+impl<T: Send> Worker<T> {
+    pub fn new() -> Self {
+        let buffer = Buffer::alloc(MIN_CAP);
+        Self {
+            inner: Arc::new(Inner {
+                front: CachePadded::new(AtomicIsize::new(0)),
+                back: CachePadded::new(AtomicIsize::new(0)),
+                buffer: CachePadded::new(AtomicPtr::new(buffer)),
+            }),
+            buffer: Cell::new(buffer),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a handle other threads steal values through.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Push `value` onto the back of the deque.
+    pub fn push(&self, value: T) {
+        let back = self.inner.back.load(Ordering::Relaxed);
+        let front = self.inner.front.load(Ordering::Acquire);
+        // SAFETY
+        // * Only the worker swaps the buffer, it can not be retired under us.
+        let mut buffer = unsafe { &*self.buffer.get() };
+        if back - front >= buffer.cap() as isize {
+            self.resize(front, back, buffer.cap() * 2);
+            buffer = unsafe { &*self.buffer.get() };
+        }
+        // SAFETY
+        // * The slot is past the back, no thief reads it until the new back
+        //   is published.
+        unsafe { buffer.write(back, value) };
+        atomic::fence(Ordering::Release);
+        self.inner.back.store(back + 1, Ordering::Release);
+    }
+
+    /// Pop the value at the back of the deque, the one pushed last.
+    pub fn pop(&self) -> Option<T> {
+        let back = self.inner.back.load(Ordering::Relaxed);
+        let front = self.inner.front.load(Ordering::Relaxed);
+        if back - front <= 0 {
+            return None;
+        }
+
+        // Reserve the back slot before checking what thieves took.
+        let back = back - 1;
+        self.inner.back.store(back, Ordering::Relaxed);
+        atomic::fence(Ordering::SeqCst);
+        let front = self.inner.front.load(Ordering::Relaxed);
+
+        if back < front {
+            // Thieves emptied the deque in the meantime.
+            self.inner.back.store(back + 1, Ordering::Relaxed);
+            return None;
+        }
+        // SAFETY
+        // * The slot is between front and back, it holds a value.
+        let value = unsafe { (*self.buffer.get()).read(back) };
+        if back == front {
+            // The last value, race the thieves for it.
+            let won = self
+                .inner
+                .front
+                .compare_exchange(front, front + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            self.inner.back.store(back + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        // SAFETY
+        // * The value was not stolen, this is the only copy that is used.
+        Some(unsafe { value.assume_init() })
+    }
+
+    /// Move the values into a buffer holding `cap` values, retiring the old
+    /// one.
+    #[cold]
+    fn resize(&self, front: isize, back: isize, cap: usize) {
+        let old = self.buffer.get();
+        let new = Buffer::alloc(cap);
+        // SAFETY
+        // * Both buffers hold at least back - front values, and the values
+        //   copied are moved, the old buffer never drops its slots.
+        // * Thieves may still read the old buffer, it is only freed once no
+        //   hazard protects it.
+        unsafe {
+            for index in front..back {
+                (*new).write(index, (*old).read(index).assume_init());
+            }
+            self.buffer.set(new);
+            self.inner.buffer.store(new, Ordering::Release);
+            hazard::retire(old);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let back = self.inner.back.load(Ordering::Relaxed);
+        let front = self.inner.front.load(Ordering::SeqCst);
+        (back - front).max(0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Send> Default for Worker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The stealing end of a [`Worker`]'s deque, it can be cloned and shared.
+///
+/// This type can be constructed through [`Worker::stealer`].
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Stealer<T> {
+    /// Steal the value at the front of the deque, the oldest one.
+    pub fn steal(&self) -> Steal<T> {
+        let front = self.inner.front.load(Ordering::Acquire);
+        atomic::fence(Ordering::SeqCst);
+        let back = self.inner.back.load(Ordering::Acquire);
+        if back - front <= 0 {
+            return Steal::Empty;
+        }
+
+        let hazard = Hazard::new();
+        let buffer = hazard.protect(&self.inner.buffer);
+        // SAFETY
+        // * The buffer is protected. The copy is only used once the compare
+        //   and swap below claims the slot.
+        let value = unsafe { (*buffer).read(front) };
+
+        // A resize may have moved the value, or another thread claimed it.
+        if self.inner.buffer.load(Ordering::Acquire) != buffer
+            || self
+                .inner
+                .front
+                .compare_exchange(front, front + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_err()
+        {
+            return Steal::Retry;
+        }
+        // SAFETY
+        // * Moving the front past the slot gave this thread its value.
+        Steal::Success(unsafe { value.assume_init() })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let front = self.inner.front.load(Ordering::Acquire);
+        atomic::fence(Ordering::SeqCst);
+        self.inner.back.load(Ordering::Acquire) <= front
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn lifo_worker_fifo_stealer() {
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        assert_eq!(worker.pop(), None);
+        assert_eq!(stealer.steal(), Steal::Empty);
+        // Grow past the first buffer a few times.
+        for n in 0..100 {
+            worker.push(n);
+        }
+        assert_eq!(worker.len(), 100);
+        assert_eq!(stealer.steal(), Steal::Success(0));
+        assert_eq!(worker.pop(), Some(99));
+        assert!((1..99).rev().eq(std::iter::from_fn(|| worker.pop())));
+        assert!(stealer.is_empty());
+
+        let flag = std::sync::Arc::new(());
+        let worker = Worker::new();
+        for _ in 0..40 {
+            worker.push(std::sync::Arc::clone(&flag));
+        }
+        drop(worker.stealer().steal());
+        drop(worker.pop());
+        drop(worker);
+        assert_eq!(std::sync::Arc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn every_value_is_taken_once() {
+        const VALUES: usize = 20_000;
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        let sum = AtomicUsize::new(0);
+        let taken = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                let (stealer, sum, taken) = (stealer.clone(), &sum, &taken);
+                s.spawn(move || {
+                    while taken.load(Ordering::Relaxed) < VALUES {
+                        match stealer.steal() {
+                            Steal::Success(n) => {
+                                sum.fetch_add(n, Ordering::Relaxed);
+                                taken.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Steal::Retry => {}
+                            Steal::Empty => std::thread::yield_now(),
+                        }
+                    }
+                });
+            }
+            for n in 0..VALUES {
+                worker.push(n);
+                if n % 3 == 0 {
+                    if let Some(n) = worker.pop() {
+                        sum.fetch_add(n, Ordering::Relaxed);
+                        taken.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            while let Some(n) = worker.pop() {
+                sum.fetch_add(n, Ordering::Relaxed);
+                taken.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        assert_eq!(sum.into_inner(), (0..VALUES).sum::<usize>());
+    }
+}
@@ -0,0 +1,227 @@
+//! Hazard pointers, deferring frees until no thread may still read a node.
+//!
+//! Before dereferencing a shared pointer a thread publishes it in a hazard
+//! record, then checks the pointer is still reachable. Unlinked nodes are
+//! retired to a thread local list, which is scanned against every published
+//! hazard once it grows, freeing the nodes nobody protects.
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use crate::sync::SpinMutex;
+
+/// Retired nodes per thread before a scan is attempted.
+const SCAN_THRESHOLD: usize = 64;
+
+/// A slot a thread publishes a pointer in. Records are never freed, they are
+/// recycled once their owner is done with them.
+struct Record {
+    hazard: AtomicPtr<u8>,
+    in_use: AtomicBool,
+    next: *const Record,
+}
+
+// Every field is either atomic or written once before the record is shared.
+unsafe impl Sync for Record {}
+
+static RECORDS: AtomicPtr<Record> = AtomicPtr::new(ptr::null_mut());
+
+fn records() -> impl Iterator<Item = &'static Record> {
+    let mut next = RECORDS.load(Ordering::Acquire).cast_const();
+    std::iter::from_fn(move || {
+        // SAFETY
+        // * Records are leaked, every pointer in the list stays valid.
+        let record = unsafe { next.as_ref()? };
+        next = record.next;
+        Some(record)
+    })
+}
+
+/// Claim a record that is not in use, or add a new one to the list.
+fn acquire() -> &'static Record {
+    for record in records() {
+        if !record.in_use.load(Ordering::Relaxed)
+            && record
+                .in_use
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        {
+            return record;
+        }
+    }
+    let record = Box::leak(Box::new(Record {
+        hazard: AtomicPtr::new(ptr::null_mut()),
+        in_use: AtomicBool::new(true),
+        next: ptr::null(),
+    }));
+    let mut head = RECORDS.load(Ordering::Relaxed);
+    loop {
+        record.next = head;
+        match RECORDS.compare_exchange_weak(head, record, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => return record,
+            Err(current) => head = current,
+        }
+    }
+}
+
+/// A published pointer, protecting its target from being freed until the
+/// hazard is reset or dropped.
+pub(crate) struct Hazard {
+    record: &'static Record,
+}
+
+impl Hazard {
+    pub(crate) fn new() -> Self {
+        Self { record: acquire() }
+    }
+
+    /// Load and protect the pointer in `src`.
+    ///
+    /// The pointer is loaded again after publishing it, a pointer that is
+    /// still in `src` was not retired before the hazard became visible.
+    pub(crate) fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        let mut ptr = src.load(Ordering::Relaxed);
+        loop {
+            self.set(ptr);
+            // SeqCst like the store, an Acquire load could be ordered before
+            // it and miss an unlink the scan then misses the hazard of.
+            let current = src.load(Ordering::SeqCst);
+            if current == ptr {
+                return ptr;
+            }
+            ptr = current;
+        }
+    }
+
+    /// Publish `ptr`, the caller has to check it is still reachable before
+    /// dereferencing it.
+    pub(crate) fn set<T>(&self, ptr: *mut T) {
+        self.record.hazard.store(ptr.cast(), Ordering::SeqCst);
+    }
+
+    pub(crate) fn reset(&self) {
+        self.record.hazard.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+impl Drop for Hazard {
+    fn drop(&mut self) {
+        self.reset();
+        self.record.in_use.store(false, Ordering::Release);
+    }
+}
+
+struct Retired {
+    ptr: *mut u8,
+    free: unsafe fn(*mut u8),
+}
+
+// Retired nodes are only dropped, by whichever thread scans them, and retire
+// only takes nodes that are Send.
+unsafe impl Send for Retired {}
+
+/// Retired nodes of threads that exited before they could be freed.
+static ORPHANS: SpinMutex<Vec<Retired>> = SpinMutex::new(Vec::new());
+
+struct RetiredList(RefCell<Vec<Retired>>);
+
+impl Drop for RetiredList {
+    fn drop(&mut self) {
+        let retired = self.0.get_mut();
+        scan(retired);
+        ORPHANS.lock().append(retired);
+    }
+}
+
+thread_local! {
+    static RETIRED: RetiredList = const { RetiredList(RefCell::new(Vec::new())) };
+}
+
+/// Free every node in `retired` that no hazard points to.
+fn scan(retired: &mut Vec<Retired>) {
+    if let Some(mut orphans) = ORPHANS.try_lock() {
+        retired.append(&mut orphans);
+    }
+    // Pairs with the SeqCst reload in protect, either the scan sees the
+    // hazard or the protecting thread sees the node unlinked.
+    std::sync::atomic::fence(Ordering::SeqCst);
+    let hazards = records()
+        .map(|record| record.hazard.load(Ordering::SeqCst))
+        .filter(|hazard| !hazard.is_null())
+        .collect::<HashSet<_>>();
+    retired.retain(|node| {
+        if hazards.contains(&node.ptr) {
+            return true;
+        }
+        // SAFETY
+        // * The node is unlinked and no hazard protects it, so no thread can
+        //   reach it any more.
+        unsafe { (node.free)(node.ptr) };
+        false
+    });
+}
+
+/// Free `ptr` once no hazard protects it.
+///
+/// # Safety
+/// `ptr` came from [`Box::into_raw`], and is already unlinked so no thread
+/// can protect it anew.
+///
+/// The node may be dropped by whichever thread scans it, hence `T: Send`.
+pub(crate) unsafe fn retire<T: Send>(ptr: *mut T) {
+    unsafe fn free<T>(ptr: *mut u8) {
+        drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+    }
+    let node = Retired {
+        ptr: ptr.cast(),
+        free: free::<T>,
+    };
+    // The hazard scan has to come after the node was unlinked.
+    std::sync::atomic::fence(Ordering::SeqCst);
+    let pushed = RETIRED.try_with(|retired| {
+        let mut retired = retired.0.borrow_mut();
+        retired.push(node);
+        if retired.len() >= SCAN_THRESHOLD {
+            scan(&mut retired);
+        }
+    });
+    if pushed.is_err() {
+        // The thread is exiting, another thread frees the node.
+        ORPHANS.lock().push(Retired {
+            ptr: ptr.cast(),
+            free: free::<T>,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn protected_nodes_outlive_scans() {
+        let flag = Arc::new(());
+        let shared = AtomicPtr::new(Box::into_raw(Box::new(Arc::clone(&flag))));
+        let hazard = Hazard::new();
+        let protected = hazard.protect(&shared);
+        shared.store(ptr::null_mut(), Ordering::Release);
+        unsafe { retire(protected) };
+
+        // Enough retirements to force scans.
+        for _ in 0..SCAN_THRESHOLD * 2 {
+            unsafe { retire(Box::into_raw(Box::new(Arc::clone(&flag)))) };
+        }
+        assert!(Arc::strong_count(&flag) < SCAN_THRESHOLD);
+        assert!(Arc::ptr_eq(unsafe { &*protected }, &flag));
+
+        drop(hazard);
+        RETIRED.with(|retired| scan(&mut retired.0.borrow_mut()));
+        assert_eq!(Arc::strong_count(&flag), 1);
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use super::hazard::{self, Hazard};
+use crate::sync::CachePadded;
+
+struct Node<T> {
+    /// Uninitialized in the dummy node at the head.
+    value: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(value: MaybeUninit<T>) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            value,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+/// A lock-free unbounded FIFO queue, the Michael-Scott queue.
+///
+/// The list always starts with a dummy node, so the head and tail never have
+/// to be updated together. Pushing links a node after the tail, popping
+/// moves the head to the next node, which becomes the new dummy once its
+/// value is taken. A thread finding the tail lagging behind moves it on
+/// instead of waiting. Unlinked dummies are freed through hazard pointers.
+///
+/// ```
+/// use nomicon::lockfree::Queue;
+///
+/// let queue = Queue::new();
+/// std::thread::scope(|s| {
+///     for n in 0..4 {
+///         let queue = &queue;
+///         s.spawn(move || queue.push(n));
+///     }
+/// });
+/// let mut values = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+/// values.sort();
+/// assert_eq!(values, [0, 1, 2, 3]);
+/// ```
+pub struct Queue<T> {
+    head: CachePadded<AtomicPtr<Node<T>>>,
+    tail: CachePadded<AtomicPtr<Node<T>>>,
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T: Send> Queue<T> {
+    pub fn new() -> Self {
+        let dummy = Node::new(MaybeUninit::uninit());
+        Self {
+            head: CachePadded::new(AtomicPtr::new(dummy)),
+            tail: CachePadded::new(AtomicPtr::new(dummy)),
+        }
+    }
+
+    /// Push `value` onto the back of the queue.
+    pub fn push(&self, value: T) {
+        let node = Node::new(MaybeUninit::new(value));
+        let hazard = Hazard::new();
+        loop {
+            let tail = hazard.protect(&self.tail);
+            // SAFETY
+            // * The tail is protected and was still reachable once the
+            //   hazard was published.
+            let next = unsafe { (*tail).next.load(Ordering::Acquire) };
+            if next.is_null() {
+                let linked = unsafe {
+                    (*tail).next.compare_exchange(
+                        ptr::null_mut(),
+                        node,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                    )
+                };
+                if linked.is_ok() {
+                    // Failing means another thread already moved the tail on.
+                    let _ = self.tail.compare_exchange(
+                        tail,
+                        node,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                    );
+                    return;
+                }
+            } else {
+                // The tail is lagging, help move it on.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pop the value at the front of the queue.
+    pub fn pop(&self) -> Option<T> {
+        let head_hazard = Hazard::new();
+        let next_hazard = Hazard::new();
+        loop {
+            let head = head_hazard.protect(&self.head);
+            // SAFETY
+            // * The head is protected.
+            let next = unsafe { (*head).next.load(Ordering::Acquire) };
+            next_hazard.set(next);
+            // Next is only retired after becoming the head and being popped,
+            // which can not have happened while head is still the head.
+            if self.head.load(Ordering::Acquire) != head {
+                continue;
+            }
+            if next.is_null() {
+                return None;
+            }
+            // Never move the head past the tail.
+            let tail = self.tail.load(Ordering::Acquire);
+            if head == tail {
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                // SAFETY
+                // * Moving the head past it gave this thread the value of
+                //   next, which is now the dummy.
+                // * The old dummy is unlinked, and only freed once no hazard
+                //   protects it.
+                unsafe {
+                    let value = (*next).value.assume_init_read();
+                    drop((head_hazard, next_hazard));
+                    hazard::retire(head);
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    /// Returns true if the queue was empty when checked.
+    pub fn is_empty(&self) -> bool {
+        let hazard = Hazard::new();
+        let head = hazard.protect(&self.head);
+        // SAFETY
+        // * The head is protected.
+        unsafe { (*head).next.load(Ordering::Acquire).is_null() }
+    }
+}
+
+impl<T: Send> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // SAFETY
+        // * The queue is owned, no other thread can reach its nodes. Nodes
+        //   after the dummy hold values.
+        unsafe {
+            let dummy = Box::from_raw(*self.head.get_mut());
+            let mut next = dummy.next.load(Ordering::Relaxed);
+            while !next.is_null() {
+                let mut node = Box::from_raw(next);
+                node.value.assume_init_drop();
+                next = *node.next.get_mut();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn fifo_and_drop() {
+        let queue = Queue::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        for n in 0..100 {
+            queue.push(n);
+        }
+        assert!(!queue.is_empty());
+        assert!((0..100).eq(std::iter::from_fn(|| queue.pop())));
+
+        let flag = std::sync::Arc::new(());
+        let queue = Queue::new();
+        for _ in 0..10 {
+            queue.push(std::sync::Arc::clone(&flag));
+        }
+        drop(queue.pop());
+        drop(queue);
+        assert_eq!(std::sync::Arc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn many_producers_and_consumers() {
+        const PER_THREAD: usize = 5_000;
+        let queue = Queue::new();
+        let popped = AtomicUsize::new(0);
+        let sum = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for n in 0..PER_THREAD {
+                        queue.push(Box::new(n));
+                    }
+                });
+                s.spawn(|| {
+                    while popped.load(Ordering::Relaxed) < 4 * PER_THREAD {
+                        match queue.pop() {
+                            Some(n) => {
+                                sum.fetch_add(*n, Ordering::Relaxed);
+                                popped.fetch_add(1, Ordering::Relaxed);
+                            }
+                            None => std::thread::yield_now(),
+                        }
+                    }
+                });
+            }
+        });
+        assert!(queue.is_empty());
+        assert_eq!(sum.into_inner(), 4 * (0..PER_THREAD).sum::<usize>());
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    hash::{Hash, Hasher},
+    ptr::NonNull,
+};
+
+use crate::IndexMap;
+
+/// A fixed capacity map that evicts the least recently used entry.
+///
+/// Entries are heap allocated nodes of an intrusive doubly linked list, kept
+/// in order from most recently used (`head`) to least recently used (`tail`).
+/// The map only stores pointers to the nodes.
+pub struct LruCache<K, V> {
+    map: IndexMap<KeyRef<K>, NonNull<Node<K, V>>>,
+    head: Option<NonNull<Node<K, V>>>,
+    tail: Option<NonNull<Node<K, V>>>,
+    cap: usize,
+    on_evict: Option<Box<dyn FnMut(K, V)>>,
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    prev: Option<NonNull<Node<K, V>>>,
+    next: Option<NonNull<Node<K, V>>>,
+}
+
+/// Hashes and compares the key stored inside of a [`Node`] without cloning it.
+struct KeyRef<K> {
+    key: *const K,
+}
+
+impl<K: Hash> Hash for KeyRef<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // SAFETY
+        // * KeyRef's in the map point into live nodes, KeyRef's used for lookups
+        //   point at a borrowed key.
+        unsafe { (*self.key).hash(state) }
+    }
+}
+
+impl<K: PartialEq> PartialEq for KeyRef<K> {
+    fn eq(&self, other: &Self) -> bool {
+        // SAFETY
+        // * See KeyRef::hash.
+        unsafe { *self.key == *other.key }
+    }
+}
+
+impl<K: Eq> Eq for KeyRef<K> {}
+
+impl<K: Hash + Eq, V> LruCache<K, V> {
+    /// Returns a new [`LruCache`] holding up to `cap` entries.
+    ///
+    /// # Panics
+    /// If `cap` is zero.
+    pub fn new(cap: usize) -> Self {
+        assert!(cap != 0, "LruCache capacity must be greater than zero");
+        Self {
+            map: IndexMap::new(),
+            head: None,
+            tail: None,
+            cap,
+            on_evict: None,
+        }
+    }
+
+    /// Returns a new [`LruCache`] that calls `on_evict` with every entry pushed
+    /// out to make room for a new one.
+    ///
+    /// ```
+    /// use std::{cell::RefCell, rc::Rc};
+    ///
+    /// use nomicon::LruCache;
+    ///
+    /// let evicted = Rc::new(RefCell::new(Vec::new()));
+    /// let sink = Rc::clone(&evicted);
+    /// let mut cache = LruCache::with_eviction(2, move |k, v| sink.borrow_mut().push((k, v)));
+    ///
+    /// cache.put("a", 1);
+    /// cache.put("b", 2);
+    /// cache.get(&"a");
+    /// cache.put("c", 3);
+    ///
+    /// assert_eq!(*evicted.borrow(), [("b", 2)]);
+    /// ```
+    pub fn with_eviction<F>(cap: usize, on_evict: F) -> Self
+    where
+        F: FnMut(K, V) + 'static,
+    {
+        let mut cache = Self::new(cap);
+        cache.on_evict = Some(Box::new(on_evict));
+        cache
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub const fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(&KeyRef { key })
+    }
+
+    /// Return the value for `key` and mark it as the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let node = *self.map.get(&KeyRef { key })?;
+        self.detach(node);
+        self.attach_front(node);
+        // SAFETY
+        // * Nodes in the map are live, the returned borrow is tied to &mut self.
+        Some(unsafe { &(*node.as_ptr()).value })
+    }
+
+    /// Return the value for `key` and mark it as the most recently used.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let node = *self.map.get(&KeyRef { key })?;
+        self.detach(node);
+        self.attach_front(node);
+        // SAFETY
+        // * See LruCache::get.
+        Some(unsafe { &mut (*node.as_ptr()).value })
+    }
+
+    /// Return the value for `key` without changing its recency.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        let node = *self.map.get(&KeyRef { key })?;
+        // SAFETY
+        // * See LruCache::get.
+        Some(unsafe { &(*node.as_ptr()).value })
+    }
+
+    /// Insert an entry as the most recently used, returning the old value if
+    /// `key` was already present.
+    ///
+    /// If the cache is full the least recently used entry is evicted.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(&node) = self.map.get(&KeyRef { key: &key }) {
+            self.detach(node);
+            self.attach_front(node);
+            // SAFETY
+            // * See LruCache::get.
+            let old = unsafe { std::mem::replace(&mut (*node.as_ptr()).value, value) };
+            return Some(old);
+        }
+
+        if self.map.len() == self.cap {
+            if let Some((key, value)) = self.pop_lru() {
+                if let Some(on_evict) = self.on_evict.as_mut() {
+                    on_evict(key, value);
+                }
+            }
+        }
+
+        let node = Box::new(Node {
+            key,
+            value,
+            prev: None,
+            next: None,
+        });
+        let node = NonNull::from(Box::leak(node));
+        // SAFETY
+        // * The key lives as long as the node, and the node is only freed after
+        //   being removed from the map.
+        let key = KeyRef {
+            key: unsafe { &(*node.as_ptr()).key },
+        };
+        self.map.insert(key, node);
+        self.attach_front(node);
+        None
+    }
+
+    /// Remove `key` from the cache.
+    pub fn pop(&mut self, key: &K) -> Option<V> {
+        let node = self.map.swap_remove(&KeyRef { key })?;
+        self.detach(node);
+        // SAFETY
+        // * The node was allocated through a Box and is no longer reachable.
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        Some(node.value)
+    }
+
+    /// Remove the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let node = self.tail?;
+        // SAFETY
+        // * The tail is a live node.
+        self.map.swap_remove(&KeyRef {
+            key: unsafe { &(*node.as_ptr()).key },
+        });
+        self.detach(node);
+        // SAFETY
+        // * The node was allocated through a Box and is no longer reachable.
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        Some((node.key, node.value))
+    }
+
+    /// Unlink `node` from the recency list.
+    fn detach(&mut self, node: NonNull<Node<K, V>>) {
+        // SAFETY
+        // * node and its neighbours are live nodes owned by self.
+        // * The links are accessed as places, a reference to the whole node
+        //   would invalidate the map's pointer to its key.
+        unsafe {
+            let n = node.as_ptr();
+            let (prev, next) = ((*n).prev, (*n).next);
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = next,
+                None => self.head = next,
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).prev = prev,
+                None => self.tail = prev,
+            }
+            (*n).prev = None;
+            (*n).next = None;
+        }
+    }
+
+    /// Link a detached `node` in as the most recently used.
+    fn attach_front(&mut self, node: NonNull<Node<K, V>>) {
+        // SAFETY
+        // * node and the head are live nodes owned by self.
+        unsafe {
+            (*node.as_ptr()).next = self.head;
+            match self.head {
+                Some(head) => (*head.as_ptr()).prev = Some(node),
+                None => self.tail = Some(node),
+            }
+        }
+        self.head = Some(node);
+    }
+}
+
+impl<K, V> Drop for LruCache<K, V> {
+    fn drop(&mut self) {
+        self.map.clear();
+        let mut cursor = self.head.take();
+        while let Some(node) = cursor {
+            // SAFETY
+            // * Every node is owned by the list exactly once.
+            let node = unsafe { Box::from_raw(node.as_ptr()) };
+            cursor = node.next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recent() {
+        let mut cache = LruCache::new(2);
+        assert_eq!(cache.put(1, "one"), None);
+        assert_eq!(cache.put(2, "two"), None);
+        assert_eq!(cache.get(&1), Some(&"one"));
+        cache.put(3, "three");
+        assert!(!cache.contains(&2));
+        assert_eq!(cache.peek(&1), Some(&"one"));
+        assert_eq!(cache.put(3, "THREE"), Some("three"));
+        assert_eq!(cache.pop_lru(), Some((1, "one")));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn pop_and_drop() {
+        let mut cache = LruCache::new(8);
+        for n in 0..16 {
+            cache.put(n, n.to_string());
+        }
+        assert_eq!(cache.len(), 8);
+        assert_eq!(cache.pop(&4), None);
+        assert_eq!(cache.pop(&12).as_deref(), Some("12"));
+        *cache.get_mut(&8).unwrap() = "eight".into();
+        assert_eq!(cache.pop_lru(), Some((9, "9".to_string())));
+        assert_eq!(cache.peek(&8).map(String::as_str), Some("eight"));
+    }
+}
@@ -0,0 +1,138 @@
+//! Zero sized fields naming the variance or auto traits a type wants.
+//!
+//! Each is a [`PhantomData`] of a type chosen for that one property, and
+//! unlike `PhantomData<T>` none of them claim to own a `T`, so they have no
+//! say in drop checking.
+//!
+//! ```
+//! use nomicon::marker::{Invariant, PhantomUnsync};
+//!
+//! /// Hands out `&'id mut` borrows, which must not be shortened.
+//! struct Brand<'id> {
+//!     _id: Invariant<&'id ()>,
+//!     _local: PhantomUnsync,
+//! }
+//! ```
+//!
+//! An invariant lifetime can not be shortened.
+//!
+//! ```compile_fail
+//! use nomicon::marker::Invariant;
+//!
+//! fn shorten<'a>(long: Invariant<&'static ()>) -> Invariant<&'a ()> {
+//!     long
+//! }
+//! ```
+
+use std::{fmt, marker::PhantomData};
+
+macro_rules! variance {
+    ($(#[$attr:meta])* $name:ident, $phantom:ty) => {
+        $(#[$attr])*
+        pub struct $name<T: ?Sized>(PhantomData<$phantom>);
+
+        impl<T: ?Sized> $name<T> {
+            pub const fn new() -> Self {
+                Self(PhantomData)
+            }
+        }
+
+        impl<T: ?Sized> Default for $name<T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<T: ?Sized> Clone for $name<T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T: ?Sized> Copy for $name<T> {}
+
+        impl<T: ?Sized> PartialEq for $name<T> {
+            fn eq(&self, _: &Self) -> bool {
+                true
+            }
+        }
+
+        impl<T: ?Sized> Eq for $name<T> {}
+
+        impl<T: ?Sized> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(stringify!($name))
+            }
+        }
+    };
+}
+
+variance!(
+    /// Covariant in `T`, like `&T`: a longer lived `T` can stand in for a
+    /// shorter one. Always [`Send`] and [`Sync`].
+    Covariant,
+    fn() -> T
+);
+
+variance!(
+    /// Contravariant in `T`, like the argument of a `fn(T)`: a shorter lived
+    /// `T` can stand in for a longer one. Always [`Send`] and [`Sync`].
+    Contravariant,
+    fn(T)
+);
+
+variance!(
+    /// Invariant in `T`, like `&mut T`: only `T` itself will do. Always
+    /// [`Send`] and [`Sync`].
+    Invariant,
+    fn(T) -> T
+);
+
+/// Makes the containing type `!Send`, leaving [`Sync`] alone.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PhantomUnsend(PhantomData<std::sync::MutexGuard<'static, ()>>);
+
+impl PhantomUnsend {
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Makes the containing type `!Sync`, leaving [`Send`] alone.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PhantomUnsync(PhantomData<std::cell::Cell<()>>);
+
+impl PhantomUnsync {
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn send<T: Send>() {}
+    fn sync<T: Sync>() {}
+
+    // Compiling is the test.
+    #[allow(dead_code)]
+    fn covariant<'a>(long: Covariant<&'static ()>) -> Covariant<&'a ()> {
+        long
+    }
+
+    #[allow(dead_code)]
+    fn contravariant(short: Contravariant<&()>) -> Contravariant<&'static ()> {
+        short
+    }
+
+    #[test]
+    fn auto_traits() {
+        send::<Covariant<*const ()>>();
+        sync::<Contravariant<std::cell::Cell<()>>>();
+        sync::<Invariant<std::rc::Rc<()>>>();
+        sync::<PhantomUnsend>();
+        send::<PhantomUnsync>();
+        assert_eq!(size_of::<(Invariant<String>, PhantomUnsend)>(), 0);
+    }
+}
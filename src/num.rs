@@ -0,0 +1,189 @@
+//! Integers with a value they can never hold, leaving room for `Option`.
+//!
+//! The compiler only knows about invalid values of a few built in types,
+//! like zero for [`NonZero`]. These types store their value shifted so that
+//! the excluded value lands on zero, which makes `Option<T>` of them the
+//! size of the integer.
+//!
+//! ```
+//! use nomicon::num::{BoundedU32, NonMaxUsize};
+//!
+//! assert_eq!(size_of::<Option<NonMaxUsize>>(), size_of::<usize>());
+//! assert_eq!(size_of::<Option<BoundedU32<1, 12>>>(), size_of::<u32>());
+//! ```
+
+use std::{cmp::Ordering, fmt, num::NonZero};
+
+/// A `usize` that is never `usize::MAX`.
+///
+/// This type can be constructed through [`NonMaxUsize::new`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NonMaxUsize(NonZero<usize>);
+
+impl NonMaxUsize {
+    pub const ZERO: Self = Self::new(0).unwrap();
+    pub const MAX: Self = Self::new(usize::MAX - 1).unwrap();
+
+    /// Returns `None` for `usize::MAX`.
+    pub const fn new(value: usize) -> Option<Self> {
+        // Stored inverted, usize::MAX becomes zero.
+        match NonZero::new(!value) {
+            Some(inverted) => Some(Self(inverted)),
+            None => None,
+        }
+    }
+
+    pub const fn get(self) -> usize {
+        !self.0.get()
+    }
+
+    pub const fn checked_add(self, rhs: usize) -> Option<Self> {
+        match self.get().checked_add(rhs) {
+            Some(value) => Self::new(value),
+            None => None,
+        }
+    }
+
+    pub const fn checked_sub(self, rhs: usize) -> Option<Self> {
+        match self.get().checked_sub(rhs) {
+            Some(value) => Self::new(value),
+            None => None,
+        }
+    }
+}
+
+impl PartialOrd for NonMaxUsize {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NonMaxUsize {
+    /// The stored value is inverted, compare the actual values.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get().cmp(&other.get())
+    }
+}
+
+impl fmt::Debug for NonMaxUsize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), f)
+    }
+}
+
+impl fmt::Display for NonMaxUsize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.get(), f)
+    }
+}
+
+impl From<NonMaxUsize> for usize {
+    fn from(value: NonMaxUsize) -> Self {
+        value.get()
+    }
+}
+
+/// A `u32` in `MIN..=MAX`.
+///
+/// The range has to leave at least one `u32` out, which is where `None`
+/// goes.
+///
+/// ```
+/// use nomicon::num::BoundedU32;
+///
+/// type Month = BoundedU32<1, 12>;
+/// assert_eq!(Month::new(12).map(Month::get), Some(12));
+/// assert!(Month::new(0).is_none());
+/// assert!(Month::MAX.checked_add(1).is_none());
+/// ```
+///
+/// This type can be constructed through [`BoundedU32::new`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BoundedU32<const MIN: u32, const MAX: u32>(NonZero<u32>);
+
+impl<const MIN: u32, const MAX: u32> BoundedU32<MIN, MAX> {
+    const VALID: () = assert!(
+        MIN <= MAX && MAX - MIN < u32::MAX,
+        "the range must be non empty and leave a u32 out",
+    );
+
+    pub const MIN: Self = Self::new(MIN).unwrap();
+    pub const MAX: Self = Self::new(MAX).unwrap();
+
+    /// Returns `None` outside of `MIN..=MAX`.
+    pub const fn new(value: u32) -> Option<Self> {
+        let () = Self::VALID;
+        if value < MIN || value > MAX {
+            return None;
+        }
+        // Stored as the offset from one below MIN, which keeps the order.
+        match NonZero::new(value - MIN + 1) {
+            Some(offset) => Some(Self(offset)),
+            None => None,
+        }
+    }
+
+    pub const fn get(self) -> u32 {
+        self.0.get() - 1 + MIN
+    }
+
+    pub const fn checked_add(self, rhs: u32) -> Option<Self> {
+        match self.get().checked_add(rhs) {
+            Some(value) => Self::new(value),
+            None => None,
+        }
+    }
+
+    pub const fn checked_sub(self, rhs: u32) -> Option<Self> {
+        match self.get().checked_sub(rhs) {
+            Some(value) => Self::new(value),
+            None => None,
+        }
+    }
+}
+
+impl<const MIN: u32, const MAX: u32> fmt::Debug for BoundedU32<MIN, MAX> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), f)
+    }
+}
+
+impl<const MIN: u32, const MAX: u32> fmt::Display for BoundedU32<MIN, MAX> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.get(), f)
+    }
+}
+
+impl<const MIN: u32, const MAX: u32> From<BoundedU32<MIN, MAX>> for u32 {
+    fn from(value: BoundedU32<MIN, MAX>) -> Self {
+        value.get()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert!(NonMaxUsize::new(usize::MAX).is_none());
+        assert_eq!(NonMaxUsize::MAX.get(), usize::MAX - 1);
+        assert!(NonMaxUsize::MAX.checked_add(1).is_none());
+        assert_eq!(
+            NonMaxUsize::ZERO.checked_add(3).map(NonMaxUsize::get),
+            Some(3)
+        );
+        assert!(NonMaxUsize::ZERO < NonMaxUsize::MAX);
+
+        type Full = BoundedU32<0, { u32::MAX - 1 }>;
+        assert_eq!(Full::new(0).map(Full::get), Some(0));
+        assert!(Full::new(u32::MAX).is_none());
+        assert_eq!(size_of::<Option<Full>>(), 4);
+
+        type Digit = BoundedU32<5, 9>;
+        assert!(Digit::new(4).is_none());
+        assert!(Digit::MIN < Digit::MAX);
+        assert_eq!(Digit::MIN.checked_add(4), Some(Digit::MAX));
+        assert!(Digit::MIN.checked_sub(1).is_none());
+    }
+}
@@ -0,0 +1,199 @@
+//! Callbacks notified of events, unsubscribed by dropping a handle.
+//!
+//! A [`Subject`] only holds [`Weak`]s to its callbacks, the [`Subscription`]
+//! returned when subscribing is the one strong reference. There is no cycle
+//! between the two, so either can be dropped first, and a callback can
+//! capture the subject it listens to.
+//!
+//! ```
+//! use nomicon::{cell::Cell, observe::Subject, rc::Rc};
+//!
+//! let clicks = Subject::new();
+//! let total = Rc::new(Cell::new(0));
+//! let counter = {
+//!     let total = Rc::clone(&total);
+//!     clicks.subscribe(move |n: &u32| total.set(total.get() + n))
+//! };
+//!
+//! clicks.emit(&2);
+//! drop(counter);
+//! clicks.emit(&3);
+//! assert_eq!(total.get(), 2);
+//! assert!(clicks.is_empty());
+//! ```
+
+use std::fmt;
+
+use crate::{
+    cell::{Cell, RefCell},
+    guard::guard,
+    rc::{Rc, Weak},
+    Vec,
+};
+
+type Callback<T> = Box<dyn Fn(&T)>;
+
+/// A source of events of type `T`.
+///
+/// Callbacks may subscribe, unsubscribe and emit again while an event is
+/// being emitted. Those subscribed during an emit first hear the next one,
+/// and those dropped during it are not called for the rest of it.
+///
+/// This type can be constructed through [`Subject::new`].
+pub struct Subject<T> {
+    subscribers: RefCell<Vec<Weak<Callback<T>>>>,
+    /// How many emits are running, dead subscribers are only swept out when
+    /// none are so indices stay put.
+    emitting: Cell<usize>,
+}
+
+impl<T> Subject<T> {
+    pub const fn new() -> Self {
+        Self {
+            subscribers: RefCell::new(Vec::new()),
+            emitting: Cell::new(0),
+        }
+    }
+
+    /// Call `callback` with every event until the returned subscription is
+    /// dropped.
+    #[must_use = "dropping the subscription unsubscribes immediately"]
+    pub fn subscribe(&self, callback: impl Fn(&T) + 'static) -> Subscription<T> {
+        let callback = Rc::new(Box::new(callback) as Callback<T>);
+        let mut subscribers = self.subscribers.borrow_mut();
+        // Sweep before growing, so the list stays within twice the live
+        // subscribers however rarely events are emitted.
+        if subscribers.len() == subscribers.capacity() && self.emitting.get() == 0 {
+            subscribers.retain(|weak| weak.strong_count() != 0);
+        }
+        subscribers.push(Rc::downgrade(&callback));
+        Subscription { callback }
+    }
+
+    /// Call every live subscriber with `event`, returning how many were
+    /// called.
+    pub fn emit(&self, event: &T) -> usize {
+        let end = self.subscribers.borrow().len();
+        self.emitting.set(self.emitting.get() + 1);
+        let _emitting = guard(&self.emitting, |emitting| {
+            emitting.set(emitting.get() - 1);
+            if emitting.get() == 0 {
+                self.prune();
+            }
+        });
+        let mut called = 0;
+        for index in 0..end {
+            // The borrow ends before the call, leaving the callback free to
+            // subscribe.
+            let Some(callback) = self.subscribers.borrow()[index].upgrade() else {
+                continue;
+            };
+            callback(event);
+            called += 1;
+        }
+        called
+    }
+
+    /// The number of live subscribers.
+    pub fn len(&self) -> usize {
+        let subscribers = self.subscribers.borrow();
+        subscribers
+            .iter()
+            .filter(|weak| weak.strong_count() != 0)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the entries of subscriptions that are gone.
+    fn prune(&self) {
+        if let Some(mut subscribers) = self.subscribers.try_borrow_mut() {
+            subscribers.retain(|weak| weak.strong_count() != 0);
+        }
+    }
+}
+
+impl<T> Default for Subject<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Subject<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subject")
+            .field("subscribers", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Keeps a callback subscribed to a [`Subject`], until dropped.
+///
+/// This type can be constructed through [`Subject::subscribe`].
+pub struct Subscription<T> {
+    callback: Rc<Callback<T>>,
+}
+
+impl<T> Subscription<T> {
+    /// Stop the callback, the same as dropping the subscription.
+    pub fn unsubscribe(self) {}
+}
+
+impl<T> fmt::Debug for Subscription<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("callback", &(&**self.callback as *const dyn Fn(&T)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reentrant_emit_subscribe_and_unsubscribe() {
+        let subject = Rc::new(Subject::new());
+        let log = Rc::new(RefCell::new(std::vec::Vec::new()));
+        let later = Rc::new(RefCell::new(None));
+        let victim = Rc::new(RefCell::new(None));
+
+        // Holding the subject from its own callback is no cycle, the
+        // subject only has a weak reference back.
+        let first = {
+            let (subject, log, later, victim) = (
+                Rc::clone(&subject),
+                Rc::clone(&log),
+                Rc::clone(&later),
+                Rc::clone(&victim),
+            );
+            Rc::clone(&subject).subscribe(move |n: &u32| {
+                log.borrow_mut().push(("first", *n));
+                if *n == 0 {
+                    let log = Rc::clone(&log);
+                    *later.borrow_mut() =
+                        Some(subject.subscribe(move |n| log.borrow_mut().push(("later", *n))));
+                    victim.borrow_mut().take();
+                    subject.emit(&1);
+                }
+            })
+        };
+        *victim.borrow_mut() = Some({
+            let log = Rc::clone(&log);
+            subject.subscribe(move |n| log.borrow_mut().push(("victim", *n)))
+        });
+
+        assert_eq!(subject.emit(&0), 1);
+        assert_eq!(*log.borrow(), [("first", 0), ("first", 1), ("later", 1)]);
+        assert_eq!(subject.len(), 2);
+        assert_eq!(subject.subscribers.borrow().len(), 2);
+
+        drop(first);
+        assert_eq!(Rc::strong_count(&subject), 1);
+        later.borrow_mut().take();
+        assert_eq!(subject.emit(&2), 0);
+        assert!(subject.subscribers.borrow().is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+//! A reference bundled with the owner it points into.
+//!
+//! A function cannot return a borrow of a local, but it can return the
+//! local along with the borrow, as long as moving the owner does not move
+//! what the borrow points at. [`StableDeref`] marks the owners for which
+//! that holds, heap allocating pointers like [`Box`], [`crate::rc::Rc`] and
+//! [`crate::Vec`].
+
+use std::{
+    fmt,
+    mem::{ManuallyDrop, MaybeUninit},
+    ops::{Deref, DerefMut},
+};
+
+/// An owner whose deref target stays put when the owner moves.
+///
+/// # Safety
+/// The address returned by [`Deref::deref`] does not change when the owner
+/// is moved, or through any method taking `&self`, for as long as the owner
+/// is not mutated through `&mut self`.
+pub unsafe trait StableDeref: Deref {}
+
+/// A [`StableDeref`] whose target can also be borrowed mutably, at the same
+/// address.
+///
+/// # Safety
+/// As for [`StableDeref`], and [`DerefMut::deref_mut`] returns the same
+/// address as [`Deref::deref`].
+pub unsafe trait StableDerefMut: StableDeref + DerefMut {}
+
+// SAFETY
+// * All of these point to a heap allocation, or a borrow, that moving the
+//   pointer leaves in place.
+unsafe impl<T: ?Sized> StableDeref for Box<T> {}
+unsafe impl<T: ?Sized> StableDerefMut for Box<T> {}
+unsafe impl<T> StableDeref for Vec<T> {}
+unsafe impl<T> StableDerefMut for Vec<T> {}
+unsafe impl<T, A: crate::alloc::Allocator> StableDeref for crate::Vec<T, A> {}
+unsafe impl<T, A: crate::alloc::Allocator> StableDerefMut for crate::Vec<T, A> {}
+unsafe impl StableDeref for String {}
+unsafe impl StableDerefMut for String {}
+unsafe impl StableDeref for crate::string::String {}
+unsafe impl StableDerefMut for crate::string::String {}
+unsafe impl<T> StableDeref for crate::rc::Rc<T> {}
+unsafe impl<T> StableDeref for crate::arc::Arc<T> {}
+unsafe impl<T: ?Sized> StableDeref for std::rc::Rc<T> {}
+unsafe impl<T: ?Sized> StableDeref for std::sync::Arc<T> {}
+unsafe impl<T: ?Sized> StableDeref for &T {}
+unsafe impl<T: ?Sized> StableDeref for &mut T {}
+unsafe impl<T: ?Sized> StableDerefMut for &mut T {}
+
+/// A [`StableDeref`] whose clones deref to the same address as the original.
+///
+/// Cloning a `Box` allocates a new target, so an [`OwningRef`] over one can
+/// not be cloned.
+///
+/// ```compile_fail
+/// use nomicon::owning_ref::OwningRef;
+///
+/// let boxed = OwningRef::new(Box::new([1u8; 4]));
+/// let _: OwningRef<_, _> = boxed.clone();
+/// ```
+///
+/// # Safety
+/// As for [`StableDeref`], and [`Clone::clone`] returns an owner of the same
+/// target.
+pub unsafe trait CloneStableDeref: StableDeref + Clone {}
+
+// SAFETY
+// * Clones of these share the one target.
+unsafe impl<T> CloneStableDeref for crate::rc::Rc<T> {}
+unsafe impl<T> CloneStableDeref for crate::arc::Arc<T> {}
+unsafe impl<T: ?Sized> CloneStableDeref for std::rc::Rc<T> {}
+unsafe impl<T: ?Sized> CloneStableDeref for std::sync::Arc<T> {}
+unsafe impl<T: ?Sized> CloneStableDeref for &T {}
+
+/// Holds an owner without asserting it is unique.
+///
+/// A `Box` moved by value is retagged as the one pointer to its target,
+/// invalidating the reference derived from it. Inside a `MaybeUninit` it is
+/// not, so the owner can move along with the reference.
+struct Owner<O>(MaybeUninit<O>);
+
+impl<O> Owner<O> {
+    const fn new(owner: O) -> Self {
+        Self(MaybeUninit::new(owner))
+    }
+
+    const fn get(&self) -> &O {
+        // SAFETY
+        // * Initialized on construction, and only dropped with the wrapper.
+        unsafe { self.0.assume_init_ref() }
+    }
+
+    const fn get_mut(&mut self) -> &mut O {
+        unsafe { self.0.assume_init_mut() }
+    }
+
+    fn into_inner(self) -> O {
+        let this = ManuallyDrop::new(self);
+        // SAFETY
+        // * The wrapper is not dropped, so the owner is read out once.
+        unsafe { this.0.assume_init_read() }
+    }
+}
+
+impl<O> Drop for Owner<O> {
+    fn drop(&mut self) {
+        unsafe { self.0.assume_init_drop() }
+    }
+}
+
+/// An owner together with a shared reference into it.
+///
+/// ```
+/// use nomicon::{owning_ref::OwningRef, rc::Rc};
+///
+/// struct Config {
+///     name: String,
+///     retries: u32,
+/// }
+///
+/// /// Hands out the name, without exposing the rest of the config.
+/// fn name(config: &Rc<Config>) -> OwningRef<Rc<Config>, str> {
+///     OwningRef::new(Rc::clone(config)).map(|config| config.name.as_str())
+/// }
+///
+/// let config = Rc::new(Config { name: String::from("primary"), retries: 3 });
+/// let name = name(&config);
+/// drop(config);
+/// assert_eq!(&*name, "primary");
+/// assert_eq!(name.owner().retries, 3);
+/// ```
+///
+/// This type can be constructed through [`OwningRef::new`].
+pub struct OwningRef<O, T: ?Sized> {
+    owner: Owner<O>,
+    /// Points into the owner's target, which does not move with it.
+    reference: *const T,
+}
+
+// SAFETY
+// * Sending the pair sends the owner and a &T.
+unsafe impl<O: Send, T: ?Sized + Sync> Send for OwningRef<O, T> {}
+unsafe impl<O: Sync, T: ?Sized + Sync> Sync for OwningRef<O, T> {}
+
+impl<O: StableDeref> OwningRef<O, O::Target> {
+    /// Returns a reference to the owner's whole target.
+    pub fn new(owner: O) -> Self {
+        // Wrapped first, as moving it later would invalidate the reference.
+        let owner = Owner::new(owner);
+        let reference = &**owner.get() as *const O::Target;
+        Self { owner, reference }
+    }
+}
+
+impl<O: StableDeref, T: ?Sized> OwningRef<O, T> {
+    /// Narrow the reference, to a field or element of what it points to.
+    pub fn map<U: ?Sized>(self, f: impl FnOnce(&T) -> &U) -> OwningRef<O, U> {
+        let reference = f(&self) as *const U;
+        OwningRef {
+            owner: self.owner,
+            reference,
+        }
+    }
+
+    /// Narrow the reference, or hand the error back.
+    pub fn try_map<U: ?Sized, E>(
+        self,
+        f: impl FnOnce(&T) -> Result<&U, E>,
+    ) -> Result<OwningRef<O, U>, E> {
+        let reference = f(&self)? as *const U;
+        Ok(OwningRef {
+            owner: self.owner,
+            reference,
+        })
+    }
+
+    pub fn owner(&self) -> &O {
+        self.owner.get()
+    }
+
+    pub fn into_owner(self) -> O {
+        self.owner.into_inner()
+    }
+}
+
+impl<O: StableDeref, T: ?Sized> Deref for OwningRef<O, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * The reference was derived from the owner's target, which is
+        //   alive and in place while the owner is, and only borrowed shared.
+        unsafe { &*self.reference }
+    }
+}
+
+impl<O: CloneStableDeref, T: ?Sized> Clone for OwningRef<O, T> {
+    /// Clones the owner, whose clone points at the same target.
+    fn clone(&self) -> Self {
+        Self {
+            owner: Owner::new(self.owner.get().clone()),
+            reference: self.reference,
+        }
+    }
+}
+
+impl<O: StableDeref, T: ?Sized + fmt::Debug> fmt::Debug for OwningRef<O, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwningRef").field(&&**self).finish()
+    }
+}
+
+/// An owner together with a mutable reference into it.
+///
+/// ```
+/// use nomicon::owning_ref::OwningRefMut;
+///
+/// let mut last = OwningRefMut::new(vec![1, 2, 3]).map(|v| v.last_mut().unwrap());
+/// *last = 30;
+/// assert_eq!(last.into_owner(), [1, 2, 30]);
+/// ```
+///
+/// This type can be constructed through [`OwningRefMut::new`].
+pub struct OwningRefMut<O, T: ?Sized> {
+    owner: Owner<O>,
+    reference: *mut T,
+}
+
+// SAFETY
+// * Sending the pair sends the owner and a &mut T.
+unsafe impl<O: Send, T: ?Sized + Send> Send for OwningRefMut<O, T> {}
+unsafe impl<O: Sync, T: ?Sized + Sync> Sync for OwningRefMut<O, T> {}
+
+impl<O: StableDerefMut> OwningRefMut<O, O::Target> {
+    pub fn new(owner: O) -> Self {
+        let mut owner = Owner::new(owner);
+        let reference = &mut **owner.get_mut() as *mut O::Target;
+        Self { owner, reference }
+    }
+}
+
+impl<O: StableDerefMut, T: ?Sized> OwningRefMut<O, T> {
+    pub fn map<U: ?Sized>(mut self, f: impl FnOnce(&mut T) -> &mut U) -> OwningRefMut<O, U> {
+        let reference = f(&mut self) as *mut U;
+        OwningRefMut {
+            owner: self.owner,
+            reference,
+        }
+    }
+
+    pub fn try_map<U: ?Sized, E>(
+        mut self,
+        f: impl FnOnce(&mut T) -> Result<&mut U, E>,
+    ) -> Result<OwningRefMut<O, U>, E> {
+        let reference = f(&mut self)? as *mut U;
+        Ok(OwningRefMut {
+            owner: self.owner,
+            reference,
+        })
+    }
+
+    /// Give up the reference for shared access.
+    pub fn into_shared(self) -> OwningRef<O, T> {
+        OwningRef {
+            owner: self.owner,
+            reference: self.reference,
+        }
+    }
+
+    /// Returns the owner, ending the borrow.
+    pub fn into_owner(self) -> O {
+        self.owner.into_inner()
+    }
+}
+
+impl<O: StableDerefMut, T: ?Sized> Deref for OwningRefMut<O, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * The owner is never touched while the reference lives, it only
+        //   comes back out through into_owner.
+        unsafe { &*self.reference }
+    }
+}
+
+impl<O: StableDerefMut, T: ?Sized> DerefMut for OwningRefMut<O, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.reference }
+    }
+}
+
+impl<O: StableDerefMut, T: ?Sized + fmt::Debug> fmt::Debug for OwningRefMut<O, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwningRefMut").field(&&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn survives_moves() {
+        let boxed = OwningRef::new(Box::new([1, 2, 3])).map(|array| &array[1]);
+        let moved = [boxed];
+        assert_eq!(*moved[0], 2);
+
+        let owned = OwningRef::new(String::from("a,b"))
+            .try_map(|s| s.split_once(',').map(|(a, _)| a).ok_or(()))
+            .unwrap();
+        let cloned = owned.owner().clone();
+        assert_eq!(&*owned, "a");
+        assert_eq!(owned.into_owner(), cloned);
+
+        let shared = OwningRef::new(crate::arc::Arc::new((1, 2))).map(|pair| &pair.1);
+        let copy = shared.clone();
+        assert_eq!(std::thread::spawn(move || *copy).join().unwrap(), 2);
+        assert_eq!(*shared, 2);
+    }
+
+    #[test]
+    fn mutable() {
+        let mut values = crate::Vec::new();
+        values.push(1);
+        // The target is the slice, the Vec cannot grow under the reference.
+        let mut first = OwningRefMut::new(values).map(|v| &mut v[0]);
+        *first += 1;
+        let first = first.into_shared();
+        assert_eq!(*first, 2);
+        assert_eq!(&**first.owner(), &[2]);
+    }
+}
@@ -0,0 +1,273 @@
+//! Projecting a pinned struct onto its fields, and [`SelfRef`], a value that
+//! has to stay pinned because it points into itself.
+//!
+//! A field is *structurally pinned* when a pinned struct only ever hands it
+//! out pinned. That is sound as long as the struct never moves the field out,
+//! is only [`Unpin`] when every pinned field is, and has no [`Drop`] impl
+//! that could move it. [`pin_project!`](crate::pin_project) checks the last
+//! two and writes the unsafe projection once, marking pinned fields with
+//! `#[pin]`.
+//!
+//! ```
+//! use std::{
+//!     future::Future,
+//!     pin::Pin,
+//!     task::{Context, Poll},
+//!     time::Instant,
+//! };
+//!
+//! nomicon::pin_project! {
+//!     #[project = TimedProjection]
+//!     /// Measures how long a future takes.
+//!     struct Timed<F> {
+//!         #[pin]
+//!         future: F,
+//!         start: Option<Instant>,
+//!     }
+//! }
+//!
+//! impl<F: Future> Future for Timed<F> {
+//!     type Output = (F::Output, std::time::Duration);
+//!
+//!     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+//!         let this = self.project();
+//!         let start = *this.start.get_or_insert_with(Instant::now);
+//!         this.future.poll(cx).map(|output| (output, start.elapsed()))
+//!     }
+//! }
+//!
+//! let timed = Timed { future: async { 1 }, start: None };
+//! let (output, _) = nomicon::executor::block_on(timed);
+//! assert_eq!(output, 1);
+//! ```
+
+use std::{
+    marker::{PhantomData, PhantomPinned},
+    pin::Pin,
+    ptr::NonNull,
+};
+
+/// Define a struct along with a projection of it, a sibling struct holding
+/// a [`Pin`](std::pin::Pin) of every `#[pin]` field and a mutable reference
+/// to every other field.
+///
+/// The projection struct is named by the leading `#[project = Name]`, and
+/// returned by a private `project(self: Pin<&mut Self>)` method. The struct
+/// is [`Unpin`] exactly when its pinned fields are, and implementing
+/// [`Drop`] for it is a compile error, see [`crate::pin`].
+///
+/// Generics take at most one bound each, and where clauses, lifetimes and
+/// field attributes other than `#[pin]` are not supported.
+///
+/// ```compile_fail
+/// nomicon::pin_project! {
+///     #[project = Projection]
+///     struct Moves<T> {
+///         #[pin]
+///         field: T,
+///     }
+/// }
+///
+/// // Could move the field out of a pinned struct.
+/// impl<T> Drop for Moves<T> {
+///     fn drop(&mut self) {}
+/// }
+/// ```
+#[macro_export]
+macro_rules! pin_project {
+    (
+        #[project = $projection:ident]
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident $(<$($generic:ident $(: $bound:path)?),* $(,)?>)? {
+            $(
+                $(#[$pin:ident])?
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name $(<$($generic $(: $bound)?),*>)? {
+            $($field_vis $field: $ty),*
+        }
+
+        #[allow(dead_code)]
+        struct $projection<'__pin $($(, $generic $(: $bound)?)*)?> {
+            $($field: $crate::pin_project!(@projected $($pin)?; '__pin; $ty)),*
+        }
+
+        impl $(<$($generic $(: $bound)?),*>)? $name $(<$($generic),*>)? {
+            #[allow(dead_code)]
+            fn project<'__pin>(
+                self: ::core::pin::Pin<&'__pin mut Self>,
+            ) -> $projection<'__pin $($(, $generic)*)?> {
+                // SAFETY
+                // * Pinned fields are only handed out pinned, the Unpin and
+                //   Drop checks below keep them from being moved otherwise.
+                unsafe {
+                    let Self { $($field),* } = self.get_unchecked_mut();
+                    $projection {
+                        $($field: $crate::pin_project!(@project $($pin)?; $field)),*
+                    }
+                }
+            }
+        }
+
+        const _: () = {
+            // Only pinned fields decide whether the struct is Unpin, the
+            // lifetime keeps the bound from being trivially true.
+            #[allow(dead_code)]
+            struct __Unpin<'__pin $($(, $generic $(: $bound)?)*)?> {
+                __lifetime: ::core::marker::PhantomData<&'__pin ()>,
+                $($field: $crate::pin_project!(@unpin $($pin)?; $ty)),*
+            }
+            impl<'__pin $($(, $generic $(: $bound)?)*)?> ::core::marker::Unpin
+                for $name $(<$($generic),*>)?
+            where
+                __Unpin<'__pin $($(, $generic)*)?>: ::core::marker::Unpin,
+            {
+            }
+
+            // Conflicts with the blanket impl if the struct implements Drop.
+            #[allow(dead_code)]
+            trait MustNotImplDrop {}
+            #[allow(drop_bounds)]
+            impl<T: ::core::ops::Drop> MustNotImplDrop for T {}
+            impl $(<$($generic $(: $bound)?),*>)? MustNotImplDrop for $name $(<$($generic),*>)? {}
+        };
+    };
+
+    (@projected pin; $lifetime:lifetime; $ty:ty) => {
+        ::core::pin::Pin<&$lifetime mut $ty>
+    };
+    (@projected; $lifetime:lifetime; $ty:ty) => {
+        &$lifetime mut $ty
+    };
+    (@project pin; $field:ident) => {
+        ::core::pin::Pin::new_unchecked($field)
+    };
+    (@project; $field:ident) => {
+        $field
+    };
+    (@unpin pin; $ty:ty) => {
+        $ty
+    };
+    (@unpin; $ty:ty) => {
+        $crate::pin::AlwaysUnpin<$ty>
+    };
+}
+
+/// Stands in for a field that is not pinned, when deciding whether a
+/// [`pin_project!`](crate::pin_project) struct is [`Unpin`].
+#[doc(hidden)]
+pub struct AlwaysUnpin<T: ?Sized>(PhantomData<fn() -> PhantomData<T>>);
+
+/// A value together with a reference into itself, the reason [`Pin`]
+/// exists.
+///
+/// The view may point into the value's own bytes, not only into memory it
+/// owns, so the value must never move once the view is taken. It is built
+/// pinned in its allocation and only ever handed out pinned.
+///
+/// ```
+/// use nomicon::pin::SelfRef;
+///
+/// let words = SelfRef::new([b'h', b'e', b'l', b'l', b'o'], |bytes| &bytes[1..4]);
+/// // Moving the box moves the pointer, not the array the view points into.
+/// let moved = words;
+/// assert_eq!(moved.as_ref().view(), b"ell");
+/// assert_eq!(moved.as_ref().value(), b"hello");
+/// ```
+///
+/// This type can be constructed through [`SelfRef::new`].
+pub struct SelfRef<T, U: ?Sized> {
+    value: T,
+    /// Points into `value`, set right after it reached its final place.
+    view: Option<NonNull<U>>,
+    _pinned: PhantomPinned,
+}
+
+// SAFETY
+// * The view is a shared borrow of the value, sending the pair sends the
+//   value and a &U along with it.
+unsafe impl<T: Send, U: ?Sized + Sync> Send for SelfRef<T, U> {}
+unsafe impl<T: Sync, U: ?Sized + Sync> Sync for SelfRef<T, U> {}
+
+impl<T, U: ?Sized> SelfRef<T, U> {
+    /// Move `value` into a pinned allocation and take `view` of it there.
+    pub fn new(value: T, view: impl FnOnce(&T) -> &U) -> Pin<Box<Self>> {
+        let mut boxed = Box::pin(Self {
+            value,
+            view: None,
+            _pinned: PhantomPinned,
+        });
+        let view = NonNull::from(view(&boxed.value));
+        // SAFETY
+        // * Only the view is written, nothing is moved out of the pin.
+        unsafe { boxed.as_mut().get_unchecked_mut() }.view = Some(view);
+        boxed
+    }
+
+    pub fn value(self: Pin<&Self>) -> &T {
+        &self.get_ref().value
+    }
+
+    pub fn view(self: Pin<&Self>) -> &U {
+        let this = self.get_ref();
+        let view = this.view.expect("view is set on construction");
+        // SAFETY
+        // * The view borrows the value, which is pinned and only ever
+        //   borrowed shared, so it is still valid for as long as self is.
+        unsafe { view.as_ref() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::marker::PhantomPinned;
+
+    use super::*;
+
+    crate::pin_project! {
+        #[project = PairProjection]
+        struct Pair<T, U> {
+            #[pin]
+            pinned: T,
+            unpinned: U,
+        }
+    }
+
+    #[test]
+    fn projects_fields() {
+        let mut pair = Box::pin(Pair {
+            pinned: PhantomPinned,
+            unpinned: 1,
+        });
+        let projection = pair.as_mut().project();
+        let _: Pin<&mut PhantomPinned> = projection.pinned;
+        *projection.unpinned += 1;
+        assert_eq!(pair.unpinned, 2);
+
+        fn assert_unpin<T: Unpin>() {}
+        assert_unpin::<Pair<(), PhantomPinned>>();
+    }
+
+    #[test]
+    fn self_ref_views() {
+        let flag = std::rc::Rc::new(());
+        let line = SelfRef::new(
+            (std::rc::Rc::clone(&flag), String::from("key=value")),
+            |(_, line)| line.split_once('=').unwrap().1,
+        );
+        let mut lines = vec![line];
+        // The vector may reallocate, moving the boxes but not their contents.
+        lines.extend((0..16).map(|n| {
+            SelfRef::new((std::rc::Rc::clone(&flag), n.to_string()), |(_, s)| {
+                s.as_str()
+            })
+        }));
+        assert_eq!(lines[0].as_ref().view(), "value");
+        assert_eq!(lines[16].as_ref().view(), "15");
+        drop(lines);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+}
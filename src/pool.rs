@@ -0,0 +1,307 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+use crate::cell::RefCell;
+
+/// Storage for a fixed number of values, which slot is in use is tracked by
+/// the pool owning it.
+struct Slots<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Slots<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+        }
+    }
+
+    /// Every slot starts out free, handed out from the front.
+    fn free_list(&self) -> Vec<usize> {
+        (0..self.slots.len()).rev().collect()
+    }
+
+    /// # Safety
+    /// `index` must be a free slot, taken off the free list by the caller.
+    unsafe fn write(&self, index: usize, value: T) {
+        unsafe { (*self.slots[index].get()).write(value) };
+    }
+
+    /// # Safety
+    /// `index` must be an occupied slot owned by the caller.
+    unsafe fn get(&self, index: usize) -> *mut T {
+        // No reference to the slot is made, it would invalidate those the
+        // owner already holds to the value.
+        self.slots[index].get().cast::<T>()
+    }
+}
+
+/// A fixed number of slots, handing out [`PoolBox`]es that give their slot
+/// back when dropped.
+///
+/// Every slot is allocated up front, so once a pool is created moving values
+/// in and out of it never touches the allocator.
+///
+/// ```
+/// use nomicon::pool::Pool;
+///
+/// let pool = Pool::new(2);
+/// let a = pool.alloc([0u8; 64]).unwrap();
+/// let b = pool.alloc([1u8; 64]).unwrap();
+/// assert_eq!(pool.available(), 0);
+/// // The pool is exhausted, the value is handed back.
+/// assert_eq!(pool.alloc([2u8; 64]).err(), Some([2u8; 64]));
+///
+/// drop(a);
+/// assert!(pool.alloc([3u8; 64]).is_ok());
+/// # drop(b);
+/// ```
+pub struct Pool<T> {
+    slots: Slots<T>,
+    free: RefCell<Vec<usize>>,
+}
+
+impl<T> Pool<T> {
+    /// Returns a pool with room for `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        let slots = Slots::new(capacity);
+        Self {
+            free: RefCell::new(slots.free_list()),
+            slots,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.slots.len()
+    }
+
+    /// Returns the number of free slots.
+    pub fn available(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Move `value` into a free slot, or hand it back if every slot is taken.
+    pub fn alloc(&self, value: T) -> Result<PoolBox<'_, T>, T> {
+        let Some(index) = self.free.borrow_mut().pop() else {
+            return Err(value);
+        };
+        // SAFETY
+        // * The slot was just taken off the free list.
+        unsafe { self.slots.write(index, value) };
+        Ok(PoolBox { pool: self, index })
+    }
+
+    fn release(&self, index: usize) {
+        self.free.borrow_mut().push(index);
+    }
+}
+
+/// A value living in a slot of a [`Pool`].
+///
+/// This type can be constructed through [`Pool::alloc`].
+pub struct PoolBox<'a, T> {
+    pool: &'a Pool<T>,
+    index: usize,
+}
+
+impl<T> PoolBox<'_, T> {
+    /// Move the value out, giving the slot back to the pool.
+    pub fn into_inner(this: Self) -> T {
+        let this = std::mem::ManuallyDrop::new(this);
+        let value = unsafe { this.pool.slots.get(this.index).read() };
+        this.pool.release(this.index);
+        value
+    }
+}
+
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.pool.slots.get(self.index) }
+    }
+}
+
+impl<T> DerefMut for PoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.pool.slots.get(self.index) }
+    }
+}
+
+impl<T> Drop for PoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY
+            // * The box owns the slot, and it is only freed after the value
+            //   is dropped.
+            self.pool.slots.get(self.index).drop_in_place();
+        }
+        self.pool.release(self.index);
+    }
+}
+
+/// A [`Pool`] that can be shared between threads.
+///
+/// The free list is guarded by a [`Mutex`], which is only held while a slot
+/// is taken or given back.
+///
+/// ```
+/// use nomicon::pool::SyncPool;
+///
+/// let pool = SyncPool::new(4);
+/// std::thread::scope(|s| {
+///     for n in 0..4 {
+///         let pool = &pool;
+///         s.spawn(move || {
+///             let mut value = pool.alloc(n).unwrap();
+///             *value += 1;
+///         });
+///     }
+/// });
+/// assert_eq!(pool.available(), 4);
+/// ```
+pub struct SyncPool<T> {
+    slots: Slots<T>,
+    free: Mutex<Vec<usize>>,
+}
+
+// The pool hands values between the threads allocating and dropping them, it
+// never shares a value itself.
+unsafe impl<T: Send> Send for SyncPool<T> {}
+unsafe impl<T: Send> Sync for SyncPool<T> {}
+
+impl<T> SyncPool<T> {
+    /// Returns a pool with room for `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        let slots = Slots::new(capacity);
+        Self {
+            free: Mutex::new(slots.free_list()),
+            slots,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.slots.len()
+    }
+
+    /// Returns the number of free slots.
+    pub fn available(&self) -> usize {
+        self.free_list().len()
+    }
+
+    /// Move `value` into a free slot, or hand it back if every slot is taken.
+    pub fn alloc(&self, value: T) -> Result<SyncPoolBox<'_, T>, T> {
+        let Some(index) = self.free_list().pop() else {
+            return Err(value);
+        };
+        // SAFETY
+        // * The slot was just taken off the free list, no other thread can
+        //   reach it.
+        unsafe { self.slots.write(index, value) };
+        Ok(SyncPoolBox {
+            pool: self,
+            index,
+            _marker: PhantomData,
+        })
+    }
+
+    fn free_list(&self) -> std::sync::MutexGuard<'_, Vec<usize>> {
+        // The free list is never left in an inconsistent state, so a panic
+        // while holding the lock is harmless.
+        self.free.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn release(&self, index: usize) {
+        self.free_list().push(index);
+    }
+}
+
+/// A value living in a slot of a [`SyncPool`].
+///
+/// This type can be constructed through [`SyncPool::alloc`].
+pub struct SyncPoolBox<'a, T> {
+    pool: &'a SyncPool<T>,
+    index: usize,
+    /// Only share the box between threads if the value can be shared.
+    _marker: PhantomData<T>,
+}
+
+impl<T> SyncPoolBox<'_, T> {
+    /// Move the value out, giving the slot back to the pool.
+    pub fn into_inner(this: Self) -> T {
+        let this = std::mem::ManuallyDrop::new(this);
+        let value = unsafe { this.pool.slots.get(this.index).read() };
+        this.pool.release(this.index);
+        value
+    }
+}
+
+impl<T> Deref for SyncPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.pool.slots.get(self.index) }
+    }
+}
+
+impl<T> DerefMut for SyncPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.pool.slots.get(self.index) }
+    }
+}
+
+impl<T> Drop for SyncPoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { self.pool.slots.get(self.index).drop_in_place() };
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuses_slots() {
+        let flag = std::rc::Rc::new(());
+        let pool = Pool::new(3);
+        let boxes = (0..3)
+            .map(|_| pool.alloc(std::rc::Rc::clone(&flag)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(pool.alloc(std::rc::Rc::clone(&flag)).is_err());
+        assert_eq!(std::rc::Rc::strong_count(&flag), 4);
+
+        drop(boxes);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+        assert_eq!(pool.available(), 3);
+
+        let inner = PoolBox::into_inner(pool.alloc(std::rc::Rc::clone(&flag)).unwrap());
+        assert_eq!(pool.available(), 3);
+        assert_eq!(std::rc::Rc::strong_count(&inner), 2);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let pool = SyncPool::new(8);
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for n in 0..1_000 {
+                        if let Ok(mut value) = pool.alloc(vec![n]) {
+                            value.push(n);
+                            assert_eq!(*value, [n, n]);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.available(), 8);
+    }
+}
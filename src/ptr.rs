@@ -0,0 +1,138 @@
+//! Raw pointers carrying ownership.
+
+use std::{fmt, marker::PhantomData, ptr::NonNull};
+
+/// A non-null pointer that owns what it points to.
+///
+/// Unlike [`NonNull`] it tells the compiler about the ownership: it is
+/// [`Send`] and [`Sync`] exactly when `T` is, and dropping a type holding
+/// one may drop a `T`. Like [`NonNull`] it is covariant and never null, so
+/// `Option<Unique<T>>` is the size of a pointer. Nothing is freed when it
+/// is dropped, that is up to the owner.
+///
+/// ```
+/// use nomicon::ptr::Unique;
+///
+/// let unique = Unique::from(Box::new(5));
+/// assert_eq!(unsafe { *unique.as_ref() }, 5);
+/// drop(unsafe { Box::from_raw(unique.as_ptr()) });
+///
+/// assert_eq!(size_of::<Option<Unique<u8>>>(), size_of::<*mut u8>());
+/// ```
+pub struct Unique<T: ?Sized> {
+    ptr: NonNull<T>,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY
+// * The pointer is the only way to reach the T, sending or sharing it is
+//   sending or sharing the T.
+unsafe impl<T: ?Sized + Send> Send for Unique<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Unique<T> {}
+
+impl<T> Unique<T> {
+    /// Returns a dangling, well aligned pointer, for empty allocations.
+    pub const fn dangling() -> Self {
+        Self::from_non_null(NonNull::dangling())
+    }
+}
+
+impl<T: ?Sized> Unique<T> {
+    /// Returns `None` if `ptr` is null.
+    pub fn new(ptr: *mut T) -> Option<Self> {
+        NonNull::new(ptr).map(Self::from_non_null)
+    }
+
+    /// # Safety
+    /// `ptr` is not null.
+    pub const unsafe fn new_unchecked(ptr: *mut T) -> Self {
+        Self::from_non_null(unsafe { NonNull::new_unchecked(ptr) })
+    }
+
+    pub const fn from_non_null(ptr: NonNull<T>) -> Self {
+        Self {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn as_ptr(self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    pub const fn as_non_null(self) -> NonNull<T> {
+        self.ptr
+    }
+
+    /// # Safety
+    /// The pointer is valid for reads, and nothing mutates the value while
+    /// the reference lives.
+    pub const unsafe fn as_ref<'a>(&self) -> &'a T {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// # Safety
+    /// The pointer is valid for writes, and nothing else reaches the value
+    /// while the reference lives.
+    pub const unsafe fn as_mut<'a>(&mut self) -> &'a mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+
+    pub const fn cast<U>(self) -> Unique<U> {
+        Unique::from_non_null(self.ptr.cast())
+    }
+}
+
+impl<T: ?Sized> Clone for Unique<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Unique<T> {}
+
+impl<T: ?Sized> fmt::Debug for Unique<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Pointer::fmt(&self.ptr, f)
+    }
+}
+
+impl<T: ?Sized> fmt::Pointer for Unique<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Pointer::fmt(&self.ptr, f)
+    }
+}
+
+impl<T: ?Sized> From<Box<T>> for Unique<T> {
+    /// Takes the allocation over, free it through [`Box::from_raw`].
+    fn from(boxed: Box<T>) -> Self {
+        // SAFETY
+        // * Box never holds a null pointer.
+        unsafe { Self::new_unchecked(Box::into_raw(boxed)) }
+    }
+}
+
+impl<T: ?Sized> From<&mut T> for Unique<T> {
+    fn from(value: &mut T) -> Self {
+        Self::from_non_null(NonNull::from(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn propagates_auto_traits() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Unique<String>>();
+        assert_send_sync::<Unique<[u8]>>();
+        assert_send_sync::<crate::Vec<u8>>();
+
+        let mut value = 1;
+        let mut unique = Unique::from(&mut value);
+        unsafe { *unique.as_mut() += 1 };
+        assert_eq!(value, 2);
+        assert!(Unique::new(std::ptr::null_mut::<u8>()).is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use std::alloc::Layout;
+
+use crate::{
+    alloc::{Allocator, Global, TryReserveError},
+    ptr::Unique,
+};
+
+/// The allocation backing a [`crate::Vec`].
+///
+/// Only tracks the pointer and capacity, it is up to the owner to keep track
+/// of which slots are initialized.
+pub(crate) struct RawVec<T, A: Allocator = Global> {
+    pub(crate) ptr: Unique<T>,
+    pub(crate) cap: usize,
+    pub(crate) alloc: A,
+}
+
+impl<T> RawVec<T> {
+    pub(crate) const fn new() -> Self {
+        Self::new_in(Global)
+    }
+
+    pub(crate) fn with_capacity(cap: usize) -> Self {
+        Self::with_capacity_in(cap, Global)
+    }
+}
+
+impl<T, A: Allocator> RawVec<T, A> {
+    pub(crate) const fn new_in(alloc: A) -> Self {
+        const {
+            assert!(
+                std::mem::size_of::<T>() != 0,
+                "Zero sized types are not supported"
+            )
+        }
+        Self {
+            ptr: Unique::dangling(),
+            cap: 0,
+            alloc,
+        }
+    }
+
+    pub(crate) fn with_capacity_in(cap: usize, alloc: A) -> Self {
+        Self::try_with_capacity_in(cap, alloc).unwrap_or_else(|e| e.handle())
+    }
+
+    pub(crate) fn try_with_capacity_in(cap: usize, alloc: A) -> Result<Self, TryReserveError> {
+        let mut buf = Self::new_in(alloc);
+        if cap != 0 {
+            buf.try_grow_to(cap)?;
+        }
+        Ok(buf)
+    }
+
+    /// Double the capacity.
+    pub(crate) fn try_grow(&mut self) -> Result<(), TryReserveError> {
+        let new_cap = if self.cap == 0 {
+            1
+        } else {
+            self.cap
+                .checked_mul(2)
+                .ok_or(TryReserveError::CapacityOverflow)?
+        };
+        self.try_grow_to(new_cap)
+    }
+
+    /// Reallocate to hold exactly `new_cap` elements, leaving the buffer
+    /// untouched on failure.
+    pub(crate) fn try_grow_to(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        debug_assert!(new_cap > self.cap);
+        // Layout::array rejects sizes above isize::MAX.
+        let new_layout =
+            Layout::array::<T>(new_cap).map_err(|_| TryReserveError::CapacityOverflow)?;
+
+        let ptr = if self.cap == 0 {
+            self.alloc.allocate(new_layout)
+        } else {
+            let old_layout = Layout::array::<T>(self.cap).unwrap();
+            unsafe {
+                self.alloc
+                    .grow(self.ptr.cast().as_non_null(), old_layout, new_layout)
+            }
+        };
+        self.ptr = Unique::from_non_null(
+            ptr.map_err(|_| TryReserveError::AllocError { layout: new_layout })?
+                .cast(),
+        );
+        self.cap = new_cap;
+        Ok(())
+    }
+}
+
+impl<T, A: Allocator> Drop for RawVec<T, A> {
+    fn drop(&mut self) {
+        if self.cap != 0 {
+            unsafe {
+                // SAFETY
+                // self.cap is not zero, so we have allocated
+                // self.cap is updated alongside the side of our allocation.
+                let layout = Layout::array::<T>(self.cap).unwrap();
+                self.alloc.deallocate(self.ptr.cast().as_non_null(), layout)
+            }
+        }
+    }
+}
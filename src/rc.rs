@@ -1,19 +1,36 @@
-use std::ptr::NonNull;
+use std::{
+    alloc::Layout,
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+    mem::ManuallyDrop,
+    ptr::NonNull,
+};
 
-use crate::cell::Cell;
+use crate::{
+    alloc::{AllocError, Allocator, Global},
+    cell::Cell,
+    marker::{PhantomUnsend, PhantomUnsync},
+};
 
 #[derive(Debug)]
+#[repr(C)]
 struct RcInner<T> {
-    value: T,
+    /// Dropped once the last [`Rc`] is dropped, even if [`Weak`]s remain.
+    value: ManuallyDrop<T>,
     refcount: Cell<usize>,
+    /// The number of [`Weak`]s, plus one shared by all of the [`Rc`]s.
+    ///
+    /// The allocation is freed once this reaches zero.
+    weak: Cell<usize>,
 }
 
 impl<T> RcInner<T> {
     /// Returns [`Self`] with refcount set to 1.
     const fn new(value: T) -> Self {
         Self {
-            value,
+            value: ManuallyDrop::new(value),
             refcount: Cell::new(1),
+            weak: Cell::new(1),
         }
     }
 
@@ -39,20 +56,174 @@ impl<T> RcInner<T> {
     const fn count(&self) -> usize {
         self.refcount.get()
     }
+
+    const fn increment_weak(&self) {
+        match self.weak.get().checked_add(1) {
+            Some(count) => self.weak.set(count),
+            None => panic!("Weak count overflown"),
+        }
+    }
+
+    /// Returns the weak count after decrementing.
+    const fn decrement_weak(&self) -> usize {
+        let new = match self.weak.get().checked_sub(1) {
+            Some(count) => count,
+            None => panic!("Weak count overflown"),
+        };
+        self.weak.set(new);
+        new
+    }
 }
 
+/// Give up a weak reference, freeing the allocation if it was the last.
+///
+/// # Safety
+/// `inner` must be a live allocation from [`Rc::new_in`] with `alloc`, and the
+/// caller must own the weak reference (the implicit one, or a [`Weak`]) it is
+/// giving up.
+unsafe fn release_weak<T>(inner: NonNull<RcInner<T>>, alloc: &impl Allocator) {
+    if unsafe { inner.as_ref() }.decrement_weak() == 0 {
+        // The value is in a ManuallyDrop and the counts need no drop.
+        unsafe { alloc.deallocate(inner.cast(), Layout::new::<RcInner<T>>()) };
+    }
+}
+
+/// The count is not atomic, so handles can not be sent or shared across
+/// threads.
+///
+/// ```
+/// use nomicon::{alloc::Bump, rc::Rc};
+///
+/// let bump = Bump::new();
+/// let rc = Rc::new_in(5, &bump);
+/// assert_eq!(*Rc::clone(&rc), 5);
+/// ```
 #[derive(Debug)]
-pub struct Rc<T> {
+pub struct Rc<T, A: Allocator = Global> {
     inner: NonNull<RcInner<T>>,
+    alloc: A,
+    _not_send: PhantomUnsend,
+    _not_sync: PhantomUnsync,
 }
 
 impl<T> Rc<T> {
     pub fn new(value: T) -> Self {
-        let inner = unsafe {
-            let b = Box::new(RcInner::new(value));
-            NonNull::new_unchecked(Box::into_raw(b))
-        };
-        Self { inner }
+        Self::new_in(value, Global)
+    }
+
+    /// Returns an error instead of aborting if the allocation fails.
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        Self::try_new_in(value, Global)
+    }
+
+    /// Consume the handle, returning a pointer to the value that keeps its
+    /// count. The handle can be rebuilt through [`Rc::from_raw`].
+    pub fn into_raw(this: Self) -> *const T {
+        let this = ManuallyDrop::new(this);
+        // The value is the first field of the repr(C) inner.
+        this.inner.as_ptr().cast_const().cast()
+    }
+
+    /// Rebuild a handle from a pointer returned by [`Rc::into_raw`].
+    ///
+    /// # Safety
+    /// `ptr` came from [`Rc::into_raw`] of the same `T`, and every pointer
+    /// is rebuilt at most once.
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        let inner = unsafe { NonNull::new_unchecked(ptr.cast_mut().cast()) };
+        Self::from_inner(inner, Global)
+    }
+}
+
+impl<T, A: Allocator> Rc<T, A> {
+    /// Panic if the counts could not belong to a live handle.
+    #[cfg(feature = "debug-invariants")]
+    pub fn assert_invariants(&self) {
+        let inner = unsafe { self.inner.as_ref() };
+        assert!(inner.count() != 0, "Rc alive with a strong count of zero");
+        assert!(
+            inner.weak.get() != 0,
+            "Rc alive without the weak count shared by the strong handles"
+        );
+    }
+
+    /// Runs [`Rc::assert_invariants`] in debug builds with the
+    /// `debug-invariants` feature.
+    fn check_invariants(&self) {
+        #[cfg(all(feature = "debug-invariants", debug_assertions))]
+        self.assert_invariants();
+    }
+
+    /// Take over a strong count already held on `inner`, allocated by
+    /// `alloc`.
+    const fn from_inner(inner: NonNull<RcInner<T>>, alloc: A) -> Self {
+        Self {
+            inner,
+            alloc,
+            _not_send: PhantomUnsend::new(),
+            _not_sync: PhantomUnsync::new(),
+        }
+    }
+
+    /// Move `value` into memory from `alloc`.
+    pub fn new_in(value: T, alloc: A) -> Self {
+        match Self::try_new_in(value, alloc) {
+            Ok(rc) => rc,
+            Err(AllocError) => std::alloc::handle_alloc_error(Layout::new::<RcInner<T>>()),
+        }
+    }
+
+    /// Returns an error instead of aborting if the allocation fails.
+    pub fn try_new_in(value: T, alloc: A) -> Result<Self, AllocError> {
+        let inner = alloc
+            .allocate(Layout::new::<RcInner<T>>())?
+            .cast::<RcInner<T>>();
+        unsafe { inner.as_ptr().write(RcInner::new(value)) };
+        Ok(Self::from_inner(inner, alloc))
+    }
+
+    pub const fn allocator(this: &Self) -> &A {
+        &this.alloc
+    }
+
+    /// Returns a [`Weak`] pointer to the value, which does not keep the value
+    /// alive.
+    ///
+    /// ```
+    /// use nomicon::rc::Rc;
+    ///
+    /// let rc = Rc::new(5);
+    /// let weak = Rc::downgrade(&rc);
+    /// assert_eq!(weak.upgrade().as_deref(), Some(&5));
+    ///
+    /// std::mem::drop(rc);
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    pub fn downgrade(this: &Self) -> Weak<T, A>
+    where
+        A: Clone,
+    {
+        unsafe { this.inner.as_ref().increment_weak() };
+        Weak {
+            inner: Some(this.inner),
+            alloc: this.alloc.clone(),
+        }
+    }
+
+    /// Returns the number of [`Rc`]s pointing to the value.
+    pub const fn strong_count(this: &Self) -> usize {
+        this.count()
+    }
+
+    /// Returns the number of [`Weak`]s pointing to the value.
+    pub const fn weak_count(this: &Self) -> usize {
+        // Do not count the weak reference shared by the Rc's.
+        unsafe { this.inner.as_ref().weak.get() - 1 }
+    }
+
+    /// Returns true if both [`Rc`]s point to the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.inner == other.inner
     }
 
     const fn increment(&self) {
@@ -68,14 +239,15 @@ impl<T> Rc<T> {
     }
 }
 
-impl<T> Clone for Rc<T> {
+impl<T, A: Allocator + Clone> Clone for Rc<T, A> {
     fn clone(&self) -> Self {
+        self.check_invariants();
         self.increment();
-        Self { inner: self.inner }
+        Self::from_inner(self.inner, self.alloc.clone())
     }
 }
 
-impl<T> std::ops::Deref for Rc<T> {
+impl<T, A: Allocator> std::ops::Deref for Rc<T, A> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -83,19 +255,153 @@ impl<T> std::ops::Deref for Rc<T> {
     }
 }
 
-impl<T> Drop for Rc<T> {
+impl<T, A: Allocator> Drop for Rc<T, A> {
     fn drop(&mut self) {
         self.decrement();
         if self.count() == 0 {
-            std::mem::drop(unsafe { Box::from_raw(self.inner.as_ptr()) });
+            unsafe {
+                // SAFETY
+                // * This was the last Rc, so no one else can reach the value.
+                // * The value is never touched again, Weak::upgrade checks the
+                //   count before handing out an Rc.
+                ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value);
+                release_weak(self.inner, &self.alloc);
+            }
         }
     }
 }
 
+impl<T, A: Allocator> AsRef<T> for Rc<T, A> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+/// Hashes and compares like the value, so the value can look up a key.
+impl<T, A: Allocator> Borrow<T> for Rc<T, A> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: PartialEq, A: Allocator> PartialEq for Rc<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for Rc<T, A> {}
+
+impl<T: PartialOrd, A: Allocator> PartialOrd for Rc<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, A: Allocator> Ord for Rc<T, A> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, A: Allocator> Hash for Rc<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+/// A non-owning pointer to the value of an [`Rc`].
+///
+/// This type can be constructed through [`Rc::downgrade`] and [`Weak::new`].
+#[derive(Debug)]
+pub struct Weak<T, A: Allocator = Global> {
+    /// None for a [`Weak`] that has never pointed at a value.
+    inner: Option<NonNull<RcInner<T>>>,
+    alloc: A,
+}
+
+impl<T> Weak<T> {
+    /// Returns a [`Weak`] that never upgrades.
+    pub const fn new() -> Self {
+        Self {
+            inner: None,
+            alloc: Global,
+        }
+    }
+}
+
+impl<T, A: Allocator> Weak<T, A> {
+    /// Returns an [`Rc`] to the value if it has not been dropped yet.
+    pub fn upgrade(&self) -> Option<Rc<T, A>>
+    where
+        A: Clone,
+    {
+        let inner = self.inner?;
+        if unsafe { inner.as_ref() }.count() == 0 {
+            return None;
+        }
+        let rc = Rc::from_inner(inner, self.alloc.clone());
+        rc.increment();
+        Some(rc)
+    }
+
+    /// Returns the number of [`Rc`]s pointing to the value.
+    pub fn strong_count(&self) -> usize {
+        self.inner
+            .map_or(0, |inner| unsafe { inner.as_ref() }.count())
+    }
+
+    /// Returns true if both [`Weak`]s point to the same allocation.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<T> Default for Weak<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, A: Allocator + Clone> Clone for Weak<T, A> {
+    fn clone(&self) -> Self {
+        if let Some(inner) = self.inner {
+            unsafe { inner.as_ref() }.increment_weak();
+        }
+        Self {
+            inner: self.inner,
+            alloc: self.alloc.clone(),
+        }
+    }
+}
+
+impl<T, A: Allocator> Drop for Weak<T, A> {
+    fn drop(&mut self) {
+        let Some(inner) = self.inner else {
+            return;
+        };
+        // SAFETY
+        // * While any Rc is alive the implicit weak reference keeps this from
+        //   freeing the allocation, otherwise the value is already dropped.
+        unsafe { release_weak(inner, &self.alloc) };
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn values_look_up_keys() {
+        let set = ["a", "b"]
+            .map(|s| Rc::new(String::from(s)))
+            .into_iter()
+            .collect::<std::collections::HashSet<_>>();
+        assert!(set.contains(&String::from("a")));
+        assert!(Rc::new(1) < Rc::new(2));
+        assert_eq!(AsRef::<i32>::as_ref(&Rc::new(3)), &3);
+    }
+
     #[test]
     fn counts() {
         let r = Rc::new(crate::Vec::<String>::new());
@@ -114,4 +420,64 @@ mod test {
         let _rs = (0..exp).map(|_| Rc::clone(&r)).collect::<Vec<_>>();
         assert_eq!(r.count(), exp + 1)
     }
+
+    #[test]
+    fn try_new() {
+        let r = Rc::try_new(String::from("fallible")).unwrap();
+        let weak = Rc::downgrade(&r);
+        assert_eq!(*r, "fallible");
+        std::mem::drop(r);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let r = Rc::new(String::from("raw"));
+        let weak = Rc::downgrade(&r);
+        let ptr = Rc::into_raw(Rc::clone(&r));
+        assert_eq!(unsafe { &*ptr }, "raw");
+        let rebuilt = unsafe { Rc::from_raw(ptr) };
+        assert_eq!(Rc::strong_count(&r), 2);
+        drop((r, rebuilt));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn allocates_from_the_arena() {
+        let bump = crate::alloc::Bump::new();
+        let r = Rc::new_in(String::from("bump"), &bump);
+        let weak = Rc::downgrade(&r);
+        assert_eq!(bump.allocated_bytes(), 4096);
+        assert!(std::ptr::eq(*Rc::allocator(&r), &bump));
+        assert!(Rc::ptr_eq(&weak.upgrade().unwrap(), &r));
+        drop(r);
+        assert!(weak.upgrade().is_none());
+
+        let tracking = crate::alloc::TrackingAlloc::system();
+        let r = Rc::new_in(1u8, &tracking);
+        let weak = Rc::downgrade(&r);
+        drop(r);
+        assert_eq!(tracking.live_allocations(), 1);
+        drop(weak);
+        assert_eq!(tracking.live_allocations(), 0);
+        tracking.fail_at(1);
+        assert!(Rc::try_new_in(1u8, &tracking).is_err());
+    }
+
+    #[test]
+    fn weak_outlives_value() {
+        let flag = std::rc::Rc::new(());
+        let r = Rc::new(std::rc::Rc::clone(&flag));
+        let weak = Rc::downgrade(&r);
+        let weak2 = weak.clone();
+        assert_eq!(Rc::weak_count(&r), 2);
+        assert_eq!(weak.strong_count(), 1);
+
+        std::mem::drop(r);
+        // The value is dropped even though the allocation is still alive.
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+        assert!(weak.upgrade().is_none());
+        assert!(weak.ptr_eq(&weak2));
+        assert!(Weak::<()>::new().upgrade().is_none());
+    }
 }
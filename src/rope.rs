@@ -0,0 +1,453 @@
+use std::{
+    fmt,
+    ops::{Range, RangeBounds},
+};
+
+use crate::{layout::HeaderStr, rc::Rc};
+
+/// The largest leaf created when building a rope from a string.
+const MAX_LEAF: usize = 512;
+
+/// A string stored as a balanced tree of chunks.
+///
+/// Nodes are immutable and shared through [`Rc`], edits only rebuild the
+/// path down to the edited position, so [`Clone`] and slicing are cheap and
+/// insert and remove are O(log n) in the length of the text.
+///
+/// Positions are byte offsets, use [`Rope::char_to_byte`] and
+/// [`Rope::byte_to_char`] to convert from and to char offsets.
+///
+/// ```
+/// use nomicon::Rope;
+///
+/// let mut rope = Rope::from("Hello World");
+/// rope.insert(5, ",");
+/// rope.remove(6..7);
+/// rope.insert(6, "\n");
+///
+/// assert_eq!(rope.to_string(), "Hello,\nWorld");
+/// assert_eq!(rope.slice(7..).to_string(), "World");
+/// ```
+#[derive(Clone, Default)]
+pub struct Rope {
+    root: Option<Rc<Node>>,
+}
+
+enum Node {
+    /// The text behind its char count, in a single allocation.
+    Leaf(Box<HeaderStr<usize>>),
+    Concat {
+        left: Rc<Node>,
+        right: Rc<Node>,
+        len: usize,
+        chars: usize,
+        height: usize,
+    },
+}
+
+impl Node {
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.str.len(),
+            Node::Concat { len, .. } => *len,
+        }
+    }
+
+    fn chars(&self) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.header,
+            Node::Concat { chars, .. } => *chars,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Concat { height, .. } => *height,
+        }
+    }
+
+    /// Returns the children of a concat node.
+    ///
+    /// # Panics
+    /// If `self` is a leaf, callers only ask for the children of nodes taller
+    /// than some other node.
+    fn children(&self) -> (&Rc<Node>, &Rc<Node>) {
+        match self {
+            Node::Concat { left, right, .. } => (left, right),
+            Node::Leaf(_) => unreachable!(),
+        }
+    }
+}
+
+fn leaf(text: &str) -> Rc<Node> {
+    Rc::new(Node::Leaf(HeaderStr::new(text.chars().count(), text)))
+}
+
+/// Create a concat node without rebalancing.
+fn concat(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    Rc::new(Node::Concat {
+        len: left.len() + right.len(),
+        chars: left.chars() + right.chars(),
+        height: left.height().max(right.height()) + 1,
+        left,
+        right,
+    })
+}
+
+/// Create a concat node from subtrees whose heights differ by at most two,
+/// rotating to restore balance.
+fn balanced(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if left.height() > right.height() + 1 {
+        let (ll, lr) = left.children();
+        if ll.height() >= lr.height() {
+            concat(ll.clone(), concat(lr.clone(), right))
+        } else {
+            let (lrl, lrr) = lr.children();
+            concat(concat(ll.clone(), lrl.clone()), concat(lrr.clone(), right))
+        }
+    } else if right.height() > left.height() + 1 {
+        let (rl, rr) = right.children();
+        if rr.height() >= rl.height() {
+            concat(concat(left, rl.clone()), rr.clone())
+        } else {
+            let (rll, rlr) = rl.children();
+            concat(concat(left, rll.clone()), concat(rlr.clone(), rr.clone()))
+        }
+    } else {
+        concat(left, right)
+    }
+}
+
+/// Concatenate two balanced trees into a balanced tree.
+fn join(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if let (Node::Leaf(l), Node::Leaf(r)) = (&*left, &*right) {
+        if l.str.len() + r.str.len() <= MAX_LEAF {
+            return leaf(&[&l.str, &r.str].concat());
+        }
+    }
+    if left.height() > right.height() + 1 {
+        let (ll, lr) = left.children();
+        balanced(ll.clone(), join(lr.clone(), right))
+    } else if right.height() > left.height() + 1 {
+        let (rl, rr) = right.children();
+        balanced(join(left, rl.clone()), rr.clone())
+    } else {
+        concat(left, right)
+    }
+}
+
+fn join_opt(left: Option<Rc<Node>>, right: Option<Rc<Node>>) -> Option<Rc<Node>> {
+    match (left, right) {
+        (Some(l), Some(r)) => Some(join(l, r)),
+        (l, None) => l,
+        (None, r) => r,
+    }
+}
+
+/// Split `node` into the bytes before and after `at`.
+///
+/// # Panics
+/// If `at` is not on a char boundary.
+fn split(node: &Rc<Node>, at: usize) -> (Option<Rc<Node>>, Option<Rc<Node>>) {
+    if at == 0 {
+        return (None, Some(node.clone()));
+    }
+    if at == node.len() {
+        return (Some(node.clone()), None);
+    }
+    match &**node {
+        Node::Leaf(boxed) => {
+            let text = &boxed.str;
+            assert!(
+                text.is_char_boundary(at),
+                "byte index is not a char boundary"
+            );
+            (Some(leaf(&text[..at])), Some(leaf(&text[at..])))
+        }
+        Node::Concat { left, right, .. } => {
+            if at <= left.len() {
+                let (a, b) = split(left, at);
+                (a, join_opt(b, Some(right.clone())))
+            } else {
+                let (a, b) = split(right, at - left.len());
+                (join_opt(Some(left.clone()), a), b)
+            }
+        }
+    }
+}
+
+/// Build a balanced tree from a run of leaves.
+fn build(leaves: &[Rc<Node>]) -> Option<Rc<Node>> {
+    match leaves {
+        [] => None,
+        [leaf] => Some(leaf.clone()),
+        _ => {
+            let (l, r) = leaves.split_at(leaves.len() / 2);
+            join_opt(build(l), build(r))
+        }
+    }
+}
+
+fn from_str(text: &str) -> Option<Rc<Node>> {
+    let mut leaves = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut at = rest.len().min(MAX_LEAF);
+        while !rest.is_char_boundary(at) {
+            at -= 1;
+        }
+        let (chunk, tail) = rest.split_at(at);
+        leaves.push(leaf(chunk));
+        rest = tail;
+    }
+    build(&leaves)
+}
+
+impl Rope {
+    pub const fn new() -> Self {
+        Self { root: None }
+    }
+
+    /// Length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map_or(0, |n| n.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Length of the text in chars.
+    pub fn len_chars(&self) -> usize {
+        self.root.as_ref().map_or(0, |n| n.chars())
+    }
+
+    /// Insert `text` at the byte offset `at`.
+    ///
+    /// # Panics
+    /// If `at` is out of bounds or not on a char boundary.
+    pub fn insert(&mut self, at: usize, text: &str) {
+        assert!(at <= self.len(), "byte index out of bounds");
+        let (before, after) = match &self.root {
+            Some(root) => split(root, at),
+            None => (None, None),
+        };
+        self.root = join_opt(join_opt(before, from_str(text)), after);
+    }
+
+    /// Remove the bytes in `range`.
+    ///
+    /// # Panics
+    /// If the range is out of bounds or does not lie on char boundaries.
+    pub fn remove<R: RangeBounds<usize>>(&mut self, range: R) {
+        let Range { start, end } = crate::slice::range(range, self.len());
+        let Some(root) = &self.root else {
+            return;
+        };
+        let (before, rest) = split(root, start);
+        let after = rest.and_then(|rest| split(&rest, end - start).1);
+        self.root = join_opt(before, after);
+    }
+
+    /// Returns the bytes in `range` as a new rope, sharing its chunks with
+    /// `self`.
+    ///
+    /// # Panics
+    /// If the range is out of bounds or does not lie on char boundaries.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Rope {
+        let Range { start, end } = crate::slice::range(range, self.len());
+        let Some(root) = &self.root else {
+            return Rope::new();
+        };
+        let (_, rest) = split(root, start);
+        let root = rest.and_then(|rest| split(&rest, end - start).0);
+        Rope { root }
+    }
+
+    /// Append `other` to the end of `self`.
+    pub fn append(&mut self, other: Rope) {
+        self.root = join_opt(self.root.take(), other.root);
+    }
+
+    /// Convert a char offset into a byte offset.
+    ///
+    /// # Panics
+    /// If `char_idx` is greater than [`Rope::len_chars`].
+    pub fn char_to_byte(&self, mut char_idx: usize) -> usize {
+        assert!(char_idx <= self.len_chars(), "char index out of bounds");
+        let Some(mut node) = self.root.as_ref() else {
+            return 0;
+        };
+        let mut byte = 0;
+        loop {
+            match &**node {
+                Node::Concat { left, right, .. } => {
+                    if char_idx < left.chars() {
+                        node = left;
+                    } else {
+                        char_idx -= left.chars();
+                        byte += left.len();
+                        node = right;
+                    }
+                }
+                Node::Leaf(leaf) => {
+                    let text = &leaf.str;
+                    let offset = text
+                        .char_indices()
+                        .nth(char_idx)
+                        .map_or(text.len(), |(i, _)| i);
+                    return byte + offset;
+                }
+            }
+        }
+    }
+
+    /// Convert a byte offset into a char offset.
+    ///
+    /// # Panics
+    /// If `byte_idx` is out of bounds or not on a char boundary.
+    pub fn byte_to_char(&self, mut byte_idx: usize) -> usize {
+        assert!(byte_idx <= self.len(), "byte index out of bounds");
+        let Some(mut node) = self.root.as_ref() else {
+            return 0;
+        };
+        let mut chars = 0;
+        loop {
+            match &**node {
+                Node::Concat { left, right, .. } => {
+                    if byte_idx < left.len() {
+                        node = left;
+                    } else {
+                        byte_idx -= left.len();
+                        chars += left.chars();
+                        node = right;
+                    }
+                }
+                Node::Leaf(leaf) => {
+                    let text = &leaf.str;
+                    assert!(
+                        text.is_char_boundary(byte_idx),
+                        "byte index is not a char boundary"
+                    );
+                    return chars + text[..byte_idx].chars().count();
+                }
+            }
+        }
+    }
+
+    /// Iterate over the chunks of text in order.
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks {
+            stack: self.root.iter().map(|n| &**n).collect(),
+        }
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(value: &str) -> Self {
+        Self {
+            root: from_str(value),
+        }
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl PartialEq<str> for Rope {
+    fn eq(&self, other: &str) -> bool {
+        self.len() == other.len() && self.chars().eq(other.chars())
+    }
+}
+
+/// An iterator over the chunks of a [`Rope`].
+///
+/// This type can be constructed through [`Rope::chunks`].
+pub struct Chunks<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Node::Leaf(leaf) => return Some(&leaf.str),
+                Node::Concat { left, right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_balanced(node: &Node) {
+        if let Node::Concat { left, right, .. } = node {
+            assert!(left.height().abs_diff(right.height()) <= 1);
+            assert_balanced(left);
+            assert_balanced(right);
+        }
+    }
+
+    #[test]
+    fn edits_match_string() {
+        let mut rope = Rope::new();
+        let mut string = String::new();
+        for n in 0..500 {
+            let text = format!("{n}é");
+            let at = string.len() / 3;
+            let at = (0..=at)
+                .rev()
+                .find(|i| string.is_char_boundary(*i))
+                .unwrap();
+            rope.insert(at, &text);
+            string.insert_str(at, &text);
+        }
+        assert_eq!(rope, *string);
+        rope.remove(100..2000);
+        string.replace_range(100..2000, "");
+        assert_eq!(rope, *string);
+        assert_balanced(rope.root.as_ref().unwrap());
+    }
+
+    #[test]
+    fn slices_share_chunks() {
+        let text = "abcdefghij".repeat(500);
+        let rope = Rope::from(text.as_str());
+        let slice = rope.slice(1234..4321);
+        assert_eq!(slice, text[1234..4321]);
+        assert_eq!(rope.len(), 5000);
+        assert_balanced(rope.root.as_ref().unwrap());
+    }
+
+    #[test]
+    fn index_conversion() {
+        let text = "añb€c".repeat(300);
+        let rope = Rope::from(text.as_str());
+        for (char_idx, (byte_idx, _)) in text.char_indices().enumerate() {
+            assert_eq!(rope.char_to_byte(char_idx), byte_idx);
+            assert_eq!(rope.byte_to_char(byte_idx), char_idx);
+        }
+        assert_eq!(rope.char_to_byte(rope.len_chars()), rope.len());
+    }
+}
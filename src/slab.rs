@@ -0,0 +1,206 @@
+/// Dense storage handing out `usize` keys, reusing the keys of removed values.
+///
+/// Vacant slots form a free list threaded through the storage itself, so
+/// inserting into a slab with vacant slots never allocates.
+///
+/// ```
+/// use nomicon::Slab;
+///
+/// let mut slab = Slab::new();
+/// let hello = slab.insert("hello");
+/// let world = slab.insert("world");
+/// assert_eq!(slab[hello], "hello");
+///
+/// slab.remove(hello);
+/// assert_eq!(slab.vacant_key(), hello);
+/// assert_eq!(slab.insert("again"), hello);
+/// assert_eq!(slab.len(), 2);
+/// # let _ = world;
+/// ```
+pub struct Slab<T> {
+    entries: Vec<Entry<T>>,
+    /// The next key to hand out, equal to `entries.len()` when no slots are
+    /// vacant.
+    next: usize,
+    len: usize,
+}
+
+enum Entry<T> {
+    Occupied(T),
+    Vacant { next: usize },
+}
+
+impl<T> Slab<T> {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(cap),
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    /// Returns the key the next call to [`Slab::insert`] will return.
+    pub const fn vacant_key(&self) -> usize {
+        self.next
+    }
+
+    /// Store `value`, returning its key.
+    pub fn insert(&mut self, value: T) -> usize {
+        let key = self.next;
+        if key == self.entries.len() {
+            self.entries.push(Entry::Occupied(value));
+            self.next = key + 1;
+        } else {
+            match std::mem::replace(&mut self.entries[key], Entry::Occupied(value)) {
+                Entry::Vacant { next } => self.next = next,
+                Entry::Occupied(_) => unreachable!(),
+            }
+        }
+        self.len += 1;
+        key
+    }
+
+    pub fn get(&self, key: usize) -> Option<&T> {
+        match self.entries.get(key)? {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant { .. } => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.entries.get_mut(key)? {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant { .. } => None,
+        }
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value at `key`, making the key available again.
+    pub fn try_remove(&mut self, key: usize) -> Option<T> {
+        let entry = self.entries.get_mut(key)?;
+        if let Entry::Vacant { .. } = entry {
+            return None;
+        }
+        let old = std::mem::replace(entry, Entry::Vacant { next: self.next });
+        self.next = key;
+        self.len -= 1;
+        match old {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant { .. } => unreachable!(),
+        }
+    }
+
+    /// Remove the value at `key`, making the key available again.
+    ///
+    /// # Panics
+    /// If `key` is vacant.
+    pub fn remove(&mut self, key: usize) -> T {
+        match self.try_remove(key) {
+            Some(value) => value,
+            None => panic!("invalid Slab key"),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.next = 0;
+        self.len = 0;
+    }
+
+    /// Iterate over the occupied entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(key, entry)| match entry {
+                Entry::Occupied(value) => Some((key, value)),
+                Entry::Vacant { .. } => None,
+            })
+    }
+
+    /// Iterate mutably over the occupied entries in key order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(key, entry)| match entry {
+                Entry::Occupied(value) => Some((key, value)),
+                Entry::Vacant { .. } => None,
+            })
+    }
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::ops::Index<usize> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &Self::Output {
+        self.get(key).expect("invalid Slab key")
+    }
+}
+
+impl<T> std::ops::IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, key: usize) -> &mut Self::Output {
+        self.get_mut(key).expect("invalid Slab key")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuses_keys() {
+        let mut slab = Slab::with_capacity(4);
+        let keys = (0..4).map(|n| slab.insert(n)).collect::<Vec<_>>();
+        assert_eq!(keys, [0, 1, 2, 3]);
+        assert_eq!(slab.remove(1), 1);
+        assert_eq!(slab.remove(3), 3);
+        assert_eq!(slab.try_remove(3), None);
+        // Most recently freed first.
+        assert_eq!(slab.insert(30), 3);
+        assert_eq!(slab.insert(10), 1);
+        assert_eq!(slab.insert(4), 4);
+        assert_eq!(slab.len(), 5);
+    }
+
+    #[test]
+    fn iterates_occupied() {
+        let mut slab = Slab::new();
+        (0..5).for_each(|n| {
+            slab.insert(n);
+        });
+        slab.remove(0);
+        slab.remove(2);
+        slab.iter_mut().for_each(|(_, v)| *v += 1);
+        assert_eq!(slab.iter().collect::<Vec<_>>(), [(1, &2), (3, &4), (4, &5)]);
+    }
+}
@@ -0,0 +1,241 @@
+//! Slice operations written against raw pointers.
+//!
+//! The borrow checker cannot see that two halves of one slice are disjoint,
+//! or that swapping two elements leaves the slice valid. Each function here
+//! takes the slice apart into a pointer and a length, does the work unsafe
+//! code is needed for, and hands back borrows tied to the input.
+//!
+//! [`Iter`] and [`IterMut`] walk a slice through a pair of pointers, and
+//! the byte functions such as [`position_byte`] work a word at a time.
+
+mod bytes;
+mod iter;
+
+pub use bytes::{contains_byte, position_byte};
+pub use iter::{Iter, IterMut};
+
+use std::{
+    ops::{Bound, Range, RangeBounds},
+    ptr,
+};
+
+/// Returns an iterator over shared borrows of every element.
+pub fn iter<T>(slice: &[T]) -> Iter<'_, T> {
+    Iter::new(slice)
+}
+
+/// Returns an iterator over mutable borrows of every element.
+pub fn iter_mut<T>(slice: &mut [T]) -> IterMut<'_, T> {
+    IterMut::new(slice)
+}
+
+/// Split `slice` into `[..mid]` and `[mid..]`, both mutable at once.
+///
+/// # Panics
+/// If `mid > slice.len()`.
+///
+/// ```
+/// use nomicon::slice;
+///
+/// let mut values = [1, 2, 3, 4, 5];
+/// let (left, right) = slice::split_at_mut(&mut values, 2);
+/// left[0] = right[0];
+/// right[2] = left[1];
+/// assert_eq!(values, [3, 2, 3, 4, 2]);
+/// ```
+pub fn split_at_mut<T>(slice: &mut [T], mid: usize) -> (&mut [T], &mut [T]) {
+    let len = slice.len();
+    assert!(mid <= len, "mid {mid} out of bounds for length {len}");
+    let ptr = slice.as_mut_ptr();
+    // SAFETY
+    // * Both halves lie within the slice and do not overlap, so handing out
+    //   two mutable borrows of them never aliases.
+    // * Both live as long as the borrow of the whole slice.
+    unsafe {
+        (
+            std::slice::from_raw_parts_mut(ptr, mid),
+            std::slice::from_raw_parts_mut(ptr.add(mid), len - mid),
+        )
+    }
+}
+
+/// Swap the elements at `a` and `b`.
+///
+/// # Panics
+/// If either index is out of bounds.
+pub fn swap<T>(slice: &mut [T], a: usize, b: usize) {
+    let len = slice.len();
+    assert!(
+        a < len && b < len,
+        "swap of {a} and {b} out of bounds for length {len}"
+    );
+    let ptr = slice.as_mut_ptr();
+    // SAFETY
+    // * Both indices are in bounds, ptr::swap allows them to be equal.
+    unsafe { ptr::swap(ptr.add(a), ptr.add(b)) }
+}
+
+/// Reverse the `len` elements starting at `ptr`.
+///
+/// # Safety
+/// `ptr` is valid for reads and writes of `len` elements.
+unsafe fn reverse<T>(ptr: *mut T, len: usize) {
+    for i in 0..len / 2 {
+        unsafe { ptr::swap_nonoverlapping(ptr.add(i), ptr.add(len - 1 - i), 1) };
+    }
+}
+
+/// Rotate `slice` so the element at `mid` comes first.
+///
+/// # Panics
+/// If `mid > slice.len()`.
+///
+/// ```
+/// use nomicon::slice;
+///
+/// let mut letters = ['a', 'b', 'c', 'd', 'e'];
+/// slice::rotate_left(&mut letters, 2);
+/// assert_eq!(letters, ['c', 'd', 'e', 'a', 'b']);
+/// slice::rotate_right(&mut letters, 2);
+/// assert_eq!(letters, ['a', 'b', 'c', 'd', 'e']);
+/// ```
+pub fn rotate_left<T>(slice: &mut [T], mid: usize) {
+    let len = slice.len();
+    assert!(mid <= len, "mid {mid} out of bounds for length {len}");
+    let ptr = slice.as_mut_ptr();
+    // Reversing both parts and then the whole swaps the parts, keeping the
+    // order within each. Only swaps are used, so a panic cannot happen
+    // between moves.
+    // SAFETY
+    // * Every range reversed lies within the slice.
+    unsafe {
+        reverse(ptr, mid);
+        reverse(ptr.add(mid), len - mid);
+        reverse(ptr, len);
+    }
+}
+
+/// Rotate `slice` so the last `k` elements come first.
+///
+/// # Panics
+/// If `k > slice.len()`.
+pub fn rotate_right<T>(slice: &mut [T], k: usize) {
+    let len = slice.len();
+    assert!(k <= len, "k {k} out of bounds for length {len}");
+    rotate_left(slice, len - k);
+}
+
+/// Overwrite every element with a clone of `value`.
+///
+/// The last element receives `value` itself.
+pub fn fill<T: Clone>(slice: &mut [T], value: T) {
+    let len = slice.len();
+    if len == 0 {
+        return;
+    }
+    let ptr = slice.as_mut_ptr();
+    // SAFETY
+    // * Every index is in bounds. Assigning through the pointer drops the
+    //   old element, the slice is valid again before the next clone runs.
+    unsafe {
+        for i in 0..len - 1 {
+            *ptr.add(i) = value.clone();
+        }
+        *ptr.add(len - 1) = value;
+    }
+}
+
+/// Copy every element of `src` into `dst`.
+///
+/// # Panics
+/// If the lengths differ.
+pub fn copy_from_slice<T: Copy>(dst: &mut [T], src: &[T]) {
+    assert_eq!(
+        dst.len(),
+        src.len(),
+        "source and destination lengths differ"
+    );
+    // SAFETY
+    // * Both are valid for dst.len() elements.
+    // * A mutable and a shared borrow never overlap.
+    unsafe { ptr::copy_nonoverlapping(src.as_ptr(), dst.as_mut_ptr(), dst.len()) }
+}
+
+/// Resolve `bounds` against a length, the way slice indexing does.
+///
+/// # Panics
+/// If a bound overflows, `start > end` or `end > len`.
+pub(crate) fn range(bounds: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match bounds.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n.checked_add(1).expect("range bound overflow"),
+        Bound::Unbounded => 0,
+    };
+    let end = match bounds.end_bound() {
+        Bound::Included(&n) => n.checked_add(1).expect("range bound overflow"),
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range start is greater than end");
+    assert!(end <= len, "range end is out of bounds");
+    start..end
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotations() {
+        for len in 0..8 {
+            for mid in 0..=len {
+                let mut ours = (0..len).collect::<Vec<_>>();
+                let mut std = ours.clone();
+                rotate_left(&mut ours, mid);
+                std.rotate_left(mid);
+                assert_eq!(ours, std);
+                rotate_right(&mut ours, mid);
+                std.rotate_right(mid);
+                assert_eq!(ours, std);
+            }
+        }
+    }
+
+    #[test]
+    fn writes() {
+        let flag = std::rc::Rc::new(());
+        let mut rcs = vec![std::rc::Rc::new(()), std::rc::Rc::new(())];
+        fill(&mut rcs, std::rc::Rc::clone(&flag));
+        assert_eq!(std::rc::Rc::strong_count(&flag), 3);
+        fill(&mut rcs[..0], std::rc::Rc::clone(&flag));
+        assert_eq!(std::rc::Rc::strong_count(&flag), 3);
+
+        let mut values = [0, 1, 2, 3];
+        swap(&mut values, 0, 3);
+        swap(&mut values, 1, 1);
+        assert_eq!(values, [3, 1, 2, 0]);
+        let (left, right) = split_at_mut(&mut values, 4);
+        assert!(right.is_empty());
+        copy_from_slice(left, &[9, 8, 7, 6]);
+        assert_eq!(values, [9, 8, 7, 6]);
+    }
+
+    #[test]
+    #[should_panic = "out of bounds"]
+    fn split_out_of_bounds() {
+        split_at_mut(&mut [1, 2], 3);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(range(.., 4), 0..4);
+        assert_eq!(range(1..=2, 4), 1..3);
+        assert_eq!(range((Bound::Excluded(1), Bound::Included(3)), 4), 2..4);
+    }
+
+    #[test]
+    #[should_panic(expected = "range bound overflow")]
+    fn range_to_max() {
+        range(..=usize::MAX, 1);
+    }
+}
@@ -0,0 +1,78 @@
+//! Byte searches a word at a time.
+//!
+//! A word holds `size_of::<usize>()` bytes. XOR with the needle repeated in
+//! every byte leaves a zero byte wherever the needle was, and
+//! `(x - 0x01..) & !x & 0x80..` is nonzero exactly when `x` has a zero byte.
+//!
+//! Fills and comparisons are left to `slice::fill` and `==`, which already
+//! lower to `memset` and `memcmp`.
+
+const WORD: usize = size_of::<usize>();
+/// 0x0101...01
+const LO: usize = usize::MAX / 0xFF;
+/// 0x8080...80
+const HI: usize = LO << 7;
+
+const fn repeat(byte: u8) -> usize {
+    LO * byte as usize
+}
+
+const fn has_zero_byte(word: usize) -> bool {
+    word.wrapping_sub(LO) & !word & HI != 0
+}
+
+/// Returns the index of the first `needle` in `haystack`.
+///
+/// ```
+/// use nomicon::slice;
+///
+/// let line = b"GET / HTTP/1.1\r\nHost: example.com\r\n";
+/// assert_eq!(slice::position_byte(line, b'\r'), Some(14));
+/// assert_eq!(slice::position_byte(line, b'\0'), None);
+/// ```
+pub fn position_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+    let scalar = |bytes: &[u8], offset: usize| {
+        bytes
+            .iter()
+            .position(|&byte| byte == needle)
+            .map(|index| offset + index)
+    };
+    // SAFETY:
+    // * Every bit pattern is a valid usize.
+    let (prefix, words, suffix) = unsafe { haystack.align_to::<usize>() };
+    if let Some(index) = scalar(prefix, 0) {
+        return Some(index);
+    }
+    let splat = repeat(needle);
+    for (n, &word) in words.iter().enumerate() {
+        if has_zero_byte(word ^ splat) {
+            let start = prefix.len() + n * WORD;
+            return scalar(&haystack[start..start + WORD], start);
+        }
+    }
+    scalar(suffix, haystack.len() - suffix.len())
+}
+
+/// Returns true if `haystack` contains `needle`.
+pub fn contains_byte(haystack: &[u8], needle: u8) -> bool {
+    position_byte(haystack, needle).is_some()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_scalar_at_every_offset() {
+        let data = (0..100u8).map(|n| n % 7).collect::<std::vec::Vec<_>>();
+        for start in 0..WORD * 2 {
+            for end in start..data.len() {
+                let window = &data[start..end];
+                for needle in 0..8 {
+                    let expected = window.iter().position(|&byte| byte == needle);
+                    assert_eq!(position_byte(window, needle), expected);
+                }
+            }
+        }
+    }
+}
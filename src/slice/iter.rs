@@ -0,0 +1,258 @@
+use std::{iter::FusedIterator, marker::PhantomData, ptr::NonNull};
+
+/// Iterates between a pair of pointers, the way std's slice iterators do.
+///
+/// Each step moves `ptr` forward or `end` back. Zero sized elements all
+/// live at the same address, so for them `ptr` stays put and `end` counts
+/// the remaining elements in its address instead.
+///
+/// ```
+/// use nomicon::slice;
+///
+/// let values = [1, 2, 3, 4];
+/// let mut iter = slice::iter(&values);
+/// assert_eq!(iter.next(), Some(&1));
+/// assert_eq!(iter.next_back(), Some(&4));
+/// assert_eq!(iter.len(), 2);
+/// assert_eq!(iter.as_slice(), [2, 3]);
+/// ```
+///
+/// This type can be constructed through [`super::iter`].
+pub struct Iter<'a, T> {
+    ptr: NonNull<T>,
+    end: *const T,
+    _marker: PhantomData<&'a T>,
+}
+
+/// The mutable counterpart of [`Iter`].
+///
+/// ```
+/// use nomicon::slice;
+///
+/// let mut values = [1, 2, 3];
+/// slice::iter_mut(&mut values).rev().for_each(|n| *n *= 10);
+/// assert_eq!(values, [10, 20, 30]);
+/// ```
+///
+/// This type can be constructed through [`super::iter_mut`].
+pub struct IterMut<'a, T> {
+    ptr: NonNull<T>,
+    end: *mut T,
+    _marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY
+// * Iter hands out &T and IterMut &mut T, they are Send and Sync like
+//   those references.
+unsafe impl<T: Sync> Send for Iter<'_, T> {}
+unsafe impl<T: Sync> Sync for Iter<'_, T> {}
+unsafe impl<T: Send> Send for IterMut<'_, T> {}
+unsafe impl<T: Sync> Sync for IterMut<'_, T> {}
+
+const fn is_zst<T>() -> bool {
+    size_of::<T>() == 0
+}
+
+/// Returns the end pointer for `len` elements from `ptr`.
+fn end_of<T>(ptr: *mut T, len: usize) -> *mut T {
+    if is_zst::<T>() {
+        // Never dereferenced, the address only counts elements.
+        ptr.wrapping_byte_add(len)
+    } else {
+        // SAFETY
+        // * One past the end of the slice is in bounds.
+        unsafe { ptr.add(len) }
+    }
+}
+
+/// Returns the number of elements between `ptr` and `end`.
+fn len_between<T>(ptr: NonNull<T>, end: *const T) -> usize {
+    if is_zst::<T>() {
+        end.addr() - ptr.as_ptr().addr()
+    } else {
+        // SAFETY
+        // * Both point into, or one past, the same slice, and ptr <= end.
+        unsafe { end.offset_from_unsigned(ptr.as_ptr()) }
+    }
+}
+
+impl<'a, T> Iter<'a, T> {
+    pub fn new(slice: &'a [T]) -> Self {
+        let ptr = slice.as_ptr().cast_mut();
+        Self {
+            ptr: NonNull::from(slice).cast(),
+            end: end_of(ptr, slice.len()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the elements not yielded yet.
+    pub fn as_slice(&self) -> &'a [T] {
+        // SAFETY
+        // * The remaining elements are a live part of the borrowed slice.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len()) }
+    }
+}
+
+impl<'a, T> IterMut<'a, T> {
+    pub fn new(slice: &'a mut [T]) -> Self {
+        let ptr = slice.as_mut_ptr();
+        Self {
+            end: end_of(ptr, slice.len()),
+            ptr: NonNull::from(slice).cast(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the elements not yielded yet, giving up the iterator.
+    pub fn into_slice(self) -> &'a mut [T] {
+        // SAFETY
+        // * The remaining elements were never handed out.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len()) }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.ptr.as_ptr().cast_const() == self.end {
+            return None;
+        }
+        let item = self.ptr;
+        if is_zst::<T>() {
+            self.end = self.end.wrapping_byte_sub(1);
+        } else {
+            // SAFETY
+            // * ptr was before end, one further is at most end.
+            self.ptr = unsafe { self.ptr.add(1) };
+        }
+        // SAFETY
+        // * The element is in the borrowed slice, zero sized ones may be
+        //   read from any aligned pointer.
+        Some(unsafe { item.as_ref() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.ptr.as_ptr().cast_const() == self.end {
+            return None;
+        }
+        if is_zst::<T>() {
+            self.end = self.end.wrapping_byte_sub(1);
+            return Some(unsafe { self.ptr.as_ref() });
+        }
+        // SAFETY
+        // * end was past ptr, one back is the last remaining element.
+        unsafe {
+            self.end = self.end.sub(1);
+            Some(&*self.end)
+        }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.ptr.as_ptr() == self.end {
+            return None;
+        }
+        let mut item = self.ptr;
+        if is_zst::<T>() {
+            self.end = self.end.wrapping_byte_sub(1);
+        } else {
+            self.ptr = unsafe { self.ptr.add(1) };
+        }
+        // SAFETY
+        // * Every element is handed out once, the mutable borrows never
+        //   alias.
+        Some(unsafe { item.as_mut() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        if self.ptr.as_ptr() == self.end {
+            return None;
+        }
+        if is_zst::<T>() {
+            self.end = self.end.wrapping_byte_sub(1);
+            return Some(unsafe { self.ptr.as_mut() });
+        }
+        unsafe {
+            self.end = self.end.sub(1);
+            Some(&mut *self.end)
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {
+    fn len(&self) -> usize {
+        len_between(self.ptr, self.end)
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {
+    fn len(&self) -> usize {
+        len_between(self.ptr, self.end)
+    }
+}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+impl<T> FusedIterator for IterMut<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn both_ends() {
+        let values = [1, 2, 3, 4, 5];
+        let mut iter = Iter::new(&values);
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&5));
+        assert_eq!(iter.clone().collect::<Vec<_>>(), [&2, &3, &4]);
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.by_ref().rev().collect::<Vec<_>>(), [&4, &3, &2]);
+        assert_eq!(iter.next(), None);
+        assert_eq!(Iter::<u8>::new(&[]).next(), None);
+
+        let mut values = [1, 2, 3];
+        let mut iter = IterMut::new(&mut values);
+        *iter.next_back().unwrap() = 30;
+        *iter.next().unwrap() = 10;
+        assert_eq!(iter.into_slice(), [2]);
+        assert_eq!(values, [10, 2, 30]);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let units = [(); 4];
+        let mut iter = Iter::new(&units);
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next_back(), Some(&()));
+        assert_eq!(iter.as_slice().len(), 3);
+        assert_eq!(iter.count(), 3);
+
+        let mut units = [(); 3];
+        assert_eq!(IterMut::new(&mut units).rev().count(), 3);
+    }
+}
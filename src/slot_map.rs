@@ -0,0 +1,242 @@
+use crate::num::BoundedU32;
+
+/// A handle to a value stored in a [`SlotMap`].
+///
+/// Keys are never reused, a slot that is emptied and filled again bumps its
+/// generation so stale keys stop resolving.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    index: u32,
+    generation: Generation,
+}
+
+/// Leaves a niche, so an `Option<Key>` is as small as a key.
+type Generation = BoundedU32<0, { u32::MAX - 1 }>;
+
+/// A map that assigns its own keys to inserted values.
+///
+/// ```
+/// use nomicon::SlotMap;
+///
+/// let mut map = SlotMap::new();
+/// let a = map.insert("a");
+/// assert_eq!(map.remove(a), Some("a"));
+///
+/// let b = map.insert("b");
+/// assert_eq!(map.get(a), None);
+/// assert_eq!(map.get(b), Some(&"b"));
+/// ```
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    /// Index of the first vacant slot, the vacant slots form a linked list.
+    free_head: Option<u32>,
+    len: usize,
+}
+
+struct Slot<T> {
+    generation: Generation,
+    entry: Entry<T>,
+}
+
+enum Entry<T> {
+    Occupied(T),
+    Vacant { next_free: Option<u32> },
+}
+
+impl<T> SlotMap<T> {
+    pub const fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_head: None,
+            len: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Store `value`, returning the key that now refers to it.
+    ///
+    /// # Panics
+    /// If more than `u32::MAX` slots would be needed.
+    pub fn insert(&mut self, value: T) -> Key {
+        let key = match self.free_head {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                match slot.entry {
+                    Entry::Vacant { next_free } => self.free_head = next_free,
+                    Entry::Occupied(_) => unreachable!(),
+                }
+                slot.entry = Entry::Occupied(value);
+                Key {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("SlotMap index overflown");
+                self.slots.push(Slot {
+                    generation: Generation::MIN,
+                    entry: Entry::Occupied(value),
+                });
+                Key {
+                    index,
+                    generation: Generation::MIN,
+                }
+            }
+        };
+        // Only counted once the slot holds the value, the index can still
+        // overflow above.
+        self.len += 1;
+        key
+    }
+
+    fn slot(&self, key: Key) -> Option<&Slot<T>> {
+        self.slots
+            .get(key.index as usize)
+            .filter(|slot| slot.generation == key.generation)
+    }
+
+    pub fn get(&self, key: Key) -> Option<&T> {
+        match &self.slot(key)?.entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant { .. } => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        let slot = self
+            .slots
+            .get_mut(key.index as usize)
+            .filter(|slot| slot.generation == key.generation)?;
+        match &mut slot.entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant { .. } => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value referred to by `key`, invalidating the key.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        let slot = &mut self.slots[key.index as usize];
+        let entry = std::mem::replace(
+            &mut slot.entry,
+            Entry::Vacant {
+                next_free: self.free_head,
+            },
+        );
+        // A slot whose generation would wrap is retired instead of risking
+        // an old key resolving again.
+        match slot.generation.checked_add(1) {
+            Some(generation) => {
+                slot.generation = generation;
+                self.free_head = Some(key.index);
+            }
+            None => {
+                slot.entry = Entry::Vacant { next_free: None };
+            }
+        }
+        self.len -= 1;
+        match entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant { .. } => unreachable!(),
+        }
+    }
+
+    /// Iterate over every occupied slot.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match &slot.entry {
+                Entry::Occupied(value) => Some((
+                    Key {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )),
+                Entry::Vacant { .. } => None,
+            })
+    }
+
+    /// Iterate mutably over every occupied slot.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| match &mut slot.entry {
+                Entry::Occupied(value) => Some((
+                    Key {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )),
+                Entry::Vacant { .. } => None,
+            })
+    }
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::ops::Index<Key> for SlotMap<T> {
+    type Output = T;
+
+    fn index(&self, key: Key) -> &Self::Output {
+        self.get(key).expect("invalid SlotMap key")
+    }
+}
+
+impl<T> std::ops::IndexMut<Key> for SlotMap<T> {
+    fn index_mut(&mut self, key: Key) -> &mut Self::Output {
+        self.get_mut(key).expect("invalid SlotMap key")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stale_keys() {
+        let mut map = SlotMap::new();
+        let keys = (0..10).map(|n| map.insert(n)).collect::<Vec<_>>();
+        assert_eq!(map.remove(keys[3]), Some(3));
+        assert_eq!(map.remove(keys[3]), None);
+        let reused = map.insert(30);
+        assert_eq!(reused.index, keys[3].index);
+        assert_ne!(reused, keys[3]);
+        assert_eq!(map.get(keys[3]), None);
+        assert_eq!(map[reused], 30);
+        assert_eq!(map.len(), 10);
+        assert_eq!(size_of::<Option<Key>>(), size_of::<Key>());
+    }
+
+    #[test]
+    fn iterates_occupied() {
+        let mut map = SlotMap::new();
+        let keys = (0..6).map(|n| map.insert(n)).collect::<Vec<_>>();
+        for key in keys.iter().step_by(2) {
+            map.remove(*key);
+        }
+        map.iter_mut().for_each(|(_, v)| *v *= 10);
+        let seen = map.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+        assert_eq!(seen, [(keys[1], 10), (keys[3], 30), (keys[5], 50)]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{ArrayVec, Vec};
+
+/// A vector storing up to `N` elements inline before spilling onto the heap.
+pub struct SmallVec<T, const N: usize> {
+    data: Data<T, N>,
+}
+
+enum Data<T, const N: usize> {
+    Inline(ArrayVec<T, N>),
+    Heap(Vec<T>),
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            data: Data::Inline(ArrayVec::new()),
+        }
+    }
+
+    /// Returns true if the elements have been moved onto the heap.
+    pub const fn spilled(&self) -> bool {
+        matches!(self.data, Data::Heap(_))
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.data {
+            Data::Inline(v) => v.len(),
+            Data::Heap(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append `item` to the back of the vector, spilling to the heap if the
+    /// inline storage is full.
+    ///
+    /// ```
+    /// use nomicon::SmallVec;
+    ///
+    /// let mut v = SmallVec::<u8, 2>::new();
+    /// v.push(1);
+    /// v.push(2);
+    /// assert!(!v.spilled());
+    ///
+    /// v.push(3);
+    /// assert!(v.spilled());
+    /// assert_eq!(&*v, &[1, 2, 3]);
+    /// ```
+    pub fn push(&mut self, item: T) {
+        match &mut self.data {
+            Data::Inline(v) => {
+                if let Err(item) = v.try_push(item) {
+                    let mut heap = Vec::new();
+                    v.drain(..).for_each(|i| heap.push(i));
+                    heap.push(item);
+                    self.data = Data::Heap(heap);
+                }
+            }
+            Data::Heap(v) => v.push(item),
+        }
+    }
+
+    /// Remove the last element.
+    ///
+    /// Popping never moves a spilled vector back into inline storage.
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.data {
+            Data::Inline(v) => v.pop(),
+            Data::Heap(v) => v.pop(),
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match &self.data {
+            Data::Inline(v) => v.as_slice(),
+            Data::Heap(v) => v.as_slice(),
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match &mut self.data {
+            Data::Inline(v) => v.as_mut_slice(),
+            Data::Heap(v) => v.as_mut_slice(),
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for SmallVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallVec<T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: std::fmt::Debug, const N: usize> std::fmt::Debug for SmallVec<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn spills() {
+        let mut v = SmallVec::<u32, 3>::new();
+        (0..3).for_each(|n| v.push(n));
+        assert!(!v.spilled());
+        (3..10).for_each(|n| v.push(n));
+        assert!(v.spilled());
+        assert_eq!(v.len(), 10);
+        v[0] = 100;
+        assert_eq!(v.pop(), Some(9));
+        assert_eq!(v.iter().sum::<u32>(), 100 + (1..9).sum::<u32>());
+    }
+
+    #[test]
+    fn drops_everything() {
+        let rc = Rc::new(());
+        {
+            let mut v = SmallVec::<Rc<()>, 2>::new();
+            (0..5).for_each(|_| v.push(Rc::clone(&rc)));
+            assert_eq!(Rc::strong_count(&rc), 6);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
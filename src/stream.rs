@@ -0,0 +1,259 @@
+//! Asynchronous sequences of values, the async counterpart of [`Iterator`].
+//!
+//! [`Stream`] is the trait sources implement, [`StreamExt`] adds adapters on
+//! top of it for every stream. The receivers of [`crate::channel::mpsc`] and
+//! [`crate::channel::mpmc`] are streams, ending once every sender is gone.
+//!
+//! ```
+//! use nomicon::{
+//!     executor::block_on,
+//!     stream::{self, StreamExt},
+//! };
+//!
+//! let odd_squares: Vec<_> = block_on(
+//!     stream::iter(1..10)
+//!         .filter(|n| n % 2 == 1)
+//!         .map(|n| n * n)
+//!         .collect(),
+//! );
+//! assert_eq!(odd_squares, [1, 9, 25, 49, 81]);
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A source of values that become ready over time.
+pub trait Stream {
+    type Item;
+
+    /// Returns the next value if it is ready, `None` once the stream ended.
+    ///
+    /// On [`Poll::Pending`] the waker of `cx` is woken once a value may be
+    /// ready.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+}
+
+impl<S: Stream + Unpin + ?Sized> Stream for &mut S {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        Pin::new(&mut **self).poll_next(cx)
+    }
+}
+
+impl<S: Stream + ?Sized> Stream for Pin<Box<S>> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        self.get_mut().as_mut().poll_next(cx)
+    }
+}
+
+/// Adapters for every [`Stream`].
+pub trait StreamExt: Stream {
+    /// Returns a future resolving to the next value, `None` once the stream
+    /// ended.
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next { stream: self }
+    }
+
+    /// Returns a stream yielding `f` of every value.
+    fn map<T, F>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(Self::Item) -> T,
+    {
+        Map { stream: self, f }
+    }
+
+    /// Returns a stream yielding the values `predicate` returns true for.
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        Filter {
+            stream: self,
+            predicate,
+        }
+    }
+
+    /// Returns a future resolving to every value of the stream, once it
+    /// ended.
+    fn collect<C>(self) -> Collect<Self, C>
+    where
+        Self: Sized,
+        C: Default + Extend<Self::Item>,
+    {
+        Collect {
+            stream: self,
+            collection: C::default(),
+        }
+    }
+}
+
+impl<S: Stream + ?Sized> StreamExt for S {}
+
+/// Returns a stream yielding every item of `iter`, always ready.
+pub fn iter<I: IntoIterator>(iter: I) -> Iter<I::IntoIter> {
+    Iter {
+        iter: iter.into_iter(),
+    }
+}
+
+/// See [`iter`].
+///
+/// This type can be constructed through [`iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<I> {
+    iter: I,
+}
+
+impl<I> Unpin for Iter<I> {}
+
+impl<I: Iterator> Stream for Iter<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.iter.next())
+    }
+}
+
+/// See [`StreamExt::next`].
+///
+/// This type can be constructed through [`StreamExt::next`].
+pub struct Next<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+crate::pin_project! {
+    #[project = MapProjection]
+    /// See [`StreamExt::map`].
+    ///
+    /// This type can be constructed through [`StreamExt::map`].
+    pub struct Map<S, F> {
+        #[pin]
+        stream: S,
+        f: F,
+    }
+}
+
+impl<S: Stream, T, F: FnMut(S::Item) -> T> Stream for Map<S, F> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = self.project();
+        this.stream.poll_next(cx).map(|item| item.map(this.f))
+    }
+}
+
+crate::pin_project! {
+    #[project = FilterProjection]
+    /// See [`StreamExt::filter`].
+    ///
+    /// This type can be constructed through [`StreamExt::filter`].
+    pub struct Filter<S, P> {
+        #[pin]
+        stream: S,
+        predicate: P,
+    }
+}
+
+impl<S: Stream, P: FnMut(&S::Item) -> bool> Stream for Filter<S, P> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let FilterProjection {
+            mut stream,
+            predicate,
+        } = self.project();
+        loop {
+            match stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) if !predicate(&item) => {}
+                poll => return poll,
+            }
+        }
+    }
+}
+
+crate::pin_project! {
+    #[project = CollectProjection]
+    /// See [`StreamExt::collect`].
+    ///
+    /// This type can be constructed through [`StreamExt::collect`].
+    pub struct Collect<S, C> {
+        #[pin]
+        stream: S,
+        collection: C,
+    }
+}
+
+impl<S: Stream, C: Default + Extend<S::Item>> Future for Collect<S, C> {
+    type Output = C;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<C> {
+        let CollectProjection {
+            mut stream,
+            collection,
+        } = self.project();
+        loop {
+            match stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => collection.extend(Some(item)),
+                Poll::Ready(None) => return Poll::Ready(std::mem::take(collection)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{channel::mpmc, channel::mpsc, executor::block_on};
+
+    #[test]
+    fn receivers_are_streams() {
+        let (tx, mut rx) = mpsc::unbounded();
+        let sender = std::thread::spawn(move || {
+            for n in 0..100 {
+                if n % 10 == 0 {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                tx.send(n).unwrap();
+            }
+        });
+        block_on(async {
+            assert_eq!(rx.next().await, Some(0));
+            let rest: Vec<_> = rx.map(|n| n * 2).collect().await;
+            assert_eq!(rest, (1..100).map(|n| n * 2).collect::<Vec<_>>());
+        });
+        sender.join().unwrap();
+
+        // A rendezvous channel only hands values over while polled.
+        let (tx, rx) = mpmc::bounded(0);
+        let sender = std::thread::spawn(move || {
+            for n in 0..10 {
+                tx.send(n).unwrap();
+            }
+        });
+        let evens: Vec<_> = block_on(rx.filter(|n| n % 2 == 0).collect());
+        assert_eq!(evens, [0, 2, 4, 6, 8]);
+        sender.join().unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//! A growable UTF-8 string on top of [`crate::Vec<u8>`].
+//!
+//! Converting between the two only checks the bytes, it never copies them.
+//! [`Validator`] checks UTF-8 arriving in chunks.
+
+mod utf8;
+
+pub use utf8::{validate, Utf8Error, Validator};
+
+use std::{
+    borrow::Borrow,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+};
+
+use crate::{borrow::Cow, Vec};
+
+/// A UTF-8 string owning its bytes.
+///
+/// ```
+/// use nomicon::{string::String, Vec};
+///
+/// let mut bytes = Vec::new();
+/// bytes.extend_from_slice(b"caf\xC3\xA9");
+/// let mut s = String::from_utf8(bytes).unwrap();
+/// s.push('!');
+/// assert_eq!(&*s, "café!");
+/// assert_eq!(s.into_bytes().len(), 6);
+/// ```
+///
+/// This type can be constructed through [`String::new`] and
+/// [`String::from_utf8`].
+#[derive(Default)]
+pub struct String {
+    /// Always UTF-8.
+    vec: Vec<u8>,
+}
+
+impl String {
+    pub const fn new() -> Self {
+        Self { vec: Vec::new() }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            vec: Vec::with_capacity(cap),
+        }
+    }
+
+    /// Take over `vec` if it is UTF-8, handing it back in the error if not.
+    ///
+    /// ```
+    /// use nomicon::{string::String, Vec};
+    ///
+    /// let mut bytes = Vec::new();
+    /// bytes.extend_from_slice(b"ok\xFF");
+    /// let error = String::from_utf8(bytes).unwrap_err();
+    /// assert_eq!(error.utf8_error().valid_up_to(), 2);
+    /// assert_eq!(error.into_bytes().len(), 3);
+    /// ```
+    pub fn from_utf8(vec: Vec<u8>) -> Result<Self, FromUtf8Error> {
+        match validate(&vec) {
+            Ok(()) => Ok(Self { vec }),
+            Err(error) => Err(FromUtf8Error { bytes: vec, error }),
+        }
+    }
+
+    /// # Safety
+    /// `vec` must be UTF-8.
+    pub const unsafe fn from_utf8_unchecked(vec: Vec<u8>) -> Self {
+        Self { vec }
+    }
+
+    /// Borrow `bytes` if they are UTF-8, otherwise copy them with every
+    /// invalid sequence replaced by U+FFFD.
+    ///
+    /// ```
+    /// use nomicon::string::String;
+    ///
+    /// assert!(String::from_utf8_lossy(b"fine").is_borrowed());
+    /// assert_eq!(&*String::from_utf8_lossy(b"a\xFFb\xE2\x98"), "a\u{FFFD}b\u{FFFD}");
+    /// ```
+    pub fn from_utf8_lossy(bytes: &[u8]) -> Cow<'_, str> {
+        let mut rest = bytes;
+        let mut owned = std::string::String::new();
+        while let Err(error) = validate(rest) {
+            let (valid, after) = rest.split_at(error.valid_up_to());
+            // SAFETY:
+            // * Validated up to here.
+            owned.push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+            owned.push(char::REPLACEMENT_CHARACTER);
+            rest = &after[error.error_len().unwrap_or(after.len())..];
+        }
+        if owned.is_empty() {
+            // SAFETY:
+            // * The first pass found no error.
+            return Cow::Borrowed(unsafe { std::str::from_utf8_unchecked(bytes) });
+        }
+        owned.push_str(unsafe { std::str::from_utf8_unchecked(rest) });
+        Cow::Owned(owned)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.vec
+    }
+
+    pub fn as_str(&self) -> &str {
+        // SAFETY:
+        // * The bytes are always UTF-8.
+        unsafe { std::str::from_utf8_unchecked(&self.vec) }
+    }
+
+    pub fn as_mut_str(&mut self) -> &mut str {
+        // SAFETY:
+        // * The bytes are always UTF-8, and str only allows changes that keep
+        //   them that way.
+        unsafe { std::str::from_utf8_unchecked_mut(&mut self.vec) }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.vec
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.vec.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.chars().next_back()?;
+        for _ in 0..c.len_utf8() {
+            self.vec.pop();
+        }
+        Some(c)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.vec.capacity()
+    }
+
+    pub fn clear(&mut self) {
+        while self.vec.pop().is_some() {}
+    }
+}
+
+impl Deref for String {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl DerefMut for String {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_str()
+    }
+}
+
+impl Clone for String {
+    fn clone(&self) -> Self {
+        Self::from(self.as_str())
+    }
+}
+
+impl From<&str> for String {
+    fn from(s: &str) -> Self {
+        let mut string = Self::with_capacity(s.len());
+        string.push_str(s);
+        string
+    }
+}
+
+impl From<String> for Vec<u8> {
+    fn from(s: String) -> Self {
+        s.into_bytes()
+    }
+}
+
+impl AsRef<str> for String {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl AsRef<[u8]> for String {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// Hashes and compares like the str, so a str can look up a key.
+impl Borrow<str> for String {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl PartialEq for String {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for String {}
+
+impl PartialEq<str> for String {
+    fn eq(&self, other: &str) -> bool {
+        &**self == other
+    }
+}
+
+impl PartialEq<&str> for String {
+    fn eq(&self, other: &&str) -> bool {
+        &**self == *other
+    }
+}
+
+impl PartialOrd for String {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for String {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl Hash for String {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl fmt::Write for String {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for String {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl fmt::Display for String {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// The bytes given to [`String::from_utf8`], which were not UTF-8.
+#[derive(Debug)]
+pub struct FromUtf8Error {
+    bytes: Vec<u8>,
+    error: Utf8Error,
+}
+
+impl FromUtf8Error {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the bytes, without having copied them.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub const fn utf8_error(&self) -> Utf8Error {
+        self.error
+    }
+}
+
+impl fmt::Display for FromUtf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl Error for FromUtf8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl crate::error::Error for FromUtf8Error {
+    fn source(&self) -> Option<&(dyn crate::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Write;
+
+    use super::*;
+
+    #[test]
+    fn round_trip_without_copying() {
+        let mut bytes = Vec::with_capacity(16);
+        bytes.extend_from_slice("h\u{e9}".as_bytes());
+        let ptr = bytes.as_ptr();
+        let s = String::from_utf8(bytes).unwrap();
+        assert_eq!(s.as_ptr(), ptr);
+        assert_eq!(s.into_bytes().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn edits() {
+        let mut s = String::from("ab");
+        write!(s, "{}", 1).unwrap();
+        s.push('\u{2603}');
+        assert_eq!(s, "ab1\u{2603}");
+        assert_eq!(s.pop(), Some('\u{2603}'));
+        s.make_ascii_uppercase();
+        assert_eq!(s, "AB1");
+        assert_eq!(s.clone(), s);
+        s.clear();
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn lossy_replaces_every_bad_sequence() {
+        let lossy = String::from_utf8_lossy(b"\xF0\x9F\x98x\xC0y\xE2\x98");
+        assert!(lossy.is_owned());
+        assert_eq!(&*lossy, "\u{FFFD}x\u{FFFD}y\u{FFFD}");
+        assert_eq!(
+            &*lossy,
+            &*std::string::String::from_utf8_lossy(b"\xF0\x9F\x98x\xC0y\xE2\x98")
+        );
+    }
+}
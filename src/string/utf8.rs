@@ -0,0 +1,212 @@
+use std::{error::Error, fmt};
+
+/// Where and why a byte sequence stopped being UTF-8.
+///
+/// This type can be constructed through [`validate`] and [`Validator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utf8Error {
+    valid_up_to: usize,
+    error_len: Option<u8>,
+}
+
+impl Utf8Error {
+    /// The length of the valid prefix.
+    pub const fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
+    /// The length of the invalid sequence after the valid prefix, or `None`
+    /// if the input ended partway through a character.
+    pub const fn error_len(&self) -> Option<usize> {
+        match self.error_len {
+            Some(len) => Some(len as usize),
+            None => None,
+        }
+    }
+}
+
+impl fmt::Display for Utf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error_len {
+            Some(len) => write!(
+                f,
+                "invalid utf-8 sequence of {len} bytes from index {}",
+                self.valid_up_to
+            ),
+            None => write!(
+                f,
+                "incomplete utf-8 byte sequence from index {}",
+                self.valid_up_to
+            ),
+        }
+    }
+}
+
+impl Error for Utf8Error {}
+
+/// Checks UTF-8 fed in chunks, which may split a character anywhere.
+///
+/// ```
+/// use nomicon::string::Validator;
+///
+/// let snowman = "☃".as_bytes();
+/// let mut validator = Validator::new();
+/// validator.push(&snowman[..1]).unwrap();
+/// assert_eq!(validator.valid_up_to(), 0);
+/// validator.push(&snowman[1..]).unwrap();
+/// assert_eq!(validator.finish(), Ok(3));
+/// ```
+///
+/// This type can be constructed through [`Validator::new`].
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    /// The number of bytes pushed so far.
+    fed: usize,
+    valid_up_to: usize,
+    /// Continuation bytes the current character still needs.
+    need: u8,
+    /// The range the next continuation byte must be in, narrower than
+    /// `0x80..=0xBF` right after some leading bytes.
+    lower: u8,
+    upper: u8,
+    error: Option<Utf8Error>,
+}
+
+impl Validator {
+    pub const fn new() -> Self {
+        Self {
+            fed: 0,
+            valid_up_to: 0,
+            need: 0,
+            lower: 0x80,
+            upper: 0xBF,
+            error: None,
+        }
+    }
+
+    /// The length of the input up to the end of the last whole character.
+    pub const fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
+    /// Check the next chunk of input.
+    ///
+    /// Once an error is found it is returned for every later push.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), Utf8Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        for (index, &byte) in chunk.iter().enumerate() {
+            let pos = self.fed + index;
+            let ok = if self.need == 0 {
+                self.lead(byte)
+            } else if (self.lower..=self.upper).contains(&byte) {
+                self.need -= 1;
+                (self.lower, self.upper) = (0x80, 0xBF);
+                true
+            } else {
+                false
+            };
+            if !ok {
+                let error = Utf8Error {
+                    valid_up_to: self.valid_up_to,
+                    // The bad byte itself starts the next sequence, unless
+                    // there was no sequence before it.
+                    error_len: Some((pos - self.valid_up_to).max(1) as u8),
+                };
+                self.error = Some(error);
+                return Err(error);
+            }
+            if self.need == 0 {
+                self.valid_up_to = pos + 1;
+            }
+        }
+        self.fed += chunk.len();
+        Ok(())
+    }
+
+    /// Start a character, returning false if `byte` can not lead one.
+    fn lead(&mut self, byte: u8) -> bool {
+        let (need, lower, upper) = match byte {
+            0x00..=0x7F => (0, 0x80, 0xBF),
+            0xC2..=0xDF => (1, 0x80, 0xBF),
+            // Overlong encodings.
+            0xE0 => (2, 0xA0, 0xBF),
+            0xE1..=0xEC | 0xEE..=0xEF => (2, 0x80, 0xBF),
+            // Surrogates.
+            0xED => (2, 0x80, 0x9F),
+            0xF0 => (3, 0x90, 0xBF),
+            0xF1..=0xF3 => (3, 0x80, 0xBF),
+            // Above U+10FFFF.
+            0xF4 => (3, 0x80, 0x8F),
+            _ => return false,
+        };
+        (self.need, self.lower, self.upper) = (need, lower, upper);
+        true
+    }
+
+    /// End the input, returning its length if all of it was UTF-8.
+    pub fn finish(self) -> Result<usize, Utf8Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.need != 0 {
+            return Err(Utf8Error {
+                valid_up_to: self.valid_up_to,
+                error_len: None,
+            });
+        }
+        Ok(self.fed)
+    }
+}
+
+/// Check that all of `bytes` is UTF-8.
+pub fn validate(bytes: &[u8]) -> Result<(), Utf8Error> {
+    let mut validator = Validator::new();
+    validator.push(bytes)?;
+    validator.finish().map(|_| ())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn agrees_with_std() {
+        let cases: &[&[u8]] = &[
+            b"plain ascii",
+            "h\u{e9}llo \u{2603} \u{1F600}".as_bytes(),
+            b"\xC0\x80",
+            b"\xE0\x80\x80",
+            b"\xED\xA0\x80",
+            b"\xF4\x90\x80\x80",
+            b"ok\xE2\x98",
+            b"ok\xE2\x98x",
+            b"\xFF",
+            b"a\x80b",
+        ];
+        for bytes in cases {
+            let ours = validate(bytes);
+            match std::str::from_utf8(bytes) {
+                Ok(_) => assert_eq!(ours, Ok(())),
+                Err(std) => {
+                    let ours = ours.unwrap_err();
+                    assert_eq!(ours.valid_up_to(), std.valid_up_to(), "{bytes:?}");
+                    assert_eq!(ours.error_len(), std.error_len(), "{bytes:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_split_anywhere() {
+        let text = "\u{1F600}a\u{e9}\u{2603}".as_bytes();
+        for size in 1..text.len() {
+            let mut validator = Validator::new();
+            for chunk in text.chunks(size) {
+                validator.push(chunk).unwrap();
+            }
+            assert_eq!(validator.finish(), Ok(text.len()));
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Locks and other primitives for sharing data between threads.
+
+mod atomic_cell;
+mod backoff;
+mod cache_padded;
+pub mod concurrent_hash_map;
+mod condvar;
+mod event;
+pub(crate) mod futex;
+mod lazy_lock;
+mod mutex;
+mod once_lock;
+mod once_map;
+mod parker;
+mod poison;
+mod reentrant_mutex;
+mod rw_lock;
+mod seg_queue;
+mod semaphore;
+mod sharded_lock;
+mod spin;
+
+pub use atomic_cell::{Atom, AtomicCell};
+pub use backoff::Backoff;
+pub use concurrent_hash_map::ConcurrentHashMap;
+pub use condvar::{Condvar, WaitTimeoutResult};
+pub use event::Event;
+pub use lazy_lock::LazyLock;
+pub use mutex::{Mutex, MutexGuard};
+pub use once_lock::OnceLock;
+pub use once_map::OnceMap;
+pub use parker::{Parker, Unparker};
+pub use poison::{LockResult, PoisonError, TryLockError, TryLockResult};
+pub use reentrant_mutex::{ReentrantMutex, ReentrantMutexGuard};
+pub use rw_lock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use seg_queue::SegQueue;
+pub use semaphore::{Semaphore, SemaphorePermit};
+pub use sharded_lock::{ShardedLock, ShardedLockReadGuard, ShardedLockWriteGuard};
+pub use spin::{SpinMutex, SpinMutexGuard};
+
+pub(crate) use cache_padded::CachePadded;
@@ -0,0 +1,424 @@
+use std::{
+    cell::UnsafeCell,
+    mem::{align_of, size_of, transmute_copy},
+    sync::atomic::{AtomicPtr, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering},
+};
+
+use super::SpinMutex;
+
+/// A value an [`AtomicCell`] can hold.
+///
+/// Integers, `bool`, `char`, floats and raw pointers are accessed through a
+/// native atomic of their size, as every byte of them is initialized.
+/// Pointers go through an `AtomicPtr`, keeping their provenance. Other types
+/// implement the trait without items, and are guarded by a lock, as their
+/// bytes may include padding that can not be read as an integer.
+///
+/// ```
+/// use nomicon::sync::{AtomicCell, Atom};
+///
+/// #[derive(Copy, Clone)]
+/// #[repr(align(4))]
+/// struct Padded(u8);
+/// impl Atom for Padded {}
+///
+/// assert!(!AtomicCell::<Padded>::is_lock_free());
+/// assert!(AtomicCell::<*mut Padded>::is_lock_free());
+/// ```
+pub trait Atom: Copy {
+    /// The native atomic to use, only chosen by the crate's own impls.
+    #[doc(hidden)]
+    const NATIVE: Option<sealed::Native> = None;
+}
+
+mod sealed {
+    /// The atomic a value without uninitialized bytes is accessed through.
+    #[derive(Clone, Copy)]
+    pub enum Native {
+        U8,
+        U16,
+        U32,
+        U64,
+        Ptr,
+    }
+}
+
+use sealed::Native as Width;
+
+/// The integer atomic matching `T`, if any.
+const fn width<T>() -> Option<Width> {
+    if fits::<T, AtomicU8>() {
+        Some(Width::U8)
+    } else if fits::<T, AtomicU16>() {
+        Some(Width::U16)
+    } else if fits::<T, AtomicU32>() {
+        Some(Width::U32)
+    } else if cfg!(target_has_atomic = "64") && fits::<T, AtomicU64>() {
+        Some(Width::U64)
+    } else {
+        None
+    }
+}
+
+macro_rules! atom {
+    ($($ty:ty),* $(,)?) => {
+        $(impl Atom for $ty {
+            const NATIVE: Option<Width> = width::<$ty>();
+        })*
+    };
+}
+
+atom!(bool, char, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+impl<T> Atom for *const T {
+    const NATIVE: Option<Width> = Some(Width::Ptr);
+}
+
+impl<T> Atom for *mut T {
+    const NATIVE: Option<Width> = Some(Width::Ptr);
+}
+
+impl Atom for () {}
+impl<T: ?Sized> Atom for &T {}
+impl<T: Atom> Atom for Option<T> {}
+impl<T: Atom, const N: usize> Atom for [T; N] {}
+
+/// A thread safe mutable memory location for [`Atom`] values.
+///
+/// Values the [`Atom`] impl marks as free of padding are accessed through
+/// a native atomic. Anything else is guarded by one of a fixed set of
+/// spinlocks, picked by the address of the cell.
+///
+/// ```
+/// use nomicon::sync::{AtomicCell, Atom};
+///
+/// #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+/// impl Atom for Point {}
+///
+/// let cell = AtomicCell::new(Point { x: 1, y: 2 });
+/// assert!(!AtomicCell::<Point>::is_lock_free());
+///
+/// let old = cell.swap(Point { x: 3, y: 4 });
+/// assert_eq!(old, Point { x: 1, y: 2 });
+/// assert_eq!(
+///     cell.compare_exchange(Point { x: 3, y: 4 }, Point { x: 5, y: 6 }),
+///     Ok(Point { x: 3, y: 4 })
+/// );
+/// assert_eq!(cell.load(), Point { x: 5, y: 6 });
+/// ```
+#[repr(transparent)]
+pub struct AtomicCell<T> {
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for AtomicCell<T> {}
+unsafe impl<T: Send> Sync for AtomicCell<T> {}
+
+/// Operations on a native atomic reinterpreted as a `T`.
+///
+/// Only handed out by [`AtomicCell::native`] for the atomic `T`'s [`Atom`]
+/// impl names, so `T` has the atomic's size and no uninitialized bytes.
+trait Native<T> {
+    fn load(&self) -> T;
+    fn store(&self, value: T);
+    fn swap(&self, value: T) -> T;
+    fn compare_exchange(&self, current: T, new: T) -> Result<T, T>;
+}
+
+impl<T: Copy> Native<T> for AtomicU8 {
+    fn load(&self) -> T {
+        unsafe { transmute_copy(&self.load(Ordering::SeqCst)) }
+    }
+
+    fn store(&self, value: T) {
+        self.store(unsafe { transmute_copy(&value) }, Ordering::SeqCst)
+    }
+
+    fn swap(&self, value: T) -> T {
+        let old = self.swap(unsafe { transmute_copy(&value) }, Ordering::SeqCst);
+        unsafe { transmute_copy(&old) }
+    }
+
+    fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        let (current, new) = unsafe { (transmute_copy(&current), transmute_copy(&new)) };
+        self.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|v| unsafe { transmute_copy(&v) })
+            .map_err(|v| unsafe { transmute_copy(&v) })
+    }
+}
+
+impl<T: Copy> Native<T> for AtomicU16 {
+    fn load(&self) -> T {
+        unsafe { transmute_copy(&self.load(Ordering::SeqCst)) }
+    }
+
+    fn store(&self, value: T) {
+        self.store(unsafe { transmute_copy(&value) }, Ordering::SeqCst)
+    }
+
+    fn swap(&self, value: T) -> T {
+        let old = self.swap(unsafe { transmute_copy(&value) }, Ordering::SeqCst);
+        unsafe { transmute_copy(&old) }
+    }
+
+    fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        let (current, new) = unsafe { (transmute_copy(&current), transmute_copy(&new)) };
+        self.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|v| unsafe { transmute_copy(&v) })
+            .map_err(|v| unsafe { transmute_copy(&v) })
+    }
+}
+
+impl<T: Copy> Native<T> for AtomicU32 {
+    fn load(&self) -> T {
+        unsafe { transmute_copy(&self.load(Ordering::SeqCst)) }
+    }
+
+    fn store(&self, value: T) {
+        self.store(unsafe { transmute_copy(&value) }, Ordering::SeqCst)
+    }
+
+    fn swap(&self, value: T) -> T {
+        let old = self.swap(unsafe { transmute_copy(&value) }, Ordering::SeqCst);
+        unsafe { transmute_copy(&old) }
+    }
+
+    fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        let (current, new) = unsafe { (transmute_copy(&current), transmute_copy(&new)) };
+        self.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|v| unsafe { transmute_copy(&v) })
+            .map_err(|v| unsafe { transmute_copy(&v) })
+    }
+}
+
+impl<T: Copy> Native<T> for AtomicU64 {
+    fn load(&self) -> T {
+        unsafe { transmute_copy(&self.load(Ordering::SeqCst)) }
+    }
+
+    fn store(&self, value: T) {
+        self.store(unsafe { transmute_copy(&value) }, Ordering::SeqCst)
+    }
+
+    fn swap(&self, value: T) -> T {
+        let old = self.swap(unsafe { transmute_copy(&value) }, Ordering::SeqCst);
+        unsafe { transmute_copy(&old) }
+    }
+
+    fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        let (current, new) = unsafe { (transmute_copy(&current), transmute_copy(&new)) };
+        self.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|v| unsafe { transmute_copy(&v) })
+            .map_err(|v| unsafe { transmute_copy(&v) })
+    }
+}
+
+impl<T: Copy> Native<T> for AtomicPtr<()> {
+    fn load(&self) -> T {
+        unsafe { transmute_copy(&self.load(Ordering::SeqCst)) }
+    }
+
+    fn store(&self, value: T) {
+        self.store(unsafe { transmute_copy(&value) }, Ordering::SeqCst)
+    }
+
+    fn swap(&self, value: T) -> T {
+        let old = self.swap(unsafe { transmute_copy(&value) }, Ordering::SeqCst);
+        unsafe { transmute_copy(&old) }
+    }
+
+    fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        let (current, new) = unsafe { (transmute_copy(&current), transmute_copy(&new)) };
+        self.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|v| unsafe { transmute_copy(&v) })
+            .map_err(|v| unsafe { transmute_copy(&v) })
+    }
+}
+
+/// The number of locks cells without a native atomic are spread over, prime
+/// to spread out addresses that are multiples of a large alignment.
+const LOCKS: usize = 67;
+
+static FALLBACK: [SpinMutex<()>; LOCKS] = [const { SpinMutex::new(()) }; LOCKS];
+
+/// Returns true if `T` can be reinterpreted as the atomic `A`.
+const fn fits<T, A>() -> bool {
+    size_of::<T>() == size_of::<A>() && align_of::<T>() >= align_of::<A>()
+}
+
+impl<T> AtomicCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub const fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+
+    fn lock(&self) -> super::SpinMutexGuard<'static, ()> {
+        FALLBACK[self.as_ptr().addr() % LOCKS].lock()
+    }
+}
+
+impl<T: Atom> AtomicCell<T> {
+    /// Returns true if operations on `AtomicCell<T>` go through native
+    /// atomics instead of a lock.
+    pub const fn is_lock_free() -> bool {
+        T::NATIVE.is_some()
+    }
+
+    /// Returns the native atomic overlaying the value, if `T` has one.
+    fn native(&self) -> Option<&dyn Native<T>> {
+        let ptr = self.as_ptr();
+        // SAFETY
+        // * The value is only accessed through the same atomic type, as the
+        //   choice only depends on T.
+        // * The crate only names an atomic for types of its size and
+        //   alignment.
+        unsafe {
+            Some(match T::NATIVE? {
+                Width::U8 => &*ptr.cast::<AtomicU8>(),
+                Width::U16 => &*ptr.cast::<AtomicU16>(),
+                Width::U32 => &*ptr.cast::<AtomicU32>(),
+                Width::U64 => &*ptr.cast::<AtomicU64>(),
+                Width::Ptr => &*ptr.cast::<AtomicPtr<()>>(),
+            })
+        }
+    }
+
+    pub fn load(&self) -> T {
+        match self.native() {
+            Some(atomic) => atomic.load(),
+            None => {
+                let _guard = self.lock();
+                unsafe { self.as_ptr().read() }
+            }
+        }
+    }
+
+    pub fn store(&self, value: T) {
+        match self.native() {
+            Some(atomic) => atomic.store(value),
+            None => {
+                let _guard = self.lock();
+                unsafe { self.as_ptr().write(value) }
+            }
+        }
+    }
+
+    /// Store `value`, returning the previous value.
+    pub fn swap(&self, value: T) -> T {
+        match self.native() {
+            Some(atomic) => atomic.swap(value),
+            None => {
+                let _guard = self.lock();
+                unsafe { self.as_ptr().replace(value) }
+            }
+        }
+    }
+}
+
+impl<T: Atom + Eq> AtomicCell<T> {
+    /// Store `new` if the value is `current`.
+    ///
+    /// Returns the previous value, as `Ok` if it was replaced.
+    pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        match self.native() {
+            Some(atomic) => atomic.compare_exchange(current, new),
+            None => {
+                let _guard = self.lock();
+                let old = unsafe { self.as_ptr().read() };
+                if old == current {
+                    unsafe { self.as_ptr().write(new) };
+                    Ok(old)
+                } else {
+                    Err(old)
+                }
+            }
+        }
+    }
+}
+
+impl<T: Default> Default for AtomicCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn native_and_locked() {
+        assert!(AtomicCell::<u16>::is_lock_free());
+        assert!(!AtomicCell::<[u8; 3]>::is_lock_free());
+        assert!(!AtomicCell::<[u64; 4]>::is_lock_free());
+
+        let small = AtomicCell::new(1u16);
+        assert_eq!(small.compare_exchange(2, 3), Err(1));
+        assert_eq!(small.swap(4), 1);
+        assert_eq!(small.load(), 4);
+
+        let big = AtomicCell::new([0u64; 4]);
+        big.store([1, 2, 3, 4]);
+        assert_eq!(big.compare_exchange([1, 2, 3, 4], [5; 4]), Ok([1, 2, 3, 4]));
+        assert_eq!(big.load(), [5; 4]);
+    }
+
+    #[test]
+    fn pointers_keep_provenance() {
+        let mut values = [1u32, 2];
+        let [a, b] = values.each_mut().map(std::ptr::from_mut);
+        let cell = AtomicCell::new(a);
+        assert_eq!(cell.compare_exchange(a, b), Ok(a));
+        unsafe { *cell.load() += 1 };
+        assert_eq!(values, [1, 3]);
+        assert!(AtomicCell::<Option<u8>>::new(None).load().is_none());
+    }
+
+    #[test]
+    fn concurrent_big_values_stay_whole() {
+        let cell = AtomicCell::new([0u64; 4]);
+        std::thread::scope(|s| {
+            for n in 1..=4 {
+                let cell = &cell;
+                s.spawn(move || {
+                    for _ in 0..1_000 {
+                        cell.store([n; 4]);
+                        let value = cell.load();
+                        assert!(value.iter().all(|v| *v == value[0]));
+                    }
+                });
+            }
+        });
+        let counter = AtomicCell::new(0u32);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1_000 {
+                        let mut current = counter.load();
+                        while let Err(actual) = counter.compare_exchange(current, current + 1) {
+                            current = actual;
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.into_inner(), 4_000);
+    }
+}
@@ -0,0 +1,99 @@
+use std::{hint, thread};
+
+use crate::cell::Cell;
+
+/// Spinning doubles up to `1 << SPIN_LIMIT` hints per step.
+const SPIN_LIMIT: u32 = 6;
+/// Steps past [`SPIN_LIMIT`] yield the thread, until the backoff completes at
+/// this step.
+const YIELD_LIMIT: u32 = 10;
+
+/// Exponential backoff for busy-wait loops.
+///
+/// Each step spins twice as long as the last, and [`Backoff::snooze`] moves on
+/// to yielding the thread once spinning gets long. When
+/// [`Backoff::is_completed`] returns true the caller should block instead.
+///
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// use nomicon::sync::Backoff;
+///
+/// let ready = AtomicBool::new(false);
+/// std::thread::scope(|s| {
+///     s.spawn(|| ready.store(true, Ordering::Release));
+///     let backoff = Backoff::new();
+///     while !ready.load(Ordering::Acquire) {
+///         backoff.snooze();
+///     }
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct Backoff {
+    step: Cell<u32>,
+}
+
+impl Backoff {
+    pub const fn new() -> Self {
+        Self { step: Cell::new(0) }
+    }
+
+    /// Start over from the shortest wait.
+    pub fn reset(&self) {
+        self.step.set(0);
+    }
+
+    /// Back off in a loop retrying after contention on an atomic, where
+    /// another thread is making progress.
+    pub fn spin(&self) {
+        for _ in 0..1 << self.step.get().min(SPIN_LIMIT) {
+            hint::spin_loop();
+        }
+        if self.step.get() <= SPIN_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Back off in a loop waiting for another thread to make a change.
+    pub fn snooze(&self) {
+        if self.step.get() <= SPIN_LIMIT {
+            for _ in 0..1 << self.step.get() {
+                hint::spin_loop();
+            }
+        } else {
+            thread::yield_now();
+        }
+        if self.step.get() <= YIELD_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Returns true once snoozing has gone on long enough that the thread
+    /// should block instead.
+    pub fn is_completed(&self) -> bool {
+        self.step.get() > YIELD_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn completes() {
+        let backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.spin();
+        }
+        // Spinning alone never tells the caller to block.
+        assert!(!backoff.is_completed());
+        let mut snoozes = 0;
+        while !backoff.is_completed() {
+            backoff.snooze();
+            snoozes += 1;
+        }
+        assert_eq!(snoozes, YIELD_LIMIT - SPIN_LIMIT);
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
+}
@@ -0,0 +1,30 @@
+use std::ops::{Deref, DerefMut};
+
+/// Keeps a value on its own cache line, so writes to it do not slow down
+/// threads using its neighbours.
+///
+/// 128 bytes covers the adjacent line prefetching of x86_64 and the line
+/// size of newer aarch64 cores.
+#[repr(align(128))]
+#[derive(Debug, Default)]
+pub(crate) struct CachePadded<T>(T);
+
+impl<T> CachePadded<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
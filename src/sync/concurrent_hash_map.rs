@@ -0,0 +1,372 @@
+//! A hash map that can be shared and updated between threads.
+
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    ops::{Deref, DerefMut},
+};
+
+use crate::{hash::RandomState, index_map, IndexMap};
+
+use super::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A hash map split into shards, each behind its own [`RwLock`].
+///
+/// Every operation locks the one shard its key hashes to, so threads working
+/// on different keys rarely wait for each other. A panic while a shard is
+/// locked does not poison the map.
+///
+/// # Consistency
+/// Operations on a single key are linearizable. Operations spanning the map,
+/// such as [`len`](Self::len), [`for_each`](Self::for_each) and
+/// [`retain`](Self::retain), lock one shard at a time: each shard is seen in a
+/// consistent state, but writes to shards that were already visited, or not
+/// visited yet, may or may not be observed. Keys never move between shards, so
+/// every key is visited at most once.
+///
+/// ```
+/// use nomicon::sync::ConcurrentHashMap;
+///
+/// let words = ConcurrentHashMap::new();
+/// std::thread::scope(|s| {
+///     for line in ["a b", "b c", "c a"] {
+///         let words = &words;
+///         s.spawn(move || {
+///             for word in line.split(' ') {
+///                 *words.entry(word).or_insert(0) += 1;
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(words.get("a", |count| *count), Some(2));
+/// assert_eq!(words.len(), 3);
+/// ```
+pub struct ConcurrentHashMap<K, V, S = RandomState> {
+    shards: Box<[RwLock<IndexMap<K, V, S>>]>,
+    hash_builder: S,
+}
+
+impl<K, V> ConcurrentHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S: Clone> ConcurrentHashMap<K, V, S> {
+    /// Returns a map with four shards for every available core.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let cores = std::thread::available_parallelism().map_or(8, usize::from);
+        Self::with_shards_and_hasher(cores * 4, hash_builder)
+    }
+
+    /// Returns a map split into `shards` shards, rounded up to a power of
+    /// two.
+    pub fn with_shards_and_hasher(shards: usize, hash_builder: S) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        Self {
+            shards: (0..shards)
+                .map(|_| RwLock::new(IndexMap::with_hasher(hash_builder.clone())))
+                .collect(),
+            hash_builder,
+        }
+    }
+}
+
+impl<K, V, S> ConcurrentHashMap<K, V, S> {
+    fn read(shard: &RwLock<IndexMap<K, V, S>>) -> RwLockReadGuard<'_, IndexMap<K, V, S>> {
+        // IndexMap operations never leave the table half updated, only a value
+        // can be, which the caller had access to anyway.
+        shard.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(shard: &RwLock<IndexMap<K, V, S>>) -> RwLockWriteGuard<'_, IndexMap<K, V, S>> {
+        shard.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of entries, see the
+    /// [consistency model](Self#consistency).
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| Self::read(shard).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| Self::read(shard).is_empty())
+    }
+
+    /// Remove every entry, one shard at a time.
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            Self::write(shard).clear();
+        }
+    }
+
+    /// Call `f` on every entry, holding a read lock on one shard at a time.
+    ///
+    /// Calling back into the map from `f` can deadlock when it writes to the
+    /// shard being visited.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in &self.shards {
+            Self::read(shard)
+                .iter()
+                .for_each(|(key, value)| f(key, value));
+        }
+    }
+
+    /// Keep only the entries `f` returns `true` for, holding a write lock on
+    /// one shard at a time.
+    pub fn retain(&self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        for shard in &self.shards {
+            Self::write(shard).retain(|key, value| f(key, value));
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> ConcurrentHashMap<K, V, S> {
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> &RwLock<IndexMap<K, V, S>> {
+        // The shard maps hash with the same hasher, use the high bits so the
+        // shard index does not correlate with their bucket index.
+        let hash = self.hash_builder.hash_one(key);
+        let bits = self.shards.len().trailing_zeros();
+        let index = hash.checked_shr(u64::BITS - bits).unwrap_or(0);
+        &self.shards[index as usize]
+    }
+
+    /// Insert `value` under `key`, returning the value it replaced.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        Self::write(self.shard(&key)).insert(key, value)
+    }
+
+    /// Call `f` with the value of `key`, while its shard is read locked.
+    ///
+    /// References can not outlive the lock, so values are reached through a
+    /// closure instead of being returned.
+    pub fn get<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        Self::read(self.shard(key)).get(key).map(f)
+    }
+
+    /// Returns a clone of the value of `key`.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Clone,
+    {
+        self.get(key, V::clone)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        Self::read(self.shard(key)).contains_key(key)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        Self::write(self.shard(key)).swap_remove(key)
+    }
+
+    /// Returns the entry of `key`, holding its shard write locked until the
+    /// entry, or the reference it turns into, is dropped.
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, S> {
+        Entry {
+            shard: Self::write(self.shard(&key)),
+            key,
+        }
+    }
+}
+
+impl<K, V> Default for ConcurrentHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Clone + Default> FromIterator<(K, V)>
+    for ConcurrentHashMap<K, V, S>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let map = Self::with_hasher(S::default());
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K, V, S> IntoIterator for ConcurrentHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    /// Owning the map means no other thread can reach it, so the entries are
+    /// a snapshot.
+    fn into_iter(self) -> IntoIter<K, V, S> {
+        IntoIter {
+            shards: self.shards.into_vec().into_iter(),
+            current: None,
+        }
+    }
+}
+
+/// An owning iterator over the entries of a [`ConcurrentHashMap`], shard by
+/// shard.
+///
+/// This type can be constructed through [`ConcurrentHashMap::into_iter`].
+pub struct IntoIter<K, V, S> {
+    shards: std::vec::IntoIter<RwLock<IndexMap<K, V, S>>>,
+    current: Option<index_map::IntoIter<K, V>>,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(entry);
+            }
+            let shard = self.shards.next()?;
+            let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+            self.current = Some(shard.into_iter());
+        }
+    }
+}
+
+/// A key and the write locked shard it belongs in.
+///
+/// This type can be constructed through [`ConcurrentHashMap::entry`].
+pub struct Entry<'a, K, V, S> {
+    shard: RwLockWriteGuard<'a, IndexMap<K, V, S>>,
+    key: K,
+}
+
+impl<'a, K: Eq + Hash, V, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Call `f` on the value, if the key is present.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Some(value) = self.shard.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+
+    pub fn or_insert(self, value: V) -> RefMut<'a, K, V, S> {
+        self.or_insert_with(|| value)
+    }
+
+    /// Insert the result of `f` if the key is absent. `f` runs with the shard
+    /// locked, so it must not touch keys of the same map.
+    pub fn or_insert_with(mut self, f: impl FnOnce() -> V) -> RefMut<'a, K, V, S> {
+        let index = match self.shard.get_index_of(&self.key) {
+            Some(index) => index,
+            None => {
+                self.shard.insert(self.key, f());
+                self.shard.len() - 1
+            }
+        };
+        let (_, value) = self.shard.get_index_mut(index).expect("the key is present");
+        let value = std::ptr::from_mut(value);
+        RefMut {
+            _shard: self.shard,
+            value,
+        }
+    }
+
+    pub fn or_default(self) -> RefMut<'a, K, V, S>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// A mutable reference to a value of a [`ConcurrentHashMap`], keeping its
+/// shard write locked.
+///
+/// This type can be constructed through [`Entry::or_insert`] and friends.
+pub struct RefMut<'a, K, V, S> {
+    _shard: RwLockWriteGuard<'a, IndexMap<K, V, S>>,
+    value: *mut V,
+}
+
+impl<K, V, S> Deref for RefMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // SAFETY
+        // * The value lives inside the locked shard, not the guard, and the
+        //   table can not change while the shard is locked.
+        unsafe { &*self.value }
+    }
+}
+
+impl<K, V, S> DerefMut for RefMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        // SAFETY
+        // * As above, and the shard is write locked.
+        unsafe { &mut *self.value }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn concurrent_updates() {
+        let map = ConcurrentHashMap::new();
+        std::thread::scope(|s| {
+            for t in 0..8u64 {
+                let map = &map;
+                s.spawn(move || {
+                    for key in 0..1_000u64 {
+                        *map.entry(key).or_default() += t;
+                        if key % 8 == t {
+                            map.insert(key + 1_000, t);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), 2_000);
+        assert_eq!(map.get(&10, |v| *v), Some((0..8).sum()));
+        assert_eq!(map.get_cloned(&1_003), Some(3));
+
+        map.retain(|key, _| *key < 1_000);
+        assert!(!map.contains_key(&1_003));
+        let mut total = 0;
+        map.for_each(|_, value| total += value);
+        assert_eq!(total, 28 * 1_000);
+        assert_eq!(map.into_iter().count(), 1_000);
+    }
+
+    #[test]
+    fn single_key_operations() {
+        let map = ConcurrentHashMap::with_shards_and_hasher(1, RandomState::new());
+        assert!(map.is_empty());
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("a", 2), Some(1));
+        let value = map.entry("a").and_modify(|v| *v *= 10).or_insert(0);
+        assert_eq!(*value, 20);
+        drop(value);
+        assert_eq!(map.remove("a"), Some(20));
+        assert_eq!(map.remove("a"), None);
+        map.insert("b", 1);
+        map.clear();
+        assert!(map.is_empty());
+    }
+}
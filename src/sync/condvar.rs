@@ -0,0 +1,161 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::{futex, LockResult, MutexGuard, PoisonError};
+
+/// Whether a timed wait on a [`Condvar`] returned because time ran out.
+///
+/// This type can be constructed through [`Condvar::wait_timeout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+/// A condition variable, putting threads to sleep until the data guarded by
+/// a [`Mutex`](super::Mutex) changes.
+///
+/// The condition variable is a counter bumped on every notification. A waiter
+/// reads it while still holding the lock, so a notification sent after the
+/// lock is released is never missed.
+///
+/// ```
+/// use nomicon::sync::{Condvar, Mutex};
+///
+/// let ready = Mutex::new(false);
+/// let changed = Condvar::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| {
+///         *ready.lock().unwrap() = true;
+///         changed.notify_all();
+///     });
+///     let guard = changed.wait_while(ready.lock().unwrap(), |ready| !*ready);
+///     assert!(*guard.unwrap());
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct Condvar {
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// Release the lock and sleep until notified, then lock again.
+    ///
+    /// Wakeups can be spurious, the condition has to be checked in a loop, or
+    /// through [`Condvar::wait_while`].
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        let seq = self.seq.load(Ordering::Relaxed);
+        let mutex = MutexGuard::mutex(&guard);
+        drop(guard);
+        futex::wait(&self.seq, seq);
+        mutex.lock()
+    }
+
+    /// Wait for as long as `condition` returns true.
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> LockResult<MutexGuard<'a, T>> {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// Like [`Condvar::wait`], giving up after `timeout`.
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Duration,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        let start = Instant::now();
+        let seq = self.seq.load(Ordering::Relaxed);
+        let mutex = MutexGuard::mutex(&guard);
+        drop(guard);
+        futex::wait_timeout(&self.seq, seq, timeout);
+        let timed_out = WaitTimeoutResult(start.elapsed() >= timeout);
+        match mutex.lock() {
+            Ok(guard) => Ok((guard, timed_out)),
+            Err(e) => Err(PoisonError::new((e.into_inner(), timed_out))),
+        }
+    }
+
+    /// Wait for as long as `condition` returns true, at most `timeout`.
+    pub fn wait_timeout_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        timeout: Duration,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        let deadline = Instant::now() + timeout;
+        while condition(&mut *guard) {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Ok((guard, WaitTimeoutResult(true)));
+            };
+            guard = self.wait_timeout(guard, remaining)?.0;
+        }
+        Ok((guard, WaitTimeoutResult(false)))
+    }
+
+    /// Wake one of the waiting threads.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex::wake_one(&self.seq);
+    }
+
+    /// Wake every waiting thread.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex::wake_all(&self.seq);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sync::Mutex;
+
+    #[test]
+    fn notifies_waiters() {
+        let counter = Mutex::new(0);
+        let changed = Condvar::new();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let guard = changed.wait_while(counter.lock().unwrap(), |n| *n < 100);
+                    assert_eq!(*guard.unwrap(), 100);
+                });
+            }
+            for _ in 0..100 {
+                *counter.lock().unwrap() += 1;
+                changed.notify_all();
+            }
+        });
+    }
+
+    #[test]
+    fn times_out() {
+        let mutex = Mutex::new(());
+        let condvar = Condvar::new();
+        let (_, result) = condvar
+            .wait_timeout(mutex.lock().unwrap(), Duration::from_millis(10))
+            .unwrap();
+        assert!(result.timed_out());
+        let (_, result) = condvar
+            .wait_timeout_while(mutex.lock().unwrap(), Duration::from_millis(10), |_| true)
+            .unwrap();
+        assert!(result.timed_out());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use super::{Mutex, MutexGuard, Parker, PoisonError, Unparker};
+
+struct State {
+    set: bool,
+    /// Parked threads, in the order they started waiting.
+    waiters: VecDeque<(usize, Unparker)>,
+}
+
+/// A flag threads can wait on, without a lock around any data.
+///
+/// A manual reset event stays set, waking every current and future waiter,
+/// until [`Event::reset`] is called. An auto reset event wakes a single
+/// waiter per [`Event::set`] and clears itself again, if no thread is waiting
+/// the signal is kept for the next one.
+///
+/// Waiting threads park in a queue, being removed from it is what tells
+/// them they were woken.
+///
+/// ```
+/// use nomicon::sync::Event;
+///
+/// let started = Event::manual();
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| started.wait());
+///     }
+///     started.set();
+/// });
+/// assert!(started.is_set());
+/// ```
+pub struct Event {
+    state: Mutex<State>,
+    auto_reset: bool,
+}
+
+impl Event {
+    const fn new(auto_reset: bool) -> Self {
+        Self {
+            state: Mutex::new(State {
+                set: false,
+                waiters: VecDeque::new(),
+            }),
+            auto_reset,
+        }
+    }
+
+    /// Returns an unset event that stays set until reset.
+    pub const fn manual() -> Self {
+        Self::new(false)
+    }
+
+    /// Returns an unset event that wakes one waiter per set.
+    pub const fn auto() -> Self {
+        Self::new(true)
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // The state is only changed by code that can not panic.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Signal the event.
+    pub fn set(&self) {
+        let mut state = self.state();
+        if self.auto_reset {
+            match state.waiters.pop_front() {
+                // The signal is handed over, the event stays unset.
+                Some((_, unparker)) => unparker.unpark(),
+                None => state.set = true,
+            }
+        } else {
+            state.set = true;
+            for (_, unparker) in state.waiters.drain(..) {
+                unparker.unpark();
+            }
+        }
+    }
+
+    /// Clear the event, later waiters block until it is set again.
+    pub fn reset(&self) {
+        self.state().set = false;
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.state().set
+    }
+
+    /// Block until the event is set, consuming the signal of an auto reset
+    /// event.
+    pub fn wait(&self) {
+        if let Some((_, parker)) = self.enqueue() {
+            // Only set unparks, after taking us off the queue.
+            parker.park();
+        }
+    }
+
+    /// Block until the event is set, for at most `timeout`.
+    ///
+    /// Returns true if the event was set.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let Some((id, parker)) = self.enqueue() else {
+            return true;
+        };
+        if parker.park_timeout(timeout) {
+            return true;
+        }
+        let mut state = self.state();
+        match state.waiters.iter().position(|(entry, _)| *entry == id) {
+            Some(index) => {
+                state.waiters.remove(index);
+                false
+            }
+            // The event was set right as the wait timed out.
+            None => true,
+        }
+    }
+
+    /// Returns a parker to block on, or `None` if the event is already set.
+    fn enqueue(&self) -> Option<(usize, Parker)> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let mut state = self.state();
+        if state.set {
+            if self.auto_reset {
+                state.set = false;
+            }
+            return None;
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let parker = Parker::new();
+        state.waiters.push_back((id, parker.unparker().clone()));
+        Some((id, parker))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manual_wakes_everyone() {
+        let event = Event::manual();
+        assert!(!event.wait_timeout(Duration::from_millis(5)));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| event.wait());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            event.set();
+        });
+        assert!(event.wait_timeout(Duration::ZERO));
+        event.reset();
+        assert!(!event.is_set());
+    }
+
+    #[test]
+    fn auto_wakes_one_per_set() {
+        let event = Event::auto();
+        let woken = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    event.wait();
+                    woken.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            for n in 1..=4 {
+                event.set();
+                while woken.load(Ordering::SeqCst) < n {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        assert_eq!(woken.into_inner(), 4);
+        assert!(!event.is_set());
+
+        // A set without waiters is kept for exactly one.
+        event.set();
+        event.wait();
+        assert!(!event.wait_timeout(Duration::from_millis(5)));
+    }
+}
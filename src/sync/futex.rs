@@ -0,0 +1,233 @@
+//! Blocking a thread until an atomic changes, the building block of the
+//! crate's sleeping locks.
+//!
+//! On Linux this is the `futex` syscall. Elsewhere threads are parked in a
+//! small table of queues keyed by the address of the atomic.
+
+use std::{sync::atomic::AtomicU32, time::Duration};
+
+/// Block while `atomic` holds `expected`.
+///
+/// May return spuriously, callers have to check the value again.
+pub(crate) fn wait(atomic: &AtomicU32, expected: u32) {
+    imp::wait(atomic, expected, None)
+}
+
+/// Block while `atomic` holds `expected`, for at most `timeout`.
+///
+/// May return spuriously, callers have to check the value and the time again.
+pub(crate) fn wait_timeout(atomic: &AtomicU32, expected: u32, timeout: Duration) {
+    imp::wait(atomic, expected, Some(timeout))
+}
+
+/// Wake one of the threads waiting on `atomic`.
+pub(crate) fn wake_one(atomic: &AtomicU32) {
+    imp::wake(atomic, 1)
+}
+
+/// Wake every thread waiting on `atomic`.
+pub(crate) fn wake_all(atomic: &AtomicU32) {
+    imp::wake(atomic, u32::MAX)
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+use linux as imp;
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+use parking as imp;
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linux {
+    use std::{sync::atomic::AtomicU32, time::Duration};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_FUTEX: i64 = 202;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_FUTEX: i64 = 98;
+
+    const FUTEX_WAIT_PRIVATE: i32 = 128;
+    const FUTEX_WAKE_PRIVATE: i32 = 129;
+
+    extern "C" {
+        // Provided by the C library std already links against.
+        fn syscall(number: i64, ...) -> i64;
+    }
+
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: i64,
+        tv_nsec: i64,
+    }
+
+    pub(super) fn wait(atomic: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        // A relative timeout, timeouts too large to represent wait forever.
+        let timespec = timeout.and_then(|timeout| {
+            Some(Timespec {
+                tv_sec: i64::try_from(timeout.as_secs()).ok()?,
+                tv_nsec: i64::from(timeout.subsec_nanos()),
+            })
+        });
+        let timespec_ptr = timespec
+            .as_ref()
+            .map_or(std::ptr::null(), std::ptr::from_ref);
+        // The kernel checks the value and goes to sleep atomically, so a wake
+        // between our check and the syscall is not lost.
+        unsafe {
+            syscall(
+                SYS_FUTEX,
+                atomic.as_ptr(),
+                FUTEX_WAIT_PRIVATE,
+                expected,
+                timespec_ptr,
+            )
+        };
+    }
+
+    pub(super) fn wake(atomic: &AtomicU32, count: u32) {
+        let count = count.min(i32::MAX as u32) as i32;
+        unsafe { syscall(SYS_FUTEX, atomic.as_ptr(), FUTEX_WAKE_PRIVATE, count) };
+    }
+}
+
+#[cfg(any(
+    test,
+    not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))
+))]
+mod parking {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU32, Ordering},
+            Arc,
+        },
+        thread::{self, Thread},
+        time::{Duration, Instant},
+    };
+
+    use crate::sync::SpinMutex;
+
+    const BUCKETS: usize = 64;
+
+    struct Waiter {
+        addr: usize,
+        thread: Thread,
+        woken: Arc<AtomicBool>,
+    }
+
+    /// Waiters are spread over buckets by address, a waker only has to look
+    /// through the threads that hashed to the same bucket.
+    static QUEUES: [SpinMutex<Vec<Waiter>>; BUCKETS] =
+        [const { SpinMutex::new(Vec::new()) }; BUCKETS];
+
+    fn queue(atomic: &AtomicU32) -> (usize, &'static SpinMutex<Vec<Waiter>>) {
+        let addr = atomic.as_ptr().addr();
+        // Atomics are 4 byte aligned, the low bits carry no information.
+        (addr, &QUEUES[(addr >> 2) % BUCKETS])
+    }
+
+    pub(in crate::sync) fn wait(atomic: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        let (addr, queue) = queue(atomic);
+        let woken = Arc::new(AtomicBool::new(false));
+        {
+            let mut waiters = queue.lock();
+            // Wakers change the value before taking the queue lock, checking
+            // under the lock means their wake cannot be missed.
+            if atomic.load(Ordering::Relaxed) != expected {
+                return;
+            }
+            waiters.push(Waiter {
+                addr,
+                thread: thread::current(),
+                woken: Arc::clone(&woken),
+            });
+        }
+        while !woken.load(Ordering::Acquire) {
+            let Some(deadline) = deadline else {
+                thread::park();
+                continue;
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                // Leave the queue, unless a waker got to us first.
+                queue
+                    .lock()
+                    .retain(|waiter| !Arc::ptr_eq(&waiter.woken, &woken));
+                return;
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+
+    pub(in crate::sync) fn wake(atomic: &AtomicU32, count: u32) {
+        let (addr, queue) = queue(atomic);
+        let mut waiters = queue.lock();
+        let mut woken = 0;
+        let mut i = 0;
+        while i < waiters.len() && woken < count {
+            if waiters[i].addr == addr {
+                // Preserve the order of the remaining waiters, first come
+                // first woken.
+                let waiter = waiters.remove(i);
+                waiter.woken.store(true, Ordering::Release);
+                waiter.thread.unpark();
+                woken += 1;
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    fn wakes_waiters(wait: fn(&AtomicU32, u32, Option<Duration>), wake_all: fn(&AtomicU32)) {
+        let atomic = AtomicU32::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while atomic.load(Ordering::Acquire) == 0 {
+                        wait(&atomic, 0, None);
+                    }
+                });
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            atomic.store(1, Ordering::Release);
+            wake_all(&atomic);
+        });
+        // Waiting on a stale value returns immediately.
+        wait(&atomic, 0, None);
+
+        let start = std::time::Instant::now();
+        wait(&atomic, 1, Some(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn futex() {
+        wakes_waiters(imp::wait, super::wake_all);
+    }
+
+    #[test]
+    fn parking() {
+        wakes_waiters(parking::wait, |atomic| parking::wake(atomic, u32::MAX));
+    }
+}
@@ -0,0 +1,99 @@
+use std::{cell::UnsafeCell, ops::Deref};
+
+use super::OnceLock;
+
+/// A value initialized on first access, which can be shared between threads.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use nomicon::sync::LazyLock;
+///
+/// static SQUARES: LazyLock<HashMap<u32, u32>> =
+///     LazyLock::new(|| (1..=10).map(|n| (n, n * n)).collect());
+///
+/// assert_eq!(SQUARES.get(&7), Some(&49));
+/// ```
+pub struct LazyLock<T, F = fn() -> T> {
+    once: OnceLock<T>,
+    /// Taken by the thread running the initializer.
+    init: UnsafeCell<Option<F>>,
+}
+
+// The initializer is moved to, and run on, whichever thread gets there first.
+unsafe impl<T: Send + Sync, F: Send> Sync for LazyLock<T, F> {}
+
+impl<T, F: FnOnce() -> T> LazyLock<T, F> {
+    pub const fn new(init: F) -> Self {
+        Self {
+            once: OnceLock::new(),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    /// Returns the value, running the initializer if this is the first access.
+    ///
+    /// # Panics
+    /// If the initializer panicked on an earlier access.
+    pub fn force(this: &Self) -> &T {
+        this.once.get_or_init(|| {
+            // SAFETY
+            // * The OnceLock runs a single initializer at a time, so only one
+            //   thread ever reaches the Option.
+            match unsafe { (*this.init.get()).take() } {
+                Some(init) => init(),
+                None => panic!("LazyLock initializer panicked"),
+            }
+        })
+    }
+
+    /// Returns the value if it has been initialized.
+    pub fn get(this: &Self) -> Option<&T> {
+        this.once.get()
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for LazyLock<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for LazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn runs_once_on_first_deref() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static LAZY: LazyLock<Vec<u8>> = LazyLock::new(|| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            vec![1, 2, 3]
+        });
+
+        assert!(LazyLock::get(&LAZY).is_none());
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| assert_eq!(LAZY.len(), 3));
+            }
+        });
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn captures_environment() {
+        let base = String::from("base");
+        let lazy = LazyLock::new(|| base.len() * 2);
+        assert_eq!(*lazy, 8);
+    }
+}
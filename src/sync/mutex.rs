@@ -0,0 +1,272 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::{
+    futex,
+    poison::{self, LockResult, TryLockError, TryLockResult},
+    Backoff,
+};
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// Locked, and other threads may be asleep waiting for the lock.
+const CONTENDED: u32 = 2;
+
+/// A mutual exclusion lock that puts waiting threads to sleep.
+///
+/// A thread finding the lock taken spins for a short while, in case the lock
+/// is released soon, then sleeps until the owner wakes it up on unlock.
+///
+/// A thread panicking while holding the lock poisons it, later attempts to
+/// lock it return a [`PoisonError`](super::PoisonError).
+///
+/// ```
+/// use nomicon::sync::Mutex;
+///
+/// let names = Mutex::new(Vec::new());
+/// std::thread::scope(|s| {
+///     for name in ["a", "b", "c"] {
+///         let names = &names;
+///         s.spawn(move || names.lock().unwrap().push(name));
+///     }
+/// });
+/// let mut names = names.into_inner().unwrap();
+/// names.sort();
+/// assert_eq!(names, ["a", "b", "c"]);
+/// ```
+pub struct Mutex<T: ?Sized> {
+    state: AtomicU32,
+    poison: poison::Flag,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+// Poisoning tells a thread observing a panic's aftermath through the lock.
+impl<T: ?Sized> UnwindSafe for Mutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            poison: poison::Flag::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the lock, returning the value, even if the lock is poisoned.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.poison.get();
+        let value = self.value.into_inner();
+        if poisoned {
+            Err(poison::PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Block until the lock is acquired.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        self.lock_raw();
+        poison::map_result(&self.poison, self.guard())
+    }
+
+    /// Block until the lock is acquired, without handing out a guard. The
+    /// caller is responsible for calling [`Mutex::unlock`].
+    pub(super) fn lock_raw(&self) {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        let backoff = Backoff::new();
+        while self.state.load(Ordering::Relaxed) == LOCKED && !backoff.is_completed() {
+            backoff.snooze();
+        }
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+        // From here on the lock is taken as contended, we cannot know whether
+        // other threads are still asleep once we own it.
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            futex::wait(&self.state, CONTENDED);
+        }
+    }
+
+    /// Returns a guard if the lock is free.
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        if !self.try_lock_raw() {
+            return Err(TryLockError::WouldBlock);
+        }
+        Ok(poison::map_result(&self.poison, self.guard())?)
+    }
+
+    /// Acquire the lock if it is free, without handing out a guard.
+    pub(super) fn try_lock_raw(&self) -> bool {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Call with the lock held.
+    fn guard(&self) -> MutexGuard<'_, T> {
+        MutexGuard {
+            mutex: self,
+            poison: self.poison.guard(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Mark the lock as no longer poisoned, once the value is known to be
+    /// valid again.
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    /// Returns a mutable reference to the value, no locking is needed as the
+    /// borrow is exclusive.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.poison.get();
+        let value = self.value.get_mut();
+        if poisoned {
+            Err(poison::PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    pub(super) fn unlock(&self) {
+        // Only pay for the syscall if someone may be asleep.
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex::wake_one(&self.state);
+        }
+    }
+}
+
+/// Exclusive access to the value of a [`Mutex`], releasing the lock when
+/// dropped.
+///
+/// This type can be constructed through [`Mutex::lock`] and
+/// [`Mutex::try_lock`].
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    poison: poison::Guard,
+    /// Only share the guard between threads if the value can be shared.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Returns the mutex the guard locks, for waiting on a condition variable.
+    pub(super) fn mutex(this: &Self) -> &'a Mutex<T> {
+        this.mutex
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * The guard holds the lock.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY
+        // * The guard holds the lock, and is borrowed exclusively.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.poison.done(&self.poison);
+        self.mutex.unlock();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn contention() {
+        let mutex = Mutex::new(0u64);
+        std::thread::scope(|s| {
+            for _ in 0..16 {
+                s.spawn(|| {
+                    for _ in 0..10_000 {
+                        *mutex.lock().unwrap() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(mutex.into_inner().unwrap(), 160_000);
+    }
+
+    #[test]
+    fn sleeps_while_held() {
+        let mutex = Mutex::new(Vec::new());
+        let guard = mutex.lock().unwrap();
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| mutex.lock().unwrap().push(2));
+            // Long enough for the waiter to give up spinning and sleep.
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!waiter.is_finished());
+            assert!(matches!(mutex.try_lock(), Err(TryLockError::WouldBlock)));
+            let mut guard = guard;
+            guard.push(1);
+        });
+        assert_eq!(mutex.into_inner().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn poisons_on_panic() {
+        let mut mutex = Mutex::new(0);
+        let result = std::panic::catch_unwind(|| {
+            let mut guard = mutex.lock().unwrap();
+            *guard += 1;
+            panic!("while holding the lock");
+        });
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+        assert!(matches!(mutex.try_lock(), Err(TryLockError::Poisoned(_))));
+        assert_eq!(mutex.get_mut().map_err(|e| *e.into_inner()), Err(1));
+
+        mutex.clear_poison();
+        assert_eq!(mutex.into_inner().unwrap(), 1);
+    }
+}
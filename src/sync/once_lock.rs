@@ -0,0 +1,187 @@
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::futex;
+
+const INCOMPLETE: u32 = 0;
+const RUNNING: u32 = 1;
+const COMPLETE: u32 = 2;
+
+/// A cell that is written to at most once, and can then be read from any
+/// thread.
+///
+/// ```
+/// use nomicon::sync::OnceLock;
+///
+/// static CONFIG: OnceLock<String> = OnceLock::new();
+///
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| CONFIG.get_or_init(|| "loaded".to_string()));
+///     }
+/// });
+/// assert_eq!(CONFIG.get().map(String::as_str), Some("loaded"));
+/// assert!(CONFIG.set("again".to_string()).is_err());
+/// ```
+pub struct OnceLock<T> {
+    state: AtomicU32,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// The value is written by one thread, and read from every thread after.
+unsafe impl<T: Send> Send for OnceLock<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+// A panicking initializer leaves the cell uninitialized, never half written.
+impl<T: RefUnwindSafe + UnwindSafe> RefUnwindSafe for OnceLock<T> {}
+impl<T: UnwindSafe> UnwindSafe for OnceLock<T> {}
+
+impl<T> OnceLock<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(INCOMPLETE),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns the value if it has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        // Acquire pairs with the Release storing COMPLETE, making the value
+        // visible.
+        if self.state.load(Ordering::Acquire) == COMPLETE {
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if *self.state.get_mut() == COMPLETE {
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Initialize the cell with `value`, handing it back if the cell was
+    /// already initialized.
+    ///
+    /// Blocks while another thread is initializing the cell.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    /// Returns the value, initializing it with `f` if it is not.
+    ///
+    /// Only one thread runs its `f`, others block until it is done. If `f`
+    /// panics the cell is left uninitialized and the next caller tries again.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        self.initialize(f);
+        self.get().expect("OnceLock is initialized")
+    }
+
+    #[cold]
+    fn initialize(&self, f: impl FnOnce() -> T) {
+        loop {
+            match self.state.compare_exchange(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(COMPLETE) => return,
+                Err(_) => futex::wait(&self.state, RUNNING),
+            }
+        }
+
+        /// Lets waiting threads retry if `f` panics.
+        struct Reset<'a>(&'a AtomicU32);
+
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.store(INCOMPLETE, Ordering::Release);
+                futex::wake_all(self.0);
+            }
+        }
+
+        let reset = Reset(&self.state);
+        let value = f();
+        std::mem::forget(reset);
+        // SAFETY
+        // * Only the thread that moved the state to RUNNING writes the value.
+        unsafe { (*self.value.get()).write(value) };
+        self.state.store(COMPLETE, Ordering::Release);
+        futex::wake_all(&self.state);
+    }
+
+    pub fn into_inner(mut self) -> Option<T> {
+        if *self.state.get_mut() != COMPLETE {
+            return None;
+        }
+        *self.state.get_mut() = INCOMPLETE;
+        Some(unsafe { self.value.get_mut().assume_init_read() })
+    }
+}
+
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.value.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn initializes_once() {
+        let calls = AtomicUsize::new(0);
+        let once = OnceLock::new();
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    let value = once.get_or_init(|| {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        42
+                    });
+                    assert_eq!(*value, 42);
+                });
+            }
+        });
+        assert_eq!(calls.into_inner(), 1);
+        assert_eq!(once.into_inner(), Some(42));
+    }
+
+    #[test]
+    fn retries_after_panic() {
+        let once = OnceLock::new();
+        let result = std::panic::catch_unwind(|| once.get_or_init(|| panic!("init failed")));
+        assert!(result.is_err());
+        assert!(once.get().is_none());
+        assert_eq!(*once.get_or_init(|| 1), 1);
+    }
+}
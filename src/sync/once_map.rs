@@ -0,0 +1,168 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, panic::RefUnwindSafe};
+
+use super::{Mutex, MutexGuard, OnceLock, PoisonError};
+use crate::marker::PhantomUnsync;
+
+/// A map whose values are computed at most once, and never move or change
+/// afterwards.
+///
+/// Each value lives in its own [`OnceLock`], boxed so it keeps its address as
+/// the map grows. The map lock is only held to find or insert the cell, the
+/// value is computed outside of it, so slow initializers for different keys
+/// run in parallel while callers racing on the same key wait for the first.
+///
+/// ```
+/// use nomicon::sync::OnceMap;
+///
+/// let compiled = OnceMap::new();
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| {
+///             let program = compiled.get_or_init("main", || "main".to_uppercase());
+///             assert_eq!(program, "MAIN");
+///         });
+///     }
+/// });
+/// assert_eq!(compiled.len(), 1);
+/// ```
+///
+/// Sharing the map shares its values, so it is only Sync for Sync values.
+///
+/// ```compile_fail
+/// use nomicon::{cell::Cell, sync::OnceMap};
+///
+/// let counters = OnceMap::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| counters.get_or_init(0, || Cell::new(0)).set(1));
+///     counters.get_or_init(0, || Cell::new(0)).set(2);
+/// });
+/// ```
+pub struct OnceMap<K, V> {
+    map: Mutex<HashMap<K, Box<OnceLock<V>>>>,
+    /// The lock alone would make the map Sync for values that are only Send.
+    _not_sync: PhantomUnsync,
+}
+
+// SAFETY
+// * Callers on other threads get a &V and insert keys through the lock.
+unsafe impl<K: Send + Sync, V: Send + Sync> Sync for OnceMap<K, V> {}
+
+/// The marker is not, but a panicking initializer leaves its cell empty.
+impl<K: RefUnwindSafe, V: RefUnwindSafe> RefUnwindSafe for OnceMap<K, V> {}
+
+impl<K, V> OnceMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            map: Mutex::new(HashMap::new()),
+            _not_sync: PhantomUnsync::new(),
+        }
+    }
+
+    /// Returns the number of keys, including those still being initialized.
+    pub fn len(&self) -> usize {
+        self.map().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Consume the map, returning every initialized value.
+    pub fn into_hash_map(self) -> HashMap<K, V>
+    where
+        K: Eq + Hash,
+    {
+        self.map
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_iter()
+            .filter_map(|(key, cell)| Some((key, cell.into_inner()?)))
+            .collect()
+    }
+
+    fn map(&self) -> MutexGuard<'_, HashMap<K, Box<OnceLock<V>>>> {
+        // Cells are inserted in a single step, a panic never leaves the map
+        // half updated.
+        self.map.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Eq + Hash, V> OnceMap<K, V> {
+    /// Returns the value of `key`, if it has been initialized.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let cell = std::ptr::from_ref(&**self.map().get(key)?);
+        // SAFETY
+        // * Cells are boxed and never removed while the map is borrowed.
+        unsafe { (*cell).get() }
+    }
+
+    /// Returns the value of `key`, computing it with `f` if no other caller
+    /// has.
+    ///
+    /// If `f` panics the key is left uninitialized, and the next caller tries
+    /// again.
+    pub fn get_or_init(&self, key: K, f: impl FnOnce() -> V) -> &V {
+        let cell = std::ptr::from_ref(&**self.map().entry(key).or_default());
+        // SAFETY
+        // * Cells are boxed and never removed while the map is borrowed, so
+        //   the cell outlives the map lock.
+        unsafe { (*cell).get_or_init(f) }
+    }
+}
+
+impl<K, V> Default for OnceMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn initializes_once() {
+        let map = OnceMap::new();
+        let calls = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let (map, calls) = (&map, &calls);
+                s.spawn(move || {
+                    for key in 0..100 {
+                        let value = map.get_or_init(key, || {
+                            calls.fetch_add(1, Ordering::Relaxed);
+                            vec![key; t % 3 + 1]
+                        });
+                        assert_eq!(value[0], key);
+                    }
+                });
+            }
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 100);
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.into_hash_map().len(), 100);
+    }
+
+    #[test]
+    fn references_stay_valid() {
+        let map = OnceMap::new();
+        let first = map.get_or_init(0, || String::from("zero"));
+        for n in 1..1_000 {
+            map.get_or_init(n, || n.to_string());
+        }
+        assert_eq!(first, "zero");
+        assert_eq!(map.get(&999).map(String::as_str), Some("999"));
+        assert_eq!(map.get(&1_000), None);
+
+        let result = std::panic::catch_unwind(|| map.get_or_init(1_000, || panic!("retried")));
+        assert!(result.is_err());
+        assert_eq!(map.get(&1_000), None);
+        assert_eq!(map.get_or_init(1_000, String::new), "");
+    }
+}
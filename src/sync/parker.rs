@@ -0,0 +1,155 @@
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::futex;
+use crate::arc::Arc;
+
+const EMPTY: u32 = 0;
+const NOTIFIED: u32 = 1;
+/// One less than [`EMPTY`], wrapping around.
+const PARKED: u32 = u32::MAX;
+
+/// Blocks the thread it belongs to until woken through an [`Unparker`].
+///
+/// Parking consumes a token, and unparking provides one, so a call to
+/// [`Unparker::unpark`] made before the thread parks is not lost. At most one
+/// token is kept around.
+///
+/// ```
+/// use nomicon::sync::Parker;
+///
+/// let parker = Parker::new();
+/// let unparker = parker.unparker().clone();
+/// std::thread::spawn(move || unparker.unpark());
+/// // Returns once the other thread unparks us, or right away if it already
+/// // has.
+/// parker.park();
+/// ```
+pub struct Parker {
+    unparker: Unparker,
+    /// Only the owning thread parks, the parker can move between threads but
+    /// not be shared.
+    _marker: PhantomData<crate::cell::Cell<()>>,
+}
+
+/// Wakes the thread blocked on a [`Parker`].
+///
+/// This type can be constructed through [`Parker::unparker`].
+#[derive(Clone)]
+pub struct Unparker {
+    state: Arc<AtomicU32>,
+}
+
+impl Parker {
+    pub fn new() -> Self {
+        Self {
+            unparker: Unparker {
+                state: Arc::new(AtomicU32::new(EMPTY)),
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn unparker(&self) -> &Unparker {
+        &self.unparker
+    }
+
+    /// Block until a token is available, and consume it.
+    pub fn park(&self) {
+        let state = &*self.unparker.state;
+        // NOTIFIED becomes EMPTY, EMPTY becomes PARKED. Acquire pairs with
+        // the Release in unpark, making the unparker's writes visible.
+        if state.fetch_sub(1, Ordering::Acquire) == NOTIFIED {
+            return;
+        }
+        loop {
+            futex::wait(state, PARKED);
+            if state
+                .compare_exchange(NOTIFIED, EMPTY, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Block until a token is available or `timeout` has passed.
+    ///
+    /// Returns true if a token was consumed.
+    pub fn park_timeout(&self, timeout: Duration) -> bool {
+        let state = &*self.unparker.state;
+        if state.fetch_sub(1, Ordering::Acquire) == NOTIFIED {
+            return true;
+        }
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => Duration::MAX,
+            };
+            if remaining.is_zero() {
+                // Give up, unless the token arrived in the meantime.
+                return state.swap(EMPTY, Ordering::Acquire) == NOTIFIED;
+            }
+            futex::wait_timeout(state, PARKED, remaining);
+            if state
+                .compare_exchange(NOTIFIED, EMPTY, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return true;
+            }
+        }
+    }
+}
+
+impl Default for Parker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Unparker {
+    /// Make a token available, waking the thread if it is parked.
+    pub fn unpark(&self) {
+        if self.state.swap(NOTIFIED, Ordering::Release) == PARKED {
+            futex::wake_one(&self.state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_is_kept() {
+        let parker = Parker::new();
+        parker.unparker().unpark();
+        parker.unparker().unpark();
+        // Only one token is kept.
+        parker.park();
+        assert!(!parker.park_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn wakes_parked_thread() {
+        let parker = Parker::new();
+        let unparker = parker.unparker().clone();
+        let handle = std::thread::spawn(move || {
+            for _ in 0..100 {
+                parker.park();
+            }
+            parker.park_timeout(Duration::from_secs(10))
+        });
+        // Keep unparking until the thread quits, tokens sent while it is
+        // running are coalesced.
+        while !handle.is_finished() {
+            unparker.unpark();
+            std::thread::yield_now();
+        }
+        assert!(handle.join().unwrap());
+    }
+}
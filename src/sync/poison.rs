@@ -0,0 +1,159 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+/// The error returned when a lock is acquired after a thread panicked while
+/// holding it.
+///
+/// The data may not uphold its invariants any more, but the guard is still
+/// handed out through [`PoisonError::into_inner`] for callers that can
+/// recover.
+///
+/// ```
+/// use nomicon::sync::{Mutex, PoisonError};
+///
+/// let mutex = Mutex::new(1);
+/// std::thread::scope(|s| {
+///     let _ = s
+///         .spawn(|| {
+///             let _guard = mutex.lock().unwrap();
+///             panic!("poisons the mutex");
+///         })
+///         .join();
+/// });
+///
+/// assert!(mutex.is_poisoned());
+/// let guard = mutex.lock().unwrap_or_else(PoisonError::into_inner);
+/// assert_eq!(*guard, 1);
+/// drop(guard);
+///
+/// mutex.clear_poison();
+/// assert!(mutex.lock().is_ok());
+/// ```
+pub struct PoisonError<T> {
+    guard: T,
+}
+
+impl<T> PoisonError<T> {
+    pub fn new(guard: T) -> Self {
+        Self { guard }
+    }
+
+    /// Returns the guard, ignoring the poison.
+    pub fn into_inner(self) -> T {
+        self.guard
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.guard
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> fmt::Debug for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("poisoned lock: another task failed inside")
+    }
+}
+
+impl<T> Error for PoisonError<T> {}
+
+/// The error returned by the `try_*` methods of locks.
+pub enum TryLockError<T> {
+    /// The lock was acquired, but is poisoned.
+    Poisoned(PoisonError<T>),
+    /// The lock is held elsewhere.
+    WouldBlock,
+}
+
+impl<T> From<PoisonError<T>> for TryLockError<T> {
+    fn from(err: PoisonError<T>) -> Self {
+        Self::Poisoned(err)
+    }
+}
+
+impl<T> fmt::Debug for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned(err) => f.debug_tuple("Poisoned").field(err).finish(),
+            Self::WouldBlock => f.write_str("WouldBlock"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned(err) => err.fmt(f),
+            Self::WouldBlock => f.write_str("try_lock failed because the operation would block"),
+        }
+    }
+}
+
+impl<T> Error for TryLockError<T> {}
+
+pub type LockResult<G> = Result<G, PoisonError<G>>;
+
+pub type TryLockResult<G> = Result<G, TryLockError<G>>;
+
+/// Tracks whether a lock is poisoned.
+pub(super) struct Flag {
+    failed: AtomicBool,
+}
+
+/// Remembers whether the thread was already panicking when it took the lock,
+/// only a panic starting while the lock is held poisons it.
+pub(super) struct Guard {
+    panicking: bool,
+}
+
+impl Flag {
+    pub(super) const fn new() -> Self {
+        Self {
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Call with the lock held.
+    pub(super) fn guard(&self) -> Guard {
+        Guard {
+            panicking: thread::panicking(),
+        }
+    }
+
+    /// Call with the lock held, right before releasing it.
+    pub(super) fn done(&self, guard: &Guard) {
+        if !guard.panicking && thread::panicking() {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub(super) fn get(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn clear(&self) {
+        self.failed.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Wrap `guard` in a [`PoisonError`] if the lock is poisoned.
+pub(super) fn map_result<G>(flag: &Flag, guard: G) -> LockResult<G> {
+    if flag.get() {
+        Err(PoisonError::new(guard))
+    } else {
+        Ok(guard)
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::Mutex;
+
+/// A lock that can be locked again by the thread already holding it.
+///
+/// As the same thread can hold several guards at once, the guards only hand
+/// out shared access. Pair it with a [`Cell`](crate::cell::Cell) or
+/// [`RefCell`](crate::cell::RefCell) to mutate the value.
+///
+/// ```
+/// use std::cell::RefCell;
+///
+/// use nomicon::sync::ReentrantMutex;
+///
+/// let log = ReentrantMutex::new(RefCell::new(String::new()));
+/// let outer = log.lock();
+/// outer.borrow_mut().push_str("outer ");
+/// {
+///     // Relocking on the same thread does not deadlock.
+///     let inner = log.lock();
+///     inner.borrow_mut().push_str("inner");
+/// }
+/// drop(outer);
+/// assert_eq!(*log.lock().borrow(), "outer inner");
+/// ```
+pub struct ReentrantMutex<T: ?Sized> {
+    mutex: Mutex<()>,
+    /// The id of the owning thread, or zero when unlocked.
+    owner: AtomicUsize,
+    /// How many guards the owner holds, only touched by the owner.
+    depth: UnsafeCell<usize>,
+    value: T,
+}
+
+// Only the owning thread reaches the value, one thread at a time.
+unsafe impl<T: ?Sized + Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ReentrantMutex<T> {}
+
+/// Returns a non-zero id unique to the current thread.
+///
+/// Ids are never reused, a thread local's address could be handed to a new
+/// thread while a lock still names the exited one as its owner.
+fn current_thread() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(1);
+    thread_local! {
+        static ID: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
+
+impl<T> ReentrantMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            owner: AtomicUsize::new(0),
+            depth: UnsafeCell::new(0),
+            value,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> ReentrantMutex<T> {
+    /// Block until the lock is acquired, returning right away if this thread
+    /// already holds it.
+    ///
+    /// # Panics
+    /// If the thread locks it more than `usize::MAX` times.
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+        let this_thread = current_thread();
+        // Relaxed is enough: only this thread ever stores its own id, so
+        // seeing it means this thread is the owner.
+        if self.owner.load(Ordering::Relaxed) == this_thread {
+            self.increment();
+        } else {
+            self.mutex.lock_raw();
+            self.owner.store(this_thread, Ordering::Relaxed);
+            // SAFETY
+            // * The lock is held.
+            unsafe { *self.depth.get() = 1 };
+        }
+        ReentrantMutexGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a guard if the lock is free or already held by this thread.
+    pub fn try_lock(&self) -> Option<ReentrantMutexGuard<'_, T>> {
+        let this_thread = current_thread();
+        if self.owner.load(Ordering::Relaxed) == this_thread {
+            self.increment();
+        } else {
+            if !self.mutex.try_lock_raw() {
+                return None;
+            }
+            self.owner.store(this_thread, Ordering::Relaxed);
+            unsafe { *self.depth.get() = 1 };
+        }
+        Some(ReentrantMutexGuard {
+            lock: self,
+            _marker: PhantomData,
+        })
+    }
+
+    fn increment(&self) {
+        // SAFETY
+        // * Only the owning thread touches the depth.
+        let depth = unsafe { &mut *self.depth.get() };
+        *depth = depth
+            .checked_add(1)
+            .expect("ReentrantMutex depth overflown");
+    }
+}
+
+/// Shared access to the value of a [`ReentrantMutex`], releasing one level of
+/// the lock when dropped.
+///
+/// This type can be constructed through [`ReentrantMutex::lock`] and
+/// [`ReentrantMutex::try_lock`].
+pub struct ReentrantMutexGuard<'a, T: ?Sized> {
+    lock: &'a ReentrantMutex<T>,
+    /// The guard has to be dropped on the thread that owns the lock.
+    _marker: PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for ReentrantMutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for ReentrantMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.lock.value
+    }
+}
+
+impl<T: ?Sized> Drop for ReentrantMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY
+        // * Guards are !Send, so this is the owning thread.
+        let depth = unsafe { &mut *self.lock.depth.get() };
+        *depth -= 1;
+        if *depth == 0 {
+            self.lock.owner.store(0, Ordering::Relaxed);
+            self.lock.mutex.unlock();
+        }
+    }
+}
+
+impl<T: Default> Default for ReentrantMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn relocks_on_same_thread() {
+        let lock = ReentrantMutex::new(crate::cell::Cell::new(0));
+        let guards = (0..10).map(|_| lock.lock()).collect::<Vec<_>>();
+        for guard in &guards {
+            guard.set(guard.get() + 1);
+        }
+
+        std::thread::scope(|s| {
+            let blocked = s.spawn(|| lock.try_lock().is_none());
+            assert!(blocked.join().unwrap());
+        });
+        drop(guards);
+        assert!(lock.try_lock().is_some());
+        assert_eq!(lock.into_inner().get(), 10);
+    }
+
+    #[test]
+    fn excludes_other_threads() {
+        let lock = ReentrantMutex::new(crate::cell::RefCell::new(0u64));
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..1_000 {
+                        let outer = lock.lock();
+                        let inner = lock.lock();
+                        *inner.borrow_mut() += 1;
+                        drop(outer);
+                    }
+                });
+            }
+        });
+        assert_eq!(*lock.lock().borrow(), 8_000);
+    }
+}
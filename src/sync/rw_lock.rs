@@ -0,0 +1,406 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::{
+    futex,
+    poison::{self, LockResult, PoisonError, TryLockError, TryLockResult},
+};
+
+/// The state of a write locked [`RwLock`].
+const WRITE_LOCKED: u32 = u32::MAX;
+
+/// A reader-writer lock, allowing either many readers or a single writer.
+///
+/// The whole lock is a single state word: twice the number of readers, plus
+/// one if a writer is waiting, or [`u32::MAX`] while write locked. New readers
+/// are held back while a writer waits, so a steady stream of readers cannot
+/// starve writers.
+///
+/// A thread panicking while holding a write lock poisons it, readers and
+/// writers coming after it get a [`PoisonError`].
+///
+/// ```
+/// use nomicon::sync::RwLock;
+///
+/// let lock = RwLock::new(5);
+/// {
+///     let a = lock.read().unwrap();
+///     let b = lock.read().unwrap();
+///     assert_eq!(*a + *b, 10);
+///     assert!(lock.try_write().is_err());
+/// }
+/// *lock.write().unwrap() += 1;
+/// assert_eq!(*lock.read().unwrap(), 6);
+/// ```
+pub struct RwLock<T: ?Sized> {
+    state: AtomicU32,
+    /// Bumped every time writers should look at the state again, waiting
+    /// writers sleep on this instead of the state.
+    writer_wake: AtomicU32,
+    poison: poison::Flag,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+// Readers share the value between threads.
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T: ?Sized> UnwindSafe for RwLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            writer_wake: AtomicU32::new(0),
+            poison: poison::Flag::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the lock, returning the value, even if the lock is poisoned.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.poison.get();
+        let value = self.value.into_inner();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Block until the lock can be shared.
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            // An odd state is either write locked or has a waiting writer.
+            if state.is_multiple_of(2) {
+                assert!(state < WRITE_LOCKED - 2, "too many readers");
+                match self.state.compare_exchange_weak(
+                    state,
+                    state + 2,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return poison::map_result(&self.poison, self.read_guard()),
+                    Err(e) => state = e,
+                }
+            }
+            if !state.is_multiple_of(2) {
+                futex::wait(&self.state, state);
+                state = self.state.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns a read guard if the lock is not write locked and no writer is
+    /// waiting.
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state.is_multiple_of(2) {
+            assert!(state < WRITE_LOCKED - 2, "too many readers");
+            match self.state.compare_exchange_weak(
+                state,
+                state + 2,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(poison::map_result(&self.poison, self.read_guard())?),
+                Err(e) => state = e,
+            }
+        }
+        Err(TryLockError::WouldBlock)
+    }
+
+    /// Block until the lock is exclusively ours.
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            // Unlocked, possibly with other writers waiting.
+            if state <= 1 {
+                match self.state.compare_exchange(
+                    state,
+                    WRITE_LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return poison::map_result(&self.poison, self.write_guard()),
+                    Err(e) => {
+                        state = e;
+                        continue;
+                    }
+                }
+            }
+            // Hold back new readers.
+            if state.is_multiple_of(2) {
+                if let Err(e) = self.state.compare_exchange(
+                    state,
+                    state + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = e;
+                    continue;
+                }
+            }
+            // Read the counter before checking the state again, a wake in
+            // between changes the counter and the wait returns immediately.
+            let wake = self.writer_wake.load(Ordering::Acquire);
+            state = self.state.load(Ordering::Relaxed);
+            if state >= 2 {
+                futex::wait(&self.writer_wake, wake);
+                state = self.state.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns a write guard if the lock is not held.
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state <= 1 {
+            match self.state.compare_exchange(
+                state,
+                WRITE_LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(poison::map_result(&self.poison, self.write_guard())?),
+                Err(e) => state = e,
+            }
+        }
+        Err(TryLockError::WouldBlock)
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Mark the lock as no longer poisoned, once the value is known to be
+    /// valid again.
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    /// Returns a mutable reference to the value, no locking is needed as the
+    /// borrow is exclusive.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.poison.get();
+        let value = self.value.get_mut();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn read_guard(&self) -> RwLockReadGuard<'_, T> {
+        RwLockReadGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+
+    fn write_guard(&self) -> RwLockWriteGuard<'_, T> {
+        RwLockWriteGuard {
+            lock: self,
+            poison: self.poison.guard(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn wake_writer(&self) {
+        self.writer_wake.fetch_add(1, Ordering::Release);
+        futex::wake_one(&self.writer_wake);
+    }
+}
+
+/// Shared access to the value of a [`RwLock`], releasing the lock when
+/// dropped.
+///
+/// This type can be constructed through [`RwLock::read`],
+/// [`RwLock::try_read`] and [`RwLockWriteGuard::downgrade`].
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * The guard holds a read lock, no writer can exist.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        // The last reader leaving with a writer waiting.
+        if self.lock.state.fetch_sub(2, Ordering::Release) == 3 {
+            self.lock.wake_writer();
+        }
+    }
+}
+
+/// Exclusive access to the value of a [`RwLock`], releasing the lock when
+/// dropped.
+///
+/// This type can be constructed through [`RwLock::write`] and
+/// [`RwLock::try_write`].
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    poison: poison::Guard,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    /// Turn the write lock into a read lock, without letting another writer
+    /// in between.
+    ///
+    /// ```
+    /// use nomicon::sync::{RwLock, RwLockWriteGuard};
+    ///
+    /// let lock = RwLock::new(Vec::new());
+    /// let mut writer = lock.write().unwrap();
+    /// writer.push(1);
+    /// let reader = RwLockWriteGuard::downgrade(writer);
+    ///
+    /// assert_eq!(*lock.try_read().unwrap(), [1]);
+    /// assert_eq!(*reader, [1]);
+    /// ```
+    pub fn downgrade(this: Self) -> RwLockReadGuard<'a, T> {
+        let this = std::mem::ManuallyDrop::new(this);
+        let lock = this.lock;
+        lock.poison.done(&this.poison);
+        // One reader, us. A waiting writer lost its bit, wake it so it can
+        // set it again.
+        lock.state.store(2, Ordering::Release);
+        futex::wake_all(&lock.state);
+        lock.wake_writer();
+        lock.read_guard()
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * The guard holds the write lock.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY
+        // * The guard holds the write lock, and is borrowed exclusively.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.poison.done(&self.poison);
+        self.lock.state.store(0, Ordering::Release);
+        // Prefer handing the lock to a writer, readers are woken as well in
+        // case none is waiting.
+        self.lock.wake_writer();
+        futex::wake_all(&self.lock.state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn readers_and_writers() {
+        let lock = RwLock::new((0u64, 0u64));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1_000 {
+                        let mut pair = lock.write().unwrap();
+                        pair.0 += 1;
+                        pair.1 += 1;
+                    }
+                });
+            }
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1_000 {
+                        let pair = lock.read().unwrap();
+                        assert_eq!(pair.0, pair.1);
+                    }
+                });
+            }
+        });
+        assert_eq!(lock.into_inner().unwrap(), (4_000, 4_000));
+    }
+
+    #[test]
+    fn waiting_writer_blocks_readers() {
+        let lock = RwLock::new(0);
+        let reader = lock.read().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| *lock.write().unwrap() += 1);
+            while lock.state.load(Ordering::Relaxed).is_multiple_of(2) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+            drop(reader);
+        });
+        assert_eq!(*lock.try_read().unwrap(), 1);
+    }
+
+    #[test]
+    fn downgrade_wakes_writer() {
+        let lock = RwLock::new(0);
+        let writer = lock.write().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| *lock.write().unwrap() += 1);
+            std::thread::sleep(Duration::from_millis(20));
+            let reader = RwLockWriteGuard::downgrade(writer);
+            assert_eq!(*reader, 0);
+        });
+        assert_eq!(*lock.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn writer_panic_poisons() {
+        let lock = RwLock::new(0);
+        let result = std::panic::catch_unwind(|| {
+            let _reader = lock.read().unwrap();
+            panic!("readers do not poison");
+        });
+        assert!(result.is_err());
+        assert!(!lock.is_poisoned());
+
+        let result = std::panic::catch_unwind(|| {
+            let _writer = lock.write().unwrap();
+            panic!("writers do");
+        });
+        assert!(result.is_err());
+        assert!(lock.read().is_err());
+        assert!(matches!(lock.try_write(), Err(TryLockError::Poisoned(_))));
+        lock.clear_poison();
+        assert!(lock.write().is_ok());
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    panic::{RefUnwindSafe, UnwindSafe},
+    ptr,
+    sync::atomic::{self, AtomicPtr, AtomicUsize, Ordering},
+};
+
+use super::{Backoff, CachePadded};
+
+// Slot states.
+/// The value has been written.
+const WRITE: usize = 1;
+/// The value has been read.
+const READ: usize = 2;
+/// The block is being destroyed, the reader of this slot takes over.
+const DESTROY: usize = 4;
+
+/// Indices advance by `LAP` per block, the last index of every lap marks the
+/// moment the tail moves on to the next block and has no slot.
+const LAP: usize = 32;
+const BLOCK_CAP: usize = LAP - 1;
+/// The low bit of the indices carries a flag, the head uses it to remember
+/// that its block has a successor.
+const SHIFT: usize = 1;
+const HAS_NEXT: usize = 1;
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    state: AtomicUsize,
+}
+
+impl<T> Slot<T> {
+    /// Wait for the pusher that claimed this slot to finish writing it.
+    fn wait_write(&self) {
+        let backoff = Backoff::new();
+        while self.state.load(Ordering::Acquire) & WRITE == 0 {
+            backoff.snooze();
+        }
+    }
+}
+
+struct Block<T> {
+    next: AtomicPtr<Block<T>>,
+    slots: [Slot<T>; BLOCK_CAP],
+}
+
+impl<T> Block<T> {
+    fn new() -> Box<Self> {
+        Box::new(Self {
+            next: AtomicPtr::new(ptr::null_mut()),
+            slots: std::array::from_fn(|_| Slot {
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+                state: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    /// Wait for the pusher that filled this block to link the next one.
+    fn wait_next(&self) -> *mut Self {
+        let backoff = Backoff::new();
+        loop {
+            let next = self.next.load(Ordering::Acquire);
+            if !next.is_null() {
+                return next;
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Free the block once every slot from `start` on has been read.
+    ///
+    /// Slots still being read are marked instead, and their reader calls back
+    /// in to finish the job.
+    ///
+    /// # Safety
+    /// Every slot before `start` must have been read, and `this` came from
+    /// [`Box::into_raw`].
+    unsafe fn destroy(this: *mut Self, start: usize) {
+        // The last slot is skipped, its reader always destroys the block.
+        for i in start..BLOCK_CAP - 1 {
+            let slot = unsafe { &(*this).slots[i] };
+            if slot.state.load(Ordering::Acquire) & READ == 0
+                && slot.state.fetch_or(DESTROY, Ordering::AcqRel) & READ == 0
+            {
+                return;
+            }
+        }
+        drop(unsafe { Box::from_raw(this) });
+    }
+}
+
+struct Position<T> {
+    index: AtomicUsize,
+    block: AtomicPtr<Block<T>>,
+}
+
+/// An unbounded multi producer multi consumer queue.
+///
+/// Values are stored in blocks of 31 slots linked into a list, so the queue
+/// allocates once per block rather than once per value. Pushing and popping
+/// claim a slot with a single compare and swap on the tail or head index, and
+/// the last reader of a block frees it, so no garbage collection scheme is
+/// needed.
+///
+/// ```
+/// use nomicon::sync::SegQueue;
+///
+/// let queue = SegQueue::new();
+/// std::thread::scope(|s| {
+///     for n in 0..4 {
+///         let queue = &queue;
+///         s.spawn(move || queue.push(n));
+///     }
+/// });
+/// let mut values = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+/// values.sort();
+/// assert_eq!(values, [0, 1, 2, 3]);
+/// ```
+pub struct SegQueue<T> {
+    head: CachePadded<Position<T>>,
+    tail: CachePadded<Position<T>>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for SegQueue<T> {}
+unsafe impl<T: Send> Sync for SegQueue<T> {}
+
+impl<T> UnwindSafe for SegQueue<T> {}
+impl<T> RefUnwindSafe for SegQueue<T> {}
+
+impl<T> SegQueue<T> {
+    /// Returns an empty queue, the first block is allocated on the first
+    /// push.
+    pub const fn new() -> Self {
+        Self {
+            head: CachePadded::new(Position {
+                index: AtomicUsize::new(0),
+                block: AtomicPtr::new(ptr::null_mut()),
+            }),
+            tail: CachePadded::new(Position {
+                index: AtomicUsize::new(0),
+                block: AtomicPtr::new(ptr::null_mut()),
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Push `value` onto the back of the queue.
+    pub fn push(&self, value: T) {
+        let backoff = Backoff::new();
+        let mut tail = self.tail.index.load(Ordering::Acquire);
+        let mut block = self.tail.block.load(Ordering::Acquire);
+        let mut next_block = None;
+
+        loop {
+            let offset = (tail >> SHIFT) % LAP;
+
+            // Another pusher is installing the next block.
+            if offset == BLOCK_CAP {
+                backoff.snooze();
+                tail = self.tail.index.load(Ordering::Acquire);
+                block = self.tail.block.load(Ordering::Acquire);
+                continue;
+            }
+
+            // Allocate the next block before claiming the last slot, so the
+            // other pushers are kept waiting as briefly as possible.
+            if offset + 1 == BLOCK_CAP && next_block.is_none() {
+                next_block = Some(Block::new());
+            }
+
+            // The very first push installs the first block.
+            if block.is_null() {
+                let new = Box::into_raw(next_block.take().unwrap_or_else(Block::new));
+                if self
+                    .tail
+                    .block
+                    .compare_exchange(block, new, Ordering::Release, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    self.head.block.store(new, Ordering::Release);
+                    block = new;
+                } else {
+                    // SAFETY
+                    // * The block was never shared.
+                    next_block = Some(unsafe { Box::from_raw(new) });
+                    tail = self.tail.index.load(Ordering::Acquire);
+                    block = self.tail.block.load(Ordering::Acquire);
+                    continue;
+                }
+            }
+
+            let new_tail = tail + (1 << SHIFT);
+            match self.tail.index.compare_exchange_weak(
+                tail,
+                new_tail,
+                Ordering::SeqCst,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => unsafe {
+                    // SAFETY
+                    // * The slot at offset is claimed by this push alone.
+                    // * Blocks are only freed once every slot was read, and
+                    //   this one has not been written yet.
+                    if offset + 1 == BLOCK_CAP {
+                        let next = Box::into_raw(next_block.expect("allocated above"));
+                        let next_index = new_tail.wrapping_add(1 << SHIFT);
+                        self.tail.block.store(next, Ordering::Release);
+                        self.tail.index.store(next_index, Ordering::Release);
+                        (*block).next.store(next, Ordering::Release);
+                    }
+
+                    let slot = &(*block).slots[offset];
+                    slot.value.get().write(MaybeUninit::new(value));
+                    slot.state.fetch_or(WRITE, Ordering::Release);
+                    return;
+                },
+                Err(current) => {
+                    tail = current;
+                    block = self.tail.block.load(Ordering::Acquire);
+                    backoff.spin();
+                }
+            }
+        }
+    }
+
+    /// Pop the value at the front of the queue.
+    pub fn pop(&self) -> Option<T> {
+        let backoff = Backoff::new();
+        let mut head = self.head.index.load(Ordering::Acquire);
+        let mut block = self.head.block.load(Ordering::Acquire);
+
+        loop {
+            let offset = (head >> SHIFT) % LAP;
+
+            // Another popper is moving the head to the next block.
+            if offset == BLOCK_CAP {
+                backoff.snooze();
+                head = self.head.index.load(Ordering::Acquire);
+                block = self.head.block.load(Ordering::Acquire);
+                continue;
+            }
+
+            let mut new_head = head + (1 << SHIFT);
+
+            if new_head & HAS_NEXT == 0 {
+                atomic::fence(Ordering::SeqCst);
+                let tail = self.tail.index.load(Ordering::Relaxed);
+                if head >> SHIFT == tail >> SHIFT {
+                    return None;
+                }
+                // The tail is in a later block, so this one is followed.
+                if (head >> SHIFT) / LAP != (tail >> SHIFT) / LAP {
+                    new_head |= HAS_NEXT;
+                }
+            }
+
+            // The first push claimed a slot but has not installed the block.
+            if block.is_null() {
+                backoff.snooze();
+                head = self.head.index.load(Ordering::Acquire);
+                block = self.head.block.load(Ordering::Acquire);
+                continue;
+            }
+
+            match self.head.index.compare_exchange_weak(
+                head,
+                new_head,
+                Ordering::SeqCst,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => unsafe {
+                    // SAFETY
+                    // * The slot at offset is claimed by this pop alone, and
+                    //   its block is freed only after it is marked read.
+                    if offset + 1 == BLOCK_CAP {
+                        let next = (*block).wait_next();
+                        let mut next_index = (new_head & !HAS_NEXT).wrapping_add(1 << SHIFT);
+                        if !(*next).next.load(Ordering::Relaxed).is_null() {
+                            next_index |= HAS_NEXT;
+                        }
+                        self.head.block.store(next, Ordering::Release);
+                        self.head.index.store(next_index, Ordering::Release);
+                    }
+
+                    let slot = &(*block).slots[offset];
+                    slot.wait_write();
+                    let value = slot.value.get().read().assume_init();
+
+                    // The last slot's reader starts destroying the block, any
+                    // other reader finishes the job if asked to.
+                    if offset + 1 == BLOCK_CAP {
+                        Block::destroy(block, 0);
+                    } else if slot.state.fetch_or(READ, Ordering::AcqRel) & DESTROY != 0 {
+                        Block::destroy(block, offset + 1);
+                    }
+                    return Some(value);
+                },
+                Err(current) => {
+                    head = current;
+                    block = self.head.block.load(Ordering::Acquire);
+                    backoff.spin();
+                }
+            }
+        }
+    }
+
+    /// Returns true if the queue was empty when checked.
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.index.load(Ordering::SeqCst);
+        let tail = self.tail.index.load(Ordering::SeqCst);
+        head >> SHIFT == tail >> SHIFT
+    }
+
+    /// Returns the number of values in the queue at some point during the
+    /// call.
+    pub fn len(&self) -> usize {
+        loop {
+            let mut tail = self.tail.index.load(Ordering::SeqCst);
+            let mut head = self.head.index.load(Ordering::SeqCst);
+
+            // Retry unless the tail was stable around reading the head.
+            if self.tail.index.load(Ordering::SeqCst) == tail {
+                tail &= !((1 << SHIFT) - 1);
+                head &= !((1 << SHIFT) - 1);
+
+                // An index stuck at the end of a lap is moving to the next
+                // block.
+                if (tail >> SHIFT) & (LAP - 1) == LAP - 1 {
+                    tail = tail.wrapping_add(1 << SHIFT);
+                }
+                if (head >> SHIFT) & (LAP - 1) == LAP - 1 {
+                    head = head.wrapping_add(1 << SHIFT);
+                }
+
+                // Rebase both indices on the head's lap, then skip the one
+                // index per lap that has no slot.
+                let lap = (head >> SHIFT) / LAP;
+                tail = tail.wrapping_sub((lap * LAP) << SHIFT) >> SHIFT;
+                head = head.wrapping_sub((lap * LAP) << SHIFT) >> SHIFT;
+                return tail - head - tail / LAP;
+            }
+        }
+    }
+}
+
+impl<T> Default for SegQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SegQueue<T> {
+    fn drop(&mut self) {
+        let mut head = *self.head.index.get_mut() & !((1 << SHIFT) - 1);
+        let tail = *self.tail.index.get_mut() & !((1 << SHIFT) - 1);
+        let mut block = *self.head.block.get_mut();
+
+        // SAFETY
+        // * The queue is owned, every slot between head and tail is written
+        //   and every block before the head has been freed.
+        unsafe {
+            while head != tail {
+                let offset = (head >> SHIFT) % LAP;
+                if offset < BLOCK_CAP {
+                    (*(*block).slots[offset].value.get()).assume_init_drop();
+                } else {
+                    let next = *(*block).next.get_mut();
+                    drop(Box::from_raw(block));
+                    block = next;
+                }
+                head = head.wrapping_add(1 << SHIFT);
+            }
+            if !block.is_null() {
+                drop(Box::from_raw(block));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fifo_and_len() {
+        let queue = SegQueue::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        for n in 0..100 {
+            queue.push(n);
+        }
+        assert_eq!(queue.len(), 100);
+        assert!((0..60).eq(std::iter::from_fn(|| queue.pop()).take(60)));
+        assert_eq!(queue.len(), 40);
+
+        let flag = std::rc::Rc::new(());
+        let queue = SegQueue::new();
+        for _ in 0..BLOCK_CAP * 3 {
+            queue.push(std::rc::Rc::clone(&flag));
+        }
+        drop(queue.pop());
+        drop(queue);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn many_producers_and_consumers() {
+        const PER_THREAD: usize = 10_000;
+        let queue = SegQueue::new();
+        let popped = AtomicUsize::new(0);
+        let sum = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for n in 0..PER_THREAD {
+                        queue.push(n);
+                    }
+                });
+                s.spawn(|| {
+                    while popped.load(Ordering::Relaxed) < 4 * PER_THREAD {
+                        if let Some(n) = queue.pop() {
+                            sum.fetch_add(n, Ordering::Relaxed);
+                            popped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(queue.is_empty());
+        assert_eq!(sum.into_inner(), 4 * (0..PER_THREAD).sum::<usize>());
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, Thread},
+};
+
+use super::{Mutex, MutexGuard, PoisonError};
+
+/// A counting semaphore, handing out up to a fixed number of permits at once.
+///
+/// Permits are taken from an atomic count, threads finding too few permits
+/// queue up and sleep until permits are released.
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use nomicon::sync::Semaphore;
+///
+/// let connections = Semaphore::new(2);
+/// let active = AtomicUsize::new(0);
+/// std::thread::scope(|s| {
+///     for _ in 0..8 {
+///         s.spawn(|| {
+///             let _permit = connections.acquire(1);
+///             assert!(active.fetch_add(1, Ordering::SeqCst) < 2);
+///             active.fetch_sub(1, Ordering::SeqCst);
+///         });
+///     }
+/// });
+/// assert_eq!(connections.available_permits(), 2);
+/// ```
+pub struct Semaphore {
+    permits: AtomicUsize,
+    waiters: Mutex<VecDeque<Thread>>,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            waiters: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Block until `n` permits are available, and take them.
+    ///
+    /// Never returns if more permits are requested than will ever be
+    /// available.
+    pub fn acquire(&self, n: usize) -> SemaphorePermit<'_> {
+        loop {
+            if let Some(permit) = self.try_acquire(n) {
+                return permit;
+            }
+            let mut waiters = self.waiters();
+            // Releasing threads add permits before taking the queue lock, so
+            // checking again under the lock cannot miss a release.
+            if let Some(permit) = self.try_acquire(n) {
+                return permit;
+            }
+            waiters.push_back(thread::current());
+            drop(waiters);
+            thread::park();
+        }
+    }
+
+    /// Take `n` permits if they are available.
+    pub fn try_acquire(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        // Acquire pairs with the Release in Semaphore::release, so whatever
+        // the previous holders did with the resource is visible.
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |permits| {
+                permits.checked_sub(n)
+            })
+            .ok()?;
+        Some(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    fn waiters(&self) -> MutexGuard<'_, VecDeque<Thread>> {
+        // The queue is never left in an inconsistent state, so a panic while
+        // holding the lock is harmless.
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Add `n` permits, waking the threads waiting for permits.
+    ///
+    /// # Panics
+    /// If the number of permits overflows.
+    pub fn release(&self, n: usize) {
+        let previous = self.permits.fetch_add(n, Ordering::Release);
+        assert!(
+            previous.checked_add(n).is_some(),
+            "Semaphore permits overflown"
+        );
+        // Waiters need different numbers of permits, let them all take
+        // another look, in the order they arrived.
+        let waiters = std::mem::take(&mut *self.waiters());
+        for thread in waiters {
+            thread.unpark();
+        }
+    }
+}
+
+/// Permits taken from a [`Semaphore`], given back when dropped.
+///
+/// This type can be constructed through [`Semaphore::acquire`] and
+/// [`Semaphore::try_acquire`].
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Returns the number of permits held.
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Drop the permit without giving the permits back.
+    pub fn forget(self) {
+        std::mem::forget(self)
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.release(self.permits);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn counts_permits() {
+        let semaphore = Semaphore::new(3);
+        let two = semaphore.try_acquire(2).unwrap();
+        assert_eq!(two.permits(), 2);
+        assert!(semaphore.try_acquire(2).is_none());
+        semaphore.try_acquire(1).unwrap().forget();
+        assert_eq!(semaphore.available_permits(), 0);
+        drop(two);
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[test]
+    fn waits_for_release() {
+        let semaphore = Semaphore::new(0);
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| semaphore.acquire(3).forget());
+            std::thread::sleep(Duration::from_millis(20));
+            semaphore.release(2);
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!waiter.is_finished());
+            semaphore.release(1);
+        });
+        assert_eq!(semaphore.available_permits(), 0);
+    }
+}
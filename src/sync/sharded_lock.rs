@@ -0,0 +1,289 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{
+    cache_padded::CachePadded,
+    poison::{self, LockResult, PoisonError, TryLockError, TryLockResult},
+    RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+/// The most shards a lock is split into.
+const MAX_SHARDS: usize = 64;
+
+/// A reader-writer lock split into shards, one per core.
+///
+/// Readers only lock the shard picked for their thread, so reads from
+/// different threads touch different cache lines. Writers have to lock every
+/// shard, making writes considerably more expensive than with a [`RwLock`].
+///
+/// ```
+/// use nomicon::sync::ShardedLock;
+///
+/// let registry = ShardedLock::new(vec!["a"]);
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| assert!(registry.read().unwrap().contains(&"a")));
+///     }
+/// });
+/// registry.write().unwrap().push("b");
+/// assert_eq!(*registry.read().unwrap(), ["a", "b"]);
+/// ```
+pub struct ShardedLock<T: ?Sized> {
+    /// Each shard sits on its own cache line, so readers on different shards
+    /// do not contend.
+    shards: Box<[CachePadded<RwLock<()>>]>,
+    poison: poison::Flag,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ShardedLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for ShardedLock<T> {}
+
+impl<T: ?Sized> UnwindSafe for ShardedLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for ShardedLock<T> {}
+
+/// Returns a number identifying the current thread, threads are spread
+/// round robin over the shards.
+fn thread_index() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static INDEX: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    INDEX.with(|index| *index)
+}
+
+impl<T> ShardedLock<T> {
+    pub fn new(value: T) -> Self {
+        let shards = std::thread::available_parallelism()
+            .map_or(8, usize::from)
+            .min(MAX_SHARDS);
+        Self {
+            shards: (0..shards)
+                .map(|_| CachePadded::new(RwLock::new(())))
+                .collect(),
+            poison: poison::Flag::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the lock, returning the value, even if the lock is poisoned.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.poison.get();
+        let value = self.value.into_inner();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl<T: ?Sized> ShardedLock<T> {
+    fn shard(&self) -> &RwLock<()> {
+        &self.shards[thread_index() % self.shards.len()]
+    }
+
+    /// Block until the lock can be shared.
+    pub fn read(&self) -> LockResult<ShardedLockReadGuard<'_, T>> {
+        // The shards only guard (), their poison is tracked by self.poison.
+        let shard = self.shard().read().unwrap_or_else(PoisonError::into_inner);
+        poison::map_result(&self.poison, self.read_guard(shard))
+    }
+
+    /// Returns a read guard if no writer holds or waits for this thread's
+    /// shard.
+    pub fn try_read(&self) -> TryLockResult<ShardedLockReadGuard<'_, T>> {
+        let shard = match self.shard().try_read() {
+            Ok(shard) => shard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+        };
+        Ok(poison::map_result(&self.poison, self.read_guard(shard))?)
+    }
+
+    /// Block until every shard is exclusively ours.
+    pub fn write(&self) -> LockResult<ShardedLockWriteGuard<'_, T>> {
+        // Shards are always locked in the same order, so writers cannot
+        // deadlock on each other.
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.write().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        poison::map_result(&self.poison, self.write_guard(shards))
+    }
+
+    /// Returns a write guard if no shard is held.
+    pub fn try_write(&self) -> TryLockResult<ShardedLockWriteGuard<'_, T>> {
+        let mut shards = Vec::with_capacity(self.shards.len());
+        for shard in &self.shards {
+            match shard.try_write() {
+                Ok(shard) => shards.push(shard),
+                Err(TryLockError::Poisoned(e)) => shards.push(e.into_inner()),
+                // Dropping the shards taken so far releases them.
+                Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+            }
+        }
+        Ok(poison::map_result(&self.poison, self.write_guard(shards))?)
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Mark the lock as no longer poisoned, once the value is known to be
+    /// valid again.
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    /// Returns a mutable reference to the value, no locking is needed as the
+    /// borrow is exclusive.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.poison.get();
+        let value = self.value.get_mut();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn read_guard<'a>(&'a self, shard: RwLockReadGuard<'a, ()>) -> ShardedLockReadGuard<'a, T> {
+        ShardedLockReadGuard {
+            lock: self,
+            _shard: shard,
+            _marker: PhantomData,
+        }
+    }
+
+    fn write_guard<'a>(
+        &'a self,
+        shards: Vec<RwLockWriteGuard<'a, ()>>,
+    ) -> ShardedLockWriteGuard<'a, T> {
+        ShardedLockWriteGuard {
+            lock: self,
+            poison: self.poison.guard(),
+            _shards: shards,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Default> Default for ShardedLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// Shared access to the value of a [`ShardedLock`], releasing the shard when
+/// dropped.
+///
+/// This type can be constructed through [`ShardedLock::read`] and
+/// [`ShardedLock::try_read`].
+pub struct ShardedLockReadGuard<'a, T: ?Sized> {
+    lock: &'a ShardedLock<T>,
+    _shard: RwLockReadGuard<'a, ()>,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<T: ?Sized> Deref for ShardedLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * A shard is read locked, writers need every shard.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+/// Exclusive access to the value of a [`ShardedLock`], releasing every shard
+/// when dropped.
+///
+/// This type can be constructed through [`ShardedLock::write`] and
+/// [`ShardedLock::try_write`].
+pub struct ShardedLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a ShardedLock<T>,
+    poison: poison::Guard,
+    _shards: Vec<RwLockWriteGuard<'a, ()>>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> Deref for ShardedLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * Every shard is write locked.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ShardedLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY
+        // * Every shard is write locked, and the guard is borrowed
+        //   exclusively.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for ShardedLockWriteGuard<'_, T> {
+    /// The shards are released after this, when the fields are dropped.
+    fn drop(&mut self) {
+        self.lock.poison.done(&self.poison);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn writers_exclude_readers() {
+        let lock = ShardedLock::new((0u64, 0u64));
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        let mut pair = lock.write().unwrap();
+                        pair.0 += 1;
+                        pair.1 += 1;
+                    }
+                });
+            }
+            for _ in 0..6 {
+                s.spawn(|| {
+                    for _ in 0..2_000 {
+                        let pair = lock.read().unwrap();
+                        assert_eq!(pair.0, pair.1);
+                    }
+                });
+            }
+        });
+        assert_eq!(lock.into_inner().unwrap(), (1_000, 1_000));
+    }
+
+    #[test]
+    fn try_locks_and_poison() {
+        let lock = ShardedLock::new(0);
+        let reader = lock.read().unwrap();
+        assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+        drop(reader);
+
+        let result = std::panic::catch_unwind(|| {
+            let _writer = lock.write().unwrap();
+            panic!("poisons the lock");
+        });
+        assert!(result.is_err());
+        assert!(lock.is_poisoned());
+        assert!(lock.try_read().is_err());
+        lock.clear_poison();
+        assert_eq!(*lock.try_read().unwrap(), 0);
+    }
+}
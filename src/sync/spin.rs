@@ -0,0 +1,151 @@
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::Backoff;
+
+/// A mutual exclusion lock that busy-waits until the lock is free.
+///
+/// Only suited for critical sections that are very short, a thread waiting
+/// on the lock burns its time slice instead of sleeping.
+///
+/// ```
+/// use nomicon::sync::SpinMutex;
+///
+/// let counter = SpinMutex::new(0);
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| *counter.lock() += 1);
+///     }
+/// });
+/// assert_eq!(counter.into_inner(), 4);
+/// ```
+#[derive(Default)]
+pub struct SpinMutex<T: ?Sized> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// The lock hands out exclusive access to one thread at a time, so the value
+// only needs to be movable between threads.
+unsafe impl<T: ?Sized + Send> Send for SpinMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SpinMutex<T> {}
+
+impl<T> SpinMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinMutex<T> {
+    /// Spin until the lock is acquired.
+    pub fn lock(&self) -> SpinMutexGuard<'_, T> {
+        let backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            // Wait for the lock to look free before trying again, so waiting
+            // threads do not keep stealing the cache line from the owner.
+            while self.locked.load(Ordering::Relaxed) {
+                backoff.snooze();
+            }
+        }
+    }
+
+    /// Returns a guard if the lock is free.
+    pub fn try_lock(&self) -> Option<SpinMutexGuard<'_, T>> {
+        // Acquire pairs with the Release in SpinMutexGuard::drop, making the
+        // previous owner's writes visible.
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(SpinMutexGuard {
+            mutex: self,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Returns a mutable reference to the value, no locking is needed as the
+    /// borrow is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Exclusive access to the value of a [`SpinMutex`], releasing the lock when
+/// dropped.
+///
+/// This type can be constructed through [`SpinMutex::lock`] and
+/// [`SpinMutex::try_lock`].
+pub struct SpinMutexGuard<'a, T: ?Sized> {
+    mutex: &'a SpinMutex<T>,
+    /// Only share the guard between threads if the value can be shared.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> Deref for SpinMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY
+        // * The guard holds the lock.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY
+        // * The guard holds the lock, and is borrowed exclusively.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exclusive() {
+        let mutex = SpinMutex::new(Vec::new());
+        let guard = mutex.lock();
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+        assert!(!mutex.is_locked());
+
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let mutex = &mutex;
+                s.spawn(move || {
+                    for n in 0..1_000 {
+                        mutex.lock().push(t * 1_000 + n);
+                    }
+                });
+            }
+        });
+        let mut values = mutex.into_inner();
+        values.sort();
+        assert!(values.into_iter().eq(0..8_000));
+    }
+}
@@ -0,0 +1,124 @@
+//! Building [`Waker`]s out of reference counted handles.
+//!
+//! A [`Waker`] is a data pointer plus a hand written vtable, and every entry
+//! of the vtable has to agree on who owns the pointer. [`waker`] gets this
+//! right once, for any [`ArcWake`] type.
+
+use std::{
+    mem::ManuallyDrop,
+    task::{RawWaker, RawWakerVTable, Waker},
+};
+
+use crate::arc::Arc;
+
+/// Something a [`Waker`] can be built around, through [`waker`].
+///
+/// Wakers move between threads and outlive the tasks that made them, hence
+/// the bounds.
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use nomicon::{arc::Arc, task::ArcWake};
+///
+/// struct Counter(AtomicUsize);
+///
+/// impl ArcWake for Counter {
+///     fn wake_by_ref(this: &Arc<Self>) {
+///         this.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counter = Arc::new(Counter(AtomicUsize::new(0)));
+/// let waker = nomicon::task::waker(Arc::clone(&counter));
+/// waker.wake_by_ref();
+/// waker.wake();
+/// assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+/// ```
+pub trait ArcWake: Sized + Send + Sync + 'static {
+    fn wake_by_ref(this: &Arc<Self>);
+
+    /// Wake, consuming the handle. Defaults to [`ArcWake::wake_by_ref`].
+    fn wake(this: Arc<Self>) {
+        Self::wake_by_ref(&this);
+    }
+}
+
+/// Build a [`Waker`] sharing ownership of `wake`.
+///
+/// Cloning the waker clones the handle, and dropping it drops the handle.
+pub fn waker<W: ArcWake>(wake: Arc<W>) -> Waker {
+    // SAFETY
+    // * Every vtable function treats the data pointer as an Arc<W>, which is
+    //   what it is.
+    // * W is Send and Sync, so the waker may move between threads.
+    unsafe { Waker::from_raw(raw_waker(wake)) }
+}
+
+fn raw_waker<W: ArcWake>(wake: Arc<W>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(wake).cast(), vtable::<W>())
+}
+
+fn vtable<W: ArcWake>() -> &'static RawWakerVTable {
+    unsafe fn clone<W: ArcWake>(ptr: *const ()) -> RawWaker {
+        // The waker keeps its handle, only borrow it.
+        let wake = ManuallyDrop::new(unsafe { Arc::from_raw(ptr.cast::<W>()) });
+        raw_waker(Arc::clone(&wake))
+    }
+    unsafe fn wake<W: ArcWake>(ptr: *const ()) {
+        // Waking by value hands the waker's handle over.
+        W::wake(unsafe { Arc::from_raw(ptr.cast::<W>()) });
+    }
+    unsafe fn wake_by_ref<W: ArcWake>(ptr: *const ()) {
+        let wake = ManuallyDrop::new(unsafe { Arc::from_raw(ptr.cast::<W>()) });
+        W::wake_by_ref(&wake);
+    }
+    unsafe fn drop<W: ArcWake>(ptr: *const ()) {
+        std::mem::drop(unsafe { Arc::from_raw(ptr.cast::<W>()) });
+    }
+    &RawWakerVTable::new(clone::<W>, wake::<W>, wake_by_ref::<W>, drop::<W>)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct Counter {
+        by_ref: AtomicUsize,
+        by_value: AtomicUsize,
+        _flag: std::sync::Arc<()>,
+    }
+
+    impl ArcWake for Counter {
+        fn wake_by_ref(this: &Arc<Self>) {
+            this.by_ref.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn wake(this: Arc<Self>) {
+            this.by_value.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn handles_are_shared() {
+        let flag = std::sync::Arc::new(());
+        let counter = Arc::new(Counter {
+            by_ref: AtomicUsize::new(0),
+            by_value: AtomicUsize::new(0),
+            _flag: std::sync::Arc::clone(&flag),
+        });
+        let waker = waker(Arc::clone(&counter));
+        let cloned = waker.clone();
+        let moved = std::thread::spawn(move || cloned.wake());
+        moved.join().unwrap();
+        waker.wake_by_ref();
+        drop(waker);
+        assert_eq!(counter.by_ref.load(Ordering::Relaxed), 1);
+        assert_eq!(counter.by_value.load(Ordering::Relaxed), 1);
+
+        drop(counter);
+        assert_eq!(std::sync::Arc::strong_count(&flag), 1);
+    }
+}
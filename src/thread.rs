@@ -0,0 +1,9 @@
+//! Spawning and managing threads.
+
+mod local;
+mod pool;
+mod scope;
+
+pub use local::{IntoIter, ThreadLocal};
+pub use pool::ThreadPool;
+pub use scope::{scope, Scope, ScopedJoinHandle};
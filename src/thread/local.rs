@@ -0,0 +1,170 @@
+use std::{
+    collections::{hash_map, HashMap},
+    thread::{self, ThreadId},
+};
+
+use crate::sync::{PoisonError, RwLock};
+
+/// A value per thread, created lazily by the first access from each thread.
+///
+/// Unlike `thread_local!` the values belong to the container rather than the
+/// threads, so once the threads are done the values can be collected.
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// use nomicon::thread::{self, ThreadLocal};
+///
+/// let counters = ThreadLocal::new();
+/// thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| {
+///             let counter = counters.get_or(|| Cell::new(0));
+///             for _ in 0..10 {
+///                 counter.set(counter.get() + 1);
+///             }
+///         });
+///     }
+/// });
+/// let total = counters.into_iter().map(Cell::into_inner).sum::<u32>();
+/// assert_eq!(total, 40);
+/// ```
+pub struct ThreadLocal<T> {
+    /// Values are boxed so their address is stable while the map grows.
+    values: RwLock<HashMap<ThreadId, Box<T>>>,
+}
+
+// Each value is only reached from the thread that created it, until the
+// container is accessed exclusively.
+unsafe impl<T: Send> Sync for ThreadLocal<T> {}
+
+impl<T> ThreadLocal<T> {
+    pub fn new() -> Self {
+        Self {
+            values: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the value of the current thread, if it has one.
+    pub fn get(&self) -> Option<&T> {
+        let values = self.values.read().unwrap_or_else(PoisonError::into_inner);
+        let value = values.get(&thread::current().id())?;
+        // SAFETY
+        // * Values are only removed through &mut self, and the box keeps the
+        //   value in place when the map moves it.
+        Some(unsafe { &*std::ptr::from_ref(&**value) })
+    }
+
+    /// Returns the value of the current thread, creating it with `create` if
+    /// this is the thread's first access.
+    pub fn get_or(&self, create: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        // Run create without the lock held, it may access this container.
+        let value = Box::new(create());
+        let mut values = self.values.write().unwrap_or_else(PoisonError::into_inner);
+        let value = values.entry(thread::current().id()).or_insert(value);
+        unsafe { &*std::ptr::from_ref(&**value) }
+    }
+
+    pub fn get_or_default(&self) -> &T
+    where
+        T: Default,
+    {
+        self.get_or(T::default)
+    }
+
+    /// Iterate over the value of every thread that accessed the container.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.values_mut().values_mut().map(|value| &mut **value)
+    }
+
+    /// Drop every value.
+    pub fn clear(&mut self) {
+        self.values_mut().clear();
+    }
+
+    fn values_mut(&mut self) -> &mut HashMap<ThreadId, Box<T>> {
+        self.values
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Default for ThreadLocal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IntoIterator for ThreadLocal<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        let values = self
+            .values
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        IntoIter {
+            inner: values.into_values(),
+        }
+    }
+}
+
+/// An owning iterator over the values of a [`ThreadLocal`].
+///
+/// This type can be constructed through [`ThreadLocal::into_iter`].
+pub struct IntoIter<T> {
+    inner: hash_map::IntoValues<ThreadId, Box<T>>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.inner.next().map(|value| *value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[test]
+    fn one_value_per_thread() {
+        let mut local = ThreadLocal::new();
+        assert!(local.get().is_none());
+        local.get_or(|| RefCell::new(vec![0])).borrow_mut().push(1);
+        assert_eq!(*local.get_or(|| unreachable!()).borrow(), [0, 1]);
+
+        crate::thread::scope(|s| {
+            for n in 1..4 {
+                let local = &local;
+                s.spawn(move || {
+                    assert!(local.get().is_none());
+                    local.get_or_default().borrow_mut().push(n);
+                });
+            }
+        });
+        for value in local.iter_mut() {
+            value.get_mut().push(10);
+        }
+        let mut values = local
+            .into_iter()
+            .map(RefCell::into_inner)
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(
+            values,
+            [vec![0, 1, 10], vec![1, 10], vec![2, 10], vec![3, 10]]
+        );
+    }
+}
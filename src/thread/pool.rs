@@ -0,0 +1,196 @@
+use std::{
+    collections::VecDeque,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    arc::Arc,
+    sync::{Mutex, MutexGuard, Parker, PoisonError, Semaphore, Unparker},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed set of worker threads running jobs from a shared queue.
+///
+/// A panicking job does not take its worker down, the panic is caught and
+/// counted. Dropping the pool lets the workers finish every queued job before
+/// they are joined.
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use nomicon::{arc::Arc, thread::ThreadPool};
+///
+/// let pool = ThreadPool::new(4);
+/// let sum = Arc::new(AtomicUsize::new(0));
+/// for n in 1..=100 {
+///     let sum = Arc::clone(&sum);
+///     pool.execute(move || {
+///         sum.fetch_add(n, Ordering::Relaxed);
+///     });
+/// }
+/// pool.join();
+/// assert_eq!(sum.load(Ordering::Relaxed), 5050);
+/// ```
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Job>>,
+    /// One permit per queued job, plus one per worker on shutdown.
+    jobs: Semaphore,
+    /// Jobs queued or running.
+    pending: AtomicUsize,
+    panicked: AtomicUsize,
+    shutdown: AtomicBool,
+    /// Threads blocked in ThreadPool::join.
+    joiners: Mutex<Vec<Unparker>>,
+}
+
+/// The locks guard plain queues that are never left half updated, so poison
+/// is ignored.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl ThreadPool {
+    /// Start a pool of `threads` workers.
+    ///
+    /// # Panics
+    /// If `threads` is zero, or the operating system fails to create a
+    /// thread.
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "ThreadPool needs at least one thread");
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            jobs: Semaphore::new(0),
+            pending: AtomicUsize::new(0),
+            panicked: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+            joiners: Mutex::new(Vec::new()),
+        });
+        let workers = (0..threads)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("pool-worker-{i}"))
+                    .spawn(move || shared.work())
+                    .expect("failed to spawn thread")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queue `job` to run on one of the workers.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        self.shared.pending.fetch_add(1, Ordering::Relaxed);
+        lock(&self.shared.queue).push_back(Box::new(job));
+        self.shared.jobs.release(1);
+    }
+
+    /// Block until every queued job has run.
+    pub fn join(&self) {
+        let parker = Parker::new();
+        loop {
+            // Acquire pairs with the Release when a job finishes, making the
+            // jobs' writes visible.
+            if self.shared.pending.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            let mut joiners = lock(&self.shared.joiners);
+            // Workers finish a job before taking the joiners lock, checking
+            // under the lock means the last job cannot be missed.
+            if self.shared.pending.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            joiners.push(parker.unparker().clone());
+            drop(joiners);
+            parker.park();
+        }
+    }
+
+    /// Returns the number of jobs that panicked.
+    pub fn panicked_jobs(&self) -> usize {
+        self.shared.panicked.load(Ordering::Relaxed)
+    }
+}
+
+impl Shared {
+    fn work(&self) {
+        loop {
+            self.jobs.acquire(1).forget();
+            let Some(job) = lock(&self.queue).pop_front() else {
+                // Permits without a job are only handed out on shutdown.
+                debug_assert!(self.shutdown.load(Ordering::Relaxed));
+                return;
+            };
+            if catch_unwind(AssertUnwindSafe(job)).is_err() {
+                self.panicked.fetch_add(1, Ordering::Relaxed);
+            }
+            if self.pending.fetch_sub(1, Ordering::Release) == 1 {
+                for joiner in lock(&self.joiners).drain(..) {
+                    joiner.unpark();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Relaxed);
+        // Queued jobs were released first, so every job is taken before a
+        // worker sees an empty queue.
+        self.shared.jobs.release(self.workers.len());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn isolates_panics() {
+        let pool = ThreadPool::new(2);
+        let done = Arc::new(AtomicUsize::new(0));
+        for n in 0..20 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                assert!(n % 5 != 0, "job {n} panicked");
+                done.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        pool.join();
+        assert_eq!(pool.panicked_jobs(), 4);
+        assert_eq!(done.load(Ordering::Relaxed), 16);
+    }
+
+    #[test]
+    fn drop_drains_queue() {
+        let done = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = ThreadPool::new(3);
+            for _ in 0..30 {
+                let done = Arc::clone(&done);
+                pool.execute(move || {
+                    thread::sleep(Duration::from_millis(1));
+                    done.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        }
+        assert_eq!(done.load(Ordering::Relaxed), 30);
+    }
+}
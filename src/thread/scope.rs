@@ -0,0 +1,234 @@
+use std::{
+    marker::PhantomData,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread::{self, Thread},
+};
+
+use crate::{
+    arc::Arc,
+    sync::{Mutex, PoisonError},
+};
+
+/// Spawn threads that can borrow from the enclosing stack frame.
+///
+/// Every thread spawned through the [`Scope`] is joined before `scope`
+/// returns. If one of them panicked and was not joined explicitly, `scope`
+/// panics once all threads are done.
+///
+/// ```
+/// use nomicon::thread;
+///
+/// let mut words = vec!["a", "b"];
+/// let total = thread::scope(|s| {
+///     let len = s.spawn(|| words.len());
+///     let chars = s.spawn(|| words.iter().map(|w| w.len()).sum::<usize>());
+///     len.join().unwrap() + chars.join().unwrap()
+/// });
+/// // The threads are done, words can be borrowed mutably again.
+/// words.push("c");
+/// assert_eq!(total, 4);
+/// ```
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    let scope = Scope {
+        data: Arc::new(ScopeData {
+            running: AtomicUsize::new(0),
+            panicked: AtomicBool::new(false),
+            main_thread: thread::current(),
+        }),
+        _scope: PhantomData,
+        _env: PhantomData,
+    };
+    let result = catch_unwind(AssertUnwindSafe(|| f(&scope)));
+
+    // Acquire pairs with the Release when a thread finishes, so whatever the
+    // threads wrote is visible once they are all done.
+    while scope.data.running.load(Ordering::Acquire) != 0 {
+        thread::park();
+    }
+
+    match result {
+        Err(payload) => resume_unwind(payload),
+        Ok(_) if scope.data.panicked.load(Ordering::Relaxed) => {
+            panic!("a scoped thread panicked")
+        }
+        Ok(value) => value,
+    }
+}
+
+/// A scope to spawn threads in.
+///
+/// This type can be constructed through [`scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+    data: Arc<ScopeData>,
+    /// Invariant over 'scope, so the scope cannot be shortened to let a
+    /// borrow end before the threads using it are joined.
+    _scope: PhantomData<&'scope mut &'scope ()>,
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+struct ScopeData {
+    /// The number of spawned threads whose results have not been dropped.
+    running: AtomicUsize,
+    /// Set when a thread panicked and its result was never looked at.
+    panicked: AtomicBool,
+    main_thread: Thread,
+}
+
+impl ScopeData {
+    fn finished(&self) {
+        if self.running.fetch_sub(1, Ordering::Release) == 1 {
+            self.main_thread.unpark();
+        }
+    }
+}
+
+/// Where a thread leaves its result for [`ScopedJoinHandle::join`].
+struct Packet<T> {
+    scope: Arc<ScopeData>,
+    result: Mutex<Option<thread::Result<T>>>,
+}
+
+impl<T> Drop for Packet<T> {
+    /// The thread only counts as finished once its result is dropped, which
+    /// may borrow from the environment.
+    fn drop(&mut self) {
+        let result = self
+            .result
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(Err(_)) = result.take() {
+            self.scope.panicked.store(true, Ordering::Relaxed);
+        }
+        self.scope.finished();
+    }
+}
+
+impl<'scope> Scope<'scope, '_> {
+    /// Spawn a thread that may borrow anything outliving the scope.
+    ///
+    /// # Panics
+    /// If the operating system fails to create a thread.
+    pub fn spawn<F, T>(&'scope self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let packet = Arc::new(Packet {
+            scope: Arc::clone(&self.data),
+            result: Mutex::new(None),
+        });
+        let their_packet = Arc::clone(&packet);
+        let main: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            let result = catch_unwind(AssertUnwindSafe(f));
+            *their_packet
+                .result
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(result);
+        });
+        // SAFETY
+        // * scope does not return before every packet is dropped, and the
+        //   thread drops its packet last, so nothing borrowed for 'scope is
+        //   used after 'scope ends.
+        let main = unsafe {
+            std::mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Box<dyn FnOnce() + Send>>(main)
+        };
+
+        self.data.running.fetch_add(1, Ordering::Relaxed);
+        let native = match thread::Builder::new().spawn(main) {
+            Ok(native) => native,
+            Err(e) => {
+                drop(packet);
+                panic!("failed to spawn thread: {e}");
+            }
+        };
+        ScopedJoinHandle {
+            native,
+            packet,
+            _scope: PhantomData,
+        }
+    }
+}
+
+/// An owned permission to join a thread spawned in a [`Scope`].
+///
+/// This type can be constructed through [`Scope::spawn`].
+pub struct ScopedJoinHandle<'scope, T> {
+    native: thread::JoinHandle<()>,
+    packet: Arc<Packet<T>>,
+    _scope: PhantomData<&'scope ()>,
+}
+
+impl<T> ScopedJoinHandle<'_, T> {
+    /// Wait for the thread to finish, returning its result or the payload
+    /// it panicked with.
+    pub fn join(self) -> thread::Result<T> {
+        // The closure catches panics, so the thread itself never panics.
+        let _ = self.native.join();
+        self.packet
+            .result
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .expect("joined thread left a result")
+    }
+
+    pub fn thread(&self) -> &Thread {
+        self.native.thread()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.native.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn borrows_environment() {
+        let mut counts = [0u32; 8];
+        scope(|s| {
+            for (i, count) in counts.iter_mut().enumerate() {
+                s.spawn(move || *count = i as u32 * 2);
+            }
+        });
+        assert_eq!(counts, [0, 2, 4, 6, 8, 10, 12, 14]);
+    }
+
+    #[test]
+    fn joined_panic_is_handled() {
+        let result = scope(|s| s.spawn(|| panic!("caught")).join());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn unjoined_panic_propagates() {
+        let result = catch_unwind(|| {
+            scope(|s| {
+                s.spawn(|| panic!("not joined"));
+            })
+        });
+        let payload = result.unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<&str>(),
+            Some(&"a scoped thread panicked")
+        );
+    }
+
+    #[test]
+    fn waits_for_unjoined_threads() {
+        let done = AtomicBool::new(false);
+        scope(|s| {
+            s.spawn(|| {
+                thread::sleep(std::time::Duration::from_millis(20));
+                done.store(true, Ordering::Relaxed);
+            });
+        });
+        assert!(done.load(Ordering::Relaxed));
+    }
+}
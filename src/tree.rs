@@ -0,0 +1,247 @@
+use crate::{
+    cell::{Ref, RefCell, RefMut},
+    rc::{Rc, Weak},
+};
+
+/// A handle to a node in a tree.
+///
+/// Parents own their children through [`Rc`]s, children only point back at
+/// their parent through a [`Weak`], so a tree never forms a reference cycle
+/// and is freed as soon as the last handle to its root is dropped.
+///
+/// ```
+/// use nomicon::tree::Node;
+///
+/// let root = Node::new("html");
+/// let body = Node::new("body");
+/// root.append_child(body.clone());
+/// body.append_child(Node::new("p"));
+///
+/// let names = root.descendants().map(|n| *n.borrow()).collect::<Vec<_>>();
+/// assert_eq!(names, ["body", "p"]);
+///
+/// body.detach();
+/// assert!(body.parent().is_none());
+/// assert_eq!(root.children().count(), 0);
+/// ```
+pub struct Node<T> {
+    data: Rc<NodeData<T>>,
+}
+
+struct NodeData<T> {
+    value: RefCell<T>,
+    parent: RefCell<Weak<NodeData<T>>>,
+    children: RefCell<Vec<Node<T>>>,
+}
+
+impl<T> Node<T> {
+    /// Returns a new node without a parent or children.
+    pub fn new(value: T) -> Self {
+        Self {
+            data: Rc::new(NodeData {
+                value: RefCell::new(value),
+                parent: RefCell::new(Weak::new()),
+                children: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Return a shared reference to the value.
+    ///
+    /// # Panics
+    /// If the value is exclusively borrowed.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.data.value.borrow()
+    }
+
+    /// Return an exclusive reference to the value.
+    ///
+    /// # Panics
+    /// If the value is borrowed.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.data.value.borrow_mut()
+    }
+
+    /// Returns true if both handles refer to the same node.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+
+    pub fn parent(&self) -> Option<Node<T>> {
+        let data = self.data.parent.borrow().upgrade()?;
+        Some(Node { data })
+    }
+
+    /// Move `child` to the end of this node's children, detaching it from its
+    /// current parent first.
+    ///
+    /// # Panics
+    /// If `child` is this node or one of its ancestors.
+    pub fn append_child(&self, child: Node<T>) {
+        assert!(
+            !self.ptr_eq(&child) && !self.ancestors().any(|a| a.ptr_eq(&child)),
+            "a node cannot be appended to itself or its descendants"
+        );
+        child.detach();
+        *child.data.parent.borrow_mut() = Rc::downgrade(&self.data);
+        self.data.children.borrow_mut().push(child);
+    }
+
+    /// Remove this node, and its descendants, from its parent.
+    pub fn detach(&self) {
+        let Some(parent) = self.parent() else {
+            return;
+        };
+        *self.data.parent.borrow_mut() = Weak::new();
+        parent
+            .data
+            .children
+            .borrow_mut()
+            .retain(|child| !child.ptr_eq(self));
+    }
+
+    /// Iterate over the direct children of this node.
+    pub fn children(&self) -> Children<T> {
+        Children {
+            parent: self.clone(),
+            index: 0,
+        }
+    }
+
+    /// Iterate over the parent, grandparent, and so on of this node.
+    pub fn ancestors(&self) -> Ancestors<T> {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// Iterate over every node below this one, in depth first pre-order.
+    pub fn descendants(&self) -> Descendants<T> {
+        Descendants {
+            stack: self.data.children.borrow().iter().rev().cloned().collect(),
+        }
+    }
+}
+
+impl<T> Clone for Node<T> {
+    /// Returns another handle to the same node.
+    fn clone(&self) -> Self {
+        Self {
+            data: Rc::clone(&self.data),
+        }
+    }
+}
+
+impl<T> Drop for NodeData<T> {
+    /// Frees the subtree with an explicit stack, as deep trees would overflow
+    /// the call stack through recursive drops.
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut *self.children.borrow_mut());
+        while let Some(node) = stack.pop() {
+            if Rc::strong_count(&node.data) == 1 {
+                stack.append(&mut node.data.children.borrow_mut());
+            }
+        }
+    }
+}
+
+/// An iterator over the children of a [`Node`].
+///
+/// This type can be constructed through [`Node::children`].
+pub struct Children<T> {
+    parent: Node<T>,
+    index: usize,
+}
+
+impl<T> Iterator for Children<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let child = self.parent.data.children.borrow().get(self.index)?.clone();
+        self.index += 1;
+        Some(child)
+    }
+}
+
+/// An iterator over the ancestors of a [`Node`].
+///
+/// This type can be constructed through [`Node::ancestors`].
+pub struct Ancestors<T> {
+    next: Option<Node<T>>,
+}
+
+impl<T> Iterator for Ancestors<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next.take()?;
+        self.next = node.parent();
+        Some(node)
+    }
+}
+
+/// An iterator over the descendants of a [`Node`].
+///
+/// This type can be constructed through [`Node::descendants`].
+pub struct Descendants<T> {
+    stack: Vec<Node<T>>,
+}
+
+impl<T> Iterator for Descendants<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let children = node.data.children.borrow();
+        self.stack.extend(children.iter().rev().cloned());
+        std::mem::drop(children);
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reparenting() {
+        let a = Node::new('a');
+        let b = Node::new('b');
+        let c = Node::new('c');
+        a.append_child(b.clone());
+        a.append_child(c.clone());
+        b.append_child(c.clone());
+
+        let children = |n: &Node<char>| n.children().map(|c| *c.borrow()).collect::<String>();
+        assert_eq!(children(&a), "b");
+        assert_eq!(children(&b), "c");
+        let ancestors = c.ancestors().map(|n| *n.borrow()).collect::<String>();
+        assert_eq!(ancestors, "ba");
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_cycles() {
+        let a = Node::new(());
+        let b = Node::new(());
+        a.append_child(b.clone());
+        b.append_child(a);
+    }
+
+    #[test]
+    fn dropping_root_frees_tree() {
+        let flag = std::rc::Rc::new(());
+        let leaf = {
+            let root = Node::new(std::rc::Rc::clone(&flag));
+            let mut node = root.clone();
+            for _ in 0..5_000 {
+                let child = Node::new(std::rc::Rc::clone(&flag));
+                node.append_child(child.clone());
+                node = child;
+            }
+            Rc::downgrade(&node.data)
+        };
+        assert!(leaf.upgrade().is_none());
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+}
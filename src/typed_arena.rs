@@ -0,0 +1,162 @@
+use crate::cell::RefCell;
+
+/// The number of bytes worth of values in the first chunk.
+const FIRST_CHUNK_BYTES: usize = 4096;
+
+/// An arena of `T`s, handing out references that live as long as the arena.
+///
+/// Unlike [`crate::alloc::Bump`] every value is dropped along with the arena.
+/// Values can hold references to other values in the same arena, which makes
+/// it a good fit for graphs and syntax trees.
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// use nomicon::TypedArena;
+///
+/// struct Node<'a> {
+///     value: u32,
+///     next: Cell<Option<&'a Node<'a>>>,
+/// }
+///
+/// let arena = TypedArena::new();
+/// let a = arena.alloc(Node { value: 1, next: Cell::new(None) });
+/// let b = arena.alloc(Node { value: 2, next: Cell::new(Some(a)) });
+/// // A cycle, both nodes are freed with the arena.
+/// a.next.set(Some(b));
+///
+/// assert_eq!(a.next.get().unwrap().next.get().unwrap().value, 1);
+/// ```
+pub struct TypedArena<T> {
+    chunks: RefCell<Chunks<T>>,
+}
+
+/// Values are stored in vectors that are never pushed past their capacity, so
+/// they never reallocate and references into them stay valid.
+///
+/// No `Drop` impl is written for the arena, values are dropped by the
+/// vectors. Vec's drop is known not to touch `T` other than dropping it,
+/// which is what allows values to reference each other.
+struct Chunks<T> {
+    current: Vec<T>,
+    full: Vec<Vec<T>>,
+}
+
+impl<T> Chunks<T> {
+    /// Move the current chunk into the full list, replacing it with a chunk
+    /// that has room for at least `additional` values.
+    fn grow(&mut self, additional: usize) {
+        let first = (FIRST_CHUNK_BYTES / std::mem::size_of::<T>().max(1)).max(1);
+        let cap = (self.current.capacity() * 2).max(first).max(additional);
+        let full = std::mem::replace(&mut self.current, Vec::with_capacity(cap));
+        if !full.is_empty() {
+            self.full.push(full);
+        }
+    }
+}
+
+impl<T> TypedArena<T> {
+    pub const fn new() -> Self {
+        Self {
+            chunks: RefCell::new(Chunks {
+                current: Vec::new(),
+                full: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of values in the arena.
+    pub fn len(&self) -> usize {
+        let chunks = self.chunks.borrow();
+        chunks.current.len() + chunks.full.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Move `value` into the arena.
+    // Every value gets its own slot, so handing out &mut from &self never
+    // aliases.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, value: T) -> &mut T {
+        &mut self.alloc_extend(std::iter::once(value))[0]
+    }
+
+    /// Move every item of `iter` into the arena as one contiguous slice.
+    ///
+    /// The iterator is collected before anything is placed in the arena, so
+    /// it is free to allocate from the arena itself.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_extend<I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
+        let items = iter.into_iter().collect::<Vec<_>>();
+        let mut chunks = self.chunks.borrow_mut();
+        let current = &chunks.current;
+        if current.capacity() - current.len() < items.len() {
+            chunks.grow(items.len());
+        }
+        let start = chunks.current.len();
+        chunks.current.extend(items);
+        let len = chunks.current.len() - start;
+        // SAFETY
+        // * The chunk had room for every item, so it did not reallocate.
+        // * The slots are never handed out again and the chunk is only freed
+        //   with the arena.
+        unsafe {
+            let ptr = chunks.current.as_mut_ptr().add(start);
+            std::slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// Move every value out of the arena, in allocation order.
+    pub fn into_vec(self) -> Vec<T> {
+        let chunks = self.chunks.into_inner();
+        let mut values = chunks.full.into_iter().flatten().collect::<Vec<_>>();
+        values.extend(chunks.current);
+        values
+    }
+}
+
+impl<T> Default for TypedArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    struct Tracked<'a>(&'a Cell<usize>);
+
+    impl Drop for Tracked<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drops_every_value() {
+        let drops = Cell::new(0);
+        {
+            let arena = TypedArena::new();
+            for _ in 0..10_000 {
+                arena.alloc(Tracked(&drops));
+            }
+            let slice = arena.alloc_extend((0..10).map(|_| Tracked(&drops)));
+            assert_eq!(slice.len(), 10);
+            assert_eq!(arena.len(), 10_010);
+        }
+        assert_eq!(drops.get(), 10_010);
+    }
+
+    #[test]
+    fn references_stay_valid() {
+        let arena = TypedArena::new();
+        let values = (0..5_000).map(|n| &*arena.alloc(n)).collect::<Vec<_>>();
+        assert!(values.iter().map(|n| **n).eq(0..5_000));
+        assert!(arena.into_vec().into_iter().eq(0..5_000));
+    }
+}
@@ -1,66 +1,138 @@
 use std::{
-    alloc::{alloc, handle_alloc_error, realloc, Layout},
+    borrow::{Borrow, BorrowMut},
+    fmt,
+    hash::{Hash, Hasher},
     mem::ManuallyDrop,
-    ptr::NonNull,
+    ops::{Deref, DerefMut},
 };
 
-pub struct Vec<T> {
-    ptr: NonNull<T>,
+use crate::{
+    alloc::{Allocator, Global, TryReserveError},
+    guard::guard,
+    marker::Covariant,
+    raw_vec::RawVec,
+};
+
+pub struct Vec<T, A: Allocator = Global> {
+    buf: RawVec<T, A>,
     len: usize,
-    cap: usize,
+    /// A `Vec<&'static T>` can be used as a `Vec<&'a T>`.
+    _variance: Covariant<T>,
 }
 
 impl<T> Vec<T> {
     pub const fn new() -> Self {
-        const {
-            assert!(
-                std::mem::size_of::<T>() != 0,
-                "Zero sized types are not supported"
-            )
+        Self {
+            buf: RawVec::new(),
+            len: 0,
+            _variance: Covariant::new(),
         }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
         Self {
-            ptr: NonNull::dangling(),
+            buf: RawVec::with_capacity(cap),
             len: 0,
-            cap: 0,
+            _variance: Covariant::new(),
         }
     }
 
-    fn grow(&mut self) {
-        let (new_cap, new_layout) = if self.cap == 0 {
-            (1, Layout::array::<T>(1).unwrap())
-        } else {
-            let new_cap = self.cap * 2;
-            (new_cap, Layout::array::<T>(new_cap).unwrap())
-        };
+    pub fn try_with_capacity(cap: usize) -> Result<Self, TryReserveError> {
+        Self::try_with_capacity_in(cap, Global)
+    }
+}
 
-        assert!(
-            new_layout.size() < isize::MAX as usize,
-            "allocations cannot exceed isize MAX"
-        );
+impl<T, A: Allocator> Vec<T, A> {
+    /// Returns an empty [`Vec`] that allocates from `alloc`.
+    pub const fn new_in(alloc: A) -> Self {
+        Self {
+            buf: RawVec::new_in(alloc),
+            len: 0,
+            _variance: Covariant::new(),
+        }
+    }
 
-        let ptr = if self.cap == 0 {
-            unsafe { alloc(new_layout) }
-        } else {
-            let old_layout = Layout::array::<T>(self.cap).unwrap();
-            let ptr = self.ptr.as_ptr() as *mut u8;
-            unsafe { realloc(ptr, old_layout, new_layout.size()) }
-        };
-        self.ptr = match NonNull::new(ptr as *mut T) {
-            Some(ptr) => ptr,
-            None => handle_alloc_error(new_layout),
-        };
-        self.cap = new_cap;
+    pub fn with_capacity_in(cap: usize, alloc: A) -> Self {
+        Self {
+            buf: RawVec::with_capacity_in(cap, alloc),
+            len: 0,
+            _variance: Covariant::new(),
+        }
+    }
+
+    pub fn try_with_capacity_in(cap: usize, alloc: A) -> Result<Self, TryReserveError> {
+        Ok(Self {
+            buf: RawVec::try_with_capacity_in(cap, alloc)?,
+            len: 0,
+            _variance: Covariant::new(),
+        })
+    }
+
+    pub const fn allocator(&self) -> &A {
+        &self.buf.alloc
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.buf.ptr.as_ptr()
+    }
+
+    fn cap(&self) -> usize {
+        self.buf.cap
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap()
+    }
+
+    /// Make room for at least `additional` more elements without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.try_reserve(additional).unwrap_or_else(|e| e.handle())
+    }
+
+    /// Make room for at least `additional` more elements, returning an error
+    /// instead of aborting if the allocation fails.
+    ///
+    /// ```
+    /// use nomicon::{alloc::TryReserveError, Vec};
+    ///
+    /// let mut v = Vec::<u64>::new();
+    /// assert!(v.try_reserve(16).is_ok());
+    /// assert_eq!(v.try_reserve(usize::MAX), Err(TryReserveError::CapacityOverflow));
+    /// assert!(v.capacity() >= 16);
+    /// ```
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        if required > self.cap() {
+            self.buf
+                .try_grow_to(required.max(self.cap().saturating_mul(2)))?;
+        }
+        self.check_invariants();
+        Ok(())
     }
 
     pub fn push(&mut self, item: T) {
-        if self.len == self.cap {
-            self.grow();
+        if let Err((e, _)) = self.try_push(item) {
+            e.handle()
+        }
+    }
+
+    /// Append `item`, handing it back if there is no room and growing fails.
+    pub fn try_push(&mut self, item: T) -> Result<(), (TryReserveError, T)> {
+        if self.len == self.cap() {
+            if let Err(e) = self.buf.try_grow() {
+                return Err((e, item));
+            }
         }
         unsafe {
-            let dst = self.ptr.as_ptr().add(self.len);
+            let dst = self.ptr().add(self.len);
             std::ptr::write(dst, item)
         }
         self.len += 1;
+        self.check_invariants();
+        Ok(())
     }
 
     pub fn pop(&mut self) -> Option<T> {
@@ -68,9 +140,10 @@ impl<T> Vec<T> {
             return None;
         }
         self.len -= 1;
+        self.check_invariants();
 
         Some(unsafe {
-            let src = self.ptr.as_ptr().add(self.len);
+            let src = self.ptr().add(self.len);
             std::ptr::read(src)
         })
     }
@@ -79,9 +152,211 @@ impl<T> Vec<T> {
         self.len().eq(&0)
     }
 
+    /// Keep only the elements `keep` returns true for, in order.
+    ///
+    /// If `keep` or a dropped element panics, the elements not looked at yet
+    /// are kept.
+    ///
+    /// ```
+    /// use nomicon::Vec;
+    ///
+    /// let mut v = Vec::new();
+    /// (1..=6).for_each(|n| v.push(n));
+    /// v.retain(|n| n % 3 != 0);
+    /// assert_eq!(&*v, &[1, 2, 4, 5]);
+    /// ```
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let len = self.len;
+        // Nothing is reachable through the Vec while holes are being made,
+        // the guard closes them whether or not we finish.
+        self.len = 0;
+        let mut state = guard((&mut *self, 0, 0), move |(vec, processed, deleted)| {
+            unsafe {
+                let ptr = vec.ptr();
+                std::ptr::copy(
+                    ptr.add(processed),
+                    ptr.add(processed - deleted),
+                    len - processed,
+                );
+            }
+            vec.len = len - deleted;
+        });
+        while state.1 < len {
+            let (vec, processed, deleted) = &mut *state;
+            let current = unsafe { vec.ptr().add(*processed) };
+            if keep(unsafe { &*current }) {
+                if *deleted > 0 {
+                    unsafe { std::ptr::copy_nonoverlapping(current, current.sub(*deleted), 1) };
+                }
+                *processed += 1;
+            } else {
+                // Counted before dropping, a panicking drop is not dropped
+                // again.
+                *processed += 1;
+                *deleted += 1;
+                unsafe { current.drop_in_place() };
+            }
+        }
+        drop(state);
+        self.check_invariants();
+    }
+
+    /// Panic if the length, capacity and pointer do not agree.
+    #[cfg(feature = "debug-invariants")]
+    pub fn assert_invariants(&self) {
+        assert!(
+            self.len <= self.cap(),
+            "Vec length {} exceeds its capacity {}",
+            self.len,
+            self.cap()
+        );
+        assert!(self.as_ptr().is_aligned(), "Vec pointer is misaligned");
+        assert!(
+            std::alloc::Layout::array::<T>(self.cap()).is_ok(),
+            "Vec capacity {} overflows isize",
+            self.cap()
+        );
+    }
+
+    /// Runs [`Vec::assert_invariants`] in debug builds with the
+    /// `debug-invariants` feature.
+    fn check_invariants(&self) {
+        #[cfg(all(feature = "debug-invariants", debug_assertions))]
+        self.assert_invariants();
+    }
+
     pub const fn len(&self) -> usize {
         self.len
     }
+
+    pub const fn as_ptr(&self) -> *const T {
+        self.buf.ptr.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr()
+    }
+
+    pub fn iter(&self) -> crate::slice::Iter<'_, T> {
+        crate::slice::iter(self.as_slice())
+    }
+
+    pub fn iter_mut(&mut self) -> crate::slice::IterMut<'_, T> {
+        crate::slice::iter_mut(self.as_mut_slice())
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY
+        // * The first self.len elements are initialized.
+        // * An empty Vec has a dangling, but aligned and non-null, pointer.
+        unsafe { std::slice::from_raw_parts(self.ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY
+        // * See Vec::as_slice.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr(), self.len) }
+    }
+}
+
+impl<T: Clone, A: Allocator> Vec<T, A> {
+    pub fn extend_from_slice(&mut self, other: &[T]) {
+        self.reserve(other.len());
+        for item in other {
+            self.push(item.clone());
+        }
+    }
+}
+
+/// Word at a time searches, see [`crate::slice::position_byte`].
+impl<A: Allocator> Vec<u8, A> {
+    pub fn contains_byte(&self, byte: u8) -> bool {
+        crate::slice::contains_byte(self, byte)
+    }
+
+    pub fn position_byte(&self, byte: u8) -> Option<usize> {
+        crate::slice::position_byte(self, byte)
+    }
+
+    /// Set every byte to `byte`, leaving the length alone.
+    pub fn fill_byte(&mut self, byte: u8) {
+        self.fill(byte)
+    }
+
+    pub fn eq_bytes(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl<T, A: Allocator> Deref for Vec<T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T, A: Allocator> DerefMut for Vec<T, A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, A: Allocator> AsRef<[T]> for Vec<T, A> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Allocator> AsMut<[T]> for Vec<T, A> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+/// Hashes and compares like the slice, so a slice can look up a key.
+impl<T, A: Allocator> Borrow<[T]> for Vec<T, A> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Allocator> BorrowMut<[T]> for Vec<T, A> {
+    fn borrow_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for Vec<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq<U>, U, A: Allocator, B: Allocator> PartialEq<Vec<U, B>> for Vec<T, A> {
+    fn eq(&self, other: &Vec<U, B>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for Vec<T, A> {}
+
+impl<T: PartialOrd, A: Allocator> PartialOrd for Vec<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, A: Allocator> Ord for Vec<T, A> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, A: Allocator> Hash for Vec<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
 }
 
 impl<T> Default for Vec<T> {
@@ -90,53 +365,62 @@ impl<T> Default for Vec<T> {
     }
 }
 
-impl<T> Drop for Vec<T> {
+impl<T, A: Allocator> Drop for Vec<T, A> {
     fn drop(&mut self) {
-        if self.cap != 0 {
-            while self.pop().is_some() {}
-            unsafe {
-                // SAFETY
-                // self.cap is not zero, so we have allocated
-                // self.cap is updated alongside the side of our allocation.
-                let ptr = self.ptr.as_ptr() as *mut u8;
-                let layout = Layout::array::<T>(self.cap).unwrap();
-                std::alloc::dealloc(ptr, layout)
-            }
-        }
+        // RawVec handles the deallocation.
+        while self.pop().is_some() {}
     }
 }
 
-pub struct IntoIter<T> {
-    buf: NonNull<T>,
-    cap: usize,
+pub struct IntoIter<T, A: Allocator = Global> {
+    _buf: RawVec<T, A>,
     start: *const T,
     end: *const T,
 }
 
-impl<T> IntoIterator for Vec<T> {
-    type IntoIter = IntoIter<T>;
+impl<T, A: Allocator> IntoIterator for Vec<T, A> {
+    type IntoIter = IntoIter<T, A>;
     type Item = T;
 
     fn into_iter(self) -> Self::IntoIter {
         let s = ManuallyDrop::new(self);
         let len = s.len;
-        let cap = s.cap;
-        let ptr = s.ptr;
-        let buf = s.ptr;
+        let cap = s.cap();
+        let ptr = s.ptr();
+        // SAFETY
+        // * s is never dropped, so the buffer is moved out exactly once.
+        let buf = unsafe { std::ptr::read(&s.buf) };
         IntoIter {
-            buf,
-            cap,
-            start: ptr.as_ptr(),
+            _buf: buf,
+            start: ptr,
             end: if cap == 0 {
-                ptr.as_ptr()
+                ptr
             } else {
-                unsafe { ptr.as_ptr().add(len) }
+                unsafe { ptr.add(len) }
             },
         }
     }
 }
 
-impl<T> Iterator for IntoIter<T> {
+impl<'a, T, A: Allocator> IntoIterator for &'a Vec<T, A> {
+    type IntoIter = crate::slice::Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a mut Vec<T, A> {
+    type IntoIter = crate::slice::IterMut<'a, T>;
+    type Item = &'a mut T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -145,7 +429,7 @@ impl<T> Iterator for IntoIter<T> {
         } else {
             unsafe {
                 let item = std::ptr::read(self.start);
-                self.start = self.start.offset(1);
+                self.start = self.start.add(1);
                 Some(item)
             }
         }
@@ -156,27 +440,19 @@ impl<T> Iterator for IntoIter<T> {
             assert!(std::mem::size_of::<T>() != 0);
         }
         unsafe {
-            // SAFEY
+            // SAFETY
             // * self.end and self.start are derived from the same object
-            // * Allocation never exceeds isize::MAX
-            // * both in bounds
-            let len = self.start.offset_from(self.end) as usize;
+            // * self.start never moves past self.end
+            let len = self.end.offset_from_unsigned(self.start);
             (len, Some(len))
         }
     }
 }
 
-impl<T> Drop for IntoIter<T> {
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
     fn drop(&mut self) {
-        if self.cap != 0 {
-            for _ in &mut *self {}
-            unsafe {
-                std::alloc::dealloc(
-                    self.buf.as_ptr() as *mut u8,
-                    Layout::array::<T>(self.cap).unwrap(),
-                )
-            }
-        }
+        // RawVec handles the deallocation.
+        for _ in &mut *self {}
     }
 }
 
@@ -184,6 +460,20 @@ impl<T> Drop for IntoIter<T> {
 mod test {
     use super::*;
 
+    #[test]
+    fn slices_look_up_keys() {
+        let mut map = std::collections::HashMap::new();
+        let mut key = Vec::new();
+        key.extend_from_slice(b"key");
+        map.insert(key, 1);
+        assert_eq!(map.get(&b"key"[..]), Some(&1));
+
+        let mut other = Vec::new();
+        other.extend_from_slice(b"kez");
+        assert!(map.keys().all(|key| *key < other));
+        assert_eq!(format!("{other:?}"), "[107, 101, 122]");
+    }
+
     #[test]
     fn push_and_pop() {
         let mut b = Vec::<u8>::new();
@@ -196,6 +486,44 @@ mod test {
         assert_eq!(b.len(), 0);
     }
 
+    #[test]
+    fn retain_survives_panics() {
+        let flag = std::rc::Rc::new(());
+        let mut v = Vec::new();
+        (0..6).for_each(|n| v.push((n, std::rc::Rc::clone(&flag))));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            v.retain(|(n, _)| {
+                assert!(*n < 4, "predicate panicked");
+                n % 2 == 0
+            })
+        }));
+        assert!(result.is_err());
+        let left = v.iter().map(|(n, _)| *n).collect::<std::vec::Vec<_>>();
+        assert_eq!(left, [0, 2, 4, 5]);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 5);
+        drop(v);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
+
+    #[test]
+    fn fallible_growth() {
+        let tracking = crate::alloc::TrackingAlloc::system();
+        let mut v = Vec::new_in(&tracking);
+        v.push(1u32);
+        tracking.fail_at(1);
+        let Err((TryReserveError::AllocError { .. }, item)) = v.try_push(2) else {
+            panic!("push should fail");
+        };
+        assert_eq!(item, 2);
+        assert_eq!(&*v, &[1]);
+
+        v.try_push(2).unwrap();
+        assert_eq!(&*v, &[1, 2]);
+        tracking.fail_at(1);
+        assert!(v.try_reserve(100).is_err());
+        assert_eq!(v.capacity(), 2);
+    }
+
     #[test]
     fn iter() {
         let mut b = Vec::<u8>::new();
@@ -210,4 +538,18 @@ mod test {
         assert_eq!(iter.next(), Some(4));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn partially_consumed_iter() {
+        let flag = std::rc::Rc::new(());
+        let mut v = Vec::new();
+        for _ in 0..5 {
+            v.push(std::rc::Rc::clone(&flag));
+        }
+        let mut iter = v.into_iter();
+        iter.next();
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        std::mem::drop(iter);
+        assert_eq!(std::rc::Rc::strong_count(&flag), 1);
+    }
 }
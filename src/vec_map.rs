@@ -0,0 +1,291 @@
+use std::{
+    borrow::Borrow,
+    ops::{Bound, RangeBounds},
+};
+
+/// A map backed by a vector of pairs sorted by key.
+///
+/// Lookups are a binary search and iteration is a walk over contiguous
+/// memory, which beats a tree for small or read-mostly maps. Inserting or
+/// removing shifts every later entry, so mutation is O(n).
+pub struct VecMap<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> VecMap<K, V> {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(cap),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the entries in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.entries.first().map(|(k, v)| (k, v))
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|(k, v)| (k, v))
+    }
+}
+
+impl<K: Ord, V> VecMap<K, V> {
+    /// Build a map from an iterator that already yields keys in strictly
+    /// ascending order, without sorting.
+    ///
+    /// # Panics
+    /// If the keys are not strictly ascending.
+    ///
+    /// ```
+    /// use nomicon::VecMap;
+    ///
+    /// let map = VecMap::from_sorted_iter((0..10).map(|n| (n, n * n)));
+    /// assert_eq!(map.get(&3), Some(&9));
+    /// ```
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let entries = iter.into_iter().collect::<Vec<_>>();
+        assert!(
+            entries.windows(2).all(|w| w[0].0 < w[1].0),
+            "keys are not strictly ascending"
+        );
+        Self { entries }
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entries.binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+
+    /// Insert a key value pair, returning the old value if the key was present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.search(&key) {
+            Ok(index) => Some(std::mem::replace(&mut self.entries[index].1, value)),
+            Err(index) => {
+                self.entries.insert(index, (key, value));
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let index = self.search(key).ok()?;
+        Some(&self.entries[index].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let index = self.search(key).ok()?;
+        Some(&mut self.entries[index].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.search(key).is_ok()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let index = self.search(key).ok()?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Iterate over the entries with keys in `range`.
+    ///
+    /// ```
+    /// use nomicon::VecMap;
+    ///
+    /// let map = VecMap::from_sorted_iter([(1, 'a'), (3, 'b'), (5, 'c'), (7, 'd')]);
+    /// let values = map.range(2..=5).map(|(_, v)| *v).collect::<String>();
+    /// assert_eq!(values, "bc");
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(q) => self.entries.partition_point(|(k, _)| k.borrow() < q),
+            Bound::Excluded(q) => self.entries.partition_point(|(k, _)| k.borrow() <= q),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(q) => self.entries.partition_point(|(k, _)| k.borrow() <= q),
+            Bound::Excluded(q) => self.entries.partition_point(|(k, _)| k.borrow() < q),
+            Bound::Unbounded => self.entries.len(),
+        };
+        Iter {
+            inner: self.entries[start..end.max(start)].iter(),
+        }
+    }
+}
+
+impl<K, V> Default for VecMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for VecMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug for VecMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for VecMap<K, V> {
+    /// Collect into a map, the last value wins for duplicate keys.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries = iter.into_iter().collect::<Vec<_>>();
+        // Stable, so later duplicates stay after earlier ones.
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut deduped: Vec<(K, V)> = Vec::with_capacity(entries.len());
+        for (k, v) in entries {
+            match deduped.last_mut() {
+                Some(last) if last.0 == k => last.1 = v,
+                _ => deduped.push((k, v)),
+            }
+        }
+        Self { entries: deduped }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a VecMap<K, V> {
+    type IntoIter = Iter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over entries of a [`VecMap`] in key order.
+///
+/// This type can be constructed through [`VecMap::iter`] and
+/// [`VecMap::range`].
+pub struct Iter<'a, K, V> {
+    inner: std::slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, v)| (k, v))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_keeps_order() {
+        let mut map = VecMap::new();
+        for n in [5, 1, 4, 2, 3] {
+            assert_eq!(map.insert(n, n * 10), None);
+        }
+        assert_eq!(map.insert(4, 0), Some(40));
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        assert_eq!(map.remove(&1), Some(10));
+        assert_eq!(map.first(), Some((&2, &20)));
+        assert_eq!(map.get(&4), Some(&0));
+    }
+
+    #[test]
+    fn range_bounds() {
+        let map = (0..10)
+            .map(|n| (n.to_string(), n))
+            .collect::<VecMap<_, _>>();
+        let keys = |r: Iter<'_, String, i32>| r.map(|(_, v)| *v).collect::<Vec<_>>();
+        assert_eq!(
+            keys(map.range::<str, _>((Bound::Included("3"), Bound::Excluded("6")))),
+            [3, 4, 5]
+        );
+        assert_eq!(
+            keys(map.range::<str, _>((Bound::Excluded("7"), Bound::Unbounded))),
+            [8, 9]
+        );
+        assert_eq!(
+            keys(map.range::<str, _>((Bound::Included("5"), Bound::Excluded("2")))),
+            []
+        );
+    }
+
+    #[test]
+    fn collect_last_wins() {
+        let map = [(2, 'a'), (1, 'b'), (2, 'c')]
+            .into_iter()
+            .collect::<VecMap<_, _>>();
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &'b'), (&2, &'c')]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsorted_input() {
+        VecMap::from_sorted_iter([(2, ()), (1, ())]);
+    }
+}
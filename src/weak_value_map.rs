@@ -0,0 +1,171 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::{
+    hash::RandomState,
+    rc::{Rc, Weak},
+    IndexMap,
+};
+
+/// A hash map holding its values through [`Weak`]s, so they are dropped once
+/// nothing else uses them.
+///
+/// Entries of dropped values are removed when their key is looked up, and
+/// all at once when inserting has grown the map to twice its size after the
+/// last sweep.
+///
+/// ```
+/// use nomicon::{WeakValueHashMap, rc::Rc};
+///
+/// let mut cache = WeakValueHashMap::new();
+/// let a = cache.get_or_insert_with("a", || Rc::new(String::from("a")));
+/// let again = cache.get_or_insert_with("a", || unreachable!());
+/// assert!(Rc::ptr_eq(&a, &again));
+///
+/// drop((a, again));
+/// assert!(cache.get("a").is_none());
+/// ```
+///
+/// This type can be constructed through [`WeakValueHashMap::new`].
+pub struct WeakValueHashMap<K, V, S = RandomState> {
+    map: IndexMap<K, Weak<V>, S>,
+    /// The length at which inserting sweeps out every dead entry.
+    prune_at: usize,
+}
+
+const MIN_PRUNE_AT: usize = 8;
+
+impl<K, V> WeakValueHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> WeakValueHashMap<K, V, S> {
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            map: IndexMap::with_hasher(hash_builder),
+            prune_at: MIN_PRUNE_AT,
+        }
+    }
+
+    /// The number of entries, including those whose values were dropped
+    /// since they were last swept.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Remove every entry whose value has been dropped.
+    pub fn prune(&mut self) {
+        self.map.retain(|_, weak| weak.strong_count() != 0);
+        self.prune_at = (self.map.len() * 2).max(MIN_PRUNE_AT);
+    }
+
+    /// Iterate the live values.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, Rc<V>)> {
+        self.map
+            .iter()
+            .filter_map(|(key, weak)| Some((key, weak.upgrade()?)))
+    }
+}
+
+impl<K, V, S> WeakValueHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Insert a weak reference to `value`, returning the previous value if
+    /// it was still alive.
+    pub fn insert(&mut self, key: K, value: &Rc<V>) -> Option<Rc<V>> {
+        if self.map.len() >= self.prune_at {
+            self.prune();
+        }
+        self.map
+            .insert(key, Rc::downgrade(value))
+            .and_then(|old| old.upgrade())
+    }
+
+    /// Returns the value of `key`, removing the entry if it has been dropped.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<Rc<V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let value = self.map.get(key)?.upgrade();
+        if value.is_none() {
+            self.map.swap_remove(key);
+        }
+        value
+    }
+
+    /// Returns the live value of `key`, or inserts the one made by `make`.
+    pub fn get_or_insert_with(&mut self, key: K, make: impl FnOnce() -> Rc<V>) -> Rc<V> {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+        let value = make();
+        self.insert(key, &value);
+        value
+    }
+
+    /// Remove `key`, returning its value if it was still alive.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Rc<V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.swap_remove(key)?.upgrade()
+    }
+}
+
+impl<K, V> Default for WeakValueHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dead_entries_removed_on_access() {
+        let mut map = WeakValueHashMap::new();
+        let one = Rc::new(1);
+        let two = Rc::new(2);
+        assert!(map.insert("one", &one).is_none());
+        map.insert("two", &two);
+        assert_eq!(map.insert("one", &one).as_deref(), Some(&1));
+
+        drop(two);
+        assert_eq!(map.len(), 2);
+        assert!(map.get("two").is_none());
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get("one").as_deref(), Some(&1));
+        assert_eq!(map.iter().count(), 1);
+        assert_eq!(map.remove("one").as_deref(), Some(&1));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn inserts_sweep_the_dead() {
+        let mut map = WeakValueHashMap::new();
+        let kept = (0..100)
+            .filter_map(|n| {
+                let value = Rc::new(n);
+                map.insert(n, &value);
+                (n % 10 == 0).then_some(value)
+            })
+            .collect::<Vec<_>>();
+        // Never more than twice the live entries, plus the inserts since.
+        assert!(map.len() < 2 * kept.len() + MIN_PRUNE_AT);
+        map.prune();
+        assert_eq!(map.len(), kept.len());
+    }
+}